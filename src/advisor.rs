@@ -0,0 +1,118 @@
+//! An AI that looks over the shoulder of a human player, like the analysis of a chess program
+//!
+//! Before each action of the player, the advisor asks a fresh AI of its profile for a hint, see `GameEngine::hint`,
+//! so that it sees the game as it is and doesn't remember earlier advice. If it would play something else, the advice
+//! is what it prefers and how much better that looks: the evaluation of the position after its action less the one
+//! after the player's, see `ai::eval::evaluate`. The difference may be negative when the AI and the evaluation
+//! function disagree.
+//!
+//! The accuracy of a session is the share of the actions the advisor agreed with. Actions that were taken back
+//! still count.
+
+use crate::ai::eval::{self, Weights};
+use crate::ai::profile::AiProfile;
+use crate::ai::Ai;
+use crate::core::Action;
+use crate::engine::GameEngine;
+
+/// What the advisor would have played instead of an action
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advice {
+    pub preferred: Action,
+    /// How much better the position after the preferred action is evaluated
+    pub gain: i64,
+}
+
+/// An AI profile that reviews the actions of a player
+#[derive(Clone)]
+pub struct Advisor {
+    profile: AiProfile,
+    weights: Weights,
+    n_reviewed: usize,
+    n_agreed: usize,
+}
+
+impl Advisor {
+    pub fn new(profile: AiProfile) -> Self {
+        Advisor {
+            profile,
+            weights: eval::default_weights(),
+            n_reviewed: 0,
+            n_agreed: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.profile.name
+    }
+
+    /// What the advisor would play in the game. Asking doesn't count towards the accuracy.
+    pub fn hint(&self, gs: &GameEngine) -> Action {
+        gs.hint(|view, rules| self.profile.make(view, rules))
+    }
+
+    /// A fresh AI of the advisor's profile, to play the game on for the player
+    pub fn make_ai(&self, gs: &GameEngine) -> Box<dyn Ai> {
+        self.profile.make(gs.observe(), gs.rules())
+    }
+
+    /// Review a legal action the player is about to take in the game. `None` if the advisor would take it too.
+    pub fn review(&mut self, gs: &GameEngine, action: &Action) -> Option<Advice> {
+        let preferred = self.hint(gs);
+        self.n_reviewed += 1;
+        if preferred == *action {
+            self.n_agreed += 1;
+            return None;
+        }
+        let gain = self.value_after(gs, &preferred) - self.value_after(gs, action);
+        Some(Advice { preferred, gain })
+    }
+
+    /// The evaluation of the position after an action
+    fn value_after(&self, gs: &GameEngine, action: &Action) -> i64 {
+        let mut next = gs.clone();
+        match next.act(action) {
+            Ok(_) => eval::evaluate(&self.weights, &next.observe()),
+            // The AI suggested an illegal action, which is worth nothing
+            Err(_) => i64::MIN / 2,
+        }
+    }
+
+    /// The share of the reviewed actions the advisor agreed with, in percent
+    pub fn accuracy(&self) -> f64 {
+        100.0 * self.n_agreed as f64 / self.n_reviewed.max(1) as f64
+    }
+
+    /// A line about the session
+    pub fn summary(&self) -> String {
+        format!(
+            "Accuracy {:.0}%: {} agreed with {} of your {} actions",
+            self.accuracy(),
+            self.name(),
+            self.n_agreed,
+            self.n_reviewed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reviews_actions() {
+        let gs = GameEngine::deal(0);
+        let mut advisor = Advisor::new(AiProfile::builtin("greedy").unwrap());
+        let mut greedy = AiProfile::builtin("greedy")
+            .unwrap()
+            .make(gs.observe(), gs.rules());
+        let best = greedy.make_move();
+        assert_eq!(advisor.review(&gs, &best), None);
+        let advice = advisor.review(&gs, &Action::Quit).unwrap();
+        assert_eq!(advice.preferred, best);
+        assert_eq!(advisor.accuracy(), 50.0);
+        assert!(advisor
+            .summary()
+            .starts_with("Accuracy 50%: greedy agreed with 1 of your 2"));
+    }
+}
@@ -0,0 +1,60 @@
+//! The evaluation function: a weighted sum of the features of a position
+//!
+//! All arithmetic is done with integers, so evaluations are identical on all platforms.
+//!
+use super::features::{self, FeatureVec, N_FEATURES};
+use super::SolitaireObserver;
+
+/// One weight per feature, in the order of `features::NAMES`
+pub type Weights = [i32; N_FEATURES];
+
+/// Hand-set weights, used until better ones are tuned
+///
+/// Cards on the foundations are good, hidden cards, buried aces and kings, and suits far ahead of the others are bad.
+pub fn default_weights() -> Weights {
+    let mut weights = [0; N_FEATURES];
+    for (name, weight) in [
+        ("foundation_total", 10),
+        ("hidden_total", -5),
+        ("empty_depots", 2),
+        ("reveals", 4),
+        ("foundation_moves", 2),
+        ("aces_buried", -3),
+        ("kings_at_base", 1),
+        ("kings_buried", -2),
+        ("depot_moves", 1),
+        ("suit_lead", -2),
+    ] {
+        weights[features::index(name).expect("Known feature")] = weight;
+    }
+    weights
+}
+
+/// The dot product of the weights and the features
+pub fn dot(weights: &Weights, features: &FeatureVec) -> i64 {
+    weights
+        .iter()
+        .zip(features.iter())
+        .map(|(&w, &f)| w as i64 * f as i64)
+        .sum()
+}
+
+/// Evaluate a position. Larger is better.
+pub fn evaluate(weights: &Weights, view: &SolitaireObserver) -> i64 {
+    dot(weights, &features::extract(view))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_product() {
+        let mut weights = [0; N_FEATURES];
+        weights[0] = 2;
+        weights[3] = -1;
+        let mut features = [1; N_FEATURES];
+        features[3] = 5;
+        assert_eq!(dot(&weights, &features), -3);
+    }
+}
@@ -0,0 +1,212 @@
+//! An AI player that searches a few actions ahead with expectimax
+//!
+//! The AI looks `depth` actions ahead from what it can see. Most actions have a result the AI knows in advance, but
+//! taking from the talon and revealing a card in a depot turn up cards it hasn't seen. These are chance nodes, and
+//! their value is the mean over the cards that may turn up, which are drawn from `SolitaireObserver::unseen_cards`.
+//! When there are more ways for the cards to turn up than `samples`, the mean is over that many random draws instead,
+//! i.e. over determinizations of the hidden cards, so that the search stays small.
+//!
+//! The value of an action is the points it gives in the scoring policy, plus the expected value of the best action
+//! after it. The search may stop anywhere, so a line of play is never worth less than nothing. At the root,
+//! quitting is worth 100 points less than turning the waste over, as for the greedy AI, and actions that were
+//! already tried in the same position are not tried again.
+//!
+//! The AI doesn't remember the cards it saw in the waste before it was turned over, so after a turnover it takes
+//! the talon to be unseen cards too.
+use super::{rng_checkpoint, softmax, Action, Ai, SeenActions, SolitaireObserver};
+use crate::core::Turned;
+use crate::rules::{RuleSet, ScoringPolicy};
+use rand::prelude::*;
+
+/// The default number of actions to look ahead
+pub const DEFAULT_DEPTH: usize = 2;
+
+/// The default number of outcomes of a chance node, when they are sampled
+pub const DEFAULT_SAMPLES: usize = 8;
+
+/// Values this many points apart are `e` times as likely in `action_probabilities`, as for the greedy AI
+const SOFTMAX_SCALE: f64 = 10.0;
+
+/// An AI player that searches with expectimax
+pub struct ExpectimaxAi {
+    view: SolitaireObserver,
+    policy: ScoringPolicy,
+    /// The number of cards a take turns up
+    draw: usize,
+    depth: usize,
+    samples: usize,
+    rng: StdRng,
+    seen_state_action_combos: SeenActions,
+    /// The untried actions at the last move, best first
+    candidates: Vec<(i64, Action)>,
+}
+
+impl ExpectimaxAi {
+    pub fn new(view: SolitaireObserver, rules: &RuleSet) -> Self {
+        ExpectimaxAi {
+            view,
+            policy: rules.scoring.policy(),
+            draw: rules.draw as usize,
+            depth: DEFAULT_DEPTH,
+            samples: DEFAULT_SAMPLES,
+            rng: StdRng::seed_from_u64(0),
+            seen_state_action_combos: SeenActions::default(),
+            candidates: vec![],
+        }
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth.max(1);
+        self
+    }
+
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// The best value of the actions in a position, looking `depth` actions ahead, and never less than 0
+    fn value(&mut self, view: &SolitaireObserver, depth: usize) -> i64 {
+        if depth == 0 || view.is_won() {
+            return 0;
+        }
+        view.forward_actions()
+            .into_iter()
+            .map(|action| self.action_value(view, action, depth))
+            .fold(0, i64::max)
+    }
+
+    /// The points of an action, and the expected value of the position after it
+    fn action_value(&mut self, view: &SolitaireObserver, action: Action, depth: usize) -> i64 {
+        let points = self.policy.points(&action) as i64;
+        let outcomes = match action {
+            Action::Take => self.outcomes(view, self.draw.min(view.talon_size)),
+            Action::Reveal(_) => self.outcomes(view, 1),
+            _ => vec![Turned::NONE],
+        };
+        let n_outcomes = outcomes.len().max(1) as i64;
+        let mut total = 0;
+        for res in outcomes {
+            let mut next = view.clone();
+            next.update(action.clone(), res);
+            total += self.value(&next, depth - 1);
+        }
+        points + total / n_outcomes
+    }
+
+    /// The cards that may turn up when `n` unseen cards are turned, each way equally likely
+    ///
+    /// A single card is each of the unseen cards, unless there are more of them than `samples`.
+    fn outcomes(&mut self, view: &SolitaireObserver, n: usize) -> Vec<Turned> {
+        let unseen = view.unseen_cards();
+        if n == 1 && unseen.len() <= self.samples {
+            return unseen
+                .into_iter()
+                .map(|card| Turned::from([card]))
+                .collect();
+        }
+        (0..self.samples)
+            .map(|_| unseen.choose_multiple(&mut self.rng, n).copied().collect())
+            .collect()
+    }
+}
+
+impl Ai for ExpectimaxAi {
+    fn make_move(&mut self) -> Action {
+        if self.view.is_won() || self.seen_state_action_combos.is_full() {
+            return Action::Quit;
+        }
+        let view = self.view.clone();
+        let mut candidates = vec![];
+        for action in view.forward_actions() {
+            if !self.seen_state_action_combos.contains(&view, &action) {
+                candidates.push((self.action_value(&view, action.clone(), self.depth), action));
+            }
+        }
+        candidates.push((self.policy.turnover.min(0) as i64 - 100, Action::Quit));
+        // Stable, so that ties go to the first action in the order of `legal_actions`
+        candidates.sort_by_key(|(value, _)| std::cmp::Reverse(*value));
+        let action = candidates[0].1.clone();
+        self.candidates = candidates;
+        action
+    }
+    fn name(&self) -> &'static str {
+        "ExpectimaxAi"
+    }
+    fn n_nodes(&self) -> usize {
+        self.seen_state_action_combos.n_nodes()
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        self.seen_state_action_combos.set_limit(limit)
+    }
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        self.candidates.clone()
+    }
+    fn action_probabilities(&self) -> Vec<(f64, Action)> {
+        softmax(&self.candidates, SOFTMAX_SCALE)
+    }
+    fn rng_checkpoint(&self) -> Option<u64> {
+        Some(rng_checkpoint(&self.rng))
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.seen_state_action_combos.insert(&self.view, &action);
+        self.view.update(action, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::DepotView;
+    use crate::core::{Addr, Suit, Value};
+    use crate::engine::GameEngine;
+    use crate::rules::{BuildRule, EmptyColumnRule};
+
+    /// The value of a reveal is the mean over the cards that may turn up
+    #[test]
+    fn reveals_are_chance_nodes() {
+        let mut view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![],
+            foundation_tops: [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades]
+                .map(|s| Some((s, Value::QUEEN))),
+            depots: Default::default(),
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        view.foundation_tops[3] = Some((Suit::Spades, Value::try_from(11).unwrap()));
+        view.depots[0] = DepotView::new(5, vec![]);
+        let rules = RuleSet::default();
+        let policy = rules.scoring.policy();
+        let mut ai = ExpectimaxAi::new(view.clone(), &rules);
+        // The unseen cards are three kings and the queen and the king of spades. All but the king of spades go to
+        // the foundations right away.
+        let reveal = Action::Reveal(Addr::Depot1);
+        let expected = (policy.reveal + 4 * policy.depot_to_foundation / 5) as i64;
+        assert_eq!(ai.action_value(&view, reveal.clone(), 2), expected);
+        assert_eq!(ai.make_move(), reveal);
+        assert_eq!(ai.candidates()[0], (expected, reveal));
+    }
+
+    #[test]
+    fn plays_legal_moves() {
+        let mut gs = GameEngine::deal(1);
+        let mut ai = ExpectimaxAi::new(gs.observe(), gs.rules()).with_seed(1);
+        for _ in 0..100 {
+            let action = ai.make_move();
+            let res = gs.act(&action).unwrap();
+            ai.update(action, res);
+            if !gs.is_running() {
+                break;
+            }
+        }
+        assert!(gs.score() > 0);
+    }
+}
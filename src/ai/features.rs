@@ -0,0 +1,264 @@
+//! Numeric features of a position, for evaluation functions and learning
+//!
+//! The features are integers computed from what an observer knows. They are versioned:
+//! whenever a feature is added, removed or changes meaning, `VERSION` is bumped,
+//! so that stored datasets and tuned weights are never mixed up with a different feature set.
+//!
+use super::SolitaireObserver;
+use crate::core::{Addr, CardView, Suit, Value};
+
+/// The version of the feature set
+pub const VERSION: u32 = 3;
+
+/// The number of features
+pub const N_FEATURES: usize = 50;
+
+/// The features of a position, in the order of `NAMES`
+pub type FeatureVec = [i32; N_FEATURES];
+
+/// The names of the features
+///
+/// - `talon`: cards in the talon
+/// - `waste`: cards in the waste
+/// - `waste_top`: value of the top card of the waste, 0 if empty
+/// - `foundation_total`, `foundation_min`, `foundation_max`: cards on the foundations, in total and in the smallest and largest pile
+/// - `foundation_hearts` .. `foundation_spades`: cards on the foundation of each suit
+/// - `suit_lead`: how many cards each foundation is more than two ahead of the smallest one, summed over the suits.
+///   A suit far ahead of the others can't take the cards of the other color back to the depots to build on.
+/// - `hidden_1` .. `hidden_7`: face down cards in each depot
+/// - `run_1` .. `run_7`: face up cards in each depot
+/// - `size_1` .. `size_7`: cards in each depot
+/// - `empty_depots`: depots without cards
+/// - `hidden_total`: face down cards in all depots
+/// - `aces_available`: aces at the top of a depot or the waste
+/// - `aces_buried`: face up aces below other cards in the depots
+/// - `foundation_moves`: cards that can be moved to a foundation
+/// - `kings_at_base`: kings with no cards below them in a depot
+/// - `kings_buried`: face up kings with cards below them in a depot
+/// - `top_1` .. `top_7`: value of the top card of each depot, 0 if empty or face down
+/// - `depot_moves`: legal moves of sequences between depots
+/// - `waste_moves`: depots the top card of the waste can be moved to
+/// - `reveals`: depots with a face down top card
+/// - `mobile_runs`: depots whose whole face up run can be moved to another depot
+pub const NAMES: [&str; N_FEATURES] = [
+    "talon",
+    "waste",
+    "waste_top",
+    "foundation_total",
+    "foundation_min",
+    "foundation_max",
+    "foundation_hearts",
+    "foundation_diamonds",
+    "foundation_clubs",
+    "foundation_spades",
+    "suit_lead",
+    "hidden_1",
+    "hidden_2",
+    "hidden_3",
+    "hidden_4",
+    "hidden_5",
+    "hidden_6",
+    "hidden_7",
+    "run_1",
+    "run_2",
+    "run_3",
+    "run_4",
+    "run_5",
+    "run_6",
+    "run_7",
+    "size_1",
+    "size_2",
+    "size_3",
+    "size_4",
+    "size_5",
+    "size_6",
+    "size_7",
+    "empty_depots",
+    "hidden_total",
+    "aces_available",
+    "aces_buried",
+    "foundation_moves",
+    "kings_at_base",
+    "kings_buried",
+    "top_1",
+    "top_2",
+    "top_3",
+    "top_4",
+    "top_5",
+    "top_6",
+    "top_7",
+    "depot_moves",
+    "waste_moves",
+    "reveals",
+    "mobile_runs",
+];
+
+/// The position of a feature in the feature vector
+pub fn index(name: &str) -> Option<usize> {
+    NAMES.iter().position(|n| *n == name)
+}
+
+/// Compute the features of a position
+pub fn extract(view: &SolitaireObserver) -> FeatureVec {
+    let mut f = Vec::with_capacity(N_FEATURES);
+    let foundation_len = |t: &Option<(Suit, Value)>| t.map_or(0, |(_, v)| v.numeric_value() as i32);
+    let hidden = view.depots.each_ref().map(|d| d.n_face_down as i32);
+    let run_quality = view.run_quality();
+
+    f.push(view.talon_size as i32);
+    f.push(view.waste.len() as i32);
+    f.push(
+        view.waste
+            .last()
+            .map_or(0, |(_, v)| v.numeric_value() as i32),
+    );
+
+    f.push(view.foundation_tops.iter().map(foundation_len).sum());
+    f.push(
+        view.foundation_tops
+            .iter()
+            .map(foundation_len)
+            .min()
+            .unwrap_or(0),
+    );
+    f.push(
+        view.foundation_tops
+            .iter()
+            .map(foundation_len)
+            .max()
+            .unwrap_or(0),
+    );
+    let progress = view.foundation_progress().map(i32::from);
+    f.extend(progress);
+    let slowest = progress.iter().min().expect("There are four suits");
+    f.push(progress.iter().map(|p| (p - slowest - 2).max(0)).sum());
+
+    f.extend(hidden);
+    f.extend(run_quality.iter().map(|r| r.length as i32));
+    f.extend(view.depots.iter().map(|d| d.len() as i32));
+    f.push(view.depots.iter().filter(|d| d.is_empty()).count() as i32);
+    f.push(hidden.iter().sum());
+
+    let tops = || {
+        view.depots
+            .iter()
+            .filter_map(|d| d.card(1))
+            .chain(view.waste.last().map(|&c| c.into()))
+    };
+    let faceup_below_top = || {
+        view.depots
+            .iter()
+            .flat_map(|d| d.face_up.iter().rev().skip(1))
+    };
+    f.push(
+        tops()
+            .filter(|c| matches!(c, CardView::FaceUp(_, v) if v.is_ace()))
+            .count() as i32,
+    );
+    f.push(faceup_below_top().filter(|(_, v)| v.is_ace()).count() as i32);
+    f.push(
+        view.needed_cards()
+            .iter()
+            .filter(|(_, loc)| loc.is_on_top())
+            .count() as i32,
+    );
+    f.push(
+        view.depots
+            .iter()
+            .filter(|d| matches!(d.iter().next(), Some(CardView::FaceUp(_, v)) if v.is_king()))
+            .count() as i32,
+    );
+    f.push(
+        view.depots
+            .iter()
+            .flat_map(|d| d.iter().skip(1))
+            .filter(|c| matches!(c, CardView::FaceUp(_, v) if v.is_king()))
+            .count() as i32,
+    );
+
+    f.extend(view.depots.iter().map(|d| match d.card(1) {
+        Some(CardView::FaceUp(_, v)) => v.numeric_value() as i32,
+        _ => 0,
+    }));
+
+    let mut depot_moves = 0;
+    for from in Addr::DEPOTS {
+        for n in 1..=view.n_takeable_cards(&from) {
+            let card = view.card_at(&from, n).expect("Takeable cards exist");
+            depot_moves += Addr::DEPOTS
+                .iter()
+                .filter(|to| **to != from && view.fits_on_depot(card, to))
+                .count() as i32;
+        }
+    }
+    f.push(depot_moves);
+    f.push(match view.card_at(&Addr::Waste, 1) {
+        Some(card) => Addr::DEPOTS
+            .iter()
+            .filter(|to| view.fits_on_depot(card, to))
+            .count() as i32,
+        None => 0,
+    });
+    f.push(
+        view.depots
+            .iter()
+            .filter(|d| d.card(1) == Some(CardView::FaceDown))
+            .count() as i32,
+    );
+    f.push(run_quality.iter().filter(|r| r.mobility > 0).count() as i32);
+
+    f.try_into()
+        .expect("There should be exactly one value per feature name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::DepotView;
+    use crate::rules::{BuildRule, EmptyColumnRule};
+
+    #[test]
+    fn extract_small_position() {
+        let view = SolitaireObserver {
+            talon_size: 3,
+            waste: vec![(Suit::Spades, Value::ACE)],
+            foundation_tops: [Some((Suit::Hearts, Value::TWO)), None, None, None],
+            depots: [
+                DepotView::new(
+                    1,
+                    vec![(Suit::Hearts, Value::KING), (Suit::Clubs, Value::QUEEN)],
+                ),
+                DepotView::new(0, vec![(Suit::Diamonds, Value::QUEEN)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        let features = extract(&view);
+        let get = |name| features[index(name).unwrap()];
+        assert_eq!(get("talon"), 3);
+        assert_eq!(get("foundation_total"), 2);
+        assert_eq!(get("foundation_hearts"), 2);
+        assert_eq!(get("suit_lead"), 0);
+        assert_eq!(get("hidden_1"), 1);
+        assert_eq!(get("run_1"), 2);
+        assert_eq!(get("empty_depots"), 5);
+        assert_eq!(get("aces_available"), 1);
+        assert_eq!(get("foundation_moves"), 1);
+        assert_eq!(get("kings_buried"), 1);
+        assert_eq!(get("top_2"), 12);
+        // The king with the queen moves to five empty depots
+        assert_eq!(get("depot_moves"), 5);
+        assert_eq!(get("mobile_runs"), 1);
+
+        let mut lopsided = view.clone();
+        lopsided.foundation_tops[0] = Some((Suit::Hearts, Value::try_from(5).unwrap()));
+        assert_eq!(extract(&lopsided)[index("suit_lead").unwrap()], 3);
+    }
+}
@@ -0,0 +1,68 @@
+//! Fixed point arithmetic for the search AIs
+//!
+//! A number `x` is stored as the integer `x * ONE`, rounded down. The search AIs keep their rewards and priorities
+//! in this form rather than as floats, so that a search with a given seed and budget makes the same choices on all
+//! platforms. `ln` and `sqrt` are computed bit by bit from integer operations, since the float versions may differ
+//! in the last bit between platforms.
+
+/// The fixed point number 1
+pub const ONE: u64 = 1 << 20;
+
+/// The number of fraction bits, so that `ONE == 1 << FRACTION_BITS`
+const FRACTION_BITS: u32 = 20;
+
+/// ln(2) in fixed point
+const LN_2: u64 = 726_817;
+
+/// `numerator / denominator` in fixed point
+pub fn ratio(numerator: u64, denominator: u64) -> u64 {
+    numerator * ONE / denominator
+}
+
+/// The product of two fixed point numbers
+pub fn mul(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) >> FRACTION_BITS) as u64
+}
+
+/// The natural logarithm of a positive integer, in fixed point
+pub fn ln(n: u64) -> u64 {
+    assert!(n > 0, "The logarithm is only defined for positive numbers");
+    // The integer part of log2, and the rest scaled into [1, 2) as a fixed point number
+    let int_part = 63 - n.leading_zeros();
+    let mut y = ((n as u128) << FRACTION_BITS >> int_part) as u64;
+    let mut log2 = (int_part as u64) << FRACTION_BITS;
+    // Squaring doubles the logarithm, so each squaring gives the next bit of the fraction
+    for bit in (0..FRACTION_BITS).rev() {
+        y = mul(y, y);
+        if y >= 2 * ONE {
+            y >>= 1;
+            log2 |= 1 << bit;
+        }
+    }
+    mul(log2, LN_2)
+}
+
+/// The square root of a fixed point number
+pub fn sqrt(x: u64) -> u64 {
+    ((x as u128) << FRACTION_BITS).isqrt() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_to_the_float_functions() {
+        let to_float = |x: u64| x as f64 / ONE as f64;
+        for n in [1, 2, 3, 10, 1000, 123_456, u32::MAX as u64] {
+            assert!((to_float(ln(n)) - (n as f64).ln()).abs() < 1e-4, "ln {n}");
+        }
+        for x in [0.0, 0.25, 2.0, 7.5, 1e6] {
+            let fixed = (x * ONE as f64) as u64;
+            assert!((to_float(sqrt(fixed)) - x.sqrt()).abs() < 1e-5, "sqrt {x}");
+        }
+        assert_eq!(ln(1), 0);
+        assert_eq!(ratio(3, 4), 3 * ONE / 4);
+        assert_eq!(mul(ratio(1, 2), ratio(1, 2)), ratio(1, 4));
+    }
+}
@@ -1,27 +1,49 @@
 //! An AI player that plays greedy
 //!
-//! It will deem the Quit action to have -200 score, otherwise it will never turn the waste over
+//! The priority of an action is the points it gives in the scoring policy of the game.
+//! It will deem the Quit action to be worth 100 points less than turning the waste over (-200 with standard scoring),
+//! otherwise it will never turn the waste over
+//! Among actions worth the same points, the king to move to an empty depot is chosen by `analysis::king_choices`.
+//! Until the first card is revealed, an `OpeningPrior` may break the remaining ties, by the depot a move uncovers.
 //!
-use super::{Action, CardView, SolitaireObserver};
-use crate::core::{Addr, Suit, Value};
+use super::{softmax, Action, SeenActions, SolitaireObserver};
+use crate::analysis;
+use crate::core::Turned;
+use crate::rules::ScoringPolicy;
+
+/// Priorities this many points apart are `e` times as likely in `action_probabilities`
+const SOFTMAX_SCALE: f64 = 10.0;
 
 /// An AI player that plays greedy
 ///
 pub struct GreedyAi {
-    seen_state_action_combos: std::collections::HashSet<(SolitaireObserver, Action)>,
-    // have we made passes through the deck?
-    number_of_passes: u64,
+    seen_state_action_combos: SeenActions,
     view: SolitaireObserver,
+    /// The untried actions at the last move, best first
+    candidates: Vec<(i64, Action)>,
+    policy: ScoringPolicy,
+    opening_prior: Option<OpeningPrior>,
+    /// The face down cards in the depots at the start, to tell when the first one is revealed
+    n_face_down_at_start: usize,
 }
 
+/// How good it is to reveal the first card in each depot, e.g. the win rates of past games by the depot they revealed
+/// first, in thousandths. Higher is better.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpeningPrior(pub [u32; 7]);
+
 struct PrioritizedAction {
     priority: i64,
+    /// Breaks ties between actions of the same priority, higher is better
+    advice: usize,
+    /// Breaks the remaining ties in the opening, see `OpeningPrior`
+    opening: usize,
     action: Action,
 }
 
 impl From<(i64, Action)> for PrioritizedAction {
     fn from((priority, action): (i64, Action)) -> Self {
-        PrioritizedAction { priority, action }
+        PrioritizedAction { priority, advice: 0, opening: 0, action }
     }
 }
 
@@ -33,13 +55,13 @@ impl PartialOrd for PrioritizedAction {
 
 impl Ord for PrioritizedAction {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.priority.cmp(&other.priority)
+        (self.priority, self.advice, self.opening).cmp(&(other.priority, other.advice, other.opening))
     }
 }
 
 impl PartialEq for PrioritizedAction {
     fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority
+        (self.priority, self.advice, self.opening) == (other.priority, other.advice, other.opening)
     }
 }
 
@@ -47,122 +69,123 @@ impl PartialEq for PrioritizedAction {
 impl Eq for PrioritizedAction {}
 
 impl GreedyAi {
-    pub fn new(view: SolitaireObserver) -> Self {
+    /// A greedy AI that plays for the points of the given scoring policy
+    pub fn new(view: SolitaireObserver, policy: ScoringPolicy) -> Self {
         GreedyAi {
-            seen_state_action_combos: std::collections::HashSet::new(),
-            number_of_passes: 0,
+            seen_state_action_combos: SeenActions::default(),
+            n_face_down_at_start: n_face_down(&view),
             view,
+            candidates: vec![],
+            policy,
+            opening_prior: None,
+        }
+    }
+
+    /// Break ties in the opening by the depot a move uncovers
+    pub fn with_opening_prior(mut self, prior: OpeningPrior) -> Self {
+        self.opening_prior = Some(prior);
+        self
+    }
+
+    fn prioritized(&self, action: Action, kings: &[(Action, usize)]) -> PrioritizedAction {
+        let advice = kings.iter().find(|(king, _)| *king == action).map_or(0, |(_, score)| *score);
+        let opening = self.opening(&action);
+        PrioritizedAction { priority: self.policy.points(&action) as i64, advice, opening, action }
+    }
+
+    /// How the opening prior ranks a move that uncovers the first face down card, or 0 for any other action
+    fn opening(&self, action: &Action) -> usize {
+        let (Some(OpeningPrior(prior)), Action::Move(from, _, n)) = (&self.opening_prior, action) else {
+            return 0;
+        };
+        let Some(i) = from.depot_id().map(|depot| depot.index()) else {
+            return 0;
+        };
+        let depot = &self.view.depots[i];
+        if n_face_down(&self.view) != self.n_face_down_at_start || depot.n_face_down == 0 || depot.face_up.len() != *n {
+            return 0;
         }
+        prior[i] as usize + 1
     }
 
     /// Produce all valid moves that we potentially would like to make in a prioritized order
     ///
     /// Prioritizes moves that give more score
-    fn suggest_actions(&mut self) -> Vec<Action> {
+    fn suggest_actions(&mut self) -> Vec<(i64, Action)> {
         if self.view.is_won() {
-            return vec![Action::Quit];
+            return vec![(0, Action::Quit)];
         }
         let mut actions: std::collections::BinaryHeap<PrioritizedAction> =
             std::collections::BinaryHeap::new();
 
-        // Build on foundations
-        for from_addr in Addr::DEPOTS_AND_WASTE.iter() {
-            if let Some(CardView::FaceUp(suit, value)) = self.view.card_at(from_addr, 1) {
-                for to_addr in Addr::FOUNDATIONS {
-                    match self.view.card_at(&to_addr, 1) {
-                        None => {
-                            if value.is_ace() {
-                                actions.push((10, Action::Move(*from_addr, to_addr, 1)).into());
-                            }
-                        }
-                        // increase by one
-                        Some(CardView::FaceUp(to_suit, to_value)) => {
-                            if suit == to_suit
-                                && value.numeric_value() == to_value.numeric_value() + 1
-                            {
-                                actions.push((10, Action::Move(*from_addr, to_addr, 1)).into());
-                            }
-                        }
-                        Some(CardView::FaceDown) => {
-                            continue;
-                        }
-                    }
-                }
-            }
-        }
-
-        // Try to reveal a card
-        for (idx, a) in self.view.depots.iter().enumerate() {
-            if let Some(CardView::FaceDown) = a.last() {
-                actions.push((5, Action::Reveal(Addr::DEPOTS[idx])).into());
-            }
-        }
-
-        // Try to increase the sequences in the tableaux
-        for from in Addr::DEPOTS_AND_WASTE {
-            let max_cards_to_move = self.view.n_takeable_cards(&from);
-            if max_cards_to_move == 0 {
-                continue;
-            }
-            for to in Addr::DEPOTS.into_iter().filter(|to| to != &from) {
-                let score = if from.is_foundation() && to.is_depot() {
-                    -15
-                } else if from.is_waste() && to.is_foundation() {
-                    10
-                } else if from.is_waste() && to.is_depot() {
-                    5
-                } else {
-                    0
-                };
-
-                for n_moves in 1..=max_cards_to_move {
-                    if let Some(CardView::FaceUp(suit, value)) = self.view.card_at(&from, n_moves) {
-                        match self.view.card_at(&to, 1) {
-                            None => {
-                                if value == Value::KING {
-                                    actions.push((score, Action::Move(from, to, n_moves)).into());
-                                }
-                            }
-                            Some(CardView::FaceUp(suit2, value2)) => {
-                                let is_valid_move = suit.color() != suit2.color()
-                                    && value.numeric_value() == value2.numeric_value() - 1;
-                                if is_valid_move {
-                                    actions.push((score, Action::Move(from, to, n_moves)).into());
-                                }
-                            }
-                            Some(CardView::FaceDown) => { /* do nothing */ }
-                        }
-                    }
-                }
-            }
-        }
-
-        // Take from the talon
-        if self.view.talon_size != 0 {
-            actions.push((0, Action::Take).into());
-        }
-
-        // Turn over the talon
-        if self.view.waste.last().is_some() && self.view.talon_size == 0 {
-            actions.push((-100, Action::Turnover).into());
+        // Build on foundations, reveal cards, increase the sequences in the tableaux, and take from the talon or turn it over
+        let kings = analysis::king_choices(&self.view);
+        for action in self.view.forward_actions() {
+            actions.push(self.prioritized(action, &kings));
         }
 
         // Give up
-        actions.push((-200, Action::Quit).into());
+        let quit_priority = self.policy.turnover.min(0) as i64 - 100;
+        actions.push((quit_priority, Action::Quit).into());
         actions
             .into_sorted_vec()
             .into_iter()
             .rev()
-            .map(|a| a.action)
+            .map(|a| (a.priority, a.action))
             .collect()
     }
 }
 
+fn n_face_down(view: &SolitaireObserver) -> usize {
+    view.depots.iter().map(|d| d.n_face_down).sum()
+}
+
+impl super::Ai for GreedyAi {
+    fn make_move(&mut self) -> Action {
+        if self.seen_state_action_combos.is_full() {
+            return Action::Quit;
+        }
+        let actions = self.suggest_actions();
+        self.candidates = actions
+            .into_iter()
+            .filter(|(_, action)| {
+                !self
+                    .seen_state_action_combos.contains(&self.view, action)
+            })
+            .collect();
+        let Some((_, action)) = self.candidates.first().cloned() else {
+            panic!("No action found");
+        };
+        action
+    }
+    fn name(&self) -> &'static str {
+        "GreedyAi"
+    }
+    fn n_nodes(&self) -> usize {
+        self.seen_state_action_combos.n_nodes()
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        self.seen_state_action_combos.set_limit(limit)
+    }
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        self.candidates.clone()
+    }
+    fn action_probabilities(&self) -> Vec<(f64, Action)> {
+        softmax(&self.candidates, SOFTMAX_SCALE)
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.seen_state_action_combos.insert(&self.view, &action);
+        self.view.update(action, res)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::core::Suit;
+    use crate::ai::{Ai, DepotView};
+    use crate::core::{Addr, Suit, Value};
 
     use super::*;
+    use crate::rules::{BuildRule, EmptyColumnRule};
 
     #[test]
     fn test_ai_can_win() {
@@ -171,47 +194,108 @@ mod tests {
             waste: vec![],
             foundation_tops: [None; 4],
             depots: [
-                vec![CardView::FaceUp(Suit::Hearts, Value::KING)],
-                vec![CardView::FaceUp(Suit::Clubs, Value::QUEEN)],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
+                DepotView::new(0, vec![(Suit::Hearts, Value::KING)]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::QUEEN)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
             ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
         };
-        let mut ai = GreedyAi::new(view);
+        let mut ai = GreedyAi::new(view, ScoringPolicy::STANDARD);
         let actions = ai.suggest_actions();
         assert!(
-            actions.contains(&Action::Move(Addr::Depot2, Addr::Depot1, 1)),
+            actions
+                .iter()
+                .any(|(_, a)| *a == Action::Move(Addr::Depot2, Addr::Depot1, 1)),
             "Should be able to move queen of clubs to king of hearts"
         );
     }
-}
 
-impl super::Ai for GreedyAi {
-    fn make_move(&mut self) -> Action {
-        let actions = self.suggest_actions();
-        for action in actions {
-            if self
-                .seen_state_action_combos
-                .contains(&(self.view.clone(), action.clone()))
-            {
-                continue;
-            }
-            self.seen_state_action_combos
-                .insert((self.view.clone(), action.clone()));
-            if action == Action::Turnover {
-                self.number_of_passes += 1;
-            }
-            return action;
-        }
-        panic!("No action found");
+    #[test]
+    fn priorities_follow_policy() {
+        let view = SolitaireObserver {
+            talon_size: 1,
+            waste: vec![(Suit::Hearts, Value::ACE)],
+            foundation_tops: [None; 4],
+            depots: Default::default(),
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        let to_foundation = Action::Move(Addr::Waste, Addr::Foundation1, 1);
+        let mut ai = GreedyAi::new(view.clone(), ScoringPolicy::STANDARD);
+        assert!(ai.suggest_actions().contains(&(10, to_foundation.clone())));
+        let mut ai = GreedyAi::new(view, ScoringPolicy::VEGAS);
+        let actions = ai.suggest_actions();
+        assert_eq!(actions[0], (5, to_foundation));
+        assert_eq!(actions.last(), Some(&(-100, Action::Quit)));
+        assert_eq!(ai.make_move(), actions[0].1);
+        let probabilities = ai.action_probabilities();
+        assert_eq!(probabilities[0].1, actions[0].1);
+        assert!(probabilities.windows(2).all(|w| w[0].0 >= w[1].0));
+        assert!((probabilities.iter().map(|(p, _)| p).sum::<f64>() - 1.0).abs() < 1e-12);
     }
-    fn name(&self) -> &'static str {
-        "GreedyAi"
+
+    #[test]
+    fn moves_the_king_that_unblocks_most() {
+        let view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![(Suit::Spades, Value::KING)],
+            foundation_tops: [None; 4],
+            depots: [
+                DepotView::new(1, vec![(Suit::Hearts, Value::KING)]),
+                DepotView::default(),
+                DepotView::new(1, vec![(Suit::Diamonds, Value::QUEEN)]),
+                DepotView::new(1, vec![(Suit::Hearts, Value::QUEEN)]),
+                DepotView::new(1, vec![(Suit::Clubs, Value::try_from(11).unwrap())]),
+                DepotView::new(0, vec![(Suit::Diamonds, Value::TWO)]),
+                DepotView::new(0, vec![(Suit::Diamonds, Value::try_from(3).unwrap())]),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        // Both red queens wait for the king of spades
+        let mut ai = GreedyAi::new(view, ScoringPolicy::VEGAS);
+        assert_eq!(ai.suggest_actions()[0], (0, Action::Move(Addr::Waste, Addr::Depot2, 1)));
     }
-    fn update(&mut self, action: Action, res: Option<(Suit, Value)>) {
-        self.view.update(action, res)
+
+    #[test]
+    fn opening_prior_breaks_ties() {
+        let view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![],
+            foundation_tops: [None; 4],
+            depots: [
+                DepotView::new(0, vec![(Suit::Spades, Value::KING)]),
+                DepotView::new(1, vec![(Suit::Hearts, Value::QUEEN)]),
+                DepotView::new(1, vec![(Suit::Diamonds, Value::QUEEN)]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::try_from(5).unwrap())]),
+                DepotView::new(0, vec![(Suit::Spades, Value::try_from(5).unwrap())]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::try_from(7).unwrap())]),
+                DepotView::new(0, vec![(Suit::Spades, Value::try_from(7).unwrap())]),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        // Either red queen can go on the king, and uncover a card
+        let priors = [
+            ([0, 200, 400, 0, 0, 0, 0], Addr::Depot3),
+            ([0, 400, 200, 0, 0, 0, 0], Addr::Depot2),
+        ];
+        for (prior, from) in priors {
+            let mut ai = GreedyAi::new(view.clone(), ScoringPolicy::VEGAS).with_opening_prior(OpeningPrior(prior));
+            assert_eq!(ai.suggest_actions()[0], (0, Action::Move(from, Addr::Depot1, 1)));
+        }
     }
 }
@@ -0,0 +1,166 @@
+//! An AI player that looks one move ahead with the evaluation function
+//!
+//! The outcome of a move between piles is known to the observer, so each such move is tried on a copy of the view
+//! and the position is scored by `eval::evaluate`. Revealing is always good. Taking from the talon and turning it over
+//! have outcomes we cannot see, so they are only done when no move improves the position.
+//!
+use super::eval::{self, Weights};
+use super::{Action, Ai, CardView, SeenActions, SolitaireObserver};
+use crate::core::{Addr, Turned};
+
+/// An AI player that plays the move to the best evaluated position
+pub struct LinearAi {
+    weights: Weights,
+    /// The untried actions at the last move, best first
+    candidates: Vec<(i64, Action)>,
+    seen_state_action_combos: SeenActions,
+    view: SolitaireObserver,
+}
+
+impl LinearAi {
+    /// A linear AI with the default weights
+    pub fn new(view: SolitaireObserver) -> Self {
+        Self::with_weights(view, eval::default_weights())
+    }
+
+    pub fn with_weights(view: SolitaireObserver, weights: Weights) -> Self {
+        LinearAi {
+            weights,
+            candidates: vec![],
+            seen_state_action_combos: SeenActions::default(),
+            view,
+        }
+    }
+
+    /// All moves of cards from the depots and the waste, to the foundations or the depots
+    fn candidate_moves(&self) -> Vec<Action> {
+        let mut moves = vec![];
+        for from in Addr::DEPOTS_AND_WASTE {
+            if let Some(CardView::FaceUp(suit, value)) = self.view.card_at(&from, 1) {
+                for to in Addr::FOUNDATIONS {
+                    let fits = match self.view.card_at(&to, 1) {
+                        None => value.is_ace(),
+                        Some(CardView::FaceUp(to_suit, to_value)) => {
+                            suit == to_suit && value.numeric_value() == to_value.numeric_value() + 1
+                        }
+                        Some(CardView::FaceDown) => false,
+                    };
+                    if fits {
+                        moves.push(Action::Move(from, to, 1));
+                        break;
+                    }
+                }
+            }
+            for n in 1..=self.view.n_takeable_cards(&from) {
+                let card = self.view.card_at(&from, n).expect("Takeable cards exist");
+                for to in Addr::DEPOTS.into_iter().filter(|to| *to != from) {
+                    if self.view.fits_on_depot(card, &to) {
+                        moves.push(Action::Move(from, to, n));
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn is_new(&self, action: &Action) -> bool {
+        !self.seen_state_action_combos.contains(&self.view, action)
+    }
+
+    /// All untried actions with their scores, best first
+    ///
+    /// A move is scored by the evaluation of the position after it. Taking from the talon or turning it over is scored
+    /// as the current position, and wins ties against moves.
+    fn rank_actions(&self) -> Vec<(i64, Action)> {
+        if self.view.is_won() {
+            return vec![(0, Action::Quit)];
+        }
+        if let Some(idx) = self
+            .view
+            .depots
+            .iter()
+            .position(|d| d.card(1) == Some(CardView::FaceDown))
+        {
+            return vec![(i64::MAX, Action::Reveal(Addr::DEPOTS[idx]))];
+        }
+
+        let mut actions = vec![];
+        let current = eval::evaluate(&self.weights, &self.view);
+        if self.view.talon_size > 0 {
+            actions.push((current, Action::Take));
+        } else if !self.view.waste.is_empty() && self.view.redeals_left != Some(0) {
+            actions.push((current, Action::Turnover));
+        }
+        for action in self.candidate_moves() {
+            let mut after = self.view.clone();
+            after.update(action.clone(), Turned::NONE);
+            actions.push((eval::evaluate(&self.weights, &after), action));
+        }
+        actions.retain(|(_, a)| self.is_new(a));
+        // Stable, so that ties keep the draw first
+        actions.sort_by_key(|(value, _)| std::cmp::Reverse(*value));
+        actions.push((i64::MIN, Action::Quit));
+        actions
+    }
+}
+
+impl Ai for LinearAi {
+    fn make_move(&mut self) -> Action {
+        if self.seen_state_action_combos.is_full() {
+            return Action::Quit;
+        }
+        self.candidates = self.rank_actions();
+        self.candidates[0].1.clone()
+    }
+    fn name(&self) -> &'static str {
+        "LinearAi"
+    }
+    fn n_nodes(&self) -> usize {
+        self.seen_state_action_combos.n_nodes()
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        self.seen_state_action_combos.set_limit(limit)
+    }
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        self.candidates.clone()
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.seen_state_action_combos.insert(&self.view, &action);
+        self.view.update(action, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::DepotView;
+    use crate::core::{Suit, Value};
+    use crate::rules::{BuildRule, EmptyColumnRule};
+
+    #[test]
+    fn prefers_move_to_foundation() {
+        let view = SolitaireObserver {
+            talon_size: 5,
+            waste: vec![],
+            foundation_tops: [None; 4],
+            depots: [
+                DepotView::new(0, vec![(Suit::Hearts, Value::KING)]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::ACE)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        let mut ai = LinearAi::new(view);
+        assert_eq!(
+            ai.make_move(),
+            Action::Move(Addr::Depot2, Addr::Foundation1, 1)
+        );
+    }
+}
@@ -0,0 +1,494 @@
+//! AI players that use Monte Carlo tree search
+//!
+//! The hidden cards are placed at random (a determinization, see `GameEngine::determinize`), and a search tree over
+//! the actions is grown by playing many random games from the current position. The action that was tried the most at
+//! the root is played. There are two variants:
+//!
+//! - Plain MCTS determinizes once per move, and searches the game that comes out of it as if all cards were known.
+//! - Information set MCTS (ISMCTS) determinizes anew in every iteration. A node then stands for all positions that
+//!   look the same to the player after the same actions, and the statistics are shared between the determinizations.
+//!   An action may be legal in some of them but not in others, so each child also counts how often it was available,
+//!   and the exploration term uses that count instead of the visits of the parent.
+//!
+//! In a game with open information (Thoughtful solitaire), all cards are known, and both search the game itself.
+//!
+//! The playouts are random moves, or the moves of another AI given as the rollout policy. A heuristic AI makes
+//! the playouts much more like real games.
+//!
+//! The reward of a playout is the fraction of the cards on the foundations at the end, so that a win is worth 1.
+//! It is discounted by the number of actions from the root, so that progress made sooner counts for more.
+//! A playout is cut off after a number of actions. The position it was cut off in may be scored partly by the
+//! evaluation function, see `MctsAi::with_eval_blend`. A game that `analysis::is_hopeless` is scored as 0 right away.
+//!
+//! The rewards and the UCB1 priorities are fixed point numbers, see `fixed`, so that a search with a given seed and
+//! number of iterations plays the same moves on all platforms.
+use super::eval::{self, Weights};
+use super::fixed::{self, ONE};
+use super::{rng_checkpoint, Action, Ai, OpenObserver, SeenActions, SolitaireObserver};
+use crate::analysis;
+use crate::core::{Suit, Turned, Value};
+use crate::engine::GameEngine;
+use crate::rules::{BuildRule, EmptyColumnRule, RuleSet};
+use rand::prelude::*;
+
+/// The default number of iterations per move
+pub const DEFAULT_ITERATIONS: usize = 100;
+
+/// The default number of actions after which a playout is cut off and scored where it stands
+pub const DEFAULT_ROLLOUT_DEPTH: usize = 50;
+
+/// The reward is discounted by this factor per action, 0.99
+const DISCOUNT: u64 = ONE * 99 / 100;
+
+/// The weight of exploration in the UCB1 formula, 0.7
+const EXPLORATION: u64 = ONE * 7 / 10;
+
+/// Creates the AI that plays a playout, from the view of the determinized game
+pub type RolloutPolicy = fn(SolitaireObserver, &RuleSet) -> Box<dyn Ai>;
+
+/// A node of the search tree
+struct Node {
+    /// The action that leads here from the parent
+    action: Action,
+    children: Vec<usize>,
+    visits: u32,
+    /// How many times the parent was visited when this action was legal
+    availability: u32,
+    total_reward: u64,
+}
+
+impl Node {
+    fn new(action: Action) -> Self {
+        Node {
+            action,
+            children: vec![],
+            visits: 0,
+            availability: 1,
+            total_reward: 0,
+        }
+    }
+
+    fn ucb(&self) -> u64 {
+        let visits = self.visits.max(1) as u64;
+        let exploration = fixed::sqrt(fixed::ln(self.availability as u64) / visits);
+        self.total_reward / visits + fixed::mul(EXPLORATION, exploration)
+    }
+}
+
+/// An AI player that searches with MCTS or ISMCTS
+pub struct MctsAi {
+    view: SolitaireObserver,
+    rules: RuleSet,
+    iterations: usize,
+    information_sets: bool,
+    /// The AI that plays the playouts, or random moves if `None`
+    rollout: Option<RolloutPolicy>,
+    rollout_depth: usize,
+    /// The share of the evaluation function in the reward of a playout that is cut off, in percent
+    eval_blend: u64,
+    weights: Weights,
+    rng: StdRng,
+    seen_state_action_combos: SeenActions,
+    /// The size of the last search tree
+    n_tree_nodes: usize,
+    /// The nodes the search tree and the seen actions may have together, see `Ai::set_node_limit`
+    node_limit: Option<usize>,
+    /// The untried actions at the last move, most visited first
+    candidates: Vec<(i64, Action)>,
+    /// All cards, if the game has open information and they were shown since the last update
+    open: Option<OpenObserver>,
+}
+
+impl MctsAi {
+    /// Plain MCTS on one determinization per move
+    pub fn new(view: SolitaireObserver, rules: &RuleSet) -> Self {
+        MctsAi {
+            view,
+            rules: rules.clone(),
+            iterations: DEFAULT_ITERATIONS,
+            information_sets: false,
+            rollout: None,
+            rollout_depth: DEFAULT_ROLLOUT_DEPTH,
+            eval_blend: 0,
+            weights: eval::default_weights(),
+            rng: StdRng::seed_from_u64(0),
+            seen_state_action_combos: SeenActions::default(),
+            n_tree_nodes: 0,
+            node_limit: None,
+            candidates: vec![],
+            open: None,
+        }
+    }
+
+    /// Information set MCTS, with a new determinization in each iteration
+    pub fn information_set(view: SolitaireObserver, rules: &RuleSet) -> Self {
+        MctsAi {
+            information_sets: true,
+            ..MctsAi::new(view, rules)
+        }
+    }
+
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn with_rollout(mut self, policy: RolloutPolicy) -> Self {
+        self.rollout = Some(policy);
+        self
+    }
+
+    pub fn with_rollout_depth(mut self, depth: usize) -> Self {
+        self.rollout_depth = depth;
+        self
+    }
+
+    /// Score the positions where playouts are cut off partly by the evaluation function
+    ///
+    /// `percent` of the reward is then the evaluation relative to that of a won game, and the rest is the fraction
+    /// of the cards on the foundations as usual.
+    pub fn with_eval_blend(mut self, percent: u8) -> Self {
+        self.eval_blend = percent.min(100) as u64;
+        self
+    }
+
+    /// The game to search: the real one if all cards are known, or else a determinization
+    fn game(&mut self) -> GameEngine {
+        match &self.open {
+            Some(open) => GameEngine::from_open(open, &self.rules),
+            None => GameEngine::determinize(&self.view, &self.rules, &mut self.rng),
+        }
+    }
+
+    /// Grow a search tree from the current position, and return the root's children with their visits
+    ///
+    /// Once the tree has as many nodes as the node limit leaves room for, it stops growing, and the iterations play
+    /// out from where they would have added a node.
+    fn search(&mut self) -> Vec<(u32, Action)> {
+        let max_tree_nodes = self.node_limit.map_or(usize::MAX, |limit| {
+            limit.saturating_sub(self.seen_state_action_combos.n_nodes())
+        });
+        let mut tree = vec![Node::new(Action::Quit)];
+        let mut gs = self.game();
+        for _ in 0..self.iterations {
+            if self.information_sets {
+                gs = self.game();
+            }
+            let mut path = vec![0];
+            let mut node = 0;
+
+            // Select down the tree, until an untried action is expanded
+            while gs.is_running() {
+                let mut legal = gs.legal_actions();
+                if node == 0 {
+                    legal.retain(|a| !self.seen_state_action_combos.contains(&self.view, a));
+                }
+                if legal.is_empty() {
+                    break;
+                }
+                let mut best: Option<usize> = None;
+                for child in tree[node].children.clone() {
+                    if let Some(pos) = legal.iter().position(|a| *a == tree[child].action) {
+                        legal.swap_remove(pos);
+                        tree[child].availability += 1;
+                        if best.is_none_or(|b| tree[child].ucb() > tree[b].ucb()) {
+                            best = Some(child);
+                        }
+                    }
+                }
+                // The remaining legal actions are untried, and the playout starts here if the tree is full
+                if !legal.is_empty() && tree.len() >= max_tree_nodes {
+                    break;
+                }
+                if let Some(action) = legal.choose(&mut self.rng).cloned() {
+                    gs.act(&action).expect("The action is legal");
+                    tree.push(Node::new(action));
+                    let child = tree.len() - 1;
+                    tree[node].children.push(child);
+                    path.push(child);
+                    break;
+                }
+                let child = best.expect("All legal actions are children");
+                gs.act(&tree[child].action).expect("The action is legal");
+                path.push(child);
+                node = child;
+            }
+
+            let reward = self.playout(&mut gs, path.len() - 1);
+            for &n in path.iter() {
+                tree[n].visits += 1;
+                tree[n].total_reward += reward;
+            }
+            // Back to the root for the next iteration, which is cheaper than starting from a copy of it
+            while gs.undo().is_some() {}
+        }
+        self.n_tree_nodes = tree.len();
+        let mut children = tree[0]
+            .children
+            .iter()
+            .map(|&c| (tree[c].visits, tree[c].action.clone()))
+            .collect::<Vec<_>>();
+        children.sort_by_key(|(visits, _)| std::cmp::Reverse(*visits));
+        children
+    }
+
+    /// Play random actions to the end of the game, or until the playout is long enough. Returns the reward.
+    ///
+    /// `depth` is the number of actions already taken from the root.
+    fn playout(&mut self, gs: &mut GameEngine, depth: usize) -> u64 {
+        // Don't waste a playout on a game that can't be won
+        if analysis::is_hopeless(gs) {
+            return 0;
+        }
+        let mut n_actions = depth;
+        let mut policy = self.rollout.map(|make| make(gs.observe(), &self.rules));
+        let mut cut_off = true;
+        for _ in 0..self.rollout_depth {
+            if !gs.is_running() {
+                cut_off = false;
+                break;
+            }
+            let action = match policy.as_mut() {
+                Some(ai) => ai.make_move(),
+                None => gs
+                    .legal_actions()
+                    .choose(&mut self.rng)
+                    .cloned()
+                    .unwrap_or(Action::Quit),
+            };
+            if action == Action::Quit {
+                cut_off = false;
+                break;
+            }
+            let res = gs.act(&action).expect("The action is legal");
+            if let Some(ai) = policy.as_mut() {
+                ai.update(action, res);
+            }
+            n_actions += 1;
+        }
+        let n_foundation = gs
+            .cards()
+            .filter(|(addr, _, _)| addr.is_foundation())
+            .count();
+        let mut reward = fixed::ratio(n_foundation as u64, 52);
+        if cut_off && gs.is_running() && self.eval_blend > 0 {
+            let won = eval::evaluate(&self.weights, &won_view()).max(1);
+            let evaluation = eval::evaluate(&self.weights, &gs.observe()).clamp(0, won);
+            let evaluation = fixed::ratio(evaluation as u64, won as u64);
+            reward = ((100 - self.eval_blend) * reward + self.eval_blend * evaluation) / 100;
+        }
+        (0..n_actions).fold(reward, |reward, _| fixed::mul(reward, DISCOUNT))
+    }
+}
+
+/// The view of a won game
+fn won_view() -> SolitaireObserver {
+    SolitaireObserver {
+        talon_size: 0,
+        waste: vec![],
+        foundation_tops: [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades]
+            .map(|s| Some((s, Value::KING))),
+        depots: Default::default(),
+        building: BuildRule::AlternateColors,
+        empty_column: EmptyColumnRule::KingsOnly,
+        foundation_retreat: true,
+        redeals_left: None,
+    }
+}
+
+impl Ai for MctsAi {
+    fn make_move(&mut self) -> Action {
+        if self.view.is_won() || self.seen_state_action_combos.is_full() {
+            return Action::Quit;
+        }
+        self.candidates = self
+            .search()
+            .into_iter()
+            .map(|(visits, action)| (visits as i64, action))
+            .collect();
+        self.candidates.push((i64::MIN, Action::Quit));
+        self.candidates[0].1.clone()
+    }
+    fn name(&self) -> &'static str {
+        if self.information_sets {
+            "IsmctsAi"
+        } else {
+            "MctsAi"
+        }
+    }
+    fn n_nodes(&self) -> usize {
+        self.seen_state_action_combos.n_nodes() + self.n_tree_nodes
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        self.seen_state_action_combos.set_limit(limit);
+        self.node_limit = Some(limit);
+    }
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        self.candidates.clone()
+    }
+    /// The share of the visits of the last search, or the action played if nothing was visited
+    fn action_probabilities(&self) -> Vec<(f64, Action)> {
+        let visits = |score: i64| score.max(0) as f64;
+        let total = self
+            .candidates
+            .iter()
+            .map(|(score, _)| visits(*score))
+            .sum::<f64>();
+        if total == 0.0 {
+            return self
+                .candidates
+                .first()
+                .map(|(_, a)| (1.0, a.clone()))
+                .into_iter()
+                .collect();
+        }
+        self.candidates
+            .iter()
+            .map(|(score, action)| (visits(*score) / total, action.clone()))
+            .collect()
+    }
+    fn rng_checkpoint(&self) -> Option<u64> {
+        Some(rng_checkpoint(&self.rng))
+    }
+    fn observe_open(&mut self, open: &OpenObserver) {
+        self.open = Some(open.clone());
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.open = None;
+        self.seen_state_action_combos.insert(&self.view, &action);
+        self.view.update(action, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::DepotView;
+    use crate::ai::GreedyAi;
+    use crate::core::Addr;
+
+    /// All cards but the king of spades are on the foundations
+    fn almost_won() -> SolitaireObserver {
+        let mut view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![],
+            foundation_tops: [None; 4],
+            depots: Default::default(),
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        for (i, suit) in [Suit::Hearts, Suit::Diamonds, Suit::Clubs]
+            .into_iter()
+            .enumerate()
+        {
+            view.foundation_tops[i] = Some((suit, Value::KING));
+        }
+        view.foundation_tops[3] = Some((Suit::Spades, Value::QUEEN));
+        view.depots[0] = DepotView::new(0, vec![(Suit::Spades, Value::KING)]);
+        view
+    }
+
+    #[test]
+    fn finds_the_winning_move() {
+        let rules = RuleSet::default();
+        for mut ai in [
+            MctsAi::new(almost_won(), &rules),
+            MctsAi::information_set(almost_won(), &rules),
+        ] {
+            assert_eq!(
+                ai.make_move(),
+                Action::Move(Addr::Depot1, Addr::Foundation4, 1)
+            );
+        }
+    }
+
+    #[test]
+    fn playout_with_a_policy() {
+        let rules = RuleSet::default();
+        let mut ai = MctsAi::new(almost_won(), &rules)
+            .with_rollout(|view, rules| Box::new(GreedyAi::new(view, rules.scoring.policy())));
+        let mut gs = GameEngine::determinize(&almost_won(), &rules, &mut ai.rng.clone());
+        // The greedy AI wins right away
+        assert_eq!(ai.playout(&mut gs, 0), DISCOUNT);
+        assert!(gs.is_won());
+
+        // A playout cut off right away is scored by the evaluation function alone
+        let mut ai = MctsAi::new(almost_won(), &rules)
+            .with_rollout_depth(0)
+            .with_eval_blend(100);
+        let mut gs = GameEngine::determinize(&almost_won(), &rules, &mut ai.rng.clone());
+        let reward = ai.playout(&mut gs, 0);
+        assert!(ONE * 9 / 10 < reward && reward < ONE, "{reward}");
+    }
+
+    #[test]
+    fn tree_stays_within_the_node_limit() {
+        let rules = RuleSet::default();
+        let mut gs = GameEngine::deal(4);
+        let mut ai = MctsAi::new(gs.observe(), &rules).with_iterations(100);
+        ai.set_node_limit(30);
+        for _ in 0..10 {
+            let action = ai.make_move();
+            assert!(ai.n_nodes() <= 30, "{} nodes", ai.n_nodes());
+            if action == Action::Quit {
+                break;
+            }
+            ai.update(action.clone(), gs.act(&action).unwrap());
+        }
+        assert!(ai.n_nodes() > 20, "The tree grows up to the limit");
+    }
+
+    /// A search with a fixed seed and number of iterations plays the same game on every platform.
+    /// The games are pinned by their score and the encoding of the position after 60 actions, so a change here is a
+    /// change of the search, or of its arithmetic. One of them plays its playouts with the greedy AI.
+    #[test]
+    fn same_games_everywhere() {
+        let rules = RuleSet::default();
+        let pinned = [
+            (false, false, 1, 15, "090a3c32190a28092a3d170531210001020423032d0c3b0400002c0b03000026050000000034050000000027080000000000003716"),
+            (true, false, 2, 25, "0e02232a00210231060d2c0b3a19380426052413040018371603001c3b03000000040000001b08000000001a290827"),
+            (false, true, 3, 90, "060f183c0a3d2812263b233829361d051711310421012d03001a3903000024000300001b030000000b0000000000002716251433"),
+        ];
+        for (information_sets, greedy_rollout, seed, score, encoding) in pinned {
+            let mut gs = GameEngine::deal(seed);
+            let ai = if information_sets {
+                MctsAi::information_set(gs.observe(), &rules)
+            } else {
+                MctsAi::new(gs.observe(), &rules)
+            };
+            let ai = if greedy_rollout {
+                ai.with_rollout(|view, rules| Box::new(GreedyAi::new(view, rules.scoring.policy())))
+            } else {
+                ai
+            };
+            let mut ai = ai
+                .with_iterations(20)
+                .with_rollout_depth(20)
+                .with_eval_blend(50)
+                .with_seed(seed);
+            for _ in 0..60 {
+                let action = ai.make_move();
+                ai.update(action.clone(), gs.act(&action).unwrap());
+            }
+            let hex = gs
+                .observe()
+                .encode()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>();
+            assert_eq!(
+                (gs.score(), hex.as_str()),
+                (score, encoding),
+                "{}",
+                ai.name()
+            );
+        }
+    }
+}
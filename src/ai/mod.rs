@@ -2,12 +2,31 @@
 //!
 //! Defines the interface for the AI players and reexports them from their respective submodules.
 //!
+pub mod eval;
+mod expectimax;
+pub mod features;
+mod fixed;
 mod greedy;
+mod linear;
+mod mcts;
+mod phased;
+pub mod profile;
+mod safe;
 mod simple;
+mod stochastic;
 
-use crate::core::{self, Action, Addr, CardView, Suit, Value};
-pub use greedy::GreedyAi;
+use crate::core::{self, Action, Addr, CardView, DepotId, Phase, Suit, SuitPermutation, Turned, Value, WinLoss};
+use crate::rules::{BuildRule, EmptyColumnRule};
+use rand::rngs::StdRng;
+use rand::RngCore;
+pub use expectimax::ExpectimaxAi;
+pub use greedy::{GreedyAi, OpeningPrior};
+pub use linear::LinearAi;
+pub use mcts::MctsAi;
+pub use phased::PhasedAi;
+pub use safe::SafeMovesAi;
 pub use simple::SimpleAi;
+pub use stochastic::StochasticAi;
 use std::hash::Hash;
 
 pub trait Ai {
@@ -21,18 +40,203 @@ pub trait Ai {
     fn name(&self) -> &'static str;
 
     /// Update the AI with the result of an action
-    /// If the action reveals a card, the suit and value of the card is given, otherwise None
-    fn update(&mut self, action: Action, res: Option<(core::Suit, core::Value)>);
+    /// The suits and values of the cards the action turned face up are given, in the order they were turned
+    fn update(&mut self, action: Action, res: core::Turned);
+
+    /// The number of nodes the AI keeps in its search structures, as a measure of its memory use
+    fn n_nodes(&self) -> usize {
+        0
+    }
+
+    /// Cap the number of nodes the AI may keep. An AI that reaches its limit gives up the game.
+    fn set_node_limit(&mut self, _limit: usize) {}
+
+    /// The actions considered for the last suggested action, best first, with the score the AI gave them
+    ///
+    /// The scores are only comparable within one list. AIs that do not keep track of this return an empty list.
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        vec![]
+    }
+
+    /// How likely the AI would be to play each of the candidates for the last suggested action, best first
+    ///
+    /// The probabilities sum to 1, e.g. a softmax of the priorities or the share of the visits of a search. They are
+    /// targets for imitation learning, and their `entropy` tells how sure the AI was. AIs without a notion of this
+    /// return an empty list.
+    fn action_probabilities(&self) -> Vec<(f64, Action)> {
+        vec![]
+    }
+
+    /// See all cards, in a game with open information (Thoughtful solitaire)
+    ///
+    /// The harness calls this before the first move and after each `update`, see `engine::show_open_cards`. AIs that
+    /// have no use for the hidden cards ignore them.
+    fn observe_open(&mut self, _open: &OpenObserver) {}
+
+    /// A fingerprint of the state of the random number generator of the AI, as it is before the next action
+    ///
+    /// Replays write it down before each action, so that a game of a sampling AI can be played again with the same
+    /// random numbers, and where they part ways is found. AIs without a random number generator return `None`.
+    fn rng_checkpoint(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// The next number a random number generator would give, without using it up, see `Ai::rng_checkpoint`
+fn rng_checkpoint(rng: &StdRng) -> u64 {
+    rng.clone().next_u64()
+}
+
+/// The softmax of the scores, where a score `scale` higher is `e` times as likely
+pub fn softmax(candidates: &[(i64, Action)], scale: f64) -> Vec<(f64, Action)> {
+    let Some(max) = candidates.iter().map(|(score, _)| *score).max() else {
+        return vec![];
+    };
+    let weights = candidates
+        .iter()
+        .map(|(score, _)| ((*score as f64 - max as f64) / scale).exp())
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+    weights.iter().zip(candidates).map(|(w, (_, action))| (w / total, action.clone())).collect()
+}
+
+/// The entropy of a probability distribution over actions, in bits. 0 for a sure choice.
+pub fn entropy(probabilities: &[(f64, Action)]) -> f64 {
+    probabilities
+        .iter()
+        .filter(|(p, _)| *p > 0.0)
+        .fold(0.0, |h, (p, _)| h + p * (1.0 / p).log2())
+}
+
+/// The (state, action) pairs an AI has played, so that it does not repeat itself
+///
+/// The pairs are recorded in `Ai::update`, not when an action is suggested, since the action played may be another
+/// one, e.g. when `StochasticAi` samples it. Each pair is a node, and the number of nodes may be capped.
+#[derive(Debug, Default)]
+struct SeenActions {
+    seen: std::collections::HashSet<(SolitaireObserver, Action)>,
+    limit: Option<usize>,
+}
+
+impl SeenActions {
+    fn contains(&self, view: &SolitaireObserver, action: &Action) -> bool {
+        self.seen.contains(&(view.clone(), action.clone()))
+    }
+
+    fn insert(&mut self, view: &SolitaireObserver, action: &Action) {
+        self.seen.insert((view.clone(), action.clone()));
+    }
+
+    fn n_nodes(&self) -> usize {
+        self.seen.len()
+    }
+
+    fn set_limit(&mut self, limit: usize) {
+        self.limit = Some(limit);
+    }
+
+    /// Has the node limit been reached?
+    fn is_full(&self) -> bool {
+        self.limit.is_some_and(|limit| self.seen.len() >= limit)
+    }
+}
+
+/// The face up run at the top of a depot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RunQuality {
+    /// The number of face up cards
+    pub length: usize,
+    /// The number of other depots that accept the base card of the run, i.e. where the whole run can be moved
+    pub mobility: usize,
+}
+
+/// Where a card is, as far as an observer knows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardLocation {
+    /// In the waste, with this many cards above it
+    Waste(usize),
+    /// Face up in a depot, with this many cards above it
+    Depot(DepotId, usize),
+    /// Face down in a depot, or in the talon
+    Unknown,
+}
+
+impl CardLocation {
+    /// Is the card at the top of its pile, so that it can be moved right away?
+    pub fn is_on_top(&self) -> bool {
+        matches!(self, CardLocation::Waste(0) | CardLocation::Depot(_, 0))
+    }
+}
+
+/// A depot as an observer sees it: some face down cards with the face up cards on top
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DepotView {
+    /// The number of face down cards at the bottom of the depot
+    pub n_face_down: usize,
+    /// The face up cards, from the bottom up
+    pub face_up: Vec<(Suit, Value)>,
+}
+
+impl DepotView {
+    pub fn new(n_face_down: usize, face_up: Vec<(Suit, Value)>) -> Self {
+        DepotView { n_face_down, face_up }
+    }
+
+    /// The number of cards in the depot
+    pub fn len(&self) -> usize {
+        self.n_face_down + self.face_up.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The card `n` from the top, where the top card is 1
+    pub fn card(&self, n: usize) -> Option<CardView> {
+        if n == 0 || n > self.len() {
+            None
+        } else if n <= self.face_up.len() {
+            Some(self.face_up[self.face_up.len() - n].into())
+        } else {
+            Some(CardView::FaceDown)
+        }
+    }
+
+    /// The cards from the bottom up
+    pub fn iter(&self) -> impl Iterator<Item = CardView> + '_ {
+        std::iter::repeat_n(CardView::FaceDown, self.n_face_down).chain(self.face_up.iter().map(|&c| c.into()))
+    }
 }
 
 /// A helper struct for the AI
 /// It holds the known information about the game state
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SolitaireObserver {
     pub talon_size: usize,
     pub waste: Vec<(Suit, Value)>,
     pub foundation_tops: [Option<(Suit, Value)>; 4],
-    pub depots: [Vec<CardView>; 7],
+    pub depots: [DepotView; 7],
+    /// Which cards may be placed on each other in the depots
+    pub building: BuildRule,
+    /// Which cards may be placed on an empty depot
+    pub empty_column: EmptyColumnRule,
+    /// May cards be moved back from the foundations to the depots?
+    pub foundation_retreat: bool,
+    /// How many more times the waste may be turned over, or `None` if there is no limit
+    pub redeals_left: Option<u32>,
+}
+
+/// What an observer sees when all cards are face up: the view of a player, and the cards that it leaves out
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpenObserver {
+    pub view: SolitaireObserver,
+    /// The cards in the talon, with the next card first
+    pub talon: Vec<(Suit, Value)>,
+    /// The face down cards of each depot, from the bottom up
+    pub face_down: [Vec<(Suit, Value)>; 7],
 }
 
 impl SolitaireObserver {
@@ -42,106 +246,336 @@ impl SolitaireObserver {
             .all(|f| matches!(f, Some((_, Value::KING))))
     }
 
+    /// The phase of the game, see `Phase`
+    ///
+    /// A game that is lost can't be seen from the observer, so a game in progress is never `Finished(Loss)`
+    pub fn phase(&self) -> Phase {
+        if self.is_won() {
+            return Phase::Finished(WinLoss::Win);
+        }
+        let n_hidden = self.depots.iter().map(|d| d.n_face_down).sum();
+        Phase::running(n_hidden, self.talon_size + self.waste.len())
+    }
+
     /// For some address, how many face card can we pick?
     pub fn n_takeable_cards(&self, addr: &Addr) -> usize {
-        match addr {
-            Addr::Waste => !self.waste.is_empty() as usize,
-            Addr::Foundation1 | Addr::Foundation2 | Addr::Foundation3 | Addr::Foundation4 => {
-                self.foundation_tops[addr.index()].is_some() as usize
+        if let Some(depot) = addr.depot_id() {
+            self.depots[depot.index()].face_up.len()
+        } else if let Some(foundation) = addr.foundation_id() {
+            self.foundation_tops[foundation.index()].is_some() as usize
+        } else if addr.is_waste() {
+            !self.waste.is_empty() as usize
+        } else {
+            0
+        }
+    }
+
+    /// The actions that are legal as far as the observer can tell, except quitting
+    ///
+    /// The actions come in a fixed order: the moves to the foundations, the reveals, the moves to the depots, and then
+    /// taking from the talon or turning it over. Moves of whole foundation piles, a house rule, are left out.
+    pub fn legal_actions(&self) -> Vec<Action> {
+        let mut actions = vec![];
+        for from in Addr::DEPOTS_AND_WASTE {
+            if let Some(CardView::FaceUp(suit, value)) = self.card_at(&from, 1) {
+                let fits = |to: &Addr| match self.card_at(to, 1) {
+                    None => value.is_ace(),
+                    Some(CardView::FaceUp(s, v)) => s == suit && value.numeric_value() == v.numeric_value() + 1,
+                    Some(CardView::FaceDown) => false,
+                };
+                actions.extend(Addr::FOUNDATIONS.into_iter().filter(fits).map(|to| Action::Move(from, to, 1)));
             }
-            Addr::Depot1
-            | Addr::Depot2
-            | Addr::Depot3
-            | Addr::Depot4
-            | Addr::Depot5
-            | Addr::Depot6
-            | Addr::Depot7 => {
-                let pile = &self.depots[addr.index()];
-                let mut n = 0;
-                for c in pile.iter().rev() {
-                    if matches!(c, CardView::FaceUp(..)) {
-                        n += 1;
-                    } else {
-                        break;
-                    }
-                }
-                n
+        }
+        actions.extend(
+            Addr::DEPOTS
+                .into_iter()
+                .filter(|depot| self.card_at(depot, 1) == Some(CardView::FaceDown))
+                .map(Action::Reveal),
+        );
+        let retreats = if self.foundation_retreat { &Addr::FOUNDATIONS[..] } else { &[] };
+        for from in Addr::DEPOTS_AND_WASTE.iter().chain(retreats) {
+            let n_takeable = self.n_takeable_cards(from);
+            for to in Addr::DEPOTS.into_iter().filter(|to| to != from) {
+                actions.extend(
+                    (1..=n_takeable)
+                        .filter(|&n| self.card_at(from, n).is_some_and(|card| self.fits_on_depot(card, &to)))
+                        .map(|n| Action::Move(*from, to, n)),
+                );
+            }
+        }
+        if self.talon_size != 0 {
+            actions.push(Action::Take);
+        } else if !self.waste.is_empty() && self.redeals_left != Some(0) {
+            actions.push(Action::Turnover);
+        }
+        actions
+    }
+
+    /// The legal actions but the moves back from the foundations, in the same order
+    ///
+    /// The heuristic AIs never take a card back from its foundation, since their priorities would play it straight up
+    /// again, and then back again.
+    pub fn forward_actions(&self) -> Vec<Action> {
+        let mut actions = self.legal_actions();
+        actions.retain(|action| !matches!(action, Action::Move(from, ..) if from.is_foundation()));
+        actions
+    }
+
+    /// Can a card be placed on a depot, by the rules for building and for empty depots?
+    pub fn fits_on_depot(&self, card: CardView, depot: &Addr) -> bool {
+        match (card, self.card_at(depot, 1)) {
+            (CardView::FaceUp(_, v), None) => v.is_king() || self.empty_column == EmptyColumnRule::AnyCard,
+            (CardView::FaceUp(s, v), Some(CardView::FaceUp(s2, v2))) => self.building.fits((s, v), (s2, v2)),
+            _ => false,
+        }
+    }
+
+    /// The length and mobility of the face up run in each depot
+    pub fn run_quality(&self) -> [RunQuality; 7] {
+        Addr::DEPOTS.map(|from| {
+            let length = self.n_takeable_cards(&from);
+            let base = (length > 0).then(|| self.card_at(&from, length)).flatten();
+            let mobility = match base {
+                Some(base) => Addr::DEPOTS
+                    .iter()
+                    .filter(|to| **to != from && self.fits_on_depot(base, to))
+                    .count(),
+                _ => 0,
+            };
+            RunQuality { length, mobility }
+        })
+    }
+
+    /// The cards on the foundation of each suit, in the order hearts, diamonds, clubs and spades
+    pub fn foundation_progress(&self) -> [u8; 4] {
+        let mut progress = [0; 4];
+        for &(suit, top) in self.foundation_tops.iter().flatten() {
+            progress[suit as usize] = top.numeric_value();
+        }
+        progress
+    }
+
+    /// The face up cards of each suit in the depots and the waste, in the order of `foundation_progress`
+    ///
+    /// All cards in the waste count, not only the top one, since they come round again.
+    pub fn visible_by_suit(&self) -> [usize; 4] {
+        let mut visible = [0; 4];
+        for &(suit, _) in self.waste.iter().chain(self.depots.iter().flat_map(|d| d.face_up.iter())) {
+            visible[suit as usize] += 1;
+        }
+        visible
+    }
+
+    /// The next card each suit needs on the foundations, and where it is
+    ///
+    /// Suits with a complete foundation need no card.
+    pub fn needed_cards(&self) -> Vec<((Suit, Value), CardLocation)> {
+        let progress = self.foundation_progress();
+        [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades]
+            .into_iter()
+            .filter_map(|suit| {
+                let card = (suit, Value::try_from(progress[suit as usize] + 1).ok()?);
+                Some((card, self.locate(card)))
+            })
+            .collect()
+    }
+
+    /// Is this a move between depots that changes nothing worth having?
+    ///
+    /// A move between depots is kept if it exposes a face down card, frees a depot, or lets the card below the moved
+    /// ones go to a foundation. All other actions are kept. AIs and the engine may use this to prune the moves to try.
+    pub fn is_pruned(&self, action: &Action) -> bool {
+        let Action::Move(from, to, n) = action else {
+            return false;
+        };
+        let (Some(from_id), true) = (from.depot_id(), to.is_depot()) else {
+            return false;
+        };
+        let below = self.depots[from_id.index()].card(n + 1);
+        let exposes_hidden = below == Some(CardView::FaceDown);
+        let frees_depot = below.is_none() && self.card_at(to, 1).is_some();
+        let enables_foundation = self
+            .needed_cards()
+            .iter()
+            .any(|(_, loc)| *loc == CardLocation::Depot(from_id, *n));
+        !(exposes_hidden || frees_depot || enables_foundation)
+    }
+
+    /// Where is a card that is not on the foundations?
+    pub fn locate(&self, card: (Suit, Value)) -> CardLocation {
+        if let Some(pos) = self.waste.iter().rposition(|c| *c == card) {
+            return CardLocation::Waste(self.waste.len() - 1 - pos);
+        }
+        for (addr, depot) in Addr::DEPOTS.iter().zip(self.depots.iter()) {
+            if let Some(pos) = depot.face_up.iter().rposition(|c| *c == card) {
+                let id = addr.depot_id().expect("Depots have depot ids");
+                return CardLocation::Depot(id, depot.face_up.len() - 1 - pos);
             }
         }
+        CardLocation::Unknown
+    }
+
+    /// The cards the observer can't see: the ones in the talon and the face down ones in the depots
+    ///
+    /// They are in the order of the suits and then of the values. The cards below the foundation tops count as seen.
+    pub fn unseen_cards(&self) -> Vec<(Suit, Value)> {
+        let mut seen = [[false; 14]; 4];
+        for &(suit, top) in self.foundation_tops.iter().flatten() {
+            seen[suit as usize][1..=top.numeric_value() as usize].fill(true);
+        }
+        for &(suit, value) in self.waste.iter().chain(self.depots.iter().flat_map(|d| d.face_up.iter())) {
+            seen[suit as usize][value.numeric_value() as usize] = true;
+        }
+        [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades]
+            .into_iter()
+            .flat_map(|suit| (1..=13).map(move |v| (suit, Value::try_from(v).expect("Known to be in range"))))
+            .filter(|&(suit, value)| !seen[suit as usize][value.numeric_value() as usize])
+            .collect()
     }
 
     /// Check what card is at some given address and depth
     ///
     pub fn card_at(&self, addr: &Addr, n: usize) -> Option<CardView> {
-        if addr.is_waste() && n == 1 {
+        if let Some(depot) = addr.depot_id() {
+            self.depots[depot.index()].card(n)
+        } else if n != 1 {
+            None
+        } else if let Some(foundation) = addr.foundation_id() {
+            self.foundation_tops[foundation.index()].map(|v| v.into())
+        } else if addr.is_waste() {
             self.waste.last().map(|&x| x.into())
-        } else if addr.is_foundation() && n == 1 {
-            return self.foundation_tops[addr.index()].map(|v| v.into());
-        } else if addr.is_depot() {
-            let pile = &self.depots[addr.index()];
-            if n <= pile.len() {
-                return Some(pile[pile.len() - n]);
-            } else {
-                return None;
-            }
         } else {
-            return None;
+            None
         }
     }
 
+    /// The same view, with all suits relabeled
+    pub fn relabel_suits(&self, perm: &SuitPermutation) -> SolitaireObserver {
+        let relabel = |d: &DepotView| DepotView {
+            n_face_down: d.n_face_down,
+            face_up: d.face_up.iter().map(|(s, v)| (perm.apply(*s), *v)).collect(),
+        };
+        SolitaireObserver {
+            talon_size: self.talon_size,
+            waste: self.waste.iter().map(|(s, v)| (perm.apply(*s), *v)).collect(),
+            foundation_tops: self.foundation_tops.map(|t| t.map(|(s, v)| (perm.apply(s), v))),
+            depots: std::array::from_fn(|i| relabel(&self.depots[i])),
+            building: self.building,
+            empty_column: self.empty_column,
+            foundation_retreat: self.foundation_retreat,
+            redeals_left: self.redeals_left,
+        }
+    }
+
+    /// Encode the view as bytes, e.g. for hashing or as input to a learning algorithm
+    ///
+    /// The layout is the talon size, the waste size and the waste cards, the four foundation tops,
+    /// and then the size and the cards of each depot, and last the redeals left, if they are limited. A face up
+    /// card is encoded as `16 * suit + value`, a face down card and an empty foundation as 0.
+    pub fn encode(&self) -> Vec<u8> {
+        let card = |s: Suit, v: Value| 16 * s as u8 + v.numeric_value();
+        let mut bytes = vec![self.talon_size as u8, self.waste.len() as u8];
+        bytes.extend(self.waste.iter().map(|&(s, v)| card(s, v)));
+        bytes.extend(self.foundation_tops.iter().map(|t| t.map_or(0, |(s, v)| card(s, v))));
+        for depot in self.depots.iter() {
+            bytes.push(depot.len() as u8);
+            bytes.extend(std::iter::repeat_n(0, depot.n_face_down));
+            bytes.extend(depot.face_up.iter().map(|&(s, v)| card(s, v)));
+        }
+        bytes.extend(self.redeals_left.map(|n| n.min(u8::MAX as u32) as u8));
+        bytes
+    }
+
+    /// Encode the view independent of the suit labels
+    ///
+    /// Views that only differ by a color preserving relabeling of the suits have the same canonical encoding,
+    /// which makes the state space up to 8 times smaller.
+    pub fn canonical_encoding(&self) -> Vec<u8> {
+        SuitPermutation::all()
+            .iter()
+            .map(|p| self.relabel_suits(p).encode())
+            .min()
+            .expect("There are permutations")
+    }
+
     /// Update the view with the result of an action
     /// Assume that the result is valid for the action, e.g. that revealing a card do indeed reveal a card with a suit and a value
-    pub fn update(&mut self, action: Action, res: Option<(Suit, Value)>) {
+    ///
+    /// When the game reveals cards automatically, a move away from a depot may result in the revealed card.
+    /// When drawing three, a take results in up to three cards, and only the last one is on top of the waste.
+    pub fn update(&mut self, action: Action, res: Turned) {
         match action {
+            Action::Move(from, _, _) if from.is_depot() && !res.is_empty() => {
+                self.update(action, Turned::NONE);
+                self.update(Action::Reveal(from), res);
+            }
             Action::Move(from, to, n) => {
-                if from.is_depot() && to.is_depot() {
-                    let n_skip = self.depots[from.index()].len().saturating_sub(n);
-                    let mut cards_to_move = self.depots[from.index()].split_off(n_skip);
-                    self.depots[to.index()].append(&mut cards_to_move);
-                } else if from.is_depot() && to.is_foundation() {
-                    assert!(n == 1);
-                    if let Some(CardView::FaceUp(s, v)) = self.depots[from.index()].pop() {
-                        self.foundation_tops[to.index()] = Some((s, v));
-                    } else {
-                        panic!("We should only move face up cards to the foundation")
+                match (
+                    (from.depot_id(), from.foundation_id()),
+                    (to.depot_id(), to.foundation_id()),
+                ) {
+                    ((Some(from), _), (Some(to), _)) => {
+                        // Moved in place, so that updating doesn't allocate once the depots have room for the cards
+                        let [from, to] = self
+                            .depots
+                            .get_disjoint_mut([from.index(), to.index()])
+                            .expect("We should only move cards between different depots");
+                        let start = from.face_up.len().saturating_sub(n);
+                        to.face_up.extend(from.face_up.drain(start..));
+                    }
+                    ((Some(from), _), (_, Some(to))) => {
+                        assert!(n == 1);
+                        let card = self.depots[from.index()].face_up.pop();
+                        let card = card.expect("We should only move face up cards to the foundation");
+                        self.foundation_tops[to.index()] = Some(card);
+                    }
+                    ((_, Some(from)), (Some(to), _)) => {
+                        let card = self.foundation_tops[from.index()].unwrap();
+                        self.foundation_tops[from.index()] =
+                            Value::try_from(card.1.numeric_value() - 1)
+                                .ok()
+                                .map(|v| (card.0, v));
+                        self.depots[to.index()].face_up.push(card);
+                    }
+                    ((None, None), (Some(to), _)) if n == 1 => {
+                        let card = self.waste.pop().unwrap();
+                        self.depots[to.index()].face_up.push(card);
+                    }
+                    ((None, None), (_, Some(to))) if n == 1 => {
+                        let card = self.waste.pop().unwrap();
+                        self.foundation_tops[to.index()] = Some(card);
+                    }
+                    _ => {
+                        dbg!(action, res);
+                        panic!("Illegal move (?)");
                     }
-                } else if from.is_foundation() && to.is_depot() {
-                    let card = self.foundation_tops[from.index()].unwrap();
-                    self.foundation_tops[from.index()].unwrap().1 =
-                        Value::try_from(card.1.numeric_value() - 1)
-                            .expect("We should never move an ace from foundation");
-                    self.depots[to.index()].push(card.into());
-                } else if from.is_waste() && to.is_depot() && n == 1 {
-                    let card = self.waste.pop().unwrap();
-                    self.depots[to.index()].push(card.into());
-                } else if from.is_waste() && to.is_foundation() && n == 1 {
-                    let card = self.waste.pop().unwrap();
-                    self.foundation_tops[to.index()] = Some(card);
-                } else {
-                    dbg!(action, res);
-                    panic!("Illegal move (?)");
                 }
             }
             Action::Take => {
-                let res = res.expect("We took a card, so there should be some card taken");
-                self.waste.push(res);
-                self.talon_size -= 1;
+                assert!(!res.is_empty(), "We took cards, so there should be some cards taken");
+                self.talon_size -= res.len();
+                self.waste.extend(res);
             }
             Action::Turnover => {
                 self.talon_size = self.waste.len();
                 self.waste.clear();
+                if let Some(left) = &mut self.redeals_left {
+                    *left -= 1;
+                }
             }
             Action::Quit => {}
+            Action::MoveFoundationPile(from, to) => {
+                let from = from.foundation_id().expect("Foundation piles are moved from a foundation");
+                let to = to.foundation_id().expect("Foundation piles are moved to a foundation");
+                self.foundation_tops[to.index()] = self.foundation_tops[from.index()].take();
+            }
             Action::Reveal(addr) => {
-                let res = res.expect("We revealed a card, so there should be some card revealed");
-                if let Some(a) = self.depots[addr.index()].last_mut() {
-                    *a = match a {
-                        CardView::FaceDown => CardView::FaceUp(res.0, res.1),
-                        _ => panic!("We should only reveal face down cards"),
-                    }
-                } else {
-                    panic!("We should only reveal face down cards");
-                }
+                let res = *res.first().expect("We revealed a card, so there should be some card revealed");
+                let depot = addr.depot_id().expect("We should only reveal cards in the depots");
+                let depot = &mut self.depots[depot.index()];
+                assert!(depot.card(1) == Some(CardView::FaceDown), "We should only reveal face down cards");
+                depot.n_face_down -= 1;
+                depot.face_up.push(res);
             }
         }
     }
@@ -150,6 +584,108 @@ impl SolitaireObserver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn softmax_of_scores() {
+        let candidates = [(10, Action::Reveal(Addr::Depot1)), (0, Action::Take), (0, Action::Quit)];
+        let probabilities = softmax(&candidates, 10.0);
+        let e = std::f64::consts::E;
+        assert!((probabilities[0].0 - e / (e + 2.0)).abs() < 1e-12);
+        assert_eq!(probabilities[1].0, probabilities[2].0);
+        assert!((probabilities.iter().map(|(p, _)| p).sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!((entropy(&softmax(&candidates[1..], 10.0)) - 1.0).abs() < 1e-12);
+        assert_eq!(entropy(&[(1.0, Action::Take)]), 0.0);
+        assert!(softmax(&[], 1.0).is_empty());
+    }
+
+    #[test]
+    fn prune_depot_moves() {
+        let view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![],
+            foundation_tops: [Some((Suit::Spades, Value::ACE)), None, None, None],
+            depots: [
+                DepotView::new(1, vec![(Suit::Spades, Value::TWO), (Suit::Hearts, Value::QUEEN)]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::KING), (Suit::Diamonds, Value::QUEEN)]),
+                DepotView::new(0, vec![(Suit::Spades, Value::KING)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        // The two of spades can go to the foundation after the queen is moved
+        assert!(!view.is_pruned(&Action::Move(Addr::Depot1, Addr::Depot3, 1)));
+        // Moving the queen of diamonds back and forth does nothing
+        assert!(view.is_pruned(&Action::Move(Addr::Depot2, Addr::Depot3, 1)));
+        // Moving a king from one empty depot to another frees nothing
+        assert!(view.is_pruned(&Action::Move(Addr::Depot3, Addr::Depot4, 1)));
+        assert!(!view.is_pruned(&Action::Move(Addr::Depot1, Addr::Foundation1, 1)));
+        assert!(!view.is_pruned(&Action::Take));
+    }
+
+    #[test]
+    fn locate_needed_cards() {
+        let view = SolitaireObserver {
+            talon_size: 3,
+            waste: vec![(Suit::Clubs, Value::ACE), (Suit::Spades, Value::KING)],
+            foundation_tops: [
+                Some((Suit::Hearts, Value::ACE)),
+                Some((Suit::Spades, Value::KING)),
+                None,
+                None,
+            ],
+            depots: [
+                DepotView::new(1, vec![(Suit::Hearts, Value::TWO), (Suit::Clubs, Value::QUEEN)]),
+                DepotView::new(0, vec![(Suit::Diamonds, Value::ACE)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        let depot = |i: usize| Addr::DEPOTS[i].depot_id().unwrap();
+        assert_eq!(
+            view.needed_cards(),
+            vec![
+                ((Suit::Hearts, Value::TWO), CardLocation::Depot(depot(0), 1)),
+                (
+                    (Suit::Diamonds, Value::ACE),
+                    CardLocation::Depot(depot(1), 0)
+                ),
+                ((Suit::Clubs, Value::ACE), CardLocation::Waste(1)),
+            ]
+        );
+        assert_eq!(
+            view.locate((Suit::Diamonds, Value::KING)),
+            CardLocation::Unknown
+        );
+        assert_eq!(view.foundation_progress(), [1, 0, 0, 13]);
+        assert_eq!(view.visible_by_suit(), [1, 1, 2, 1]);
+    }
+
+    #[test]
+    fn unseen_cards_are_hidden() {
+        use crate::engine::GameEngine;
+        let mut gs = GameEngine::deal(4);
+        gs.act(&Action::Take).unwrap();
+        let view = gs.observe();
+        let unseen = view.unseen_cards();
+        let n_face_down = view.depots.iter().map(|d| d.n_face_down).sum::<usize>();
+        assert_eq!(unseen.len(), view.talon_size + n_face_down);
+        assert!(unseen.iter().all(|&card| view.locate(card) == CardLocation::Unknown));
+        assert!(unseen.windows(2).all(|w| w[0] < w[1]));
+    }
 
     #[test]
     fn correct_move_counts() {
@@ -158,17 +694,178 @@ mod tests {
             waste: vec![],
             foundation_tops: [None; 4],
             depots: [
-                vec![CardView::FaceUp(Suit::Hearts, Value::KING)],
-                vec![CardView::FaceUp(Suit::Clubs, Value::QUEEN)],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
+                DepotView::new(0, vec![(Suit::Hearts, Value::KING)]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::QUEEN)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
             ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
         };
         assert_eq!(view.n_takeable_cards(&Addr::Depot1), 1);
         assert_eq!(view.n_takeable_cards(&Addr::Depot2), 1);
         assert_eq!(view.n_takeable_cards(&Addr::Waste), 0);
     }
+
+    #[test]
+    fn run_quality_counts_accepting_depots() {
+        let view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![],
+            foundation_tops: [None; 4],
+            depots: [
+                DepotView::new(1, vec![(Suit::Hearts, Value::KING), (Suit::Clubs, Value::QUEEN)]),
+                DepotView::new(0, vec![(Suit::Spades, Value::KING)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::new(1, vec![]),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        let quality = view.run_quality();
+        // The king of hearts fits on the four empty depots
+        assert_eq!(quality[0], RunQuality { length: 2, mobility: 4 });
+        assert_eq!(quality[1], RunQuality { length: 1, mobility: 4 });
+        assert_eq!(quality[2], RunQuality::default());
+        assert_eq!(quality[6], RunQuality::default());
+    }
+
+    #[test]
+    fn gives_up_at_node_limit() {
+        let view = SolitaireObserver {
+            talon_size: 2,
+            waste: vec![],
+            foundation_tops: [None; 4],
+            depots: Default::default(),
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        let mut ai = GreedyAi::new(view, crate::rules::ScoringPolicy::STANDARD);
+        ai.set_node_limit(1);
+        assert_eq!(ai.make_move(), Action::Take);
+        ai.update(Action::Take, Turned::from([(Suit::Hearts, Value::KING)]));
+        assert_eq!(ai.n_nodes(), 1);
+        assert_eq!(ai.make_move(), Action::Quit);
+    }
+
+    #[test]
+    fn canonical_encoding_ignores_suit_labels() {
+        let view = SolitaireObserver {
+            talon_size: 3,
+            waste: vec![(Suit::Spades, Value::TWO)],
+            foundation_tops: [Some((Suit::Hearts, Value::ACE)), None, None, None],
+            depots: [
+                DepotView::new(1, vec![(Suit::Clubs, Value::KING)]),
+                DepotView::new(0, vec![(Suit::Diamonds, Value::QUEEN)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        let encodings = SuitPermutation::all().map(|p| view.relabel_suits(&p).encode());
+        assert!(encodings.iter().all_unique());
+        for p in SuitPermutation::all() {
+            let relabeled = view.relabel_suits(&p);
+            assert_eq!(relabeled.canonical_encoding(), view.canonical_encoding());
+        }
+    }
+
+    #[test]
+    fn update_with_auto_revealed_card() {
+        let mut view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![],
+            foundation_tops: [None; 4],
+            depots: [
+                DepotView::new(1, vec![(Suit::Hearts, Value::ACE)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        view.update(
+            Action::Move(Addr::Depot1, Addr::Foundation1, 1),
+            Turned::from([(Suit::Clubs, Value::KING)]),
+        );
+        assert_eq!(view.depots[0], DepotView::new(0, vec![(Suit::Clubs, Value::KING)]));
+        assert_eq!(view.foundation_tops[0], Some((Suit::Hearts, Value::ACE)));
+    }
+
+    /// With room for the cards in the waste and the depots, updating a view doesn't allocate
+    #[test]
+    fn update_does_not_allocate() {
+        use crate::alloc_count::count_allocations;
+        use crate::engine::GameEngine;
+        let mut gs = GameEngine::builder().seed(5).auto_reveal(true).build().unwrap();
+        let mut ai = GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        let mut view = gs.observe();
+        view.waste.reserve(24);
+        for depot in view.depots.iter_mut() {
+            depot.face_up.reserve(13);
+        }
+        while gs.is_running() {
+            let action = ai.make_move();
+            let res = gs.act(&action).unwrap();
+            ai.update(action.clone(), res);
+            let (n, ()) = count_allocations(|| view.update(action.clone(), res));
+            assert_eq!(n, 0, "{action:?}");
+        }
+        assert_eq!(view, gs.observe());
+    }
+
+    #[test]
+    fn legal_actions_match_the_engine() {
+        use crate::engine::GameEngine;
+        use crate::rules::RuleSet;
+        // Leaving out the moves of whole foundation piles, the view sees the same actions as the engine
+        for rules in [RuleSet::KLONDIKE_CLASSIC, RuleSet::VEGAS_DRAW3, RuleSet::SOFTIE] {
+            let mut gs = GameEngine::builder().seed(3).rules(rules).build().unwrap();
+            let mut ai = GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+            let sorted = |actions: Vec<Action>| actions.into_iter().sorted_by_key(|a| format!("{a:?}")).collect_vec();
+            while gs.is_running() {
+                let expected = gs.legal_actions().into_iter().filter(|a| !matches!(a, Action::MoveFoundationPile(..)));
+                assert_eq!(sorted(gs.observe().legal_actions()), sorted(expected.collect()));
+                let action = ai.make_move();
+                ai.update(action.clone(), gs.act(&action).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn any_card_fills_an_empty_depot_in_softie() {
+        use crate::engine::GameEngine;
+        use crate::rules::RuleSet;
+        let mut view = GameEngine::builder().rules(RuleSet::SOFTIE).build().unwrap().observe();
+        view.depots[0] = DepotView::default();
+        view.waste.push((Suit::Hearts, Value::QUEEN));
+        let to_empty = Action::Move(Addr::Waste, Addr::Depot1, 1);
+        assert!(view.legal_actions().contains(&to_empty));
+        view.empty_column = EmptyColumnRule::KingsOnly;
+        assert!(!view.legal_actions().contains(&to_empty));
+    }
 }
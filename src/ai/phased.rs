@@ -0,0 +1,139 @@
+//! An AI player that switches policy depending on the phase of the game
+//!
+//! The opening, the midgame and the endgame have different objectives.
+//! In the opening we want to reveal as many cards as possible, in the midgame we want to build score,
+//! and in the endgame (all cards in the depots revealed) we just want to get the cards to the foundations.
+//! The phases are defined in `core::Phase`.
+//!
+use super::{Action, Ai, CardView, GreedyAi, SimpleAi, SolitaireObserver};
+use crate::core::{Addr, Phase, Turned};
+use crate::rules::ScoringPolicy;
+
+/// An AI player that uses SimpleAi in the opening, GreedyAi in the midgame
+/// and plays to the foundations as soon as possible in the endgame
+pub struct PhasedAi {
+    opening: SimpleAi,
+    midgame: GreedyAi,
+    view: SolitaireObserver,
+    /// The phase of the last suggested action
+    last_phase: Phase,
+}
+
+impl PhasedAi {
+    /// The greedy midgame AI plays for the points of the given scoring policy
+    pub fn new(view: SolitaireObserver, policy: ScoringPolicy) -> Self {
+        PhasedAi {
+            opening: SimpleAi::new(view.clone()),
+            midgame: GreedyAi::new(view.clone(), policy),
+            last_phase: view.phase(),
+            view,
+        }
+    }
+
+    /// Find a move of a card from the depots or the waste onto the foundations, if there is any
+    fn foundation_move(&self) -> Option<Action> {
+        for from_addr in Addr::DEPOTS_AND_WASTE {
+            if let Some(CardView::FaceUp(suit, value)) = self.view.card_at(&from_addr, 1) {
+                for to_addr in Addr::FOUNDATIONS {
+                    let fits = match self.view.card_at(&to_addr, 1) {
+                        None => value.is_ace(),
+                        Some(CardView::FaceUp(to_suit, to_value)) => {
+                            suit == to_suit && value.numeric_value() == to_value.numeric_value() + 1
+                        }
+                        Some(CardView::FaceDown) => false,
+                    };
+                    if fits {
+                        return Some(Action::Move(from_addr, to_addr, 1));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Ai for PhasedAi {
+    fn make_move(&mut self) -> Action {
+        if self.view.is_won() {
+            return Action::Quit;
+        }
+        self.last_phase = self.view.phase();
+        match self.last_phase {
+            Phase::Opening => self.opening.make_move(),
+            Phase::Midgame => self.midgame.make_move(),
+            Phase::Endgame | Phase::AutoCompletable | Phase::Finished(_) => self
+                .foundation_move()
+                .unwrap_or_else(|| self.midgame.make_move()),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "PhasedAi"
+    }
+    fn n_nodes(&self) -> usize {
+        self.opening.n_nodes() + self.midgame.n_nodes()
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        // Each sub AI gets half of the budget
+        self.opening.set_node_limit(limit / 2);
+        self.midgame.set_node_limit(limit - limit / 2);
+    }
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        match self.last_phase {
+            Phase::Opening => self.opening.candidates(),
+            Phase::Midgame => self.midgame.candidates(),
+            Phase::Endgame | Phase::AutoCompletable | Phase::Finished(_) => self
+                .foundation_move()
+                .map_or_else(|| self.midgame.candidates(), |a| vec![(0, a)]),
+        }
+    }
+    fn action_probabilities(&self) -> Vec<(f64, Action)> {
+        match self.last_phase {
+            Phase::Opening => self.opening.action_probabilities(),
+            Phase::Midgame => self.midgame.action_probabilities(),
+            Phase::Endgame | Phase::AutoCompletable | Phase::Finished(_) => self
+                .foundation_move()
+                .map_or_else(|| self.midgame.action_probabilities(), |a| vec![(1.0, a)]),
+        }
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.opening.update(action.clone(), res);
+        self.midgame.update(action.clone(), res);
+        self.view.update(action, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::DepotView;
+    use crate::core::{Suit, Value};
+    use crate::rules::{BuildRule, EmptyColumnRule};
+
+    #[test]
+    fn endgame_plays_to_foundation() {
+        let view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![],
+            foundation_tops: [None; 4],
+            depots: [
+                DepotView::new(0, vec![(Suit::Hearts, Value::KING)]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::ACE)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        assert_eq!(view.phase(), Phase::AutoCompletable);
+        let mut ai = PhasedAi::new(view, ScoringPolicy::STANDARD);
+        assert_eq!(
+            ai.make_move(),
+            Action::Move(Addr::Depot2, Addr::Foundation1, 1)
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Named configurations of the AIs
+//!
+//! A profile is one of the built-in AIs together with settings that change how it plays,
+//! so that heuristics can be experimented with without changing the code.
+//!
+//! - `greedy` and `phased` take the priorities `waste_to_foundation`, `waste_to_depot`, `depot_to_foundation`,
+//!   `foundation_to_depot`, `reveal` and `turnover_penalty`. They default to the points of the scoring policy.
+//! - `linear` takes the weight of each feature, by feature name.
+//! - `mcts` and `ismcts` take the number of `iterations` per move and the `seed` of their random number generator.
+//!   Their playouts are random, unless another AI is given as the `rollout` policy. A playout is cut off after
+//!   `rollout_depth` actions, and `eval_blend` percent of its reward is then given by the evaluation function.
+//! - `expectimax` takes the `depth` of its search in actions, the number of `samples` of the unseen cards at a chance
+//!   node, and the `seed` of its random number generator.
+//! - `simple` takes no settings.
+//!
+//! The settings that count something, like `iterations` and `depth`, can't be negative.
+//!
+//! A profile may also carry an `OpeningPrior`, e.g. from `solitaire openings`, which only `greedy` takes into account.
+//!
+//! Any AI also takes a `temperature` in hundredths, e.g. 100 for 1.0, to sample its actions instead of always playing
+//! the best one, see `StochasticAi`. It samples with the `seed`, which the search AIs also use for their own ends.
+//! With `safe_moves` set to 1, any AI plays the safe foundation moves before it considers anything else, see
+//! `SafeMovesAi`.
+use super::mcts::RolloutPolicy;
+use super::{
+    eval, features, Ai, ExpectimaxAi, GreedyAi, LinearAi, MctsAi, OpeningPrior, PhasedAi,
+    SafeMovesAi, SimpleAi, SolitaireObserver, StochasticAi,
+};
+use crate::rules::{RuleSet, ScoringPolicy};
+use thiserror::Error;
+
+/// The built-in AIs
+pub const BUILTIN: [&str; 7] = [
+    "simple",
+    "greedy",
+    "phased",
+    "linear",
+    "mcts",
+    "ismcts",
+    "expectimax",
+];
+
+/// The settings that count something, e.g. iterations of a search, and so can't be negative
+const COUNTS: [&str; 4] = ["iterations", "rollout_depth", "depth", "samples"];
+
+/// The AIs that play a batch of games unless others are asked for. The search AIs are too slow to be among them.
+pub const DEFAULT: [&str; 4] = ["simple", "greedy", "phased", "linear"];
+
+/// Errors with AI profiles
+#[derive(Error, Debug, PartialEq)]
+pub enum ProfileError {
+    #[error("Unknown AI {0}")]
+    UnknownAi(String),
+    #[error("The AI profile {profile} has no setting {key}")]
+    UnknownSetting { profile: String, key: String },
+    #[error("The setting {key} of the AI profile {profile} can't be negative")]
+    NegativeSetting { profile: String, key: String },
+}
+
+/// A built-in AI with settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct AiProfile {
+    pub name: String,
+    /// One of `BUILTIN`
+    pub base: &'static str,
+    pub settings: Vec<(String, i64)>,
+    /// The AI that plays the playouts of a search AI. Random moves if `None`
+    pub rollout: Option<&'static str>,
+    /// Ties in the opening are broken by this prior. The AIs other than `greedy` ignore it
+    pub opening_prior: Option<OpeningPrior>,
+}
+
+impl AiProfile {
+    /// A built-in AI without settings
+    pub fn builtin(name: &str) -> Result<AiProfile, ProfileError> {
+        AiProfile::new(name, name, vec![])
+    }
+
+    /// A profile, checking that the base AI exists and takes all the settings
+    pub fn new(
+        name: &str,
+        base: &str,
+        settings: Vec<(String, i64)>,
+    ) -> Result<AiProfile, ProfileError> {
+        let base = BUILTIN
+            .into_iter()
+            .find(|b| *b == base)
+            .ok_or_else(|| ProfileError::UnknownAi(base.to_string()))?;
+        let profile = AiProfile {
+            name: name.to_string(),
+            base,
+            settings,
+            rollout: None,
+            opening_prior: None,
+        };
+        let mut policy = ScoringPolicy::STANDARD;
+        let mut weights = eval::default_weights();
+        for (key, value) in profile.settings.iter() {
+            let known = ["temperature", "seed", "safe_moves"].contains(&key.as_str())
+                || match base {
+                    "greedy" | "phased" => set_priority(&mut policy, key, *value),
+                    "linear" => set_weight(&mut weights, key, *value),
+                    "mcts" | "ismcts" => {
+                        ["iterations", "rollout_depth", "eval_blend"].contains(&key.as_str())
+                    }
+                    "expectimax" => ["depth", "samples"].contains(&key.as_str()),
+                    _ => false,
+                };
+            if !known {
+                return Err(ProfileError::UnknownSetting {
+                    profile: profile.name.clone(),
+                    key: key.clone(),
+                });
+            }
+            if COUNTS.contains(&key.as_str()) && *value < 0 {
+                return Err(ProfileError::NegativeSetting {
+                    profile: profile.name.clone(),
+                    key: key.clone(),
+                });
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Let a search AI play its playouts with a built-in AI
+    pub fn with_rollout(mut self, rollout: &str) -> Result<AiProfile, ProfileError> {
+        if !matches!(self.base, "mcts" | "ismcts") {
+            return Err(ProfileError::UnknownSetting {
+                profile: self.name,
+                key: "rollout".to_string(),
+            });
+        }
+        let name = BUILTIN
+            .into_iter()
+            .find(|b| *b == rollout && rollout_policy(b).is_some())
+            .ok_or_else(|| ProfileError::UnknownAi(rollout.to_string()))?;
+        self.rollout = Some(name);
+        Ok(self)
+    }
+
+    /// Break ties in the opening by a prior
+    pub fn with_opening_prior(mut self, prior: OpeningPrior) -> Self {
+        self.opening_prior = Some(prior);
+        self
+    }
+
+    /// Create the AI for a game with the given rules
+    pub fn make(&self, view: SolitaireObserver, rules: &RuleSet) -> Box<dyn Ai> {
+        let ai = self.make_base(view.clone(), rules);
+        let ai = match self.setting("temperature") {
+            Some(temperature) if temperature > 0 => {
+                let seed = self.setting("seed").unwrap_or(0) as u64;
+                Box::from(StochasticAi::new(ai, temperature as f64 / 100.0).with_seed(seed))
+            }
+            _ => ai,
+        };
+        match self.setting("safe_moves") {
+            Some(on) if on != 0 => Box::from(SafeMovesAi::new(ai, view)),
+            _ => ai,
+        }
+    }
+
+    /// Create the base AI, without sampling
+    fn make_base(&self, view: SolitaireObserver, rules: &RuleSet) -> Box<dyn Ai> {
+        let mut policy = rules.scoring.policy();
+        let mut weights = eval::default_weights();
+        for (key, value) in self.settings.iter() {
+            set_priority(&mut policy, key, *value);
+            set_weight(&mut weights, key, *value);
+        }
+        match self.base {
+            "simple" => Box::from(SimpleAi::new(view)),
+            "greedy" => {
+                let ai = GreedyAi::new(view, policy);
+                match self.opening_prior.clone() {
+                    Some(prior) => Box::from(ai.with_opening_prior(prior)),
+                    None => Box::from(ai),
+                }
+            }
+            "phased" => Box::from(PhasedAi::new(view, policy)),
+            "linear" => Box::from(LinearAi::with_weights(view, weights)),
+            "mcts" | "ismcts" => {
+                let mut ai = match self.base {
+                    "mcts" => MctsAi::new(view, rules),
+                    _ => MctsAi::information_set(view, rules),
+                };
+                if let Some(iterations) = self.setting("iterations") {
+                    ai = ai.with_iterations(iterations as usize);
+                }
+                if let Some(seed) = self.setting("seed") {
+                    ai = ai.with_seed(seed as u64);
+                }
+                if let Some(depth) = self.setting("rollout_depth") {
+                    ai = ai.with_rollout_depth(depth as usize);
+                }
+                if let Some(percent) = self.setting("eval_blend") {
+                    ai = ai.with_eval_blend(percent.clamp(0, 100) as u8);
+                }
+                if let Some(policy) = self.rollout.and_then(rollout_policy) {
+                    ai = ai.with_rollout(policy);
+                }
+                Box::from(ai)
+            }
+            "expectimax" => {
+                let mut ai = ExpectimaxAi::new(view, rules);
+                if let Some(depth) = self.setting("depth") {
+                    ai = ai.with_depth(depth.max(1) as usize);
+                }
+                if let Some(samples) = self.setting("samples") {
+                    ai = ai.with_samples(samples.max(1) as usize);
+                }
+                if let Some(seed) = self.setting("seed") {
+                    ai = ai.with_seed(seed as u64);
+                }
+                Box::from(ai)
+            }
+            _ => unreachable!("The base is checked when the profile is created"),
+        }
+    }
+
+    fn setting(&self, key: &str) -> Option<i64> {
+        self.settings
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| *v)
+    }
+
+    /// The name to report results under. Profiles with settings are told apart from the AI they are based on.
+    pub fn label(&self, ai_name: &str) -> String {
+        if self.name == self.base {
+            ai_name.to_string()
+        } else {
+            format!("{ai_name} ({})", self.name)
+        }
+    }
+}
+
+/// The AIs that can play the playouts of a search AI, with the default settings
+fn rollout_policy(name: &str) -> Option<RolloutPolicy> {
+    let policy: RolloutPolicy = match name {
+        "simple" => |view, _| Box::new(SimpleAi::new(view)),
+        "greedy" => |view, rules| Box::new(GreedyAi::new(view, rules.scoring.policy())),
+        "phased" => |view, rules| Box::new(PhasedAi::new(view, rules.scoring.policy())),
+        "linear" => |view, _| Box::new(LinearAi::with_weights(view, eval::default_weights())),
+        _ => return None,
+    };
+    Some(policy)
+}
+
+/// Set a greedy priority. Returns false if there is no such priority.
+fn set_priority(policy: &mut ScoringPolicy, key: &str, value: i64) -> bool {
+    let value = value as i32;
+    match key {
+        "waste_to_foundation" => policy.waste_to_foundation = value,
+        "waste_to_depot" => policy.waste_to_depot = value,
+        "depot_to_foundation" => policy.depot_to_foundation = value,
+        "foundation_to_depot" => policy.foundation_to_depot = value,
+        "reveal" => policy.reveal = value,
+        "turnover_penalty" => policy.turnover = value,
+        _ => return false,
+    }
+    true
+}
+
+/// Set the weight of a feature. Returns false if there is no such feature.
+fn set_weight(weights: &mut eval::Weights, key: &str, value: i64) -> bool {
+    match features::index(key) {
+        Some(i) => {
+            weights[i] = value as i32;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_checked() {
+        let aggressive = AiProfile::new(
+            "greedy-aggressive",
+            "greedy",
+            vec![("turnover_penalty".to_string(), -20)],
+        );
+        assert!(aggressive.is_ok());
+        assert_eq!(
+            aggressive.unwrap().label("GreedyAi"),
+            "GreedyAi (greedy-aggressive)"
+        );
+        assert_eq!(
+            AiProfile::new("x", "simple", vec![("reveal".to_string(), 1)]),
+            Err(ProfileError::UnknownSetting {
+                profile: "x".to_string(),
+                key: "reveal".to_string()
+            })
+        );
+        assert!(AiProfile::new("x", "linear", vec![("hidden_total".to_string(), -9)]).is_ok());
+        assert_eq!(
+            AiProfile::new("x", "mcts", vec![("iterations".to_string(), -1)]),
+            Err(ProfileError::NegativeSetting {
+                profile: "x".to_string(),
+                key: "iterations".to_string()
+            })
+        );
+        assert!(AiProfile::new("x", "expectimax", vec![("depth".to_string(), -3)]).is_err());
+        assert!(AiProfile::new("x", "phased", vec![("temperature".to_string(), 50)]).is_ok());
+        let safe = AiProfile::new("x", "simple", vec![("safe_moves".to_string(), 1)]).unwrap();
+        let gs = crate::engine::GameEngine::deal(2);
+        assert_eq!(
+            safe.make(gs.observe(), gs.rules()).make_move(),
+            crate::core::Action::Move(crate::core::Addr::Depot6, crate::core::Addr::Foundation1, 1)
+        );
+        let ismcts = AiProfile::new("x", "ismcts", vec![("iterations".to_string(), 10)]).unwrap();
+        assert_eq!(
+            ismcts.clone().with_rollout("greedy").unwrap().rollout,
+            Some("greedy")
+        );
+        assert_eq!(
+            ismcts.with_rollout("mcts"),
+            Err(ProfileError::UnknownAi("mcts".to_string()))
+        );
+        assert_eq!(
+            AiProfile::builtin("minimax"),
+            Err(ProfileError::UnknownAi("minimax".to_string()))
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Safe foundation moves before anything else, with any AI
+//!
+//! A card that no card in the depots could ever need, e.g. an ace or a two, is best played to its foundation at once,
+//! see `analysis::is_safe_to_foundation`. The wrapped AI is only asked for an action when there is no such move, so
+//! it can't pass one up, and a search AI spends no time on moves that need no thought.
+//!
+//! The engine doesn't play the moves by itself, like `DealOptions::auto_finish` does, since the AIs only learn of
+//! the game through the actions they take.
+use super::{Ai, OpenObserver, SolitaireObserver};
+use crate::analysis;
+use crate::core::{Action, Turned};
+
+/// An AI that plays the safe foundation moves itself, and leaves the rest to another AI
+pub struct SafeMovesAi {
+    inner: Box<dyn Ai>,
+    view: SolitaireObserver,
+    /// The last action, if it was a safe move rather than one of the wrapped AI
+    safe: Option<Action>,
+}
+
+impl SafeMovesAi {
+    pub fn new(inner: Box<dyn Ai>, view: SolitaireObserver) -> Self {
+        SafeMovesAi {
+            inner,
+            view,
+            safe: None,
+        }
+    }
+}
+
+impl Ai for SafeMovesAi {
+    fn make_move(&mut self) -> Action {
+        self.safe = analysis::safe_foundation_move(&self.view);
+        match &self.safe {
+            Some(action) => action.clone(),
+            None => self.inner.make_move(),
+        }
+    }
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+    fn n_nodes(&self) -> usize {
+        self.inner.n_nodes()
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        self.inner.set_node_limit(limit)
+    }
+    /// A safe move is the only candidate
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        match &self.safe {
+            Some(action) => vec![(0, action.clone())],
+            None => self.inner.candidates(),
+        }
+    }
+    fn action_probabilities(&self) -> Vec<(f64, Action)> {
+        match &self.safe {
+            Some(action) => vec![(1.0, action.clone())],
+            None => self.inner.action_probabilities(),
+        }
+    }
+    fn observe_open(&mut self, open: &OpenObserver) {
+        self.inner.observe_open(open)
+    }
+    fn rng_checkpoint(&self) -> Option<u64> {
+        self.inner.rng_checkpoint()
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.view.update(action.clone(), res);
+        self.inner.update(action, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::GreedyAi;
+    use crate::core::Addr;
+    use crate::engine::GameEngine;
+
+    #[test]
+    fn plays_safe_moves_first() {
+        let mut gs = GameEngine::deal(2);
+        let greedy = GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        let mut ai = SafeMovesAi::new(Box::new(greedy), gs.observe());
+        let first = ai.make_move();
+        assert_eq!(first, Action::Move(Addr::Depot6, Addr::Foundation1, 1));
+        assert_eq!(ai.candidates(), [(0, first.clone())]);
+        ai.update(first.clone(), gs.act(&first).unwrap());
+
+        // The wrapped AI keeps track of the safe moves, and the game goes on as usual
+        let mut n_actions = 1;
+        while gs.is_running() && n_actions < 1000 {
+            let action = ai.make_move();
+            ai.update(action.clone(), gs.act(&action).unwrap());
+            n_actions += 1;
+        }
+        assert!(!gs.is_running());
+    }
+}
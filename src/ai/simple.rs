@@ -1,23 +1,26 @@
 //! A simple AI player that can play solitaire
 //!
-use super::{Action, CardView, SolitaireObserver};
-use crate::core::{Addr, Value};
+use super::{Action, SeenActions, SolitaireObserver};
+use crate::core::{Addr, Turned, Value};
 
 /// A simple AI player that can play solitaire
 ///
 pub struct SimpleAi {
-    seen_state_action_combos: std::collections::HashSet<(SolitaireObserver, Action)>,
+    seen_state_action_combos: SeenActions,
     // have we made passes through the deck?
     number_of_passes: u64,
     view: SolitaireObserver,
+    /// The untried actions at the last move, best first
+    candidates: Vec<(i64, Action)>,
 }
 
 impl SimpleAi {
     pub fn new(view: SolitaireObserver) -> Self {
         SimpleAi {
-            seen_state_action_combos: std::collections::HashSet::new(),
+            seen_state_action_combos: SeenActions::default(),
             number_of_passes: 0,
             view,
+            candidates: vec![],
         }
     }
 
@@ -31,89 +34,18 @@ impl SimpleAi {
             return actions;
         }
 
-        // Build on foundations
-        for from_addr in Addr::DEPOTS_AND_WASTE.iter() {
-            if let Some(CardView::FaceUp(suit, value)) = self.view.card_at(from_addr, 1) {
-                for to_addr in Addr::FOUNDATIONS {
-                    match self.view.card_at(&to_addr, 1) {
-                        None => {
-                            if value.is_ace() {
-                                actions.push(Action::Move(*from_addr, to_addr, 1));
-                            }
-                        }
-                        // increase by one
-                        Some(CardView::FaceUp(to_suit, to_value)) => {
-                            if suit == to_suit
-                                && value.numeric_value() == to_value.numeric_value() + 1
-                            {
-                                actions.push(Action::Move(*from_addr, to_addr, 1));
-                            }
-                        }
-                        Some(CardView::FaceDown) => {
-                            continue;
-                        }
-                    }
-                }
-            }
-        }
-
-        // Try to reveal a card
-        for (idx, a) in self.view.depots.iter().enumerate() {
-            if let Some(CardView::FaceDown) = a.last() {
-                actions.push(Action::Reveal(Addr::DEPOTS[idx]));
-            }
-        }
-
-        // Try to increase the sequences in the tableaux
-        for from in Addr::DEPOTS_AND_WASTE {
-            let max_cards_to_move = self.view.n_takeable_cards(&from);
-            if max_cards_to_move == 0 {
-                continue;
-            }
-            for to in Addr::DEPOTS.into_iter().filter(|to| to != &from) {
-                if from.is_waste() && matches!(self.view.waste.last(), Some((_, Value::TWO))) {
-                    // Don't move 2's from the hand to the tableaux - they can only ever block other cards
-                    continue;
-                }
-                if from.is_waste() && to.is_depot() {
-                    // Dont move low values from the hand to the tableaux too early
-                    if let Some((_, value)) = self.view.waste.last() {
-                        if value.numeric_value() < 5 && self.number_of_passes == 0 {
-                            continue;
-                        }
-                    }
-                }
-                for n_moves in 1..=max_cards_to_move {
-                    if let Some(CardView::FaceUp(suit, value)) = self.view.card_at(&from, n_moves) {
-                        match self.view.card_at(&to, 1) {
-                            None => {
-                                if value == Value::KING {
-                                    actions.push(Action::Move(from, to, n_moves));
-                                }
-                            }
-                            Some(CardView::FaceUp(suit2, value2)) => {
-                                let is_valid_move = suit.color() != suit2.color()
-                                    && value.numeric_value() == value2.numeric_value() - 1;
-                                if is_valid_move {
-                                    actions.push(Action::Move(from, to, n_moves));
-                                }
-                            }
-                            Some(CardView::FaceDown) => { /* do nothing */ }
-                        }
-                    }
-                }
+        // Build on foundations, reveal cards, increase the sequences in the tableaux, and take from the talon or turn it over
+        let is_low_waste_card = |value: Value| {
+            // Don't move 2's from the hand to the tableaux - they can only ever block other cards
+            // Dont move low values from the hand to the tableaux too early
+            value == Value::TWO || (value.numeric_value() < 5 && self.number_of_passes == 0)
+        };
+        actions.extend(self.view.forward_actions().into_iter().filter(|action| match action {
+            Action::Move(Addr::Waste, to, _) if to.is_depot() => {
+                !self.view.waste.last().is_some_and(|(_, value)| is_low_waste_card(*value))
             }
-        }
-
-        // Take from the talon
-        if self.view.talon_size != 0 {
-            actions.push(Action::Take);
-        }
-
-        // Turn over the talon
-        if self.view.waste.last().is_some() && self.view.talon_size == 0 {
-            actions.push(Action::Turnover);
-        }
+            _ => true,
+        }));
 
         // Give up
         actions.push(Action::Quit);
@@ -123,37 +55,54 @@ impl SimpleAi {
 
 impl super::Ai for SimpleAi {
     fn make_move(&mut self) -> Action {
+        if self.seen_state_action_combos.is_full() {
+            return Action::Quit;
+        }
         let actions = self.suggest_actions();
         // dbg!(&actions);
-        for action in actions {
-            if self
-                .seen_state_action_combos
-                .contains(&(self.view.clone(), action.clone()))
-            {
-                continue;
-            }
-            self.seen_state_action_combos
-                .insert((self.view.clone(), action.clone()));
-            if action == Action::Turnover {
-                self.number_of_passes += 1;
-            }
-            return action;
-        }
-        panic!("No action found");
+        // The actions are only ordered, so the score is minus the rank
+        self.candidates = actions
+            .into_iter()
+            .filter(|action| {
+                !self
+                    .seen_state_action_combos.contains(&self.view, action)
+            })
+            .enumerate()
+            .map(|(rank, action)| (-(rank as i64), action))
+            .collect();
+        let Some((_, action)) = self.candidates.first().cloned() else {
+            panic!("No action found");
+        };
+        action
     }
     fn name(&self) -> &'static str {
         "SimpleAi"
     }
-    fn update(&mut self, action: Action, res: Option<(crate::core::Suit, Value)>) {
+    fn n_nodes(&self) -> usize {
+        self.seen_state_action_combos.n_nodes()
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        self.seen_state_action_combos.set_limit(limit)
+    }
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        self.candidates.clone()
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.seen_state_action_combos.insert(&self.view, &action);
+        if action == Action::Turnover {
+            self.number_of_passes += 1;
+        }
         self.view.update(action, res)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::ai::DepotView;
     use crate::core::Suit;
 
     use super::*;
+    use crate::rules::{BuildRule, EmptyColumnRule};
 
     #[test]
     fn test_ai_can_win() {
@@ -162,14 +111,18 @@ mod tests {
             waste: vec![],
             foundation_tops: [None; 4],
             depots: [
-                vec![CardView::FaceUp(Suit::Hearts, Value::KING)],
-                vec![CardView::FaceUp(Suit::Clubs, Value::QUEEN)],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
+                DepotView::new(0, vec![(Suit::Hearts, Value::KING)]),
+                DepotView::new(0, vec![(Suit::Clubs, Value::QUEEN)]),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
+                DepotView::default(),
             ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
         };
         let mut ai = SimpleAi::new(view);
         let actions = ai.suggest_actions();
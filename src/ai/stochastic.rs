@@ -0,0 +1,165 @@
+//! Stochastic play with any AI that has action probabilities
+//!
+//! The wrapped AI suggests an action as usual, and then its `Ai::action_probabilities` are sharpened or flattened by
+//! a temperature and sampled from. At temperature 1 the AI plays by its own distribution, towards 0 it plays its best
+//! action, and at high temperatures all its candidates are about as likely. For the softmax of the greedy AI, this
+//! is the same as scaling the priorities by the temperature.
+//!
+//! Deterministic AIs play a narrow range of strategies, however many deals they play. Sampling spreads them out, e.g.
+//! for more varied training data from `solitaire features`, or to see how robust a policy is to its own mistakes.
+//! An AI without action probabilities plays as before.
+use super::{rng_checkpoint, Ai, OpenObserver};
+use crate::core::{Action, Turned};
+use rand::prelude::*;
+
+/// An AI that samples the actions of another AI
+pub struct StochasticAi {
+    inner: Box<dyn Ai>,
+    temperature: f64,
+    rng: StdRng,
+    /// The distribution the last action was sampled from
+    probabilities: Vec<(f64, Action)>,
+}
+
+impl StochasticAi {
+    pub fn new(inner: Box<dyn Ai>, temperature: f64) -> Self {
+        StochasticAi {
+            inner,
+            temperature: temperature.max(0.0),
+            rng: StdRng::seed_from_u64(0),
+            probabilities: vec![],
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+}
+
+/// Raise the probabilities to the power `1 / temperature`, and normalize them again
+fn with_temperature(probabilities: &[(f64, Action)], temperature: f64) -> Vec<(f64, Action)> {
+    let weights = probabilities
+        .iter()
+        .map(|(p, _)| p.powf(1.0 / temperature))
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+    weights
+        .iter()
+        .zip(probabilities)
+        .map(|(w, (_, action))| (w / total, action.clone()))
+        .collect()
+}
+
+impl Ai for StochasticAi {
+    fn make_move(&mut self) -> Action {
+        let best = self.inner.make_move();
+        self.probabilities = vec![];
+        let probabilities = self.inner.action_probabilities();
+        if self.temperature == 0.0 || probabilities.is_empty() {
+            return best;
+        }
+        self.probabilities = with_temperature(&probabilities, self.temperature);
+        match self
+            .probabilities
+            .choose_weighted(&mut self.rng, |(p, _)| *p)
+        {
+            Ok((_, action)) => action.clone(),
+            // All the weights are 0 when a low temperature underflows them
+            Err(_) => best,
+        }
+    }
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+    fn n_nodes(&self) -> usize {
+        self.inner.n_nodes()
+    }
+    fn set_node_limit(&mut self, limit: usize) {
+        self.inner.set_node_limit(limit)
+    }
+    fn candidates(&self) -> Vec<(i64, Action)> {
+        self.inner.candidates()
+    }
+    fn action_probabilities(&self) -> Vec<(f64, Action)> {
+        self.probabilities.clone()
+    }
+    fn observe_open(&mut self, open: &OpenObserver) {
+        self.inner.observe_open(open)
+    }
+    /// Both the generator of the sampling and the one of the wrapped AI, which may be seeded alike
+    fn rng_checkpoint(&self) -> Option<u64> {
+        let inner = self.inner.rng_checkpoint().unwrap_or(0);
+        Some(rng_checkpoint(&self.rng).rotate_left(32) ^ inner)
+    }
+    fn update(&mut self, action: Action, res: Turned) {
+        self.inner.update(action, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::GreedyAi;
+    use crate::engine::GameEngine;
+
+    #[test]
+    fn temperature_reshapes() {
+        let probabilities = [(0.75, Action::Take), (0.25, Action::Quit)];
+        assert_eq!(with_temperature(&probabilities, 1.0), probabilities);
+        let cold = with_temperature(&probabilities, 0.5);
+        assert!((cold[0].0 - 0.9).abs() < 1e-12);
+        let hot = with_temperature(&probabilities, 1000.0);
+        assert!((hot[0].0 - 0.5).abs() < 1e-3);
+    }
+
+    /// The actions of an AI on the first deal
+    fn actions(ai: &mut dyn Ai) -> Vec<Action> {
+        let mut gs = GameEngine::deal(0);
+        let mut actions = vec![];
+        while gs.is_running() && actions.len() < 1000 {
+            let action = ai.make_move();
+            ai.update(action.clone(), gs.act(&action).unwrap());
+            actions.push(action);
+        }
+        actions
+    }
+
+    #[test]
+    fn samples_other_games() {
+        let gs = GameEngine::deal(0);
+        let greedy = || Box::new(GreedyAi::new(gs.observe(), gs.rules().scoring.policy()));
+        let sampled = |temperature, seed| {
+            actions(&mut StochasticAi::new(greedy(), temperature).with_seed(seed))
+        };
+        assert_eq!(sampled(0.0, 1), actions(greedy().as_mut()));
+        assert_ne!(sampled(1.0, 1), sampled(1.0, 2));
+
+        // A checkpoint doesn't use up a random number
+        let mut ai = StochasticAi::new(greedy(), 1.0).with_seed(1);
+        let checkpoint = ai.rng_checkpoint();
+        assert_eq!(ai.rng_checkpoint(), checkpoint);
+        assert_eq!(actions(&mut ai), sampled(1.0, 1));
+        assert_ne!(ai.rng_checkpoint(), checkpoint);
+    }
+
+    /// The wrapped AI remembers the actions that were sampled, not the ones it would have played, so it never
+    /// offers an action again in a position where it was played
+    #[test]
+    fn samples_are_remembered() {
+        for seed in 0..5 {
+            let mut gs = GameEngine::deal(seed);
+            let greedy = GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+            let mut ai = StochasticAi::new(Box::new(greedy), 2.0).with_seed(seed);
+            let mut played = std::collections::HashSet::new();
+            while gs.is_running() {
+                let action = ai.make_move();
+                assert!(
+                    played.insert((gs.observe(), action.clone())),
+                    "{action:?} again"
+                );
+                ai.update(action.clone(), gs.act(&action).unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! Analysis of game positions
+//!
+//! These tools evaluate a position from what an observer knows, e.g. to show how a game is going while watching it.
+//! The bounds look at the full game, like a search over determinized games does.
+
+use crate::ai::{GreedyAi, SolitaireObserver};
+use crate::core::{Action, Addr, CardView, Suit, Value};
+use crate::engine::{run_game, GameEngine};
+use crate::rules::{EmptyColumnRule, RuleSet, Variant};
+use rand::prelude::*;
+
+/// A rollout that has not finished after this many actions is counted as a loss
+const ROLLOUT_MAX_ACTIONS: usize = 2000;
+
+/// Estimate the probability that the game will be won from the observed position
+///
+/// The hidden cards are placed at random `n_rollouts` times, and each of these games is played out by a `GreedyAi`.
+/// The estimate is the fraction of the rollouts that were won.
+pub fn win_probability(
+    view: &SolitaireObserver,
+    rules: &RuleSet,
+    n_rollouts: usize,
+    seed: u64,
+) -> f64 {
+    if n_rollouts == 0 {
+        return 0.0;
+    }
+    let mut rng: StdRng = rand::SeedableRng::seed_from_u64(seed);
+    let mut n_wins = 0;
+    for _ in 0..n_rollouts {
+        let mut gs = GameEngine::determinize(view, rules, &mut rng);
+        let mut ai = GreedyAi::new(gs.observe(), rules.scoring.policy());
+        run_game(&mut gs, &mut ai, ROLLOUT_MAX_ACTIONS);
+        n_wins += gs.is_won() as usize;
+    }
+    n_wins as f64 / n_rollouts as f64
+}
+
+/// A lower bound on the number of moves left to win: every card that is not on a foundation has to be moved there
+pub fn min_moves_to_win(gs: &GameEngine) -> usize {
+    gs.cards()
+        .filter(|(addr, _, _)| !addr.is_foundation())
+        .count()
+}
+
+/// Is the game certainly lost? See `Deadlock`
+pub fn is_hopeless(gs: &GameEngine) -> bool {
+    find_deadlock(gs).is_some()
+}
+
+/// Find a proof that the game can't be won, if there is a simple one
+pub fn find_deadlock(gs: &GameEngine) -> Option<Deadlock> {
+    let depot_cards = gs
+        .cards()
+        .filter(|(addr, _, _)| addr.is_depot())
+        .map(|(addr, i, card)| (addr, i, card.clone().into()))
+        .collect::<Vec<(Addr, usize, (Suit, Value))>>();
+    depot_cards.iter().find_map(|&(addr, i, card)| {
+        depot_cards
+            .iter()
+            .filter(|&&(a, j, c)| a == addr && j < i && c.0 == card.0 && c.1 < card.1)
+            .map(|&(_, _, covered)| Deadlock { card, covered })
+            .find(|deadlock| deadlock.verify(gs))
+    })
+}
+
+/// Find a shortest sequence of at most `max_moves` moves that leaves a depot without cards
+///
+/// Empty depots are the most useful places on the table, so it is worth knowing how far away the next one is.
+/// Only face up cards are moved: the talon is left alone and no card is turned face up, not even by auto reveal.
+/// The plan therefore depends only on what an observer knows, and holds in every determinization of its view.
+/// The plan is empty if a depot is empty already, and `None` if no depot can be emptied in time.
+pub fn plan_empty_column(gs: &GameEngine, max_moves: usize) -> Option<Vec<Action>> {
+    let mut gs = gs.clone();
+    let mut plan = vec![];
+    (0..=max_moves).find(|&depth| search_empty_column(&mut gs, depth, &mut plan))?;
+    Some(plan)
+}
+
+fn has_empty_depot(gs: &GameEngine) -> bool {
+    Addr::DEPOTS
+        .iter()
+        .any(|depot| gs.cards().all(|(addr, _, _)| addr != *depot))
+}
+
+/// Depth first search for a plan of at most `depth` more moves, which are added to `plan`
+fn search_empty_column(gs: &mut GameEngine, depth: usize, plan: &mut Vec<Action>) -> bool {
+    if has_empty_depot(gs) {
+        return true;
+    }
+    if depth == 0 {
+        return false;
+    }
+    for action in gs.legal_actions() {
+        if !matches!(action, Action::Move(..) | Action::MoveFoundationPile(..)) {
+            continue;
+        }
+        let turned = gs.act(&action).expect("The action is legal");
+        if turned.is_empty() {
+            plan.push(action);
+            let found = search_empty_column(gs, depth - 1, plan);
+            gs.undo();
+            if found {
+                return true;
+            }
+            plan.pop();
+        } else {
+            gs.undo();
+        }
+    }
+    false
+}
+
+/// Score each way to move a king to an empty depot by what it unblocks
+///
+/// A king that leaves a depot uncovers its face down cards, which count double. The cards that could follow the moved
+/// run count once each: the visible cards that fit on its top card and don't already lie on a card they fit, and the
+/// ones that fit on those in turn. These are e.g. the red queens and black jacks that wait for a black king.
+pub fn king_choices(view: &SolitaireObserver) -> Vec<(Action, usize)> {
+    view.forward_actions()
+        .into_iter()
+        .filter_map(|action| {
+            let Action::Move(from, to, n) = action else {
+                return None;
+            };
+            let (true, None) = (to.is_depot(), view.card_at(&to, 1)) else {
+                return None;
+            };
+            let Some(CardView::FaceUp(suit, value)) = view.card_at(&from, 1) else {
+                return None;
+            };
+            let n_hidden = from
+                .depot_id()
+                .map_or(0, |depot| view.depots[depot.index()].n_face_down);
+            let loose = loose_cards(view, from, n);
+            let fits =
+                |card: &(Suit, Value), onto: &(Suit, Value)| view.building.fits(*card, *onto);
+            let followers = loose
+                .iter()
+                .filter(|card| fits(card, &(suit, value)))
+                .collect::<Vec<_>>();
+            let n_next = loose
+                .iter()
+                .filter(|card| followers.iter().any(|onto| fits(card, onto)))
+                .count();
+            Some((action, 2 * n_hidden + followers.len() + n_next))
+        })
+        .collect()
+}
+
+/// The visible cards that don't lie on a card they fit, except the top `n` cards of `skip`
+fn loose_cards(view: &SolitaireObserver, skip: Addr, n: usize) -> Vec<(Suit, Value)> {
+    let mut loose = view.waste.clone();
+    for (addr, depot) in Addr::DEPOTS.iter().zip(view.depots.iter()) {
+        let face_up = &depot.face_up;
+        let end = if *addr == skip {
+            face_up.len() - n
+        } else {
+            face_up.len()
+        };
+        // The bottom face up card lies on a face down card or on nothing, so it is never built
+        for (i, &card) in face_up[..end].iter().enumerate() {
+            let is_built = i
+                .checked_sub(1)
+                .is_some_and(|j| view.building.fits(card, face_up[j]));
+            if !is_built {
+                loose.push(card);
+            }
+        }
+    }
+    loose
+}
+
+/// Can the card go to its foundation without being missed in the depots?
+///
+/// Nothing is built on aces and twos, as the aces go to the foundations anyway. A higher card is only needed to build
+/// the cards one lower on it, see `BuildRule::fits`, so it is safe once all of those are on the foundations. With the
+/// classic rules, these are the two cards of the other color one lower.
+pub fn is_safe_to_foundation(view: &SolitaireObserver, card: (Suit, Value)) -> bool {
+    let on_foundation = |suit: Suit| {
+        view.foundation_tops
+            .iter()
+            .flatten()
+            .find(|(s, _)| *s == suit)
+            .map_or(0, |(_, v)| v.numeric_value())
+    };
+    let Ok(lower) = Value::try_from(card.1.numeric_value() - 1) else {
+        return true;
+    };
+    lower == Value::ACE
+        || [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades]
+            .into_iter()
+            .filter(|&s| view.building.fits((s, lower), card))
+            .all(|s| on_foundation(s) >= lower.numeric_value())
+}
+
+/// A move of a card from the waste or a depot to its foundation that is safe, see `is_safe_to_foundation`
+pub fn safe_foundation_move(view: &SolitaireObserver) -> Option<Action> {
+    view.legal_actions()
+        .into_iter()
+        .find(|action| match action {
+            Action::Move(from, to, 1) if to.is_foundation() => match view.card_at(from, 1) {
+                Some(CardView::FaceUp(suit, value)) => is_safe_to_foundation(view, (suit, value)),
+                _ => false,
+            },
+            _ => false,
+        })
+}
+
+/// A proof that a game can't be won: a card that is stuck
+///
+/// The card lies above a lower card of its own suit in a depot, so it can't go to the foundations,
+/// and it can't go to another depot either. That is, it is not a king, empty depots only take kings,
+/// and the cards it could be placed on are below it in the same depot, or on the foundations for good.
+/// A face up card right beneath it doesn't count, as the two can move on together as a run.
+/// Many lost games have no stuck card, so only some of them have a proof like this.
+/// There are no proofs in Yukon and Russian, where any face up group can be moved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deadlock {
+    pub card: (Suit, Value),
+    /// The card of the same suit below it
+    pub covered: (Suit, Value),
+}
+
+impl Deadlock {
+    /// Check the proof, by looking at the stuck card and the cards around it only
+    pub fn verify(&self, gs: &GameEngine) -> bool {
+        let rules = gs.rules();
+        let position = |card: (Suit, Value)| {
+            gs.cards()
+                .find(|(_, _, c)| <(Suit, Value)>::from((*c).clone()) == card)
+                .map(|(addr, i, _)| (addr, i))
+                .expect("All cards are in the game")
+        };
+        let (depot, i) = position(self.card);
+        let (covered_addr, j) = position(self.covered);
+        let Ok(parent_value) = Value::try_from(self.card.1.numeric_value() + 1) else {
+            return false;
+        };
+        let n_face_down = depot
+            .depot_id()
+            .map_or(0, |d| gs.observe().depots[d.index()].n_face_down);
+        let is_blocked = |parent: (Suit, Value)| match position(parent) {
+            (addr, k) if addr == depot => k < i && !(k + 1 == i && k >= n_face_down),
+            (addr, _) if addr.is_foundation() => !rules.foundation_retreat,
+            _ => false,
+        };
+        rules.variant == Variant::Klondike
+            && rules.empty_column == EmptyColumnRule::KingsOnly
+            && depot.is_depot()
+            && covered_addr == depot
+            && j < i
+            && self.covered.0 == self.card.0
+            && self.covered.1 < self.card.1
+            && [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades]
+                .into_iter()
+                .filter(|&s| rules.building.fits(self.card, (s, parent_value)))
+                .all(|s| is_blocked((s, parent_value)))
+    }
+}
+
+impl std::fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}{} covers {}{}, and the cards it could go on are out of reach",
+            self.card.0, self.card.1, self.covered.0, self.covered.1
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        let gs = GameEngine::deal(0);
+        assert_eq!(min_moves_to_win(&gs), 52);
+        assert!(!is_hopeless(&gs));
+        // The seven of clubs in depot 7 lies above the ace of clubs and both red eights
+        let gs = GameEngine::deal(10);
+        let seven = Value::try_from(7).unwrap();
+        let deadlock = Deadlock {
+            card: (Suit::Clubs, seven),
+            covered: (Suit::Clubs, Value::ACE),
+        };
+        assert_eq!(find_deadlock(&gs), Some(deadlock.clone()));
+        assert!(deadlock.verify(&gs));
+        // The queen of spades in depot 4 lies above the jack of spades, but it can go on a red king
+        let bogus = Deadlock {
+            card: (Suit::Spades, Value::QUEEN),
+            covered: (Suit::Spades, Value::try_from(11).unwrap()),
+        };
+        assert!(!bogus.verify(&gs));
+    }
+
+    /// A game with the given face down and face up cards in depot 1, and all other cards in the talon
+    fn one_depot(
+        face_down: &[(Suit, Value)],
+        face_up: &[(Suit, Value)],
+        rules: &RuleSet,
+    ) -> GameEngine {
+        use crate::ai::DepotView;
+        let mut open = GameEngine::deal(0).observe_open();
+        open.talon = GameEngine::deal(0)
+            .cards()
+            .map(|(_, _, c)| c.clone().into())
+            .filter(|c| !face_down.contains(c) && !face_up.contains(c))
+            .collect();
+        open.face_down = Default::default();
+        open.face_down[0] = face_down.to_vec();
+        open.view.talon_size = open.talon.len();
+        open.view.waste = vec![];
+        open.view.foundation_tops = [None; 4];
+        open.view.depots = Default::default();
+        open.view.depots[0] = DepotView::new(face_down.len(), face_up.to_vec());
+        GameEngine::from_open(&open, rules)
+    }
+
+    #[test]
+    fn runs_are_not_stuck() {
+        // The seven of clubs lies on the eight of hearts, and can go on with it to a black nine. Then the eight of
+        // diamonds can follow, and the ace of clubs is free.
+        let card = |suit, value: u8| (suit, Value::try_from(value).unwrap());
+        let face_down = [card(Suit::Clubs, 1), card(Suit::Diamonds, 8)];
+        let face_up = [card(Suit::Hearts, 8), card(Suit::Clubs, 7)];
+        let gs = one_depot(&face_down, &face_up, &RuleSet::default());
+        assert_eq!(find_deadlock(&gs), None);
+        assert!(!is_hopeless(&gs));
+        // With both eights face down, the seven can't reach either of them
+        let face_down = [
+            card(Suit::Clubs, 1),
+            card(Suit::Diamonds, 8),
+            card(Suit::Hearts, 8),
+        ];
+        let gs = one_depot(&face_down, &face_up[1..], &RuleSet::default());
+        assert!(is_hopeless(&gs));
+        // In Yukon, the group from the eight of hearts up can move even if a two lies between it and the seven
+        let face_up = [
+            card(Suit::Hearts, 8),
+            card(Suit::Diamonds, 2),
+            card(Suit::Clubs, 7),
+        ];
+        let gs = one_depot(&face_down[..2], &face_up, &RuleSet::YUKON);
+        assert!(!is_hopeless(&gs));
+    }
+
+    #[test]
+    fn proofs_with_a_run_are_rejected() {
+        let card = |suit, value: u8| (suit, Value::try_from(value).unwrap());
+        let claim = Deadlock {
+            card: card(Suit::Clubs, 7),
+            covered: card(Suit::Clubs, 1),
+        };
+        // The seven of clubs lies right on the eight of hearts, so the two can move on together
+        let face_down = [card(Suit::Clubs, 1), card(Suit::Diamonds, 8)];
+        let gs = one_depot(
+            &face_down,
+            &[card(Suit::Hearts, 8), card(Suit::Clubs, 7)],
+            &RuleSet::default(),
+        );
+        assert!(!claim.verify(&gs));
+        // Face down, the eight of hearts is out of reach
+        let face_down = [
+            card(Suit::Clubs, 1),
+            card(Suit::Diamonds, 8),
+            card(Suit::Hearts, 8),
+        ];
+        let gs = one_depot(&face_down, &[card(Suit::Clubs, 7)], &RuleSet::default());
+        assert!(claim.verify(&gs));
+    }
+
+    #[test]
+    fn safe_foundation_moves() {
+        let mut view = GameEngine::deal(0).observe();
+        let three = Value::try_from(3).unwrap();
+        assert!(is_safe_to_foundation(&view, (Suit::Hearts, Value::ACE)));
+        assert!(is_safe_to_foundation(&view, (Suit::Hearts, Value::TWO)));
+        assert!(!is_safe_to_foundation(&view, (Suit::Hearts, three)));
+        // The black twos are all a red three could hold
+        view.foundation_tops = [
+            None,
+            None,
+            Some((Suit::Clubs, Value::TWO)),
+            Some((Suit::Spades, Value::TWO)),
+        ];
+        assert!(is_safe_to_foundation(&view, (Suit::Hearts, three)));
+        assert!(!is_safe_to_foundation(&view, (Suit::Clubs, three)));
+
+        // The ace in depot 6 can go up at once
+        let gs = GameEngine::deal(2);
+        assert_eq!(
+            safe_foundation_move(&gs.observe()),
+            Some(Action::Move(Addr::Depot6, Addr::Foundation1, 1))
+        );
+        assert_eq!(safe_foundation_move(&GameEngine::deal(0).observe()), None);
+    }
+
+    #[test]
+    fn empty_column_plans() {
+        // The ace in depot 6 has to go up before the two in depot 1 can follow it
+        let gs = GameEngine::deal(2);
+        assert_eq!(plan_empty_column(&gs, 1), None);
+        let plan = plan_empty_column(&gs, 4).unwrap();
+        assert_eq!(
+            plan,
+            [
+                Action::Move(Addr::Depot6, Addr::Foundation1, 1),
+                Action::Move(Addr::Depot1, Addr::Foundation1, 1),
+            ]
+        );
+        let mut gs = gs.clone();
+        for action in &plan {
+            gs.act(action).unwrap();
+        }
+        assert!(gs.cards().all(|(addr, _, _)| addr != Addr::Depot1));
+        assert_eq!(plan_empty_column(&gs, 0), Some(vec![]));
+    }
+
+    #[test]
+    fn king_choices_count_what_they_unblock() {
+        use crate::{
+            ai::DepotView,
+            rules::{BuildRule, EmptyColumnRule},
+        };
+        let up = |suit, value: u8| (suit, Value::try_from(value).unwrap());
+        let view = SolitaireObserver {
+            talon_size: 0,
+            waste: vec![(Suit::Spades, Value::KING)],
+            foundation_tops: [None; 4],
+            depots: [
+                DepotView::new(1, vec![up(Suit::Hearts, 13)]),
+                DepotView::default(),
+                DepotView::new(1, vec![up(Suit::Diamonds, 12)]),
+                DepotView::new(1, vec![up(Suit::Hearts, 12)]),
+                DepotView::new(1, vec![up(Suit::Clubs, 11)]),
+                DepotView::new(0, vec![up(Suit::Diamonds, 2)]),
+                DepotView::new(0, vec![up(Suit::Diamonds, 3)]),
+            ],
+            building: BuildRule::AlternateColors,
+            empty_column: EmptyColumnRule::KingsOnly,
+            foundation_retreat: true,
+            redeals_left: None,
+        };
+        // The king of spades takes both red queens, and then the jack of clubs.
+        // The king of hearts uncovers a card, but no queen waits for it.
+        assert_eq!(
+            king_choices(&view),
+            [
+                (Action::Move(Addr::Depot1, Addr::Depot2, 1), 2),
+                (Action::Move(Addr::Waste, Addr::Depot2, 1), 3),
+            ]
+        );
+    }
+}
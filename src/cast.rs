@@ -0,0 +1,99 @@
+//! Export of games as terminal recordings
+//!
+//! Writes the asciicast v2 format (<https://docs.asciinema.org/manual/asciicast/v2/>), which can be played back
+//! with `asciinema play` or embedded on web pages. Each frame clears the screen and prints the board.
+//!
+//! The frames follow each other at a fixed pace, so that the moves can be followed, but when it took longer to get to
+//! a frame, e.g. while an AI was thinking, the recording waits as long.
+
+use crate::json::json_string;
+use std::io::Write;
+
+/// Clear the screen and move the cursor to the top left corner
+const CLEAR_SCREEN: &str = "\u{1b}[2J\u{1b}[H";
+
+/// A recording of a game, one frame per position
+pub struct Cast {
+    title: String,
+    /// Seconds between two frames, at least
+    frame_duration: f64,
+    /// The frames, with the seconds from the start of the recording
+    frames: Vec<(f64, String)>,
+}
+
+impl Cast {
+    pub fn new(title: String, frame_duration: f64) -> Self {
+        Cast {
+            title,
+            frame_duration,
+            frames: vec![],
+        }
+    }
+
+    /// Add a frame with the given text to the recording
+    pub fn push_frame(&mut self, text: &str) {
+        self.push_frame_after(0.0, text);
+    }
+
+    /// Add a frame that took some seconds to get to since the last one
+    pub fn push_frame_after(&mut self, seconds: f64, text: &str) {
+        let time = match self.frames.last() {
+            Some((last, _)) => last + seconds.max(self.frame_duration),
+            None => 0.0,
+        };
+        self.frames.push((time, text.to_string()));
+    }
+
+    /// Write the recording in the asciicast v2 format
+    pub fn write_to(&self, w: &mut impl Write) -> Result<(), std::io::Error> {
+        let width = self
+            .frames
+            .iter()
+            .flat_map(|(_, f)| f.lines())
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0);
+        let height = self
+            .frames
+            .iter()
+            .map(|(_, f)| f.lines().count())
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            w,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"title\": {}}}",
+            width.max(1),
+            height.max(1),
+            json_string(&self.title)
+        )?;
+        for (time, frame) in self.frames.iter() {
+            let text = format!("{CLEAR_SCREEN}{}", frame.replace('\n', "\r\n"));
+            writeln!(w, "[{time:.3}, \"o\", {}]", json_string(&text))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_header_and_frames() {
+        let mut cast = Cast::new("A \"game\"".to_string(), 0.5);
+        cast.push_frame("ab\ncd");
+        cast.push_frame("abc");
+        cast.push_frame_after(2.0, "a");
+        let mut out = vec![];
+        cast.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            r#"{"version": 2, "width": 3, "height": 2, "title": "A \"game\""}"#
+        );
+        assert_eq!(lines[1], r#"[0.000, "o", "\u001b[2J\u001b[Hab\r\ncd"]"#);
+        assert_eq!(lines[2], r#"[0.500, "o", "\u001b[2J\u001b[Habc"]"#);
+        assert_eq!(lines[3], r#"[2.500, "o", "\u001b[2J\u001b[Ha"]"#);
+    }
+}
@@ -0,0 +1,176 @@
+//! The configuration file
+//!
+//! The file `solitaire.toml` (or the file given with `--config`) is a small subset of TOML:
+//! sections `[ai.NAME]`, each with lines `key = value` where the value is an integer or a quoted string.
+//! Lines starting with `#` are comments.
+//!
+//! ```toml
+//! [ai.greedy-aggressive]
+//! turnover_penalty = -20
+//!
+//! [ai.careful]
+//! base = "phased"
+//! foundation_to_depot = -50
+//!
+//! [ai.mcts-greedy]
+//! rollout = "greedy"
+//!
+//! [ai.greedy-sampled]
+//! temperature = 100
+//! seed = 7
+//!
+//! [ai.mcts-safe]
+//! safe_moves = 1
+//! ```
+//!
+//! A section `[ai.NAME]` defines an AI profile. Its `base` AI defaults to the start of the name up to the first `-`.
+//! The search AIs take the AI that plays their playouts as `rollout`.
+
+use crate::ai::profile::{AiProfile, ProfileError, BUILTIN};
+use thiserror::Error;
+
+/// The default configuration file, read if it exists
+pub const DEFAULT_PATH: &str = "solitaire.toml";
+
+/// Errors in the configuration file
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigError {
+    #[error("Line {0}: {1}")]
+    Syntax(usize, String),
+    #[error(transparent)]
+    Profile(#[from] ProfileError),
+}
+
+/// The contents of the configuration file
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub ai_profiles: Vec<AiProfile>,
+}
+
+/// A section of the file, before it is checked
+struct Section {
+    name: String,
+    base: Option<String>,
+    rollout: Option<String>,
+    settings: Vec<(String, i64)>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let mut sections: Vec<Section> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let syntax = |msg: &str| ConfigError::Syntax(line_number, msg.to_string());
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| syntax("Unterminated section header"))?;
+                let name = header
+                    .strip_prefix("ai.")
+                    .ok_or_else(|| syntax("Only [ai.NAME] sections are supported"))?;
+                sections.push(Section {
+                    name: name.to_string(),
+                    base: None,
+                    rollout: None,
+                    settings: vec![],
+                });
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| syntax("Expected key = value"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let section = sections
+                .last_mut()
+                .ok_or_else(|| syntax("Setting outside of a section"))?;
+            let quoted = || {
+                value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .map(str::to_string)
+                    .ok_or_else(|| syntax(&format!("The {key} should be a quoted string")))
+            };
+            if key == "base" {
+                section.base = Some(quoted()?);
+            } else if key == "rollout" {
+                section.rollout = Some(quoted()?);
+            } else {
+                let value = value.parse().map_err(|_| syntax("Expected an integer"))?;
+                section.settings.push((key.to_string(), value));
+            }
+        }
+
+        let mut config = Config::default();
+        for section in sections {
+            let base = section.base.unwrap_or_else(|| {
+                let prefix = section.name.split('-').next().unwrap_or_default();
+                BUILTIN
+                    .into_iter()
+                    .find(|b| *b == prefix)
+                    .unwrap_or(&section.name)
+                    .to_string()
+            });
+            let mut profile = AiProfile::new(&section.name, &base, section.settings)?;
+            if let Some(rollout) = section.rollout {
+                profile = profile.with_rollout(&rollout)?;
+            }
+            config.ai_profiles.push(profile);
+        }
+        Ok(config)
+    }
+
+    /// Find a profile by name. The built-in AIs are also profiles.
+    pub fn ai_profile(&self, name: &str) -> Result<AiProfile, ProfileError> {
+        match self.ai_profiles.iter().find(|p| p.name == name) {
+            Some(profile) => Ok(profile.clone()),
+            None => AiProfile::builtin(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_profiles() {
+        let config = Config::parse(
+            "# Experiments\n[ai.greedy-aggressive]\nturnover_penalty = -20\n\n[ai.careful]\nbase = \"phased\"\nreveal = 7\n",
+        )
+        .unwrap();
+        let aggressive = config.ai_profile("greedy-aggressive").unwrap();
+        assert_eq!(aggressive.base, "greedy");
+        assert_eq!(
+            aggressive.settings,
+            vec![("turnover_penalty".to_string(), -20)]
+        );
+        assert_eq!(config.ai_profile("careful").unwrap().base, "phased");
+        assert_eq!(
+            config.ai_profile("simple").unwrap(),
+            AiProfile::builtin("simple").unwrap()
+        );
+        assert_eq!(
+            Config::parse("[ai.x]\nreveal = many\n"),
+            Err(ConfigError::Syntax(2, "Expected an integer".to_string()))
+        );
+        let config = Config::parse("[ai.ismcts-greedy]\nrollout = \"greedy\"\n").unwrap();
+        assert_eq!(config.ai_profiles[0].rollout, Some("greedy"));
+        assert_eq!(
+            Config::parse("[ai.x]\nbase = \"mcts\"\nrollout = greedy\n"),
+            Err(ConfigError::Syntax(
+                3,
+                "The rollout should be a quoted string".to_string()
+            ))
+        );
+        assert_eq!(
+            Config::parse("[ai.custom]\nreveal = 1\n"),
+            Err(ConfigError::Profile(ProfileError::UnknownAi(
+                "custom".to_string()
+            )))
+        );
+    }
+}
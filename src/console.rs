@@ -0,0 +1,273 @@
+//! Play a game one command per line, e.g. from a script
+//!
+//! `solitaire play --lines [DEAL]` prints the board and reads commands from the standard input, one per line, without
+//! taking over the terminal. After each action the board is printed again, and a command that can't be parsed or an
+//! action that isn't legal is answered with a line starting with `Error:`. The game ends when it is won, when it is
+//! given up, or when the input ends.
+//!
+//! With an advisor, see `advisor`, an action the advisor would not have taken is followed by a line starting with
+//! `Advice:`, and the accuracy is printed at the end. The advisor also gives hints.
+//!
+//! The commands are
+//!  - `take` or `t`, and `turnover` or `o`
+//!  - `reveal D`, where D is a depot `1` to `7`
+//!  - `move FROM TO [N]`, where the piles are `w` for the waste, `1` to `7` for the depots and `f1` to `f4` for the
+//!    foundations. N is the number of cards, 1 if left out.
+//!  - `undo` or `u`, `board` or `b` to print the board again, `help` or `h`, and `quit` or `q`
+//!  - `hint` to print what the advisor would play, in a line starting with `Hint:`
+//!  - `play [N]` to let the advisor play N actions for you, or on to the end of the game, each in a line starting
+//!    with `Played:`
+//!  - `finish` to play all cards to the foundations once the talon and the waste are empty and all cards are face up,
+//!    see `GameEngine::auto_finish`
+//!  - an action in the move notation of replay files, e.g. `W->D3`, see `Action`, or as written by `Debug`, e.g.
+//!    `Move(Waste, Depot3, 1)`
+
+use crate::advisor::Advisor;
+use crate::core::{parse_action, Action, Addr};
+use crate::engine::{Autoplay, GameEngine, Stop};
+use std::io::{BufRead, Write};
+
+const HELP: &str =
+    "Commands: take, turnover, reveal D, move FROM TO [N], undo, finish, board, hint, play [N], help, quit. \
+     Piles: w, 1-7, f1-f4";
+
+/// A parsed line of input
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Act(Action),
+    Undo,
+    Finish,
+    Board,
+    Hint,
+    /// Let the advisor play, at most this many actions
+    PlayOn(Option<usize>),
+    Help,
+}
+
+/// Parse a line of input
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let command = match words[..] {
+        ["take" | "t"] => Command::Act(Action::Take),
+        ["turnover" | "o"] => Command::Act(Action::Turnover),
+        ["quit" | "q"] => Command::Act(Action::Quit),
+        ["undo" | "u"] => Command::Undo,
+        ["finish"] => Command::Finish,
+        ["board" | "b"] => Command::Board,
+        ["hint"] => Command::Hint,
+        ["play"] => Command::PlayOn(None),
+        ["play", n] => Command::PlayOn(Some(
+            n.parse()
+                .map_err(|_| format!("Not a number of actions: {n}"))?,
+        )),
+        ["help" | "h" | "?"] => Command::Help,
+        ["reveal", depot] => Command::Act(Action::Reveal(parse_pile(depot)?)),
+        ["move", from, to] => Command::Act(Action::Move(parse_pile(from)?, parse_pile(to)?, 1)),
+        ["move", from, to, n] => {
+            let n = n
+                .parse()
+                .map_err(|_| format!("Not a number of cards: {n}"))?;
+            Command::Act(Action::Move(parse_pile(from)?, parse_pile(to)?, n))
+        }
+        _ => Command::Act(
+            line.parse()
+                .ok()
+                .or_else(|| parse_action(line))
+                .ok_or_else(|| format!("Unknown command: {}", line.trim()))?,
+        ),
+    };
+    Ok(command)
+}
+
+/// Parse a pile: `w` for the waste, `1` to `7` for the depots, and `f1` to `f4` for the foundations
+fn parse_pile(s: &str) -> Result<Addr, String> {
+    let index = |piles: &[Addr], i: &str| {
+        i.parse::<usize>()
+            .ok()
+            .filter(|i| (1..=piles.len()).contains(i))
+    };
+    match s {
+        "w" => Ok(Addr::Waste),
+        _ => match s.strip_prefix('f') {
+            Some(i) => index(&Addr::FOUNDATIONS, i).map(|i| Addr::FOUNDATIONS[i - 1]),
+            None => index(&Addr::DEPOTS, s).map(|i| Addr::DEPOTS[i - 1]),
+        }
+        .ok_or_else(|| format!("Unknown pile: {s}")),
+    }
+}
+
+/// Play the game with the commands in the input, until it ends or the input does, and return the actions taken
+pub fn play(
+    gs: &mut GameEngine,
+    input: impl BufRead,
+    mut out: impl Write,
+    mut advisor: Option<&mut Advisor>,
+) -> Result<Vec<Action>, std::io::Error> {
+    let mut actions = vec![];
+    write!(out, "{gs}")?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_command(&line) {
+            Ok(Command::Act(action)) => {
+                let advice = match advisor.as_mut() {
+                    Some(advisor) if gs.is_legal(&action) => advisor.review(gs, &action),
+                    _ => None,
+                };
+                match gs.act(&action) {
+                    Ok(_) => {
+                        actions.push(action);
+                        write!(out, "{gs}")?;
+                    }
+                    Err(e) => writeln!(out, "Error: {action} is not allowed: {e}")?,
+                }
+                if let Some(advice) = advice {
+                    let name = advisor.as_ref().map_or("", |a| a.name());
+                    writeln!(
+                        out,
+                        "Advice: {name} prefers {} ({:+})",
+                        advice.preferred, advice.gain
+                    )?;
+                }
+            }
+            Ok(Command::Undo) => match gs.undo() {
+                Some(_) => {
+                    actions.pop();
+                    write!(out, "{gs}")?;
+                }
+                None => writeln!(out, "Error: nothing to undo")?,
+            },
+            Ok(Command::Finish) => {
+                let finish = gs.auto_finish();
+                if finish.is_empty() {
+                    writeln!(out, "Error: the cards can't all go to the foundations yet")?;
+                } else {
+                    actions.extend(finish);
+                    write!(out, "{gs}")?;
+                }
+            }
+            Ok(Command::Board) => write!(out, "{gs}")?,
+            Ok(Command::Hint) => match advisor.as_ref() {
+                Some(advisor) => writeln!(out, "Hint: {}", advisor.hint(gs))?,
+                None => writeln!(out, "Error: hints need an advisor, see --advisor")?,
+            },
+            Ok(Command::PlayOn(max_actions)) => match advisor.as_ref() {
+                Some(advisor) => {
+                    let mut ai = advisor.make_ai(gs);
+                    let mut autoplay = Autoplay::new(gs, ai.as_mut());
+                    if let Some(max_actions) = max_actions {
+                        autoplay = autoplay.max_actions(max_actions);
+                    }
+                    for snapshot in autoplay.by_ref() {
+                        let action = snapshot.last_action.expect("The advisor took an action");
+                        writeln!(out, "Played: {action}")?;
+                        actions.push(action);
+                    }
+                    if let Some(Stop::IllegalAction(action)) = autoplay.stop() {
+                        let name = advisor.name();
+                        writeln!(
+                            out,
+                            "Error: {name} suggested {action}, which is not allowed"
+                        )?;
+                    }
+                    write!(out, "{gs}")?;
+                }
+                None => writeln!(out, "Error: playing on needs an advisor, see --advisor")?,
+            },
+            Ok(Command::Help) => writeln!(out, "{HELP}")?,
+            Err(e) => writeln!(out, "Error: {e}")?,
+        }
+        if !gs.is_running() {
+            break;
+        }
+    }
+    if let Some(advisor) = advisor {
+        writeln!(out, "{}", advisor.summary())?;
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::profile::AiProfile;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse_command("t"), Ok(Command::Act(Action::Take)));
+        assert_eq!(
+            parse_command("  move w 3 "),
+            Ok(Command::Act(Action::Move(Addr::Waste, Addr::Depot3, 1)))
+        );
+        assert_eq!(
+            parse_command("move 7 f2 3"),
+            Ok(Command::Act(Action::Move(
+                Addr::Depot7,
+                Addr::Foundation2,
+                3
+            )))
+        );
+        assert_eq!(
+            parse_command("Reveal(Depot1)"),
+            Ok(Command::Act(Action::Reveal(Addr::Depot1)))
+        );
+        assert_eq!(
+            parse_command("D3->D7 x2"),
+            Ok(Command::Act(Action::Move(Addr::Depot3, Addr::Depot7, 2)))
+        );
+        assert_eq!(parse_command("undo"), Ok(Command::Undo));
+        assert_eq!(parse_command("finish"), Ok(Command::Finish));
+        assert_eq!(parse_command("play 3"), Ok(Command::PlayOn(Some(3))));
+        assert_eq!(
+            parse_command("move 8 w"),
+            Err("Unknown pile: 8".to_string())
+        );
+        assert_eq!(
+            parse_command("move f0 w"),
+            Err("Unknown pile: f0".to_string())
+        );
+        assert_eq!(
+            parse_command("jump"),
+            Err("Unknown command: jump".to_string())
+        );
+    }
+
+    #[test]
+    fn play_from_lines() {
+        let mut gs = GameEngine::deal(0);
+        let input = "take\nmove 1 2\n\nnonsense\nfinish\ntake\nundo\nquit\ntake\n";
+        let mut out = vec![];
+        let actions = play(&mut gs, input.as_bytes(), &mut out, None).unwrap();
+        assert_eq!(actions, [Action::Take, Action::Quit]);
+        assert!(!gs.is_running());
+        let out = String::from_utf8(out).unwrap();
+        let errors = out
+            .lines()
+            .filter(|line| line.starts_with("Error:"))
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 3, "{out}");
+        assert!(errors[1].contains("Unknown command: nonsense"));
+        assert!(errors[2].contains("can't all go to the foundations"));
+        assert_eq!(out.matches("Talon size").count(), 5);
+
+        let mut gs = GameEngine::deal(0);
+        let mut advisor = Advisor::new(AiProfile::builtin("greedy").unwrap());
+        let mut out = vec![];
+        play(
+            &mut gs,
+            "hint\nplay 2\nquit\n".as_bytes(),
+            &mut out,
+            Some(&mut advisor),
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let hint = GameEngine::deal(0)
+            .hint(|view, rules| AiProfile::builtin("greedy").unwrap().make(view, rules));
+        assert!(out.contains(&format!("Hint: {hint}\n")), "{out}");
+        assert!(out.contains("Advice: greedy prefers"), "{out}");
+        assert_eq!(out.matches("Played: ").count(), 2);
+        assert!(out.ends_with("Accuracy 0%: greedy agreed with 0 of your 1 actions\n"));
+    }
+}
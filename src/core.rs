@@ -5,6 +5,7 @@ use thiserror::Error;
 
 /// The suits in a 52-cards deck are hearts, diamonds, clubs and spades
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Suit {
     Hearts,
     Diamonds,
@@ -32,10 +33,42 @@ impl Suit {
     }
 }
 
+/// A relabeling of the suits, that maps suits of the same color to suits of the same color
+///
+/// Relabeling all cards in a game like this does not change which actions are legal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuitPermutation([Suit; 4]);
+
+impl SuitPermutation {
+    /// All 8 color preserving permutations: swap the red suits, swap the black suits, and swap the colors
+    pub fn all() -> [SuitPermutation; 8] {
+        std::array::from_fn(|i| {
+            let mut red = [Suit::Hearts, Suit::Diamonds];
+            let mut black = [Suit::Clubs, Suit::Spades];
+            if i & 1 != 0 {
+                red.swap(0, 1);
+            }
+            if i & 2 != 0 {
+                black.swap(0, 1);
+            }
+            if i & 4 != 0 {
+                std::mem::swap(&mut red, &mut black);
+            }
+            SuitPermutation([red[0], red[1], black[0], black[1]])
+        })
+    }
+
+    pub fn apply(&self, suit: Suit) -> Suit {
+        self.0[suit as usize]
+    }
+}
 
 /// Names on all piles in a game of solitaire
 #[derive(Debug, PartialEq, Copy, Clone, Hash, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Addr {
+    /// The talon is the face down pile that cards are taken from. Cards are never moved to or from it directly
+    Talon,
     /// The waste is the pile of cards that are turned over from the talon
     Waste,
     /// The foundation is the pile of cards that are built up from ace to king
@@ -69,6 +102,7 @@ impl Addr {
             | Addr::Foundation2
             | Addr::Foundation3
             | Addr::Foundation4
+            | Addr::Talon
             | Addr::Waste => false,
         }
     }
@@ -83,13 +117,15 @@ impl Addr {
             | Addr::Depot5
             | Addr::Depot6
             | Addr::Depot7
+            | Addr::Talon
             | Addr::Waste => false,
         }
     }
     pub fn is_waste(&self) -> bool {
         match self {
             Addr::Waste => true,
-            Addr::Foundation1
+            Addr::Talon
+            | Addr::Foundation1
             | Addr::Foundation2
             | Addr::Foundation3
             | Addr::Foundation4
@@ -103,22 +139,14 @@ impl Addr {
         }
     }
 
-    /// Return the index of the address into its own array
-    pub fn index(&self) -> usize {
-        match self {
-            Addr::Waste => 1,
-            Addr::Foundation1 => 0,
-            Addr::Foundation2 => 1,
-            Addr::Foundation3 => 2,
-            Addr::Foundation4 => 3,
-            Addr::Depot1 => 0,
-            Addr::Depot2 => 1,
-            Addr::Depot3 => 2,
-            Addr::Depot4 => 3,
-            Addr::Depot5 => 4,
-            Addr::Depot6 => 5,
-            Addr::Depot7 => 6,
-        }
+    /// The depot at this address, if it is a depot
+    pub fn depot_id(&self) -> Option<DepotId> {
+        DepotId::try_from(*self).ok()
+    }
+
+    /// The foundation at this address, if it is a foundation
+    pub fn foundation_id(&self) -> Option<FoundationId> {
+        FoundationId::try_from(*self).ok()
     }
 
     pub const FOUNDATIONS: [Addr; 4] = [
@@ -148,6 +176,106 @@ impl Addr {
     ];
 }
 
+/// The pile in the move notation, see `Action`: `T` for the talon, `W` for the waste, `F1` to `F4` for the foundations
+/// and `D1` to `D7` for the depots
+impl std::fmt::Display for Addr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Addr::Talon => write!(f, "T"),
+            Addr::Waste => write!(f, "W"),
+            _ => match (self.foundation_id(), self.depot_id()) {
+                (Some(id), _) => write!(f, "F{}", id.index() + 1),
+                (_, Some(id)) => write!(f, "D{}", id.index() + 1),
+                (None, None) => unreachable!("Every other pile is a foundation or a depot"),
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for Addr {
+    type Err = NotationError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let index = |piles: &[Addr], i: &str| {
+            i.parse::<usize>()
+                .ok()
+                .filter(|i| (1..=piles.len()).contains(i))
+                .map(|i| piles[i - 1])
+        };
+        match s {
+            "T" => Some(Addr::Talon),
+            "W" => Some(Addr::Waste),
+            _ => match s.split_at_checked(1) {
+                Some(("F", i)) => index(&Addr::FOUNDATIONS, i),
+                Some(("D", i)) => index(&Addr::DEPOTS, i),
+                _ => None,
+            },
+        }
+        .ok_or_else(|| NotationError(s.to_string()))
+    }
+}
+
+/// The number of a depot, from 0 to 6. Use it to index arrays of depots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DepotId(u8);
+
+impl DepotId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl TryFrom<Addr> for DepotId {
+    type Error = &'static str;
+    fn try_from(addr: Addr) -> Result<Self, Self::Error> {
+        match addr {
+            Addr::Depot1 => Ok(DepotId(0)),
+            Addr::Depot2 => Ok(DepotId(1)),
+            Addr::Depot3 => Ok(DepotId(2)),
+            Addr::Depot4 => Ok(DepotId(3)),
+            Addr::Depot5 => Ok(DepotId(4)),
+            Addr::Depot6 => Ok(DepotId(5)),
+            Addr::Depot7 => Ok(DepotId(6)),
+            Addr::Talon
+            | Addr::Waste
+            | Addr::Foundation1
+            | Addr::Foundation2
+            | Addr::Foundation3
+            | Addr::Foundation4 => Err("Address is not a depot"),
+        }
+    }
+}
+
+/// The number of a foundation, from 0 to 3. Use it to index arrays of foundations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FoundationId(u8);
+
+impl FoundationId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl TryFrom<Addr> for FoundationId {
+    type Error = &'static str;
+    fn try_from(addr: Addr) -> Result<Self, Self::Error> {
+        match addr {
+            Addr::Foundation1 => Ok(FoundationId(0)),
+            Addr::Foundation2 => Ok(FoundationId(1)),
+            Addr::Foundation3 => Ok(FoundationId(2)),
+            Addr::Foundation4 => Ok(FoundationId(3)),
+            Addr::Talon
+            | Addr::Waste
+            | Addr::Depot1
+            | Addr::Depot2
+            | Addr::Depot3
+            | Addr::Depot4
+            | Addr::Depot5
+            | Addr::Depot6
+            | Addr::Depot7 => Err("Address is not a foundation"),
+        }
+    }
+}
+
 
 /// Color of a card. Red or black
 #[derive(Debug, Clone, PartialEq)]
@@ -158,6 +286,8 @@ pub enum Color {
 
 /// Numerical value on a card. Ace, 2, 3 ... 10, Jack, Queen, King
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u8", into = "u8"))]
 pub struct Value(u8);
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -179,7 +309,6 @@ impl Value {
 
     pub const ACE: Value = Value(1);
     pub const TWO: Value = Value(2);
-    #[cfg(test)]
     pub const QUEEN: Value = Value(12);
     pub const KING: Value = Value(13);
 }
@@ -193,9 +322,15 @@ impl std::convert::TryFrom<u8> for Value {
         }
     }
 }
+impl From<Value> for u8 {
+    fn from(v: Value) -> u8 {
+        v.0
+    }
+}
 
 /// A CardView is a card that is either face up or face down
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CardView {
     FaceUp(Suit, Value),
     FaceDown,
@@ -211,7 +346,15 @@ impl From<(Suit, Value)> for CardView {
 /// The different actions that can be taken in the game
 ///
 /// Implemented as a kind of command pattern, decoupling from the actual methods on the game engine.
+///
+/// Actions are written in a compact move notation by `Display`, and read back by `FromStr`. The piles are written as
+/// by `Addr`, and the actions are
+///  - `T` to take from the talon, `O` to turn the waste over and `Q` to quit
+///  - `R D5` to reveal the card in depot 5
+///  - `W->F1` to move one card from the waste to foundation 1, and `D3->D7 x3` to move three cards
+///  - `F1=>F2` to move the whole pile of foundation 1 to foundation 2
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
     /// Take the first card of the talon and place it on the waste pile face up
     Take,
@@ -228,21 +371,302 @@ pub enum Action {
     Turnover,
     /// Reveal a face down cards in some pile
     Reveal(Addr),
+    /// Move a whole foundation pile to an empty foundation. Only allowed by some house rules.
+    MoveFoundationPile(Addr, Addr),
     /// Stop playing the game
     Quit,
 }
 
+/// Parse an action as it is written by `Debug`, e.g. `Move(Waste, Depot3, 1)`
+pub fn parse_action(s: &str) -> Option<Action> {
+    let (name, args) = match s.trim().split_once('(') {
+        Some((name, args)) => (name, args.strip_suffix(')')?.split(", ").collect()),
+        None => (s.trim(), vec![]),
+    };
+    let addr = |name: &str| {
+        [Addr::Talon, Addr::Waste]
+            .into_iter()
+            .chain(Addr::FOUNDATIONS)
+            .chain(Addr::DEPOTS)
+            .find(|addr| format!("{addr:?}") == name)
+    };
+    match (name, &args[..]) {
+        ("Take", []) => Some(Action::Take),
+        ("Turnover", []) => Some(Action::Turnover),
+        ("Quit", []) => Some(Action::Quit),
+        ("Reveal", [addr_name]) => Some(Action::Reveal(addr(addr_name)?)),
+        ("Move", [from, to, n]) => Some(Action::Move(addr(from)?, addr(to)?, n.parse().ok()?)),
+        ("MoveFoundationPile", [from, to]) => {
+            Some(Action::MoveFoundationPile(addr(from)?, addr(to)?))
+        }
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Action::Take => write!(f, "T"),
+            Action::Turnover => write!(f, "O"),
+            Action::Quit => write!(f, "Q"),
+            Action::Reveal(addr) => write!(f, "R {addr}"),
+            Action::Move(from, to, 1) => write!(f, "{from}->{to}"),
+            Action::Move(from, to, n) => write!(f, "{from}->{to} x{n}"),
+            Action::MoveFoundationPile(from, to) => write!(f, "{from}=>{to}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = NotationError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || NotationError(s.to_string());
+        let pair = |s: &str, arrow: &str| -> Result<(Addr, Addr), NotationError> {
+            let (from, to) = s.split_once(arrow).ok_or_else(error)?;
+            Ok((from.parse()?, to.parse()?))
+        };
+        match s.trim() {
+            "T" => Ok(Action::Take),
+            "O" => Ok(Action::Turnover),
+            "Q" => Ok(Action::Quit),
+            s if s.starts_with("R ") => Ok(Action::Reveal(s[2..].trim().parse()?)),
+            s if s.contains("=>") => {
+                let (from, to) = pair(s, "=>")?;
+                Ok(Action::MoveFoundationPile(from, to))
+            }
+            s => {
+                let (piles, n) = match s.split_once(" x") {
+                    Some((piles, n)) => (piles, n.parse().map_err(|_| error())?),
+                    None => (s, 1),
+                };
+                let (from, to) = pair(piles, "->")?;
+                Ok(Action::Move(from, to, n))
+            }
+        }
+    }
+}
+
+/// A pile or an action that is not in the move notation, see `Action`
+#[derive(Error, Debug, PartialEq)]
+#[error("Not in the move notation: {0}")]
+pub struct NotationError(String);
+
+/// The most cards one action can turn face up, which is the most cards taken from the talon at a time
+pub const MAX_TURNED: usize = 3;
+
+/// The cards an action turned face up, in the order they were turned
+///
+/// There are at most `MAX_TURNED` of them, so they are kept inline, and acting doesn't allocate for them.
+/// It derefs to a slice of the cards.
+#[derive(Clone, Copy)]
+pub struct Turned {
+    cards: [(Suit, Value); MAX_TURNED],
+    len: usize,
+}
+
+impl Turned {
+    /// No cards
+    pub const NONE: Turned = Turned { cards: [(Suit::Hearts, Value::ACE); MAX_TURNED], len: 0 };
+
+    /// Add a card. Panics if there are `MAX_TURNED` cards already.
+    pub fn push(&mut self, card: (Suit, Value)) {
+        self.cards[self.len] = card;
+        self.len += 1;
+    }
+}
+
+impl Default for Turned {
+    fn default() -> Self {
+        Turned::NONE
+    }
+}
+
+impl std::ops::Deref for Turned {
+    type Target = [(Suit, Value)];
+    fn deref(&self) -> &Self::Target {
+        &self.cards[..self.len]
+    }
+}
+
+impl std::fmt::Debug for Turned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Turned {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Turned {}
+
+impl FromIterator<(Suit, Value)> for Turned {
+    fn from_iter<I: IntoIterator<Item = (Suit, Value)>>(iter: I) -> Self {
+        let mut turned = Turned::NONE;
+        for card in iter {
+            turned.push(card);
+        }
+        turned
+    }
+}
+
+impl<const N: usize> From<[(Suit, Value); N]> for Turned {
+    fn from(cards: [(Suit, Value); N]) -> Self {
+        cards.into_iter().collect()
+    }
+}
+
+impl IntoIterator for Turned {
+    type Item = (Suit, Value);
+    type IntoIter = std::iter::Take<std::array::IntoIter<(Suit, Value), MAX_TURNED>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.cards.into_iter().take(self.len)
+    }
+}
+
+impl<'a> IntoIterator for &'a Turned {
+    type Item = &'a (Suit, Value);
+    type IntoIter = std::slice::Iter<'a, (Suit, Value)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// How a finished game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WinLoss {
+    Win,
+    Loss,
+}
+
+/// The result of a finished game, with what the reports need about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// All cards went to the foundations, with this final score after this many moves
+    Won { score: i32, moves: usize },
+    Lost { reason: LossReason },
+}
+
+impl Outcome {
+    pub fn win_loss(&self) -> WinLoss {
+        match self {
+            Outcome::Won { .. } => WinLoss::Win,
+            Outcome::Lost { .. } => WinLoss::Loss,
+        }
+    }
+}
+
+/// Why a game was lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LossReason {
+    /// The player gave up, with `Action::Quit`
+    Quit,
+    /// Nothing was left to do that makes progress, see `GameEngine::is_stuck`
+    Stuck,
+}
+
+/// The opening lasts until fewer than this many cards in the depots are face down
+const OPENING_HIDDEN_CARDS: usize = 15;
+
+/// The phase of a game, shared by the engine, the AIs and the statistics
+///
+/// The phases of a running game are decided by the number of face down cards in the depots,
+/// and by the number of cards left in the talon and the waste.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    /// At least 15 cards in the depots are face down. Reveal them!
+    Opening,
+    /// Some cards in the depots are face down
+    Midgame,
+    /// All cards in the depots are face up, but there are cards in the talon or the waste
+    Endgame,
+    /// The talon and the waste are empty and all cards in the depots are face up.
+    /// Moving the lowest card to the foundations, over and over, wins the game
+    AutoCompletable,
+    /// The game is over
+    Finished(WinLoss),
+}
+
+impl Phase {
+    /// The phase of a running game with `n_hidden` face down cards in the depots and `n_stock` cards in the talon and waste
+    pub fn running(n_hidden: usize, n_stock: usize) -> Phase {
+        if n_hidden >= OPENING_HIDDEN_CARDS {
+            Phase::Opening
+        } else if n_hidden > 0 {
+            Phase::Midgame
+        } else if n_stock > 0 {
+            Phase::Endgame
+        } else {
+            Phase::AutoCompletable
+        }
+    }
+}
+
 /// Errors that can occur when trying to make a move
 /// This is bit haphazard, and got extended as needed in my debuggning.
 #[derive(Error, Debug)]
 pub enum MoveError {
     /// An error with some textual explanation
+    ///
+    /// The explanation is usually a fixed text, so that checking a move that turns out to be illegal doesn't allocate.
     #[error("Got explanation {0}")]
-    WithDescription(String),
+    WithDescription(std::borrow::Cow<'static, str>),
     /// Tried to move a card from a position, but there is no movable cards at that place
     #[error("Found no card to move")]
     NoCardToMove,
+    /// The game is won or lost, and takes no more actions
+    #[error("The game is over")]
+    GameOver,
     /// The catch-all error type
     #[error("Unspecified move error")]
     Unspecified,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pile_ids_of_addresses() {
+        assert_eq!(Addr::Waste.depot_id(), None);
+        assert_eq!(Addr::Waste.foundation_id(), None);
+        for (i, addr) in Addr::DEPOTS.iter().enumerate() {
+            assert_eq!(addr.depot_id().map(DepotId::index), Some(i));
+            assert_eq!(addr.foundation_id(), None);
+        }
+        for (i, addr) in Addr::FOUNDATIONS.iter().enumerate() {
+            assert_eq!(addr.foundation_id().map(FoundationId::index), Some(i));
+        }
+    }
+
+    #[test]
+    fn move_notation() {
+        let actions = [
+            ("T", Action::Take),
+            ("O", Action::Turnover),
+            ("Q", Action::Quit),
+            ("R D5", Action::Reveal(Addr::Depot5)),
+            ("W->F1", Action::Move(Addr::Waste, Addr::Foundation1, 1)),
+            ("D3->D7 x3", Action::Move(Addr::Depot3, Addr::Depot7, 3)),
+            (
+                "F1=>F2",
+                Action::MoveFoundationPile(Addr::Foundation1, Addr::Foundation2),
+            ),
+        ];
+        for (text, action) in actions {
+            assert_eq!(action.to_string(), text);
+            assert_eq!(text.parse::<Action>(), Ok(action));
+        }
+        assert_eq!(
+            " D3->D7 ".parse::<Action>(),
+            Ok(Action::Move(Addr::Depot3, Addr::Depot7, 1))
+        );
+        let not_actions = ["", "X", "R", "D8->D1", "W->F1 x", "W->F1 3", "F0=>F1"];
+        for text in not_actions.into_iter().chain(["Move(Waste, Depot3, 1)"]) {
+            assert_eq!(text.parse::<Action>().ok(), None, "{text}");
+        }
+    }
+}
@@ -1,119 +1,384 @@
 //! The game engine/logic.
 //! It is mostly private, but creating a new game and sending actions to the game engine is public.
 
-use crate::{core::{Addr,CardView, Suit, Value, Action, MoveError}, ai::SolitaireObserver};
-use itertools::Itertools;
+use crate::{core::{Addr, Suit, Value, Action, LossReason, MoveError, Outcome, Phase, Turned}, ai::{Ai, DepotView, OpenObserver, SolitaireObserver}, rules::{EmptyColumnRule, RuleSet, RulesError, Scoring, Variant}};
 use rand::prelude::*;
+use std::time::{Duration, Instant};
 
+mod autoplay;
+mod shared;
+pub use autoplay::{Autoplay, Stop};
+pub use shared::{SharedGame, Snapshot};
+
+
+/// The positions `GameEngine::is_stuck` searches at most
+const STUCK_SEARCH_LIMIT: usize = 1_000;
 
 /// A simple flag to know if the game is running, and if not, was it a win or a loss?
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum State {
     Running,
-    Fail,
+    Fail(LossReason),
     Win,
 }
 
+/// Options for how the game engine behaves, that do not change the rules of the game
+#[derive(Debug, Default, Clone, PartialEq, Hash, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DealOptions {
+    /// Reveal the card below automatically when moving the last face up card away from a depot.
+    /// The revealed card is returned as the result of the move, and no `Action::Reveal` is needed.
+    pub auto_reveal: bool,
+    /// Play all cards to the foundations as soon as the game can be won that way, see `GameEngine::auto_finish`.
+    /// The moves are taken back together with the action that led to them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub auto_finish: bool,
+    /// End the game as lost as soon as it is stuck, see `GameEngine::is_stuck`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub end_when_stuck: bool,
+    /// Keep time from the deal, and score it like the timed games of Windows Solitaire, see `GameEngine::timed_score`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timed: bool,
+    /// The help the player gets in interactive play. The engine doesn't give it, but keeps it with the game, so that
+    /// the scores of games with and without help can be told apart.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub assistance: Assistance,
+}
+
+/// How much help a player gets in interactive play, from none to the most. Each level also gives the help of the
+/// levels before it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Assistance {
+    #[default]
+    None,
+    /// The piles with a card that can be played are marked
+    PlayableCards,
+    /// The moves after which the solver still finds a way to win can be listed
+    SolverMoves,
+    /// Moves after which the solver proves that the game can't be won are refused
+    NoLosingMoves,
+}
+
+impl Assistance {
+    pub const ALL: [Assistance; 4] = [
+        Assistance::None,
+        Assistance::PlayableCards,
+        Assistance::SolverMoves,
+        Assistance::NoLosingMoves,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Assistance::None => "none",
+            Assistance::PlayableCards => "playable",
+            Assistance::SolverMoves => "solver",
+            Assistance::NoLosingMoves => "no-losing",
+        }
+    }
+}
+
+impl std::str::FromStr for Assistance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Assistance::ALL
+            .into_iter()
+            .find(|assistance| assistance.name() == s)
+            .ok_or_else(|| format!("Unknown assistance {s}, use none, playable, solver or no-losing"))
+    }
+}
+
 /// The game state. It has methods to observe the state (create a solitaire view) and to act.
 ///
 /// Invariant: the game is always valid, meaning
 ///  - all 52 cards are in there
 ///  - the talon have cards face down
-///  - face up cards in the columns are alternating colors and decreasing values
+///  - face up cards in the columns are built by the rules, e.g. alternating colors and decreasing values
+///    (except in Yukon and Russian, where the first face up cards are dealt in any order)
 ///  - the foundations are increasing values of the same suit
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GameEngine {
-    /// the last element = the face up card. pop from last element when picking one.
-    talon: Vec<Card>,
-    /// the last element = the visible card
+    /// The talon and the waste as one pile, in the order the cards are taken
+    ///
+    /// The cards before `cursor` are the waste, with the visible card last. The cards from `cursor` on are the talon,
+    /// with the next card to take first. Taking moves the cursor forward, and turning over the waste moves it back to
+    /// the start, so neither moves any cards. Playing the top card of the waste does: it is removed from the middle of
+    /// the pile, and the talon behind it shifts down by one place, see `pop_card`.
     ///
     /// Waste is also sometimes called the "hand" in solitaire, since that is the pile we can play from
-    waste: Vec<Card>,
+    stock: Vec<Card>,
+    /// Where the talon starts in `stock`
+    cursor: usize,
+    /// How many more times the waste may be turned over, or `None` if there is no limit, see `RuleSet::redeals`
+    ///
+    /// The count goes down, rather than the turnovers up, so that the same position is the same game when there is
+    /// no limit.
+    redeals_left: Option<u32>,
     /// The columns of cards on the table The major part of the tableaux
     ///
     /// last element = the available card.
-    columns: [Vec<Card>; 7],
+    columns: [Depot; 7],
     /// The foundations are where we build the ace piles
     foundations: [Vec<Card>; 4],
     state: State,
     /// The current score
-    current_score: u32,
+    current_score: i32,
+    /// The rules this game is played by
+    rules: RuleSet,
+    options: DealOptions,
+    history: History,
+}
+
+/// A depot: a pile of cards, where the cards from `first_face_up` on are face up and the ones below are face down
+///
+/// Cards only leave a depot from its face up top, and cards put on a depot are face up, so moves keep `first_face_up`
+/// within the pile without changing it. Only revealing the top card and taking that back change it.
+#[derive(Debug, Clone, Default, PartialEq, Hash, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Depot {
+    cards: Vec<Card>,
+    first_face_up: usize,
+}
+
+impl Depot {
+    fn face_up(&self) -> &[Card] {
+        &self.cards[self.first_face_up..]
+    }
+
+    fn has_face_down_top(&self) -> bool {
+        !self.cards.is_empty() && self.first_face_up == self.cards.len()
+    }
+
+    /// Turn the top card face up, if it is face down
+    fn reveal(&mut self) -> Option<&Card> {
+        if !self.has_face_down_top() {
+            return None;
+        }
+        self.first_face_up -= 1;
+        self.cards.last()
+    }
+
+    /// Turn the top card face down again
+    fn hide_top(&mut self) {
+        self.first_face_up = self.cards.len();
+    }
+}
+
+/// What it takes to undo an action
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Step {
+    action: Action,
+    /// The score before the action
+    score: i32,
+    /// The state before the action
+    state: State,
+    /// The number of cards the action turned face up
+    n_turned: usize,
+    /// The bottom card of the cards the action moved, if it moved any
+    moved: Option<(Suit, Value)>,
+    /// Taken by the engine after another action, see `DealOptions::auto_finish`
+    #[cfg_attr(feature = "serde", serde(default))]
+    auto: bool,
+    /// When the action was taken, counted from the start of the clock, if it was running
+    #[cfg_attr(feature = "serde", serde(default))]
+    elapsed: Option<Duration>,
+}
+
+/// The actions that can be undone, and the undone actions that can be redone
+///
+/// How a position came about is not part of it, so the history is ignored when games are compared.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct History {
+    undo: Vec<Step>,
+    redo: Vec<Action>,
+    /// When the clock was started, see `GameEngine::start_clock`. An instant can't be saved, so the time on the clock
+    /// is saved instead, and the clock runs on from there when the game is loaded.
+    #[cfg_attr(feature = "serde", serde(default, with = "clock_time"))]
+    clock: Option<Instant>,
+}
+
+/// Saving the clock of a game as the time on it, see `History::clock`
+#[cfg(feature = "serde")]
+mod clock_time {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::{Duration, Instant};
+
+    pub fn serialize<S: Serializer>(clock: &Option<Instant>, serializer: S) -> Result<S::Ok, S::Error> {
+        clock.map(|clock| clock.elapsed()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Instant>, D::Error> {
+        let elapsed = Option::<Duration>::deserialize(deserializer)?;
+        let now = Instant::now();
+        Ok(elapsed.map(|elapsed| now.checked_sub(elapsed).unwrap_or(now)))
+    }
+}
+
+impl PartialEq for History {
+    fn eq(&self, _: &History) -> bool {
+        true
+    }
+}
+
+impl Eq for History {}
+
+impl std::hash::Hash for History {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+}
+
+/// The cards of a game where they lie, see `GameEngine::layout`
+///
+/// Unlike the game, it leaves out the score, which depends on how a position was reached, e.g. a card that went to a
+/// foundation and back.
+pub struct Layout<'a>(&'a GameEngine);
+
+impl std::hash::Hash for Layout<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        let gs = self.0;
+        gs.stock.hash(state);
+        gs.cursor.hash(state);
+        gs.redeals_left.hash(state);
+        gs.columns.hash(state);
+        gs.foundations.hash(state);
+    }
 }
 
 
 
 impl GameEngine {
-    pub fn score(&self) -> u32 {
+    pub fn score(&self) -> i32 {
         self.current_score
     }
 
-    /// Update the score, according to the rules at <https://australiancardgames.com.au/solitaire/>
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn options(&self) -> &DealOptions {
+        &self.options
+    }
+
+    /// Update the score according to the scoring policy of the rules
     fn score_action(&mut self, action: &Action) {
-        match action {
-            Action::Take => {}
-            Action::Move(from, to, _) => {
-                if from.is_waste() && to.is_foundation() {
-                    self.current_score += 10;
-                } else if from.is_waste() && to.is_depot() {
-                    self.current_score += 5;
-                } else if from.is_depot() && to.is_foundation() {
-                    self.current_score += 10;
-                } else if from.is_foundation() && to.is_depot() {
-                    self.current_score=self.current_score.saturating_sub(15);
-                }
-            }
-            Action::Reveal(_) => {
-                self.current_score += 5;
-            }
-            Action::Turnover => {self.current_score=self.current_score.saturating_sub(100)},
-            Action::Quit => {}
+        let policy = self.rules.scoring.policy();
+        self.current_score = policy.add(self.current_score, policy.points(action));
+    }
+
+    /// A copy of the game in its current position, without the history, which is the largest part of a long game
+    fn without_history(&self) -> GameEngine {
+        GameEngine {
+            stock: self.stock.clone(),
+            cursor: self.cursor,
+            redeals_left: self.redeals_left,
+            columns: self.columns.clone(),
+            foundations: self.foundations.clone(),
+            state: self.state.clone(),
+            current_score: self.current_score,
+            rules: self.rules.clone(),
+            options: self.options.clone(),
+            history: History::default(),
         }
     }
 
+    /// Where the cards lie, and nothing else, so that a position hashes the same however it was reached
+    pub fn layout(&self) -> Layout<'_> {
+        Layout(self)
+    }
+
     pub fn observe(&self) -> SolitaireObserver {
         SolitaireObserver {
-            talon_size: self.talon.len(),
-            waste: self.waste.iter().map(|c| (c.suit, c.value)).collect_vec()
-            ,
+            talon_size: self.talon_len(),
+            waste: self.waste().iter().map(|c| (c.suit, c.value)).collect::<Vec<_>>(),
             foundation_tops: [
                 self.foundations[0].last().map(|c| c.clone().into()),
                 self.foundations[1].last().map(|c| c.clone().into()),
                 self.foundations[2].last().map(|c| c.clone().into()),
                 self.foundations[3].last().map(|c| c.clone().into()),
             ],
-            depots: [
-                self.columns[0].iter().map(|c| c.clone().into()).collect(),
-                self.columns[1].iter().map(|c| c.clone().into()).collect(),
-                self.columns[2].iter().map(|c| c.clone().into()).collect(),
-                self.columns[3].iter().map(|c| c.clone().into()).collect(),
-                self.columns[4].iter().map(|c| c.clone().into()).collect(),
-                self.columns[5].iter().map(|c| c.clone().into()).collect(),
-                self.columns[6].iter().map(|c| c.clone().into()).collect(),
-            ],
+            depots: std::array::from_fn(|i| DepotView {
+                n_face_down: self.columns[i].first_face_up,
+                face_up: self.columns[i].face_up().iter().map(|c| (c.suit, c.value)).collect(),
+            }),
+            building: self.rules.building,
+            empty_column: self.rules.empty_column,
+            foundation_retreat: self.rules.foundation_retreat,
+            redeals_left: self.redeals_left,
+        }
+    }
+
+    /// Observe the game with all cards face up, the talon and the face down cards in the depots included
+    ///
+    /// This leaks what the player can't see, so it is only for games with open information (Thoughtful solitaire,
+    /// see `RuleSet::open_information`), and for tools that look at the whole game.
+    pub fn observe_open(&self) -> OpenObserver {
+        let cards = |cards: &[Card]| cards.iter().map(|c| (c.suit, c.value)).collect::<Vec<_>>();
+        OpenObserver {
+            view: self.observe(),
+            talon: cards(&self.stock[self.cursor..]),
+            face_down: std::array::from_fn(|i| cards(&self.columns[i].cards[..self.columns[i].first_face_up])),
         }
     }
 
-    /// Are we still playing?
+    /// Ask an AI what to play in this position, e.g. for a hint to a human player
+    ///
+    /// The AI is made by `make_ai` from a fresh view of the game, and shown all cards if the game has open information.
+    /// It is dropped after suggesting an action, so neither the game nor any AI of the caller is changed.
+    pub fn hint(&self, make_ai: impl FnOnce(SolitaireObserver, &RuleSet) -> Box<dyn Ai>) -> Action {
+        let mut ai = make_ai(self.observe(), &self.rules);
+        show_open_cards(self, ai.as_mut());
+        ai.make_move()
+    }
+
+    /// How the game ended, or `None` while we are still playing
+    pub fn outcome(&self) -> Option<Outcome> {
+        match self.state {
+            State::Running => None,
+            State::Win => Some(Outcome::Won { score: self.current_score, moves: self.history.undo.len() }),
+            State::Fail(reason) => Some(Outcome::Lost { reason }),
+        }
+    }
+
+    /// Are we still playing? The same as `outcome().is_none()`
     pub fn is_running(&self) -> bool {
         self.state == State::Running
     }
 
-    /// Have we won?
+    /// Have we won? The same as matching `outcome()` with `Some(Outcome::Won { .. })`
     pub fn is_won(&self) -> bool {
         self.state == State::Win
     }
 
-    /// Deal a new game
+    /// The phase of the game, see `Phase` for the criteria
+    pub fn phase(&self) -> Phase {
+        match self.outcome() {
+            Some(outcome) => Phase::Finished(outcome.win_loss()),
+            None => {
+                let n_hidden = self.columns.iter().map(|d| d.first_face_up).sum();
+                Phase::running(n_hidden, self.stock.len())
+            }
+        }
+    }
+
+    /// Start building a game with other rules or options than the defaults
+    pub fn builder() -> GameBuilder {
+        GameBuilder::default()
+    }
+
+    /// Deal a new game with the default rules
     pub fn deal(seed: u64) -> Self {
         /// Inner function that is just a helper to build the depots
-        fn build_depot(iter: &mut dyn Iterator<Item = Card>, n: usize) -> Vec<Card> {
+        fn build_depot(iter: &mut dyn Iterator<Item = Card>, n: usize) -> Depot {
             let mut v = vec![];
             for c in iter.take(n - 1) {
                 v.push(c);
             }
             v.push(iter.next().expect("Preconditon"));
-            v.last_mut().unwrap().reveal();
-            v
+            Depot { cards: v, first_face_up: n - 1 }
         }
 
         let mut pack = shuffled_deck(seed).into_iter();
@@ -126,41 +391,65 @@ impl GameEngine {
             build_depot(&mut pack, 6),
             build_depot(&mut pack, 7),
         ];
-        let talon: Vec<_> = pack.collect();
+        // The rest of the pack is the talon, and the last card of the pack is taken first
+        let stock = pack.rev().collect();
         let foundations = [vec![], vec![], vec![], vec![]];
         GameEngine {
-            talon,
-            waste: vec![],
+            stock,
+            cursor: 0,
+            redeals_left: None,
             columns: depots,
             foundations,
             state: State::Running,
             current_score: 0,
+            rules: RuleSet::default(),
+            options: DealOptions::default(),
+            history: History::default(),
         }
     }
 
-    /// Take the topmost card from the talon and place it on the waste pile
-    fn take(&mut self) -> Result<(Suit,Value), MoveError> {
-        if let Some(c) = self.talon.pop() {
-            self.waste.push(c.clone());
-            self.waste.last_mut().unwrap().reveal();
-            Ok((c.suit, c.value))
-        } else {
-            Err(MoveError::Unspecified)
+    /// Deal a game of Yukon: the first depot has one card face up, and depot `i` has `i - 1` cards face down
+    /// with five cards face up on top. All cards are in the depots.
+    fn deal_yukon(seed: u64) -> Self {
+        let mut pack = shuffled_deck(seed).into_iter();
+        let columns = std::array::from_fn(|i| {
+            let n_faceup = if i == 0 { 1 } else { 5 };
+            Depot { cards: pack.by_ref().take(i + n_faceup).collect(), first_face_up: i }
+        });
+        GameEngine {
+            stock: vec![],
+            columns,
+            ..GameEngine::deal(seed)
         }
     }
 
-    /// If the talon is empty, we may turn over the waste pile
+    /// Take the topmost cards from the talon, one by one, and place them on the waste pile
+    ///
+    /// The rules decide how many cards are taken. When drawing three, only the last one is on top of the waste,
+    /// and the other two are played after it. Returns the taken cards in the order they were taken.
+    fn take(&mut self) -> Result<Turned, MoveError> {
+        if self.talon_len() == 0 {
+            return Err(MoveError::Unspecified);
+        }
+        let n = self.talon_len().min(self.rules.draw as usize);
+        let taken = self.stock[self.cursor..self.cursor + n]
+            .iter()
+            .map(|c| (c.suit, c.value))
+            .collect();
+        self.cursor += n;
+        Ok(taken)
+    }
+
+    /// If the talon is empty, we may turn over the waste pile, unless the rules allow no more passes
     fn turnover(&mut self) -> Result<(), MoveError> {
-        if self.talon.is_empty() {
-            if self.waste.is_empty() {
+        if self.talon_len() == 0 {
+            if self.cursor == 0 {
                 Err(MoveError::Unspecified)
+            } else if self.redeals_left == Some(0) {
+                Err(MoveError::WithDescription("No passes through the talon are left".into()))
             } else {
-                self.talon = self
-                    .waste
-                    .drain(..)
-                    .map(|c| Card { faceup: false, ..c })
-                    .rev()
-                    .collect();
+                self.cursor = 0;
+                self.redeals_left = self.redeals_left.map(|n| n - 1);
                 Ok(())
             }
         } else {
@@ -168,79 +457,109 @@ impl GameEngine {
         }
     }
 
+    /// How many more times the waste may be turned over, or `None` if there is no limit
+    pub fn redeals_left(&self) -> Option<u32> {
+        self.redeals_left
+    }
+
     /// Reveal the topmost card in a depot, if there is one
     fn reveal(&mut self, addr: &Addr) -> Result<(Suit,Value), MoveError> {
-        let depot = match addr {
-            Addr::Waste
-            | Addr::Foundation1
-            | Addr::Foundation2
-            | Addr::Foundation3
-            | Addr::Foundation4 => Err(MoveError::WithDescription(
-                "Cannot reveal cards in this pile".to_string(),
-            )),
-            Addr::Depot1 => Ok(0),
-            Addr::Depot2 => Ok(1),
-            Addr::Depot3 => Ok(2),
-            Addr::Depot4 => Ok(3),
-            Addr::Depot5 => Ok(4),
-            Addr::Depot6 => Ok(5),
-            Addr::Depot7 => Ok(6),
-        }?;
-        if let Some(c) = self.columns[depot].last_mut() {
-            if c.faceup {
-                Err(MoveError::Unspecified)
-            } else {
-                c.reveal();
-                Ok((c.suit, c.value))
-            }
+        let depot = addr.depot_id().ok_or(MoveError::WithDescription(
+            "Cannot reveal cards in this pile".into(),
+        ))?;
+        self.columns[depot.index()]
+            .reveal()
+            .map(|c| (c.suit, c.value))
+            .ok_or(MoveError::Unspecified)
+    }
+
+    /// Return the depot or foundation at the given address
+    ///
+    /// Only face up cards may be taken from a depot, see `Depot`. The talon and the waste share `stock`, so they are
+    /// changed with `pop_card` and `push_card` instead.
+    fn pile_mut(&mut self, addr: &Addr) -> &mut Vec<Card> {
+        if let Some(depot) = addr.depot_id() {
+            &mut self.columns[depot.index()].cards
+        } else if let Some(foundation) = addr.foundation_id() {
+            &mut self.foundations[foundation.index()]
         } else {
-            Err(MoveError::Unspecified)
+            panic!("The talon and the waste are not separate piles")
         }
     }
 
-    /// Return the pile at the given address
-    fn pile_mut(&mut self, addr: &Addr) -> &mut Vec<Card> {
-        match addr {
-            Addr::Waste => &mut self.waste,
-            Addr::Depot1 => &mut self.columns[0],
-            Addr::Depot2 => &mut self.columns[1],
-            Addr::Depot3 => &mut self.columns[2],
-            Addr::Depot4 => &mut self.columns[3],
-            Addr::Depot5 => &mut self.columns[4],
-            Addr::Depot6 => &mut self.columns[5],
-            Addr::Depot7 => &mut self.columns[6],
-            Addr::Foundation1 => &mut self.foundations[0],
-            Addr::Foundation2 => &mut self.foundations[1],
-            Addr::Foundation3 => &mut self.foundations[2],
-            Addr::Foundation4 => &mut self.foundations[3],
-        }
-    }
-
-    /// Return the pile at the given address
-    fn pile(&self, addr: &Addr) -> &Vec<Card> {
-        match addr {
-            Addr::Waste => &self.waste,
-            Addr::Depot1 => &self.columns[0],
-            Addr::Depot2 => &self.columns[1],
-            Addr::Depot3 => &self.columns[2],
-            Addr::Depot4 => &self.columns[3],
-            Addr::Depot5 => &self.columns[4],
-            Addr::Depot6 => &self.columns[5],
-            Addr::Depot7 => &self.columns[6],
-            Addr::Foundation1 => &self.foundations[0],
-            Addr::Foundation2 => &self.foundations[1],
-            Addr::Foundation3 => &self.foundations[2],
-            Addr::Foundation4 => &self.foundations[3],
-        }
-    }
-
-    fn move_to_foundation(&mut self, from: &Addr, to: &Addr) -> Result<(), MoveError> {
+    /// Return the pile at the given address. The top card is last, except in the talon, where it is first.
+    fn pile(&self, addr: &Addr) -> &[Card] {
+        if let Some(depot) = addr.depot_id() {
+            &self.columns[depot.index()].cards
+        } else if let Some(foundation) = addr.foundation_id() {
+            &self.foundations[foundation.index()]
+        } else if addr.is_waste() {
+            self.waste()
+        } else {
+            &self.stock[self.cursor..]
+        }
+    }
+
+    /// Remove the top card of a depot, a foundation or the waste
+    ///
+    /// The top card of the waste is in the middle of `stock`, so the cards of the talon after it are moved down by one
+    /// place. That is at most 23 cards, a copy cheaper than keeping the waste and the talon apart.
+    fn pop_card(&mut self, addr: &Addr) -> Card {
+        if addr.is_waste() {
+            self.cursor -= 1;
+            self.stock.remove(self.cursor)
+        } else {
+            self.pile_mut(addr).pop().expect("Precondition: there is a card to move")
+        }
+    }
+
+    /// Put a card on top of a depot, a foundation or the waste. Like `pop_card`, it moves the talon for the waste.
+    fn push_card(&mut self, addr: &Addr, card: Card) {
+        if addr.is_waste() {
+            self.stock.insert(self.cursor, card);
+            self.cursor += 1;
+        } else {
+            self.pile_mut(addr).push(card);
+        }
+    }
+
+    /// Move the top `n` cards of a pile onto another pile, without checking that the move is legal
+    ///
+    /// The cards are moved in place, so that acting and undoing don't allocate.
+    fn transfer(&mut self, from: &Addr, to: &Addr, n: usize) {
+        if let (Some(from), Some(to)) = (from.depot_id(), to.depot_id()) {
+            let [from, to] = self
+                .columns
+                .get_disjoint_mut([from.index(), to.index()])
+                .expect("Precondition: cards move between different depots");
+            let start = from.cards.len() - n;
+            to.cards.extend(from.cards.drain(start..));
+        } else {
+            debug_assert_eq!(n, 1, "Only single cards move to or from the waste and the foundations");
+            let card = self.pop_card(from);
+            self.push_card(to, card);
+        }
+    }
+
+    /// The number of face up cards at the top of a pile. The talon is face down, and the waste and foundations face up.
+    fn n_face_up(&self, addr: &Addr) -> usize {
+        match addr.depot_id() {
+            Some(depot) => self.columns[depot.index()].face_up().len(),
+            None if *addr == Addr::Talon => 0,
+            None => self.pile(addr).len(),
+        }
+    }
+
+    fn check_move_to_foundation(&self, from: &Addr, to: &Addr) -> Result<(), MoveError> {
         let card_to_move = self.pile(from).last().ok_or(MoveError::NoCardToMove)?;
+        if self.n_face_up(from) == 0 {
+            return Err(MoveError::WithDescription(
+                "Cannot move a face down card".into(),
+            ));
+        }
 
         // Place ace on empty slot
         if card_to_move.numeric_value() == 1 && self.pile(to).is_empty() {
-            let card = self.pile_mut(from).pop().unwrap();
-            self.pile_mut(to).push(card);
             return Ok(());
         } else if card_to_move.numeric_value() == 1 {
             return Err(MoveError::WithDescription(
@@ -248,15 +567,10 @@ impl GameEngine {
             ));
         }
 
-        // Place card on top of same suit and one higher, possibly ending the game
+        // Place card on top of same suit and one higher
         if let Some(c) = self.pile(to).last() {
             if c.suit == card_to_move.suit && card_to_move.numeric_value() == c.numeric_value() + 1
             {
-                let card = self.pile_mut(from).pop().unwrap();
-                self.pile_mut(to).push(card);
-                if self.foundations.iter().all(|f| f.len() == 13) {
-                    self.state = State::Win;
-                }
                 Ok(())
             } else {
                 Err(MoveError::WithDescription(
@@ -270,7 +584,7 @@ impl GameEngine {
         }
     }
 
-    fn move_to_depot(&mut self, from: &Addr, to: &Addr, n: usize) -> Result<(), MoveError> {
+    fn check_move_to_depot(&self, from: &Addr, to: &Addr, n: usize) -> Result<(), MoveError> {
         // are there enough cards to move?
         if self.pile(from).len() < n {
             return Err(MoveError::Unspecified);
@@ -278,27 +592,30 @@ impl GameEngine {
 
         // all face up?
         let n_skip = self.pile(from).len().saturating_sub(n);
-        if self.pile(from).iter().skip(n_skip).any(|c| !c.faceup) {
+        if self.n_face_up(from) < n {
             return Err(MoveError::Unspecified);
         }
 
+        if from.is_foundation() && !self.rules.foundation_retreat {
+            return Err(MoveError::WithDescription(
+                "Cannot move cards back from the foundations".into(),
+            ));
+        }
+
         let base_card = &self.pile(from)[n_skip];
 
-        // move king-starting sequence to empty slot
-        if base_card.value.is_king() && self.pile(to).last().is_none() {
-            let mut cards_to_move = self.pile_mut(from).split_off(n_skip);
-            self.pile_mut(to).append(&mut cards_to_move);
+        // move king-starting sequence (or any sequence, if the rules allow) to empty slot
+        let fills_empty = match self.rules.empty_column {
+            EmptyColumnRule::KingsOnly => base_card.value.is_king(),
+            EmptyColumnRule::AnyCard => true,
+        };
+        if fills_empty && self.pile(to).last().is_none() {
             return Ok(());
         }
 
-        // move red on a black or vice versa, decrease value by one, and destination is face up
+        // build on a face up card by the rules, e.g. red on black with the value decreased by one
         if let Some(c) = self.pile(to).last() {
-            if base_card.suit.color() != c.suit.color()
-                && base_card.numeric_value() == c.numeric_value() - 1
-                && c.faceup
-            {
-                let mut cards_to_move = self.pile_mut(from).split_off(n_skip);
-                self.pile_mut(to).append(&mut cards_to_move);
+            if self.n_face_up(to) > 0 && self.rules.building.fits((base_card.suit, base_card.value), (c.suit, c.value)) {
                 return Ok(());
             }
         }
@@ -306,17 +623,28 @@ impl GameEngine {
         Err(MoveError::Unspecified)
     }
 
-    fn move_cards(&mut self, from: &Addr, to: &Addr, n: usize) -> Result<(), MoveError> {
-        if (from.is_waste() || from.is_foundation()) && n != 1 {
+    /// Check if moving `n` cards from one pile to another is legal, without moving them
+    fn check_move(&self, from: &Addr, to: &Addr, n: usize) -> Result<(), MoveError> {
+        if n == 0 || ((from.is_waste() || from.is_foundation()) && n != 1) {
             return Err(MoveError::Unspecified);
         }
+        if *from == Addr::Talon {
+            return Err(MoveError::WithDescription(
+                "Cards can only be taken from the talon".into(),
+            ));
+        }
+        if from.is_foundation() && to.is_foundation() {
+            return Err(MoveError::WithDescription(
+                "Cannot move cards between foundations".into(),
+            ));
+        }
         match to {
-            Addr::Waste => Err(MoveError::Unspecified),
+            Addr::Talon | Addr::Waste => Err(MoveError::Unspecified),
             Addr::Foundation1 | Addr::Foundation2 | Addr::Foundation3 | Addr::Foundation4 => {
                 if n != 1 {
                     return Err(MoveError::Unspecified);
                 }
-                self.move_to_foundation(from, to)
+                self.check_move_to_foundation(from, to)
             }
             Addr::Depot1
             | Addr::Depot2
@@ -324,136 +652,843 @@ impl GameEngine {
             | Addr::Depot4
             | Addr::Depot5
             | Addr::Depot6
-            | Addr::Depot7 => self.move_to_depot(from, to, n),
+            | Addr::Depot7 => self.check_move_to_depot(from, to, n),
         }
     }
 
-    pub fn act(&mut self, action: &Action) -> Result<Option<(Suit,Value)>, MoveError> {
-        let moveres = match action {
-            Action::Take => self.take().map(Some),
-            Action::Move(a1, a2, k) => self.move_cards(a1, a2, *k).map(|_| Option::None),
-            Action::Reveal(a) => self.reveal(a).map(Some),
-            Action::Quit => self.quit().map(|_|Option::None),
-            Action::Turnover => self.turnover().map(|_|Option::None),
-        };
-        if moveres.is_ok() {
-            self.score_action(action);
+    /// Check that a whole foundation pile can be moved to an empty foundation
+    fn check_foundation_pile_move(&self, from: &Addr, to: &Addr) -> Result<(), MoveError> {
+        if !self.rules.foundation_pile_moves {
+            return Err(MoveError::WithDescription("Foundation piles may not be moved".into()));
+        }
+        if !from.is_foundation() || !to.is_foundation() || from == to {
+            return Err(MoveError::WithDescription("Foundation piles only move to another foundation".into()));
+        }
+        if self.pile(from).is_empty() {
+            return Err(MoveError::NoCardToMove);
         }
-        moveres
+        if !self.pile(to).is_empty() {
+            return Err(MoveError::WithDescription("Foundation piles only move to an empty foundation".into()));
+        }
+        Ok(())
     }
 
-    fn quit(&mut self) -> Result<(), MoveError> {
-        self.state = State::Fail;
+    fn move_foundation_pile(&mut self, from: &Addr, to: &Addr) -> Result<(), MoveError> {
+        self.check_foundation_pile_move(from, to)?;
+        let pile = std::mem::take(self.pile_mut(from));
+        *self.pile_mut(to) = pile;
         Ok(())
     }
 
-    pub fn talon_len(&self) -> usize {
-        self.talon.len()
+    /// Move the cards, possibly ending the game
+    fn move_cards(&mut self, from: &Addr, to: &Addr, n: usize) -> Result<(), MoveError> {
+        self.check_move(from, to, n)?;
+        self.transfer(from, to, n);
+        if to.is_foundation() && self.foundations.iter().all(|f| f.len() == 13) {
+            self.state = State::Win;
+        }
+        Ok(())
     }
-}
-
-impl std::fmt::Display for GameEngine {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(f, "Talon size {}", self.talon_len())?;
-        writeln!(f)?;
 
-        write!(f, "Waste ({} cards)", self.waste.len())?;
-        if let Some(c) = self.waste.last() {
-            write!(f, " Top card: {c},")?;
+    /// Is the action legal in the current state? Quitting always is, while the game is running.
+    pub fn is_legal(&self, action: &Action) -> bool {
+        if !self.is_running() {
+            return false;
         }
-        writeln!(f)?;
+        match action {
+            Action::Take => self.talon_len() != 0,
+            Action::Turnover => self.talon_len() == 0 && self.cursor != 0 && self.redeals_left != Some(0),
+            Action::Reveal(addr) => addr
+                .depot_id()
+                .is_some_and(|d| self.columns[d.index()].has_face_down_top()),
+            Action::Move(from, to, n) => self.check_move(from, to, *n).is_ok(),
+            Action::MoveFoundationPile(from, to) => self.check_foundation_pile_move(from, to).is_ok(),
+            Action::Quit => true,
+        }
+    }
 
-        // Foundations
-        for i in 0..4 {
-            if let Some(c) = self.foundations[i].last() {
-                write!(f, "{c},")?;
-            } else {
-                write!(f, "□ ")?
+    /// All legal actions in the current state, except quitting, which is always legal while the game is running
+    ///
+    /// The order is fixed: taking or turning over, reveals, moves of cards by the pile they move from and to,
+    /// and moves of foundation piles.
+    pub fn legal_actions(&self) -> Vec<Action> {
+        let mut actions = vec![];
+        self.legal_actions_into(&mut actions);
+        actions
+    }
+
+    /// Replace the contents of `actions` with the legal actions, see `legal_actions`
+    ///
+    /// A search that lists the actions in every state can reuse one vector, and does not allocate once it is large
+    /// enough.
+    pub fn legal_actions_into(&self, actions: &mut Vec<Action>) {
+        actions.clear();
+        if !self.is_running() {
+            return;
+        }
+        if self.talon_len() != 0 {
+            actions.push(Action::Take);
+        } else if self.is_legal(&Action::Turnover) {
+            actions.push(Action::Turnover);
+        }
+        actions.extend(Addr::DEPOTS.into_iter().map(Action::Reveal).filter(|a| self.is_legal(a)));
+        for from in Addr::DEPOTS_AND_WASTE.iter().chain(Addr::FOUNDATIONS.iter()) {
+            let n_faceup = self.n_face_up(from);
+            for to in Addr::FOUNDATIONS.iter().chain(Addr::DEPOTS.iter()) {
+                if to != from {
+                    actions.extend(
+                        (1..=n_faceup)
+                            .filter(|&n| self.check_move(from, to, n).is_ok())
+                            .map(|n| Action::Move(*from, *to, n)),
+                    );
+                }
             }
         }
-        writeln!(f)?;
+        for from in Addr::FOUNDATIONS {
+            actions.extend(
+                Addr::FOUNDATIONS
+                    .into_iter()
+                    .filter(|to| self.check_foundation_pile_move(&from, to).is_ok())
+                    .map(|to| Action::MoveFoundationPile(from, to)),
+            );
+        }
+    }
 
-        // The tableaux
-        writeln!(f)?;
-        for i in 0..7 {
-            for c in self.columns[i].iter() {
-                write!(f, "{c},")?;
+    /// The number of legal actions in the current state, i.e. the branching factor of the game tree.
+    ///
+    /// Quitting is always possible, and is not counted.
+    pub fn n_legal_actions(&self) -> usize {
+        self.legal_actions().len()
+    }
+
+    /// The number of legal moves between depots that `SolitaireObserver::is_pruned` rejects
+    pub fn n_pruned_actions(&self) -> usize {
+        if !self.is_running() {
+            return 0;
+        }
+        let view = self.observe();
+        let mut n_pruned = 0;
+        for from in Addr::DEPOTS {
+            let n_faceup = self.n_face_up(&from);
+            for to in Addr::DEPOTS.into_iter().filter(|to| *to != from) {
+                n_pruned += (1..=n_faceup)
+                    .filter(|&n| self.check_move(&from, &to, n).is_ok())
+                    .filter(|&n| view.is_pruned(&Action::Move(from, to, n)))
+                    .count();
             }
-            writeln!(f)?;
         }
-        writeln!(f)?;
-        Ok(())
+        n_pruned
     }
-}
 
-/// A card in play. Information about suit, value and whether it is face up/down
-#[derive(Debug, Clone, PartialEq, Hash, Eq)]
-struct Card {
-    suit: Suit,
-    value: Value,
-    faceup: bool,
-}
-impl std::fmt::Display for Card {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        if self.faceup {
-            write!(f, "{}{:02}", self.suit, self.value)
-        } else {
-            write!(f, "▨")
+    /// The legal actions that make progress on their own: reveals, moves to the foundations and moves that empty a depot
+    fn progress_actions(&self) -> Vec<Action> {
+        let mut actions = vec![];
+        if !self.is_running() {
+            return actions;
         }
-    }
-}
-impl Card {
-    fn reveal(&mut self) {
-        self.faceup = true;
+        for depot in Addr::DEPOTS {
+            if self.is_legal(&Action::Reveal(depot)) {
+                actions.push(Action::Reveal(depot));
+            }
+        }
+        for from in Addr::DEPOTS_AND_WASTE {
+            for to in Addr::FOUNDATIONS {
+                if self.check_move(&from, &to, 1).is_ok() {
+                    actions.push(Action::Move(from, to, 1));
+                }
+            }
+        }
+        for from in Addr::DEPOTS {
+            let n = self.pile(&from).len();
+            for to in Addr::DEPOTS {
+                // Moving a whole depot to an empty one frees nothing
+                if to != from && !self.pile(&to).is_empty() && self.check_move(&from, &to, n).is_ok() {
+                    actions.push(Action::Move(from, to, n));
+                }
+            }
+        }
+        actions
     }
 
-    fn numeric_value(&self) -> u8 {
-        self.value.numeric_value()
+    /// Take an action, and return the cards it turned face up
+    ///
+    /// The action can be undone, and undoing actions can be redone until another action is taken.
+    /// A game that is over takes no actions, not even quitting, see `is_running`.
+    pub fn act(&mut self, action: &Action) -> Result<Turned, MoveError> {
+        if !self.is_running() {
+            return Err(MoveError::GameOver);
+        }
+        let turned = self.act_step(action, false)?;
+        self.history.redo.clear();
+        if self.options.auto_finish {
+            self.finish(true);
+        }
+        if self.options.end_when_stuck && self.is_stuck() {
+            // Undoing the action restores the state from before it
+            self.state = State::Fail(LossReason::Stuck);
+        }
+        Ok(turned)
     }
-}
-impl From<Card> for (Suit, Value) {
-    fn from(val: Card) -> Self {
-        (val.suit, val.value)
+
+    /// Is the game running, with nothing left to do that makes progress?
+    ///
+    /// The engine knows all cards, so it searches the positions that can be reached without making progress, and the
+    /// game is stuck if none of them has an action that does: a reveal, a move to a foundation, or emptying a depot.
+    /// Cards that only go back and forth, and turning the talon round and round, don't count. A search that reaches
+    /// `STUCK_SEARCH_LIMIT` positions gives up, and then the game is not stuck, so a game that could still be won is
+    /// never stuck. The search takes its time, which is why ending stuck games is an option, see `DealOptions`.
+    pub fn is_stuck(&self) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+        let mut start = self.clone();
+        // The copies mustn't look for being stuck in turn, nor remember how they came about
+        start.options = DealOptions::default();
+        start.history = History::default();
+        let mut seen = std::collections::HashSet::from([start.clone()]);
+        let mut unexplored = vec![start];
+        let mut actions = vec![];
+        while let Some(gs) = unexplored.pop() {
+            if !gs.progress_actions().is_empty() {
+                return false;
+            }
+            gs.legal_actions_into(&mut actions);
+            for action in &actions {
+                let mut next = gs.clone();
+                let turned = next.act_once(action).expect("The action is legal");
+                if !turned.is_empty() && !matches!(action, Action::Take) {
+                    return false;
+                }
+                // The points of going round in circles make no difference
+                next.current_score = gs.current_score;
+                if seen.len() >= STUCK_SEARCH_LIMIT {
+                    return false;
+                }
+                if seen.insert(next.clone()) {
+                    unexplored.push(next);
+                }
+            }
+        }
+        true
     }
-}
-impl From<Card> for CardView {
-    fn from(val: Card) -> Self {
-        match val {
-            Card {
-                faceup: false,
-                ..
-            } => CardView::FaceDown,
-            Card {
-                suit,
-                value,
-                faceup: true,
-            } => CardView::FaceUp(suit, value),
+
+    /// Play all cards to the foundations, when the talon and the waste are empty and all cards are face up
+    ///
+    /// Returns the moves, which score as usual and can be undone one by one. In Klondike that always wins the game.
+    /// Where the depots need not be in order, as in Yukon, the cards may get stuck, and then no move is made.
+    pub fn auto_finish(&mut self) -> Vec<Action> {
+        let actions = self.finish(false);
+        if !actions.is_empty() {
+            self.history.redo.clear();
         }
+        actions
     }
-}
 
-/// A deck of cards in random shuffled order. 52 cards of 4 suits and 13 values each.
-fn shuffled_deck(seed: u64) -> Vec<Card> {
-    let mut d = vec![];
-    for c in [Suit::Hearts, Suit::Clubs, Suit::Diamonds, Suit::Spades] {
-        for v in 1..=13 {
-            d.push(Card {
-                suit: c,
-                value: Value::try_from(v).expect("Known to be in range"),
-                faceup: false,
-            })
+    /// Move the cards of the depots to the foundations until the game is won, or take the moves back if it can't be
+    fn finish(&mut self, auto: bool) -> Vec<Action> {
+        if self.phase() != Phase::AutoCompletable {
+            return vec![];
+        }
+        let mut actions = vec![];
+        while let Some(action) = Addr::DEPOTS
+            .into_iter()
+            .flat_map(|from| Addr::FOUNDATIONS.map(|to| Action::Move(from, to, 1)))
+            .find(|action| self.is_legal(action))
+        {
+            self.act_step(&action, auto).expect("The action is legal");
+            actions.push(action);
         }
+        if !self.is_won() {
+            for _ in actions.drain(..) {
+                self.undo_step();
+            }
+        }
+        actions
     }
-    let mut rng: StdRng = rand::SeedableRng::seed_from_u64(seed);
-    d.shuffle(&mut rng);
-    d
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn act_step(&mut self, action: &Action, auto: bool) -> Result<Turned, MoveError> {
+        let (score, state) = (self.current_score, self.state.clone());
+        let moved = match action {
+            Action::Move(from, _, n) => self.pile(from).len().checked_sub(*n).map(|i| &self.pile(from)[i]),
+            Action::MoveFoundationPile(from, _) => self.pile(from).first(),
+            _ => None,
+        }
+        .map(|c| (c.suit, c.value));
+        let turned = self.act_once(action)?;
+        let elapsed = self.history.clock.map(|clock| clock.elapsed());
+        self.history.undo.push(Step { action: action.clone(), score, state, n_turned: turned.len(), moved, auto, elapsed });
+        Ok(turned)
+    }
 
-    #[test]
+    fn act_once(&mut self, action: &Action) -> Result<Turned, MoveError> {
+        let moveres = match action {
+            Action::Take => self.take(),
+            Action::Move(a1, a2, k) => self.move_cards(a1, a2, *k).map(|_| Turned::NONE),
+            Action::Reveal(a) => self.reveal(a).map(|card| Turned::from([card])),
+            Action::Quit => self.quit().map(|_| Turned::NONE),
+            Action::Turnover => self.turnover().map(|_| Turned::NONE),
+            Action::MoveFoundationPile(from, to) => self.move_foundation_pile(from, to).map(|_| Turned::NONE),
+        };
+        if moveres.is_ok() {
+            self.score_action(action);
+        }
+        match (action, moveres) {
+            (Action::Move(from, _, _), Ok(_)) if from.is_depot() && self.options.auto_reveal => {
+                let revealed = self.reveal(from).ok();
+                if revealed.is_some() {
+                    self.score_action(&Action::Reveal(*from));
+                }
+                Ok(revealed.into_iter().collect())
+            }
+            (_, moveres) => moveres,
+        }
+    }
+
+    fn quit(&mut self) -> Result<(), MoveError> {
+        self.state = State::Fail(LossReason::Quit);
+        Ok(())
+    }
+
+    /// Take back the last action, together with its points and the cards it turned face up
+    ///
+    /// Returns the action, or `None` if there is nothing to undo. It is cheaper than keeping a copy of the game
+    /// from before the action. The moves of `DealOptions::auto_finish` are taken back with the action before them.
+    pub fn undo(&mut self) -> Option<Action> {
+        while self.history.undo.last()?.auto {
+            self.undo_step();
+        }
+        let action = self.undo_step()?;
+        self.history.redo.push(action.clone());
+        Some(action)
+    }
+
+    /// Take back the last step of the history, without keeping it for redo
+    fn undo_step(&mut self) -> Option<Action> {
+        let step = self.history.undo.pop()?;
+        match step.action {
+            Action::Take => {
+                self.cursor -= step.n_turned;
+            }
+            Action::Turnover => {
+                self.cursor = self.stock.len();
+                self.redeals_left = self.redeals_left.map(|n| n + 1);
+            }
+            Action::Move(from, to, n) => {
+                if let (true, Some(depot)) = (step.n_turned > 0, from.depot_id()) {
+                    self.columns[depot.index()].hide_top();
+                }
+                self.transfer(&to, &from, n);
+            }
+            Action::Reveal(addr) => {
+                let depot = addr.depot_id().expect("Only cards in the depots are revealed");
+                self.columns[depot.index()].hide_top();
+            }
+            Action::MoveFoundationPile(from, to) => {
+                *self.pile_mut(&from) = std::mem::take(self.pile_mut(&to));
+            }
+            Action::Quit => {}
+        }
+        self.current_score = step.score;
+        self.state = step.state;
+        Some(step.action)
+    }
+
+    /// The number of moves that were wasted, since a later move took the same cards straight back to where they were
+    ///
+    /// Both the move and the move that takes it back are counted, e.g. moving a card to a foundation and back again,
+    /// or moving cards back and forth between two depots. Undone moves are not counted.
+    pub fn n_wasted_moves(&self) -> usize {
+        let mut last_move = std::collections::HashMap::new();
+        let mut wasted = vec![false; self.history.undo.len()];
+        for (i, step) in self.history.undo.iter().enumerate() {
+            let (Some(card), Action::Move(from, to, _) | Action::MoveFoundationPile(from, to)) = (step.moved, &step.action) else {
+                continue;
+            };
+            if let Some((j, last_from, last_to)) = last_move.insert(card, (i, *from, *to)) {
+                if (last_from, last_to) == (*to, *from) {
+                    wasted[i] = true;
+                    wasted[j] = true;
+                }
+            }
+        }
+        wasted.iter().filter(|&&w| w).count()
+    }
+
+    /// Start timing the game: from now on, each action is stamped with the time since the clock was started
+    ///
+    /// Games don't keep time unless asked to, since the searches of the AIs take actions on copies of the game by the
+    /// million. A saved game keeps the time on its clock.
+    pub fn start_clock(&mut self) {
+        self.history.clock = Some(Instant::now());
+    }
+
+    /// The times of the actions in the history since the clock was started, or `None` for actions taken before it
+    pub fn action_times(&self) -> impl Iterator<Item = Option<Duration>> + '_ {
+        self.history.undo.iter().map(|step| step.elapsed)
+    }
+
+    /// How long the game has taken since the clock was started, until now or until the action that ended it.
+    /// `None` if the clock was never started.
+    pub fn duration(&self) -> Option<Duration> {
+        let clock = self.history.clock?;
+        match self.action_times().last() {
+            Some(Some(end)) if !self.is_running() => Some(end),
+            _ => Some(clock.elapsed()),
+        }
+    }
+
+    /// The score with the points for the time the game has taken, if it is timed, see `DealOptions::timed`
+    ///
+    /// Points are taken off for every ten seconds, and a won game gets a bonus that is larger the faster it was won,
+    /// see `ScoringPolicy::time_points`. So the score of a running game goes down as the clock runs, while `score` only
+    /// changes with the actions. `None` if the game isn't timed, or the clock was never started.
+    pub fn timed_score(&self) -> Option<i32> {
+        let seconds = self.duration().filter(|_| self.options.timed)?.as_secs();
+        let policy = self.rules.scoring.policy();
+        Some(policy.add(self.current_score, policy.time_points(seconds, self.is_won())))
+    }
+
+    /// Take the last undone action again. Returns the action, or `None` if there is nothing to redo.
+    pub fn redo(&mut self) -> Option<Action> {
+        let action = self.history.redo.pop()?;
+        let redo = std::mem::take(&mut self.history.redo);
+        self.act(&action).expect("An undone action can be taken again");
+        self.history.redo = redo;
+        Some(action)
+    }
+
+    pub fn talon_len(&self) -> usize {
+        self.stock.len() - self.cursor
+    }
+
+    /// The cards in the waste, with the visible card last
+    pub fn waste(&self) -> &[Card] {
+        &self.stock[..self.cursor]
+    }
+
+    /// All cards in the game, with their pile and their position in it counted from the bottom
+    pub fn cards(&self) -> impl Iterator<Item = (Addr, usize, &Card)> {
+        [Addr::Talon, Addr::Waste]
+            .into_iter()
+            .chain(Addr::FOUNDATIONS)
+            .chain(Addr::DEPOTS)
+            .flat_map(move |addr| {
+                let pile = self.pile(&addr);
+                // The top of the talon is first in its pile
+                let position = move |i: usize| if addr == Addr::Talon { pile.len() - 1 - i } else { i };
+                pile.iter()
+                    .enumerate()
+                    .map(move |(i, card)| (addr, position(i), card))
+            })
+    }
+
+    /// Check the invariant that every card of the deck is in the game exactly once
+    fn has_all_cards(&self) -> bool {
+        let cards = self
+            .cards()
+            .map(|(_, _, c)| (c.suit, c.value))
+            .collect::<std::collections::HashSet<_>>();
+        cards.len() == 52 && self.cards().count() == 52
+    }
+
+    /// Create a game consistent with what an observer knows, where the hidden cards are randomly placed.
+    ///
+    /// The cards in the talon and the face down cards in the depots are drawn from the cards the observer has not seen.
+    pub fn determinize(view: &SolitaireObserver, rules: &RuleSet, rng: &mut impl Rng) -> Self {
+        let unseen = view.unseen_cards();
+        let mut unseen = shuffled_deck(rng.gen())
+            .into_iter()
+            .filter(|c| unseen.binary_search(&(c.suit, c.value)).is_ok())
+            .collect::<Vec<_>>();
+        let face_down = std::array::from_fn(|i| {
+            (0..view.depots[i].n_face_down)
+                .map(|_| unseen.pop().expect("Precondition: the view is consistent"))
+                .collect()
+        });
+        let talon = unseen.split_off(unseen.len() - view.talon_size);
+        debug_assert!(unseen.is_empty(), "All unseen cards should be placed");
+        GameEngine::with_hidden_cards(view, rules, face_down, talon.into_iter().rev().collect())
+    }
+
+    /// Create the game an open observer sees, see `observe_open`
+    ///
+    /// The history and the score are not part of the view, so the game starts over from the position with no score.
+    pub fn from_open(open: &OpenObserver, rules: &RuleSet) -> Self {
+        let cards = |cards: &[(Suit, Value)]| cards.iter().map(|&(suit, value)| Card { suit, value }).collect();
+        GameEngine::with_hidden_cards(&open.view, rules, open.face_down.each_ref().map(|c| cards(c)), cards(&open.talon))
+    }
+
+    /// Create a game from a view and the cards it doesn't show: the face down cards of each depot from the bottom
+    /// up, and the talon with the next card first
+    fn with_hidden_cards(view: &SolitaireObserver, rules: &RuleSet, face_down: [Vec<Card>; 7], talon: Vec<Card>) -> Self {
+        let foundations: [Vec<Card>; 4] = std::array::from_fn(|i| match view.foundation_tops[i] {
+            Some((suit, top)) => (1..=top.numeric_value())
+                .map(|v| Card {
+                    suit,
+                    value: Value::try_from(v).expect("Known to be in range"),
+                })
+                .collect(),
+            None => vec![],
+        });
+        let waste = view
+            .waste
+            .iter()
+            .map(|&(suit, value)| Card { suit, value })
+            .collect::<Vec<_>>();
+        let mut face_down = face_down;
+        let columns = std::array::from_fn(|i| {
+            let depot = &view.depots[i];
+            let mut cards = std::mem::take(&mut face_down[i]);
+            cards.extend(depot.face_up.iter().map(|&(suit, value)| Card { suit, value }));
+            Depot { cards, first_face_up: depot.n_face_down }
+        });
+        let cursor = waste.len();
+        let mut stock = waste;
+        stock.extend(talon);
+        let gs = GameEngine {
+            stock,
+            cursor,
+            redeals_left: view.redeals_left,
+            columns,
+            foundations,
+            state: State::Running,
+            current_score: 0,
+            rules: rules.clone(),
+            options: DealOptions::default(),
+            history: History::default(),
+        };
+        debug_assert!(gs.has_all_cards(), "Precondition: the view is consistent");
+        gs
+    }
+}
+
+/// Builds a game, with `GameEngine::builder().seed(7).rules(rules).build()`
+///
+/// The rules start out as the default ones, and can be replaced as a whole or one by one.
+/// `build` fails if the rules do not make sense together.
+#[derive(Debug, Default, Clone)]
+pub struct GameBuilder {
+    seed: u64,
+    rules: RuleSet,
+    options: DealOptions,
+}
+
+impl GameBuilder {
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn options(mut self, options: DealOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(self) -> Result<GameEngine, RulesError> {
+        self.rules.validate()?;
+        let dealt = match self.rules.variant {
+            Variant::Klondike => GameEngine::deal(self.seed),
+            Variant::Yukon | Variant::Russian => GameEngine::deal_yukon(self.seed),
+            variant => return Err(RulesError::NotOnTableau(variant.name())),
+        };
+        let mut gs = GameEngine {
+            current_score: self.rules.scoring.policy().initial,
+            redeals_left: self.rules.redeals(),
+            rules: self.rules,
+            options: self.options,
+            ..dealt
+        };
+        if gs.options.timed {
+            gs.start_clock();
+        }
+        Ok(gs)
+    }
+}
+
+// Setters for single rules, to change the rule set bit by bit
+impl GameBuilder {
+    pub fn draw(mut self, draw: u8) -> Self {
+        self.rules.draw = draw;
+        self
+    }
+
+    pub fn passes(mut self, passes: u32) -> Self {
+        self.rules.passes = Some(passes);
+        self
+    }
+
+    pub fn empty_column(mut self, empty_column: EmptyColumnRule) -> Self {
+        self.rules.empty_column = empty_column;
+        self
+    }
+
+    pub fn foundation_retreat(mut self, foundation_retreat: bool) -> Self {
+        self.rules.foundation_retreat = foundation_retreat;
+        self
+    }
+
+    pub fn foundation_pile_moves(mut self, foundation_pile_moves: bool) -> Self {
+        self.rules.foundation_pile_moves = foundation_pile_moves;
+        self
+    }
+
+    pub fn scoring(mut self, scoring: Scoring) -> Self {
+        self.rules.scoring = scoring;
+        self
+    }
+
+    pub fn open_information(mut self, open_information: bool) -> Self {
+        self.rules.open_information = open_information;
+        self
+    }
+
+    pub fn auto_reveal(mut self, auto_reveal: bool) -> Self {
+        self.options.auto_reveal = auto_reveal;
+        self
+    }
+
+    pub fn auto_finish(mut self, auto_finish: bool) -> Self {
+        self.options.auto_finish = auto_finish;
+        self
+    }
+
+    pub fn end_when_stuck(mut self, end_when_stuck: bool) -> Self {
+        self.options.end_when_stuck = end_when_stuck;
+        self
+    }
+
+    pub fn timed(mut self, timed: bool) -> Self {
+        self.options.timed = timed;
+        self
+    }
+
+    pub fn assistance(mut self, assistance: Assistance) -> Self {
+        self.options.assistance = assistance;
+        self
+    }
+}
+
+/// Can the action be part of progress in the game?
+///
+/// An action is productive if it reveals a card, moves a card to a foundation or empties a depot,
+/// or if it makes such an action possible that was not possible before.
+/// Illegal actions are not productive.
+pub fn is_productive(action: &Action, state: &GameEngine) -> bool {
+    let before = state.progress_actions();
+    if before.contains(action) {
+        return true;
+    }
+    // The copy only takes the action, without the moves the engine may make by itself after it, or the search for
+    // whether the game is stuck
+    let mut after = state.without_history();
+    after.options = DealOptions { auto_reveal: state.options.auto_reveal, ..DealOptions::default() };
+    if after.act(action).is_err() {
+        return false;
+    }
+    after
+        .progress_actions()
+        .iter()
+        .any(|a| !before.contains(a))
+}
+
+/// Let the AI play until the game is over, or until `max_actions` actions have been taken
+///
+/// Returns the number of actions taken. Panics if the AI suggests an illegal action.
+/// In a game with open information, the AI is shown all cards, see `show_open_cards`.
+pub fn run_game(gs: &mut GameEngine, ai: &mut dyn Ai, max_actions: usize) -> usize {
+    let mut n_actions_taken = 0;
+    show_open_cards(gs, ai);
+    while gs.is_running() && n_actions_taken < max_actions {
+        let action = ai.make_move();
+        let res = gs
+            .act(&action)
+            .unwrap_or_else(|_| panic!("The AI suggested {:?} an illegal move!", action));
+        ai.update(action, res);
+        show_open_cards(gs, ai);
+        n_actions_taken += 1;
+    }
+    n_actions_taken
+}
+
+/// Show the AI all cards if the game is played with open information, see `Ai::observe_open`
+///
+/// Call it before the first move and after each `Ai::update`, so that the AI doesn't plan with cards that have moved.
+pub fn show_open_cards(gs: &GameEngine, ai: &mut dyn Ai) {
+    if gs.rules().open_information {
+        ai.observe_open(&gs.observe_open());
+    }
+}
+
+impl std::fmt::Display for GameEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "Talon size {}", self.talon_len())?;
+        writeln!(f)?;
+
+        write!(f, "Waste ({} cards)", self.cursor)?;
+        if let Some(c) = self.waste().last() {
+            write!(f, " Top card: {c},")?;
+        }
+        writeln!(f)?;
+
+        // Foundations
+        for i in 0..4 {
+            if let Some(c) = self.foundations[i].last() {
+                write!(f, "{c},")?;
+            } else {
+                write!(f, "□ ")?
+            }
+        }
+        writeln!(f)?;
+
+        // The tableaux
+        writeln!(f)?;
+        for depot in self.columns.iter() {
+            for _ in 0..depot.first_face_up {
+                write!(f, "▨,")?;
+            }
+            for c in depot.face_up() {
+                write!(f, "{c},")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+/// Saved games, to resume later or to reproduce a bug
+///
+/// A game is saved as JSON, with its rules, its options and its history, so that its moves can still be undone.
+#[cfg(feature = "serde")]
+impl GameEngine {
+    /// Save the game to a file
+    pub fn save_to(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Load a game saved by `save_to`. A file that doesn't hold each of the 52 cards once, or whose history doesn't
+    /// lead to the game, is rejected.
+    pub fn load_from(path: impl AsRef<std::path::Path>) -> std::io::Result<GameEngine> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let gs: GameEngine = serde_json::from_reader(file)?;
+        // The piles are only well formed after these checks, so they come before anything looks at the cards
+        if gs.columns.iter().any(|d| d.first_face_up > d.cards.len()) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "A depot has too many face down cards"));
+        }
+        if gs.cursor > gs.stock.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "The waste has more cards than the stock"));
+        }
+        if !gs.has_all_cards() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "The game does not have a full deck"));
+        }
+        if !gs.is_history_consistent() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "The history does not lead to the game"));
+        }
+        Ok(gs)
+    }
+
+    /// Can the history be undone to its start, and do its actions lead from there to the game again, and on to the
+    /// actions to redo?
+    fn is_history_consistent(&self) -> bool {
+        let mut start = self.without_history();
+        start.history.undo = self.history.undo.clone();
+        while let Some(step) = start.history.undo.last() {
+            if !start.can_undo(step) {
+                return false;
+            }
+            start.undo_step();
+        }
+        let mut replayed = start;
+        for step in self.history.undo.iter().filter(|step| !step.auto) {
+            if replayed.act(&step.action).is_err() {
+                return false;
+            }
+        }
+        if replayed != *self {
+            return false;
+        }
+        self.history.redo.iter().rev().all(|action| replayed.act(action).is_ok())
+    }
+
+    /// Can the step be undone without leaving the piles malformed? Only a history that was tampered with fails this.
+    fn can_undo(&self, step: &Step) -> bool {
+        let face_up_on = |addr: &Addr, n: usize| match addr.depot_id() {
+            Some(depot) => self.columns[depot.index()].face_up().len() >= n,
+            None => self.pile(addr).len() >= n,
+        };
+        match step.action {
+            Action::Take => (1..=crate::core::MAX_TURNED).contains(&step.n_turned) && step.n_turned <= self.cursor,
+            Action::Turnover => self.cursor == 0 && self.redeals_left != Some(u32::MAX),
+            Action::Move(from, to, n) => {
+                let piles = from != to && from != Addr::Talon && to != Addr::Talon;
+                let n_ok = n >= 1 && (n == 1 || (from.is_depot() && to.is_depot()));
+                let turned = step.n_turned == 0 || (step.n_turned == 1 && from.is_depot());
+                piles && n_ok && turned && face_up_on(&to, n)
+            }
+            Action::Reveal(addr) => addr.is_depot() && face_up_on(&addr, 1),
+            Action::MoveFoundationPile(from, to) => from.is_foundation() && to.is_foundation(),
+            Action::Quit => true,
+        }
+    }
+}
+
+/// A card in play
+///
+/// Whether a card is face up follows from where it is: the talon is face down, the waste and the foundations are face
+/// up, and a depot knows where its face up cards start.
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Card {
+    suit: Suit,
+    value: Value,
+}
+impl std::fmt::Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}{:02}", self.suit, self.value)
+    }
+}
+impl Card {
+    fn numeric_value(&self) -> u8 {
+        self.value.numeric_value()
+    }
+}
+impl From<Card> for (Suit, Value) {
+    fn from(val: Card) -> Self {
+        (val.suit, val.value)
+    }
+}
+
+/// A deck of cards in random shuffled order. 52 cards of 4 suits and 13 values each.
+///
+/// Games other than Klondike may be dealt from it too.
+pub fn shuffled_deck(seed: u64) -> Vec<Card> {
+    let mut d = vec![];
+    for c in [Suit::Hearts, Suit::Clubs, Suit::Diamonds, Suit::Spades] {
+        for v in 1..=13 {
+            d.push(Card {
+                suit: c,
+                value: Value::try_from(v).expect("Known to be in range"),
+            })
+        }
+    }
+    let mut rng: StdRng = rand::SeedableRng::seed_from_u64(seed);
+    d.shuffle(&mut rng);
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::WinLoss;
+
+    #[test]
     fn can_only_move_one_from_waste() {
         let mut gs = GameEngine::deal(0);
         let action = Action::Move(Addr::Waste, Addr::Depot3, 2);
@@ -464,38 +1499,43 @@ mod tests {
     /// 1) move card from waste to foundation
     /// 2) reveal a card in the tableaux
     /// 3) move card from tableaux to foundation
+    ///
     /// make sure the score increase by 10 + 5 + 10 = 25
     #[test]
     fn score_when_moving_cards() {
         let mut gs = GameEngine {
-            talon: vec![],
-            waste: vec![Card {
+            stock: vec![Card {
                 suit: Suit::Hearts,
                 value: Value::ACE,
-                faceup: true,
             }],
+            cursor: 1,
+            redeals_left: None,
             columns: [
-                vec![Card {
-                    suit: Suit::Spades,
-                    value: Value::TWO,
-                    faceup: false,
-                }],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
+                Depot {
+                    cards: vec![Card {
+                        suit: Suit::Spades,
+                        value: Value::TWO,
+                    }],
+                    first_face_up: 1,
+                },
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
             ],
             foundations: [vec![], vec![
                 Card {
                     suit: Suit::Spades,
                     value: Value::ACE,
-                    faceup: true,
                 }
             ], vec![], vec![]],
             state: State::Running,
             current_score: 0,
+            rules: RuleSet::default(),
+            options: DealOptions::default(),
+            history: History::default(),
         };
         gs.act(&Action::Move(Addr::Waste, Addr::Foundation1, 1))
             .map_err(|e| eprintln!("{}", e))
@@ -509,32 +1549,690 @@ mod tests {
         assert_eq!(gs.score(), 25);
     }
 
+    /// Count the legal actions in a small game
+    #[test]
+    fn branching_factor() {
+        let gs = GameEngine {
+            stock: vec![Card {
+                suit: Suit::Hearts,
+                value: Value::ACE,
+            }],
+            cursor: 1,
+            redeals_left: None,
+            columns: [
+                Depot {
+                    cards: vec![Card {
+                        suit: Suit::Spades,
+                        value: Value::TWO,
+                    }],
+                    first_face_up: 1,
+                },
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+            ],
+            foundations: [vec![], vec![
+                Card {
+                    suit: Suit::Spades,
+                    value: Value::ACE,
+                }
+            ], vec![], vec![]],
+            state: State::Running,
+            current_score: 0,
+            rules: RuleSet::default(),
+            options: DealOptions::default(),
+            history: History::default(),
+        };
+        // The ace to three foundations, reveal the two and turn over the waste
+        assert_eq!(gs.n_legal_actions(), 5);
+        assert!(gs.is_legal(&Action::Turnover));
+        assert!(!gs.is_legal(&Action::Take));
+        assert!(gs.is_legal(&Action::Reveal(Addr::Depot1)));
+        assert!(!gs.is_legal(&Action::Reveal(Addr::Depot2)));
+        assert!(gs.is_legal(&Action::Move(Addr::Waste, Addr::Foundation1, 1)));
+        assert!(!gs.is_legal(&Action::Move(Addr::Waste, Addr::Foundation2, 1)));
+    }
+
+    #[test]
+    fn cards_are_all_in_place() {
+        let mut gs = GameEngine::deal(2);
+        assert!(gs.has_all_cards());
+        assert_eq!(gs.cards().filter(|(addr, _, _)| *addr == Addr::Talon).count(), 24);
+        let (addr, position, card) = gs.cards().last().unwrap();
+        assert_eq!((addr, position), (Addr::Depot7, 6));
+        assert_eq!(gs.columns[6].face_up(), std::slice::from_ref(card));
+        gs.act(&Action::Take).unwrap();
+        assert!(gs.has_all_cards());
+        assert!(gs.act(&Action::Move(Addr::Talon, Addr::Depot1, 1)).is_err());
+    }
+
+    #[test]
+    fn productive_actions() {
+        let mut gs = GameEngine::deal(0);
+        gs.columns[1] = Depot {
+            cards: vec![
+                Card {
+                    suit: Suit::Spades,
+                    value: Value::TWO,
+                },
+                Card {
+                    suit: Suit::Hearts,
+                    value: Value::ACE,
+                },
+            ],
+            first_face_up: 1,
+        };
+        gs.columns[2] = Depot {
+            cards: vec![Card {
+                suit: Suit::Hearts,
+                value: Value::KING,
+            }],
+            first_face_up: 0,
+        };
+        // To the foundation
+        assert!(is_productive(&Action::Move(Addr::Depot2, Addr::Foundation1, 1), &gs));
+        // Moving a king between depots, or illegal actions
+        assert!(!is_productive(&Action::Move(Addr::Depot3, Addr::Depot4, 1), &gs));
+        assert!(!is_productive(&Action::Reveal(Addr::Depot2), &gs));
+        gs.act(&Action::Move(Addr::Depot2, Addr::Foundation1, 1)).unwrap();
+        assert!(is_productive(&Action::Reveal(Addr::Depot2), &gs));
+        assert!(!is_productive(&Action::Quit, &gs));
+    }
+
+    #[test]
+    fn limits_the_redeals() {
+        let mut gs = GameEngine::builder().seed(7).rules(RuleSet::VEGAS_DRAW3).build().unwrap();
+        let mut view = gs.observe();
+        for redeals_left in [2, 1, 0] {
+            while gs.talon_len() != 0 {
+                view.update(Action::Take, gs.act(&Action::Take).unwrap());
+            }
+            assert_eq!(view.redeals_left, Some(redeals_left));
+            assert_eq!(view.legal_actions().contains(&Action::Turnover), redeals_left != 0);
+            assert_eq!(gs.legal_actions().contains(&Action::Turnover), redeals_left != 0);
+            if redeals_left != 0 {
+                view.update(Action::Turnover, gs.act(&Action::Turnover).unwrap());
+            }
+        }
+        assert!(gs.act(&Action::Turnover).is_err());
+        assert_eq!(gs.observe(), view);
+        while gs.undo().is_some_and(|action| action != Action::Turnover) {}
+        assert_eq!(gs.redeals_left(), Some(1));
+    }
+
+    #[test]
+    fn keeps_time() {
+        let mut gs = GameEngine::deal(0);
+        gs.act(&Action::Take).unwrap();
+        assert_eq!(gs.duration(), None);
+        gs.start_clock();
+        gs.act(&Action::Take).unwrap();
+        gs.act(&Action::Quit).unwrap();
+        let times = gs.action_times().collect::<Vec<_>>();
+        assert_eq!(times.len(), 3);
+        assert_eq!(times[0], None);
+        assert!(times[1].is_some() && times[1] <= times[2]);
+        assert_eq!(gs.duration(), times[2]);
+        assert_eq!(gs.timed_score(), None);
+
+        // A timed game starts the clock at the deal
+        let mut gs = GameEngine::builder().seed(2).timed(true).build().unwrap();
+        assert!(gs.duration().is_some());
+        gs.act(&Action::Move(Addr::Depot6, Addr::Foundation1, 1)).unwrap();
+        assert_eq!(gs.timed_score(), Some(gs.score()));
+    }
+
+    /// The builder checks the rules before dealing
+    #[test]
+    fn builder_validates_rules() {
+        assert_eq!(
+            GameEngine::builder().scoring(Scoring::Vegas).build().err(),
+            Some(RulesError::Incompatible("Vegas scoring", "unlimited passes"))
+        );
+        let vegas = GameEngine::builder().seed(7).draw(3).passes(3).scoring(Scoring::Vegas).build().unwrap();
+        assert_eq!(vegas.redeals_left(), Some(2));
+        let gs = GameEngine::builder()
+            .seed(7)
+            .empty_column(EmptyColumnRule::AnyCard)
+            .build()
+            .unwrap();
+        assert_eq!(gs.rules().empty_column, EmptyColumnRule::AnyCard);
+        let view = SolitaireObserver { empty_column: EmptyColumnRule::AnyCard, ..GameEngine::deal(7).observe() };
+        assert_eq!(gs.observe(), view);
+    }
+
+    /// With the casual rules, a foundation pile can be moved to an empty foundation
+    #[test]
+    fn foundation_pile_moves() {
+        let ace = Card {
+            suit: Suit::Hearts,
+            value: Value::ACE,
+        };
+        let pile_move = Action::MoveFoundationPile(Addr::Foundation1, Addr::Foundation3);
+        let mut gs = GameEngine::deal(0);
+        gs.foundations[0].push(ace.clone());
+        assert!(!gs.is_legal(&pile_move));
+        let mut gs = GameEngine::builder().rules(RuleSet::CASUAL).build().unwrap();
+        gs.foundations[0].push(ace.clone());
+        gs.foundations[1].push(Card { suit: Suit::Spades, ..ace });
+        let mut view = gs.observe();
+        assert!(!gs.is_legal(&Action::MoveFoundationPile(Addr::Foundation1, Addr::Foundation2)));
+        let res = gs.act(&pile_move).unwrap();
+        view.update(pile_move, res);
+        assert_eq!(view, gs.observe());
+        assert_eq!(gs.foundations[2].len(), 1);
+    }
+
+    /// Undoing all actions of a game goes back through the same positions and scores, and redoing them plays it again
+    #[test]
+    fn undo_and_redo() {
+        for (rules, auto_reveal) in [(RuleSet::KLONDIKE_CLASSIC, true), (RuleSet::KLONDIKE_DRAW3, false)] {
+            let mut gs = GameEngine::builder()
+                .seed(5)
+                .rules(rules)
+                .options(DealOptions { auto_reveal, ..DealOptions::default() })
+                .build()
+                .unwrap();
+            let mut ai = crate::ai::GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+            let mut positions = vec![gs.clone()];
+            while gs.is_running() {
+                let action = ai.make_move();
+                ai.update(action.clone(), gs.act(&action).unwrap());
+                positions.push(gs.clone());
+            }
+            let end = positions.pop().unwrap();
+            while let Some(before) = positions.pop() {
+                gs.undo().unwrap();
+                assert_eq!(gs, before);
+            }
+            assert_eq!(gs.undo(), None);
+            while gs.redo().is_some() {}
+            assert_eq!(gs, end);
+            assert_eq!(gs.score(), end.score());
+
+            // Taking an action forgets the undone ones
+            gs.undo();
+            gs.act(&Action::Quit).unwrap();
+            assert_eq!(gs.redo(), None);
+        }
+    }
+
+    /// Once the history and the list of actions have room, acting and listing the legal actions don't allocate
+    #[test]
+    fn hot_paths_do_not_allocate() {
+        use crate::alloc_count::count_allocations;
+        for (rules, auto_reveal) in [(RuleSet::KLONDIKE_CLASSIC, true), (RuleSet::KLONDIKE_DRAW3, false)] {
+            let mut gs = GameEngine::builder()
+                .seed(5)
+                .rules(rules)
+                .options(DealOptions { auto_reveal, ..DealOptions::default() })
+                .build()
+                .unwrap();
+            let mut ai = crate::ai::GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+            let mut legal = vec![];
+            let mut actions = vec![];
+            while gs.is_running() {
+                gs.legal_actions_into(&mut legal);
+                let action = ai.make_move();
+                ai.update(action.clone(), gs.act(&action).unwrap());
+                actions.push(action);
+            }
+            // Undoing keeps the room in the history
+            while gs.undo().is_some() {}
+            for action in actions {
+                let (n, ()) = count_allocations(|| gs.legal_actions_into(&mut legal));
+                assert_eq!(n, 0, "Listing the actions before {action:?}");
+                let (n, _) = count_allocations(|| gs.act(&action).unwrap());
+                assert_eq!(n, 0, "{action:?}");
+            }
+        }
+    }
+
+    /// Taking and turning over only move the cursor, so every pass through the stock has the cards in the same order
+    #[test]
+    fn passes_through_the_stock() {
+        let mut gs = GameEngine::deal(4);
+        let stock = gs.stock.clone();
+        let mut taken = vec![];
+        while gs.is_legal(&Action::Take) {
+            taken.extend(gs.act(&Action::Take).unwrap());
+            assert_eq!(gs.waste().len() + gs.talon_len(), 24);
+        }
+        assert_eq!(taken, stock.iter().map(|c| (c.suit, c.value)).collect::<Vec<_>>());
+        gs.act(&Action::Turnover).unwrap();
+        assert_eq!(gs.stock, stock);
+        assert_eq!((gs.waste().len(), gs.talon_len()), (0, 24));
+        assert_eq!(gs.act(&Action::Take).unwrap()[..], taken[..1]);
+    }
+
+    /// When drawing three, only the last card taken is on top of the waste, and the others are played after it
+    #[test]
+    fn draw_three() {
+        let mut gs = GameEngine::builder().seed(3).rules(RuleSet::KLONDIKE_DRAW3).build().unwrap();
+        let mut view = gs.observe();
+        let talon = gs.pile(&Addr::Talon).iter().map(|c| (c.suit, c.value)).collect::<Vec<_>>();
+        let res = gs.act(&Action::Take).unwrap();
+        assert_eq!(res[..], talon[..3]);
+        view.update(Action::Take, res);
+        assert_eq!(view, gs.observe());
+        assert_eq!(view.talon_size, 21);
+        assert_eq!(view.card_at(&Addr::Waste, 1), Some(talon[2].into()));
+
+        // Uncover the card below by moving the top card of the waste away
+        let (suit, value) = talon[2];
+        gs.foundations[0] = (1..value.numeric_value())
+            .map(|v| Card { suit, value: Value::try_from(v).unwrap() })
+            .collect();
+        let mut view = gs.observe();
+        let res = gs.act(&Action::Move(Addr::Waste, Addr::Foundation1, 1)).unwrap();
+        view.update(Action::Move(Addr::Waste, Addr::Foundation1, 1), res);
+        assert_eq!(view, gs.observe());
+        assert_eq!(view.card_at(&Addr::Waste, 1), Some(talon[1].into()));
+
+        // The last take gets the cards that are left
+        while gs.talon_len() > 3 {
+            gs.act(&Action::Take).unwrap();
+        }
+        gs.stock.pop();
+        assert_eq!(gs.act(&Action::Take).unwrap().len(), 2);
+        assert!(gs.act(&Action::Take).is_err());
+    }
+
+    /// With Vegas rules, moving to the foundation gives 5 points and cards cannot be moved back
+    #[test]
+    fn vegas_scoring_and_no_retreat() {
+        let card = |suit, value| Card {
+            suit,
+            value,
+        };
+        let mut gs = GameEngine {
+            stock: vec![card(Suit::Diamonds, Value::ACE)],
+            cursor: 1,
+            redeals_left: None,
+            columns: [
+                Depot { cards: vec![card(Suit::Spades, Value::TWO)], first_face_up: 0 },
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+            ],
+            foundations: [vec![card(Suit::Hearts, Value::ACE)], vec![], vec![], vec![]],
+            state: State::Running,
+            current_score: 0,
+            rules: RuleSet {
+                foundation_retreat: false,
+                scoring: Scoring::Vegas,
+                ..RuleSet::default()
+            },
+            options: DealOptions::default(),
+            history: History::default(),
+        };
+        assert!(gs.act(&Action::Move(Addr::Foundation1, Addr::Depot1, 1)).is_err());
+        gs.act(&Action::Move(Addr::Waste, Addr::Foundation2, 1)).unwrap();
+        assert_eq!(gs.score(), 5);
+    }
+
+    /// The legal actions are exactly the actions that `is_legal` accepts, in every position of a game
+    #[test]
+    fn legal_actions_are_all_legal_actions() {
+        let piles = [Addr::Talon, Addr::Waste].into_iter().chain(Addr::FOUNDATIONS).chain(Addr::DEPOTS);
+        let mut all_actions = vec![Action::Take, Action::Turnover];
+        all_actions.extend(Addr::DEPOTS.map(Action::Reveal));
+        for (from, to) in piles.clone().flat_map(|from| piles.clone().map(move |to| (from, to))) {
+            all_actions.extend((1..=13).map(|n| Action::Move(from, to, n)));
+            all_actions.push(Action::MoveFoundationPile(from, to));
+        }
+
+        let mut gs = GameEngine::builder().seed(2).rules(RuleSet::CASUAL).build().unwrap();
+        let mut ai = crate::ai::GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        while gs.is_running() {
+            let legal = gs.legal_actions();
+            let expected = all_actions.iter().filter(|a| gs.is_legal(a)).collect::<Vec<_>>();
+            assert_eq!(legal.len(), expected.len());
+            assert!(expected.iter().all(|a| legal.contains(a)));
+            let action = ai.make_move();
+            ai.update(action.clone(), gs.act(&action).unwrap());
+        }
+        assert!(gs.legal_actions().is_empty());
+    }
+
+    /// A move and the move that takes it straight back are both wasted
+    #[test]
+    fn wasted_moves() {
+        let card = |suit, value| Card {
+            suit,
+            value,
+        };
+        let mut gs = GameEngine {
+            stock: vec![],
+            cursor: 0,
+            columns: [
+                Depot { cards: vec![card(Suit::Spades, Value::TWO)], first_face_up: 0 },
+                Depot { cards: vec![card(Suit::Hearts, Value::try_from(3).unwrap())], first_face_up: 0 },
+                Depot { cards: vec![card(Suit::Diamonds, Value::try_from(3).unwrap())], first_face_up: 0 },
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+            ],
+            foundations: [vec![card(Suit::Spades, Value::ACE)], vec![], vec![], vec![]],
+            ..GameEngine::deal(0)
+        };
+        // Back and forth between the depots, and between a depot and a foundation
+        gs.act(&Action::Move(Addr::Depot1, Addr::Depot2, 1)).unwrap();
+        gs.act(&Action::Move(Addr::Depot2, Addr::Depot3, 1)).unwrap();
+        assert_eq!(gs.n_wasted_moves(), 0);
+        gs.act(&Action::Move(Addr::Depot3, Addr::Foundation1, 1)).unwrap();
+        gs.act(&Action::Move(Addr::Foundation1, Addr::Depot3, 1)).unwrap();
+        assert_eq!(gs.n_wasted_moves(), 2);
+        // Only a move straight back counts
+        gs.act(&Action::Move(Addr::Depot3, Addr::Depot2, 1)).unwrap();
+        assert_eq!(gs.n_wasted_moves(), 2);
+        gs.undo();
+        gs.undo();
+        assert_eq!(gs.n_wasted_moves(), 0);
+    }
+
+    /// Yukon deals all cards to the depots, and Russian builds in suit. Any face up group can be moved in both.
+    #[test]
+    fn yukon_and_russian() {
+        let gs = GameEngine::builder().seed(3).rules(RuleSet::YUKON).build().unwrap();
+        assert_eq!(gs.talon_len(), 0);
+        assert_eq!(gs.columns.iter().map(|d| d.cards.len()).collect::<Vec<_>>(), vec![1, 6, 7, 8, 9, 10, 11]);
+        assert!(gs.columns.iter().all(|d| d.face_up().len() == d.cards.len().min(5)));
+
+        let card = |suit, value| Card {
+            suit,
+            value,
+        };
+        let mut gs = GameEngine::builder().rules(RuleSet::RUSSIAN).build().unwrap();
+        let seven = Value::try_from(7).unwrap();
+        let six = Value::try_from(6).unwrap();
+        gs.columns[0] = Depot { cards: vec![card(Suit::Hearts, seven)], first_face_up: 0 };
+        gs.columns[1] = Depot { cards: vec![card(Suit::Clubs, six)], first_face_up: 0 };
+        gs.columns[2] = Depot { cards: vec![card(Suit::Hearts, six), card(Suit::Spades, Value::KING)], first_face_up: 0 };
+        assert!(gs.act(&Action::Move(Addr::Depot2, Addr::Depot1, 1)).is_err());
+        // The king is not in sequence with the six, but it is moved along with it
+        gs.act(&Action::Move(Addr::Depot3, Addr::Depot1, 2)).unwrap();
+        assert_eq!(gs.columns[0].cards.len(), 3);
+    }
+
+    /// With auto reveal, moving the last face up card reveals the card below
+    #[test]
+    fn auto_reveal_after_move() {
+        let mut gs = GameEngine::builder().auto_reveal(true).build().unwrap();
+        gs.columns[1] = Depot {
+            cards: vec![
+                Card {
+                    suit: Suit::Spades,
+                    value: Value::TWO,
+                },
+                Card {
+                    suit: Suit::Hearts,
+                    value: Value::ACE,
+                },
+            ],
+            first_face_up: 1,
+        };
+        let res = gs.act(&Action::Move(Addr::Depot2, Addr::Foundation1, 1)).unwrap();
+        assert_eq!(res[..], [(Suit::Spades, Value::TWO)]);
+        assert_eq!(gs.observe().depots[1], DepotView::new(0, vec![(Suit::Spades, Value::TWO)]));
+        assert_eq!(gs.score(), 15);
+    }
+
+    #[test]
+    fn phases() {
+        let mut gs = GameEngine::deal(0);
+        assert_eq!(gs.phase(), Phase::Opening);
+        for depot in gs.columns.iter_mut().skip(3) {
+            depot.first_face_up = 0;
+        }
+        assert_eq!(gs.phase(), Phase::Midgame);
+        for depot in gs.columns.iter_mut() {
+            depot.first_face_up = 0;
+        }
+        assert_eq!(gs.phase(), Phase::Endgame);
+        // Not a valid game, but the phase only looks at the counts
+        gs.stock.clear();
+        gs.cursor = 0;
+        assert_eq!(gs.phase(), Phase::AutoCompletable);
+        assert_eq!(gs.observe().phase(), Phase::AutoCompletable);
+        gs.state = State::Fail(LossReason::Quit);
+        assert_eq!(gs.phase(), Phase::Finished(WinLoss::Loss));
+    }
+
+    /// Finishing by hand and with the option score the same, but the option takes its moves back with the action
+    #[test]
+    fn auto_finish() {
+        let mut manual = GameEngine::deal(0);
+        let mut gs = GameEngine::builder().seed(0).auto_finish(true).build().unwrap();
+        let mut ai = crate::ai::GreedyAi::new(manual.observe(), manual.rules().scoring.policy());
+        assert!(manual.auto_finish().is_empty());
+        let mut previous = gs.clone();
+        while manual.phase() != Phase::AutoCompletable {
+            assert!(manual.is_running());
+            let action = ai.make_move();
+            ai.update(action.clone(), manual.act(&action).unwrap());
+            previous = gs.clone();
+            gs.act(&action).unwrap();
+        }
+        assert!(gs.is_won());
+        let finish = manual.auto_finish();
+        assert!(!finish.is_empty());
+        assert!(manual.is_won());
+        assert_eq!(manual.outcome(), gs.outcome());
+
+        manual.undo();
+        assert_eq!(manual.phase(), Phase::AutoCompletable);
+        gs.undo();
+        assert_eq!(gs, previous);
+        gs.redo();
+        assert!(gs.is_won());
+    }
+
+    /// A game that is stuck is lost, until the action that got it stuck is taken back
+    #[test]
+    fn ends_when_stuck() {
+        let mut gs = GameEngine::builder().seed(7).end_when_stuck(true).build().unwrap();
+        assert!(!gs.is_stuck());
+        let mut ai = crate::ai::GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        run_game(&mut gs, &mut ai, 1000);
+        assert_eq!(gs.outcome(), Some(Outcome::Lost { reason: LossReason::Stuck }));
+        assert!(!gs.is_stuck());
+        gs.undo();
+        assert!(gs.is_running());
+        gs.redo();
+        assert_eq!(gs.phase(), Phase::Finished(WinLoss::Loss));
+    }
+
+    #[test]
+    fn outcomes() {
+        let mut gs = GameEngine::deal(0);
+        assert_eq!(gs.outcome(), None);
+        let mut ai = crate::ai::GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        let n_actions = run_game(&mut gs, &mut ai, 1000);
+        assert_eq!(gs.outcome(), Some(Outcome::Won { score: gs.score(), moves: n_actions }));
+        assert!(gs.is_won());
+
+        gs.undo();
+        assert_eq!(gs.outcome(), None);
+        gs.act(&Action::Quit).unwrap();
+        assert_eq!(gs.outcome(), Some(Outcome::Lost { reason: LossReason::Quit }));
+        assert!(!gs.is_running() && !gs.is_won());
+    }
+
+    /// Relabeling the suits keeps exactly the same actions legal
+    #[test]
+    fn relabeling_suits_preserves_legality() {
+        let gs = GameEngine::deal(5);
+        for perm in crate::core::SuitPermutation::all() {
+            let relabel = |pile: &Vec<Card>| {
+                pile.iter()
+                    .map(|c| Card { suit: perm.apply(c.suit), ..c.clone() })
+                    .collect::<Vec<_>>()
+            };
+            let relabeled = GameEngine {
+                stock: relabel(&gs.stock),
+                columns: std::array::from_fn(|i| Depot { cards: relabel(&gs.columns[i].cards), ..gs.columns[i] }),
+                foundations: std::array::from_fn(|i| relabel(&gs.foundations[i])),
+                ..gs.clone()
+            };
+            assert_eq!(relabeled.observe(), gs.observe().relabel_suits(&perm));
+            assert_eq!(relabeled.n_legal_actions(), gs.n_legal_actions());
+            for from in Addr::DEPOTS_AND_WASTE {
+                for to in Addr::DEPOTS.iter().chain(Addr::FOUNDATIONS.iter()) {
+                    for n in 1..=13 {
+                        assert_eq!(
+                            relabeled.check_move(&from, to, n).is_ok(),
+                            gs.check_move(&from, to, n).is_ok()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// A hint is what a fresh AI would play, and changes nothing
+    #[test]
+    fn hints() {
+        let mut gs = GameEngine::deal(0);
+        gs.act(&Action::Take).unwrap();
+        let before = gs.clone();
+        let greedy = |view, rules: &RuleSet| -> Box<dyn Ai> {
+            Box::new(crate::ai::GreedyAi::new(view, rules.scoring.policy()))
+        };
+        let hint = gs.hint(greedy);
+        assert_eq!(hint, greedy(gs.observe(), gs.rules()).make_move());
+        assert!(gs.is_legal(&hint));
+        assert_eq!(gs, before);
+    }
+
+    /// A determinized game looks the same as the original game from the outside
+    #[test]
+    fn determinize_is_consistent_with_view() {
+        let mut gs = GameEngine::deal(3);
+        gs.act(&Action::Take).unwrap();
+        let view = gs.observe();
+        let mut rng: StdRng = rand::SeedableRng::seed_from_u64(0);
+        let determinized = GameEngine::determinize(&view, gs.rules(), &mut rng);
+        assert_eq!(determinized.observe(), view);
+    }
+
+    /// The open view shows all of the game, so the game can be set up again from it
+    #[test]
+    fn open_view_shows_everything() {
+        let mut gs = GameEngine::builder().seed(3).rules(RuleSet::THOUGHTFUL).build().unwrap();
+        gs.act(&Action::Take).unwrap();
+        let open = gs.observe_open();
+        assert_eq!(open.view, gs.observe());
+        assert_eq!(open.talon.len(), gs.talon_len());
+        assert_eq!(open.face_down[6].len(), 6);
+        let copy = GameEngine::from_open(&open, gs.rules());
+        assert_eq!(copy.observe_open(), open);
+        assert_eq!(copy.legal_actions(), gs.legal_actions());
+    }
+
     /// Test there wont be underflow in scoring when turning the deck over
     #[test]
     fn score_when_turning_over() {
         let mut gs = GameEngine {
-            talon: vec![],
-            waste: vec![Card {
+            stock: vec![Card {
                 suit: Suit::Spades,
                 value: Value::TWO,
-                faceup: true,
             }],
+            cursor: 1,
+            redeals_left: None,
             columns: [
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
+                Depot::default(),
             ],
             foundations: [vec![], vec![], vec![], vec![]],
             state: State::Running,
             current_score: 0,
+            rules: RuleSet::default(),
+            options: DealOptions::default(),
+            history: History::default(),
         };
         gs.act(&Action::Turnover)
             .map_err(|e| eprintln!("{}", e))
             .expect("This should be fin. No underflows. No funny business.");
         assert_eq!(gs.score(), 0);
     }
+
+    /// A saved game comes back the same, with its rules, its options and its history
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_and_load() {
+        let mut gs = GameEngine::builder()
+            .seed(5)
+            .rules(RuleSet::KLONDIKE_DRAW3)
+            .options(DealOptions { auto_reveal: true, assistance: Assistance::PlayableCards, ..DealOptions::default() })
+            .build()
+            .unwrap();
+        let mut ai = crate::ai::GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        for _ in 0..20 {
+            let action = ai.make_move();
+            ai.update(action.clone(), gs.act(&action).unwrap());
+        }
+        let path = std::env::temp_dir().join(format!("solitaire-save-{}.json", std::process::id()));
+        gs.save_to(&path).unwrap();
+        let mut loaded = GameEngine::load_from(&path).unwrap();
+        assert_eq!(loaded, gs);
+        assert_eq!((loaded.rules(), loaded.options(), loaded.score()), (gs.rules(), gs.options(), gs.score()));
+        assert_eq!(loaded.undo(), gs.undo());
+        assert_eq!((&loaded, loaded.score()), (&gs, gs.score()));
+
+        // A hearts card that turned into a spades card is there twice
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replacen("\"Hearts\"", "\"Spades\"", 1)).unwrap();
+        let err = GameEngine::load_from(&path).unwrap_err();
+        assert_eq!(err.to_string(), "The game does not have a full deck");
+
+        // A cursor past the end of the stock is rejected, not followed
+        let mut json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        json["cursor"] = 100.into();
+        std::fs::write(&path, json.to_string()).unwrap();
+        let err = GameEngine::load_from(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "The waste has more cards than the stock");
+
+        // A history that doesn't add up is rejected, rather than taken back one step at a time
+        let mut json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        for step in json["history"]["undo"].as_array_mut().unwrap() {
+            step["n_turned"] = 30.into();
+        }
+        std::fs::write(&path, json.to_string()).unwrap();
+        let err = GameEngine::load_from(&path).unwrap_err();
+        assert_eq!(err.to_string(), "The history does not lead to the game");
+        let mut json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        json["history"]["undo"][0]["score"] = 100.into();
+        std::fs::write(&path, json.to_string()).unwrap();
+        let err = GameEngine::load_from(&path).unwrap_err();
+        assert_eq!(err.to_string(), "The history does not lead to the game");
+
+        // The clock of a timed game runs on from the time on it, rather than starting over
+        assert_eq!(loaded.duration(), None);
+        let mut timed = GameEngine::builder()
+            .options(DealOptions { timed: true, ..DealOptions::default() })
+            .build()
+            .unwrap();
+        timed.history.clock = Some(Instant::now() - Duration::from_secs(100));
+        timed.save_to(&path).unwrap();
+        let loaded = GameEngine::load_from(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.duration().unwrap() >= Duration::from_secs(100));
+    }
 }
+
+#[cfg(test)]
+mod scenarios;
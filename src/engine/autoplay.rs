@@ -0,0 +1,162 @@
+//! Let an AI play on from a position, one action at a time
+//!
+//! `Autoplay` is an iterator over the positions of a game as an AI plays it, see `Snapshot`. It stops when the game is
+//! over, or earlier at one of the limits it is given: a number of actions, a change of the phase of the game, or a
+//! flag that another thread sets to cancel. The positions can be drawn one by one, or published to a `SharedGame`.
+//!
+//! Each step asks the AI for an action, takes it, and tells the AI the result, and the limits are checked before the
+//! AI is asked. So wherever the iterator stops or is dropped, the game and the AI agree on the position, and the AI
+//! can play on from there later. The one thing that can't be cancelled is the thinking of the AI about its next move.
+
+use super::{show_open_cards, GameEngine, Snapshot};
+use crate::ai::Ai;
+use crate::core::{Action, Phase};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Why autoplay stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    GameOver,
+    /// The number of actions was used up, see `Autoplay::max_actions`
+    Budget,
+    /// The game went into another phase, see `Autoplay::until_phase_change`
+    PhaseChange(Phase),
+    /// The flag of `Autoplay::cancel_on` was set
+    Cancelled,
+    /// The AI suggested an action that isn't legal, and it was not taken
+    IllegalAction(Action),
+}
+
+/// An AI playing a game, see the module documentation
+pub struct Autoplay<'a> {
+    gs: &'a mut GameEngine,
+    ai: &'a mut dyn Ai,
+    max_actions: Option<usize>,
+    phase: Option<Phase>,
+    cancel: Option<&'a AtomicBool>,
+    n_actions: usize,
+    stop: Option<Stop>,
+}
+
+impl<'a> Autoplay<'a> {
+    /// Let an AI that knows the position of the game play on, until the game is over
+    pub fn new(gs: &'a mut GameEngine, ai: &'a mut dyn Ai) -> Self {
+        show_open_cards(gs, ai);
+        Autoplay {
+            gs,
+            ai,
+            max_actions: None,
+            phase: None,
+            cancel: None,
+            n_actions: 0,
+            stop: None,
+        }
+    }
+
+    /// Stop after this many actions
+    pub fn max_actions(mut self, max_actions: usize) -> Self {
+        self.max_actions = Some(max_actions);
+        self
+    }
+
+    /// Stop once the game is no longer in the phase it is in now, e.g. when the opening is over
+    pub fn until_phase_change(mut self) -> Self {
+        self.phase = Some(self.gs.phase());
+        self
+    }
+
+    /// Stop once the flag is set, e.g. by a user interface or a server thread
+    pub fn cancel_on(mut self, flag: &'a AtomicBool) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Why autoplay stopped, or `None` while it goes on
+    pub fn stop(&self) -> Option<&Stop> {
+        self.stop.as_ref()
+    }
+
+    /// The number of actions taken so far
+    pub fn n_actions(&self) -> usize {
+        self.n_actions
+    }
+
+    /// The game in its current position
+    pub fn game(&self) -> &GameEngine {
+        self.gs
+    }
+
+    /// The reason to stop before the next action, if there is one
+    fn check_stop(&self) -> Option<Stop> {
+        if !self.gs.is_running() {
+            Some(Stop::GameOver)
+        } else if self.cancel.is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+            Some(Stop::Cancelled)
+        } else if self.max_actions.is_some_and(|max| self.n_actions >= max) {
+            Some(Stop::Budget)
+        } else {
+            let phase = self.gs.phase();
+            self.phase
+                .filter(|start| *start != phase)
+                .map(|_| Stop::PhaseChange(phase))
+        }
+    }
+}
+
+impl Iterator for Autoplay<'_> {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Snapshot> {
+        if self.stop.is_some() {
+            return None;
+        }
+        self.stop = self.check_stop();
+        if self.stop.is_some() {
+            return None;
+        }
+        let action = self.ai.make_move();
+        let Ok(res) = self.gs.act(&action) else {
+            self.stop = Some(Stop::IllegalAction(action));
+            return None;
+        };
+        self.ai.update(action, res);
+        show_open_cards(self.gs, self.ai);
+        self.n_actions += 1;
+        Some(Snapshot::of(self.gs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::GreedyAi;
+
+    #[test]
+    fn stops_at_the_limits() {
+        let mut gs = GameEngine::deal(0);
+        let mut ai = GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        let mut autoplay = Autoplay::new(&mut gs, &mut ai).max_actions(3);
+        let positions = autoplay.by_ref().collect::<Vec<_>>();
+        assert_eq!(autoplay.stop(), Some(&Stop::Budget));
+        assert_eq!(positions.len(), 3);
+        assert_eq!(&positions[2].game, autoplay.game());
+        assert_eq!(positions[2].n_actions, 3);
+
+        // The AI plays on from where it stopped
+        let mut autoplay = Autoplay::new(&mut gs, &mut ai).until_phase_change();
+        let last = autoplay.by_ref().last().unwrap();
+        assert_eq!(autoplay.stop(), Some(&Stop::PhaseChange(last.game.phase())));
+        assert_ne!(last.game.phase(), Phase::Opening);
+
+        let cancel = AtomicBool::new(true);
+        let mut autoplay = Autoplay::new(&mut gs, &mut ai).cancel_on(&cancel);
+        assert!(autoplay.next().is_none());
+        assert_eq!(autoplay.stop(), Some(&Stop::Cancelled));
+        cancel.store(false, Ordering::Relaxed);
+
+        let mut autoplay = Autoplay::new(&mut gs, &mut ai).cancel_on(&cancel);
+        assert!(autoplay.by_ref().count() > 0);
+        assert_eq!(autoplay.stop(), Some(&Stop::GameOver));
+        assert!(!gs.is_running());
+    }
+}
@@ -0,0 +1,265 @@
+//! Scenario tests: rule edge cases written as data files instead of hand-built games
+//!
+//! Each file in `fixtures/scenarios` sets up a position, takes some actions, and says what should come out of them.
+//! The sections of a file are
+//!
+//! - `[board]`: the position. `rules` is a rule preset, with all of its passes through the talon left, `score` the
+//!   score so far, and `auto-reveal`, `auto-finish` and `end-when-stuck` the options of the deal. `talon` lists the
+//!   cards in the order they are taken, `waste` lists the cards from the bottom up, and `foundations` the four
+//!   foundation tops, with `-` for an empty foundation. The depots `1` to `7` list their cards from the bottom up,
+//!   with the face down cards before a `|`. Piles that are left out are empty, and the position need not hold all 52
+//!   cards.
+//! - `[actions]`: one action per line, as written by `Debug`. An action followed by `!` must fail, and the text after
+//!   the `!`, if any, must be part of the error.
+//! - `[expect]`: `score`, `result` (`running`, `won` or `lost`), and any of the piles of the board, which must be
+//!   exactly as given.
+//!
+//! Cards are written as by `Display`, e.g. `H04` for the four of hearts, and `#` starts a comment line.
+//!
+//! ```text
+//! # A king may move to an empty depot
+//! [board]
+//! waste: D13
+//! [actions]
+//! Move(Waste, Depot1, 1)
+//! [expect]
+//! score: 5
+//! 1: D13
+//! ```
+use super::*;
+use crate::core::parse_action;
+
+/// The keys of the piles, as they are written in a scenario
+const PILES: [&str; 10] = [
+    "talon",
+    "waste",
+    "foundations",
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "7",
+];
+
+fn parse_card(s: &str) -> Result<Card, String> {
+    let mut chars = s.chars();
+    let suit = match chars.next() {
+        Some('H') => Suit::Hearts,
+        Some('D') => Suit::Diamonds,
+        Some('C') => Suit::Clubs,
+        Some('S') => Suit::Spades,
+        _ => return Err(format!("Not a card: {s}")),
+    };
+    let value = chars
+        .as_str()
+        .parse::<u8>()
+        .ok()
+        .and_then(|v| Value::try_from(v).ok())
+        .ok_or_else(|| format!("Not a card: {s}"))?;
+    Ok(Card { suit, value })
+}
+
+fn parse_cards(s: &str) -> Result<Vec<Card>, String> {
+    s.split_whitespace().map(parse_card).collect()
+}
+
+/// A pile of the game, written as in a scenario
+fn describe(gs: &GameEngine, pile: &str) -> String {
+    let cards = |cards: &[Card]| cards.iter().map(Card::to_string).collect::<Vec<_>>();
+    let words = match pile {
+        "talon" => cards(&gs.stock[gs.cursor..]),
+        "waste" => cards(gs.waste()),
+        "foundations" => gs
+            .foundations
+            .iter()
+            .map(|f| f.last().map_or("-".to_string(), Card::to_string))
+            .collect(),
+        _ => {
+            let depot = &gs.columns[pile.parse::<usize>().expect("A depot") - 1];
+            let mut words = cards(&depot.cards[..depot.first_face_up]);
+            if !words.is_empty() {
+                words.push("|".to_string());
+            }
+            words.extend(cards(depot.face_up()));
+            words
+        }
+    };
+    words.join(" ")
+}
+
+/// Set up the position of the `[board]` section
+fn parse_board(lines: &[(usize, &str, &str)]) -> Result<GameEngine, String> {
+    let mut gs = GameEngine {
+        stock: vec![],
+        cursor: 0,
+        redeals_left: None,
+        columns: Default::default(),
+        foundations: Default::default(),
+        state: State::Running,
+        current_score: 0,
+        rules: RuleSet::default(),
+        options: DealOptions::default(),
+        history: History::default(),
+    };
+    let mut talon = vec![];
+    for &(line, key, value) in lines {
+        let at_line = |e: String| format!("Line {line}: {e}");
+        match key {
+            "rules" => {
+                gs.rules = RuleSet::preset(value).map_err(|e| at_line(e.to_string()))?;
+                gs.redeals_left = gs.rules.redeals();
+            }
+            "auto-reveal" => gs.options.auto_reveal = value == "true",
+            "auto-finish" => gs.options.auto_finish = value == "true",
+            "end-when-stuck" => gs.options.end_when_stuck = value == "true",
+            "score" => {
+                gs.current_score = value
+                    .parse()
+                    .map_err(|_| at_line(format!("Not a score: {value}")))?
+            }
+            "talon" => talon = parse_cards(value).map_err(at_line)?,
+            "waste" => gs.stock = parse_cards(value).map_err(at_line)?,
+            "foundations" => {
+                let tops = value.split_whitespace().collect::<Vec<_>>();
+                if tops.len() != 4 {
+                    return Err(at_line("Expected four foundation tops".to_string()));
+                }
+                for (foundation, top) in gs.foundations.iter_mut().zip(tops) {
+                    if top != "-" {
+                        let top = parse_card(top).map_err(at_line)?;
+                        *foundation = (1..=top.numeric_value())
+                            .map(|v| Card {
+                                suit: top.suit,
+                                value: Value::try_from(v).expect("Known to be in range"),
+                            })
+                            .collect();
+                    }
+                }
+            }
+            _ if PILES[3..].contains(&key) => {
+                let (face_down, face_up) = value.split_once('|').unwrap_or(("", value));
+                let mut cards = parse_cards(face_down).map_err(at_line)?;
+                let first_face_up = cards.len();
+                cards.extend(parse_cards(face_up).map_err(at_line)?);
+                gs.columns[key.parse::<usize>().expect("A depot") - 1] = Depot {
+                    cards,
+                    first_face_up,
+                };
+            }
+            _ => return Err(at_line(format!("Unknown key {key}"))),
+        }
+    }
+    gs.cursor = gs.stock.len();
+    gs.stock.extend(talon);
+    Ok(gs)
+}
+
+/// Run a scenario, and describe the first way it doesn't go as expected
+fn run(text: &str) -> Result<(), String> {
+    let mut sections: Vec<(&str, Vec<(usize, &str)>)> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            Some(name) => sections.push((name, vec![])),
+            None => sections
+                .last_mut()
+                .ok_or_else(|| format!("Line {}: Expected a section", i + 1))?
+                .1
+                .push((i + 1, line)),
+        }
+    }
+    let section = |name: &str| {
+        sections
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or(&[][..], |(_, lines)| &lines[..])
+    };
+    let key_values = |name: &str| {
+        section(name)
+            .iter()
+            .map(|&(line, text)| match text.split_once(':') {
+                Some((key, value)) => Ok((line, key.trim(), value.trim())),
+                None => Err(format!("Line {line}: Expected a key and a value")),
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let mut gs = parse_board(&key_values("board")?)?;
+    for &(line, text) in section("actions") {
+        let (action, error) = match text.split_once('!') {
+            Some((action, error)) => (action, Some(error.trim())),
+            None => (text, None),
+        };
+        let action =
+            parse_action(action).ok_or_else(|| format!("Line {line}: Expected an action"))?;
+        match (gs.act(&action), error) {
+            (Ok(_), None) => {}
+            (Err(e), Some(expected)) if e.to_string().contains(expected) => {}
+            (Ok(_), Some(_)) => return Err(format!("Line {line}: {action:?} should fail")),
+            (Err(e), _) => return Err(format!("Line {line}: {action:?} failed with: {e}")),
+        }
+    }
+    for (line, key, expected) in key_values("expect")? {
+        let actual = match key {
+            "score" => gs.score().to_string(),
+            "result" => match gs.outcome() {
+                None => "running",
+                Some(Outcome::Won { .. }) => "won",
+                Some(Outcome::Lost { .. }) => "lost",
+            }
+            .to_string(),
+            _ if PILES.contains(&key) => describe(&gs, key),
+            _ => return Err(format!("Line {line}: Unknown key {key}")),
+        };
+        let expected = expected.split_whitespace().collect::<Vec<_>>().join(" ");
+        if actual != expected {
+            return Err(format!(
+                "Line {line}: Expected {key} {expected}, but it is {actual}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn scenarios() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/scenarios");
+    let mut paths = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty());
+    let failures = paths
+        .iter()
+        .filter_map(|path| {
+            let text = std::fs::read_to_string(path).unwrap();
+            run(&text).err().map(|e| format!("{}: {e}", path.display()))
+        })
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// A scenario that doesn't go as expected is caught, so that the files are really checked
+#[test]
+fn wrong_expectations_fail() {
+    let scenario = "[board]\nwaste: D13\n[actions]\nMove(Waste, Depot1, 1)\n[expect]\n";
+    assert_eq!(run(&format!("{scenario}1: D13\nscore: 5")), Ok(()));
+    assert_eq!(
+        run(&format!("{scenario}1: D12")),
+        Err("Line 6: Expected 1 D12, but it is D13".to_string())
+    );
+    assert_eq!(
+        run("[board]\nwaste: D12\n[actions]\nMove(Waste, Depot1, 1)\n"),
+        Err("Line 4: Move(Waste, Depot1, 1) failed with: Unspecified move error".to_string())
+    );
+    assert_eq!(
+        run("[board]\nwaste: D13\n[actions]\nMove(Waste, Depot1, 1) !\n"),
+        Err("Line 4: Move(Waste, Depot1, 1) should fail".to_string())
+    );
+}
@@ -0,0 +1,113 @@
+//! Read access to a running game from other threads
+//!
+//! The thread that plays the game publishes a snapshot of the position after each action, and any number of other
+//! threads, e.g. a web server or a user interface, load the latest snapshot to show it. A snapshot is never changed
+//! once it is published, so readers share it behind an `Arc` without copying it, and a reader that holds on to one
+//! only keeps the memory alive, never the lock.
+//!
+//! The lock around the current snapshot is only held to swap or clone the `Arc`. The copy of the game is made
+//! before that, so the playing thread doesn't wait for readers, and readers don't wait for the AI to think.
+
+use super::GameEngine;
+use crate::core::Action;
+use std::sync::{Arc, RwLock};
+
+/// A position of a game, as it was published
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// The game without its history, so it can't be undone
+    pub game: GameEngine,
+    /// The actions taken to get to the position, which grows with each new snapshot of a game
+    ///
+    /// Like `last_action`, it leaves out the moves that the engine made by itself, see `DealOptions::auto_finish`.
+    pub n_actions: usize,
+    pub last_action: Option<Action>,
+}
+
+/// The latest snapshot of a game, to share between threads
+///
+/// Clones share the same snapshot, so one can be given to each thread. There is no snapshot until the first one is
+/// published.
+#[derive(Debug, Clone, Default)]
+pub struct SharedGame {
+    current: Arc<RwLock<Option<Arc<Snapshot>>>>,
+}
+
+impl Snapshot {
+    pub(super) fn of(gs: &GameEngine) -> Self {
+        let mut actions = gs.history.undo.iter().filter(|step| !step.auto);
+        Snapshot {
+            game: gs.without_history(),
+            n_actions: actions.clone().count(),
+            last_action: actions.next_back().map(|step| step.action.clone()),
+        }
+    }
+}
+
+impl SharedGame {
+    /// Replace the snapshot with the current position of a game, e.g. after an action or when a new game starts
+    pub fn publish(&self, gs: &GameEngine) {
+        let snapshot = Arc::new(Snapshot::of(gs));
+        let mut current = self
+            .current
+            .write()
+            .expect("No thread panics while holding the lock");
+        let old = current.replace(snapshot);
+        drop(current);
+        // The last reference to the old snapshot may be this one, and then it is freed outside the lock
+        drop(old);
+    }
+
+    /// The latest snapshot, if any
+    pub fn load(&self) -> Option<Arc<Snapshot>> {
+        self.current
+            .read()
+            .expect("No thread panics while holding the lock")
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{Ai, GreedyAi};
+
+    #[test]
+    fn readers_see_the_game_as_it_goes() {
+        let mut gs = GameEngine::deal(3);
+        let shared = SharedGame::default();
+        assert!(shared.load().is_none());
+        shared.publish(&gs);
+        let first = shared.load().unwrap();
+        let reader = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                let mut n_actions = 0;
+                loop {
+                    let snapshot = shared.load().unwrap();
+                    assert!(snapshot.n_actions >= n_actions);
+                    n_actions = snapshot.n_actions;
+                    if !snapshot.game.is_running() {
+                        return n_actions;
+                    }
+                }
+            })
+        };
+        let mut ai = GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+        while gs.is_running() {
+            let action = ai.make_move();
+            ai.update(action.clone(), gs.act(&action).unwrap());
+            shared.publish(&gs);
+        }
+        let snapshot = shared.load().unwrap();
+        assert_eq!(reader.join().unwrap(), snapshot.n_actions);
+        assert_eq!(snapshot.game, gs);
+        assert_eq!(
+            snapshot.last_action,
+            gs.history.undo.last().map(|s| s.action.clone())
+        );
+        // An old snapshot stays as it was
+        assert_eq!((first.n_actions, first.last_action.as_ref()), (0, None));
+        assert_eq!(first.game, GameEngine::deal(3));
+    }
+}
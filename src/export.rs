@@ -0,0 +1,148 @@
+//! Export of the results of a batch, one record per game
+//!
+//! With `--format csv`, `--format json` or `--format jsonl`, the batch writes the games to the standard output in a
+//! form that can be read directly into e.g. pandas or R, instead of the report. The fields are those of `GameRecord`,
+//! with the flag `won` next to the way the game ended.
+
+use crate::stats::{GameEnd, GameRecord};
+use std::io::Write;
+
+/// The formats the games can be exported in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Comma separated values, with a header line
+    Csv,
+    /// A JSON array of objects
+    Json,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(format!("Unknown format {s}, use csv, json or jsonl")),
+        }
+    }
+}
+
+/// The columns of the CSV export, in the order of the keys of `GameRecord::to_json`
+const CSV_HEADER: &str = "ai,deal,score,won,result,moves,seconds,peak_nodes,wasted_moves";
+
+/// Writes game records in one of the export formats
+pub struct Export<W: Write> {
+    out: W,
+    format: Format,
+    n_games: usize,
+}
+
+impl<W: Write> Export<W> {
+    /// Start the export, with the header of a CSV file
+    pub fn new(mut out: W, format: Format) -> Result<Self, std::io::Error> {
+        if format == Format::Csv {
+            writeln!(out, "{CSV_HEADER}")?;
+        }
+        Ok(Export {
+            out,
+            format,
+            n_games: 0,
+        })
+    }
+
+    /// Write the record of one game
+    pub fn add(&mut self, record: &GameRecord) -> Result<(), std::io::Error> {
+        match self.format {
+            Format::Csv => writeln!(
+                self.out,
+                "{},{},{},{},{:?},{},{:.3},{},{}",
+                csv_field(&record.ai),
+                csv_field(&record.deal),
+                record.score,
+                record.end == GameEnd::Won,
+                record.end,
+                record.moves,
+                record.seconds,
+                record.peak_nodes,
+                record.wasted_moves
+            )?,
+            Format::Json => {
+                let separator = if self.n_games == 0 { "[" } else { "," };
+                writeln!(self.out, "{separator}{}", record.to_json())?;
+            }
+            Format::Jsonl => writeln!(self.out, "{}", record.to_json())?,
+        }
+        self.n_games += 1;
+        Ok(())
+    }
+
+    /// End the export, closing the array of a JSON file
+    pub fn finish(mut self) -> Result<(), std::io::Error> {
+        if self.format == Format::Json {
+            writeln!(self.out, "{}", if self.n_games == 0 { "[]" } else { "]" })?;
+        }
+        self.out.flush()
+    }
+}
+
+/// Quote a CSV field if it holds a comma, a quote or a line break
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(format: Format, records: &[GameRecord]) -> String {
+        let mut out = vec![];
+        let mut export = Export::new(&mut out, format).unwrap();
+        for record in records {
+            export.add(record).unwrap();
+        }
+        export.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn one_record_per_game() {
+        let won = GameRecord {
+            ai: "GreedyAi".to_string(),
+            deal: "klondike-classic:0".to_string(),
+            score: 600,
+            end: GameEnd::Won,
+            moves: 278,
+            seconds: 0.0114,
+            peak_nodes: 278,
+            wasted_moves: 4,
+        };
+        let lost = GameRecord {
+            ai: "fast, greedy".to_string(),
+            end: GameEnd::Livelock,
+            ..won.clone()
+        };
+        assert_eq!(
+            export(Format::Csv, &[won.clone(), lost.clone()]),
+            "ai,deal,score,won,result,moves,seconds,peak_nodes,wasted_moves\n\
+             GreedyAi,klondike-classic:0,600,true,Won,278,0.011,278,4\n\
+             \"fast, greedy\",klondike-classic:0,600,false,Livelock,278,0.011,278,4\n"
+        );
+        assert_eq!(
+            export(Format::Jsonl, &[won.clone(), lost.clone()]),
+            format!("{}\n{}\n", won.to_json(), lost.to_json())
+        );
+        assert_eq!(
+            export(Format::Json, &[won.clone(), lost.clone()]),
+            format!("[{}\n,{}\n]\n", won.to_json(), lost.to_json())
+        );
+        assert_eq!(export(Format::Json, &[]), "[]\n");
+    }
+}
@@ -0,0 +1,32 @@
+//! Klondike solitaire: the game engine, the rules, and AI players
+//!
+//! The engine deals and runs a game, and an AI sees it through a `SolitaireObserver`, which holds what a player can
+//! see. The types for typical use are in the `prelude`:
+//!
+//! ```
+//! use solitaire::prelude::*;
+//!
+//! let mut gs = GameEngine::deal(42);
+//! let mut ai = solitaire::ai::GreedyAi::new(gs.observe(), gs.rules().scoring.policy());
+//! run_game(&mut gs, &mut ai, 1000);
+//! assert!(!gs.is_running());
+//! ```
+//!
+//! The command line tool in `main.rs` is built on this library. The parts of it that are not tied to the command
+//! line are here too: the statistics of a batch, replay files, post-game reports and the variants that are not
+//! played on the Klondike tableau.
+
+pub mod ai;
+#[cfg(test)]
+mod alloc_count;
+pub mod analysis;
+pub mod core;
+pub mod engine;
+pub mod json;
+pub mod prelude;
+pub mod replay;
+pub mod report;
+pub mod rules;
+pub mod solver;
+pub mod stats;
+pub mod variants;
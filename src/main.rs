@@ -1,53 +1,1303 @@
-use itertools::Itertools;
+use solitaire::{ai, analysis, core, engine, json, replay, report, rules, solver, stats, variants};
 
-mod ai;
-mod engine;
-mod core;
+mod advisor;
+mod cast;
+mod config;
+mod console;
+mod export;
+mod openings;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod race;
+mod records;
+mod stream;
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
+mod tune;
+mod verdicts;
+mod watchdog;
 
 /// The main function.
-fn main() -> Result<(), std::io::Error> {
-    let n_games_to_play = 10;
+///
+/// Usage:
+///  - `solitaire` plays a batch of games with all AIs and reports statistics
+///  - `solitaire watch [AI] [DEAL]` shows a single game move by move
+///  - `solitaire cast [AI] [DEAL] [FILE]` records a single game as an asciinema cast
+///  - `solitaire encode [AI] [DEAL]` prints the raw and the canonical encoding of every position in a single game
+///  - `solitaire features [AI] [DEAL]` prints the features of every position in a single game as CSV.
+///    With the option `--temperature T`, the AI samples its actions instead, see `ai::StochasticAi`
+///  - `solitaire tune [GENERATIONS] [POPULATION] [N_DEALS]` tunes the weights of the evaluation function
+///  - `solitaire best [DEAL...]` shows the personal bests on the deals, or on all deals played so far
+///  - `solitaire play [DEAL]` lets you play a single game in the terminal (with the `tui` feature), see `tui`.
+///    With the flag `--lines`, it reads one command per line instead, e.g. from a script, see `console`.
+///    With the option `--advisor AI`, the AI reviews your actions, tells your accuracy and gives hints, see `advisor`.
+///    With the option `--report FILE`, a report on the game is written to FILE, see `report`.
+///    With the option `--assist LEVEL`, the terminal marks the piles with a card that can be played (`playable`), also
+///    lists the moves after which the solver still wins (`solver`), or also refuses the moves after which the game can't
+///    be won (`no-losing`), see `engine::Assistance`. The level is kept with the game and its personal best
+///    At the end, your moves are compared to par, the number of moves of the winning line the solver finds on the deal,
+///    which the option `--verdicts FILE` keeps, see `verdicts`
+///  - `solitaire replay FILE` plays the moves of a replay file again and shows how the game ended.
+///    With the flag `--check-ai`, the AI that played the game plays it again, see `replay`
+///  - `solitaire report FILE` prints a report on the game in a replay file: its turning points, the foundation moves
+///    that were missed, and when it was lost, see `report`. The report is Markdown, or HTML with the flag `--html`.
+///    It takes the option `--rollouts N` for the rollouts that estimate the chance to win in each position, and the
+///    option `--max-nodes N` to limit the positions the solver searches for each position of the game, and
+///    `--max-solves N` to limit the positions of the game it looks at, going back from the end
+///  - `solitaire openings DIR...` plays the games in the replay files in the directories again, and prints how they
+///    went by the depot where they revealed the first card, and by their first action, see `openings`
+///  - `solitaire bench [N_DEALS]` runs the standard benchmark, see `bench`
+///  - `solitaire solve [DEAL...]` tells whether the deals can be won, with all cards known, and how, see `solitaire::solver`.
+///    It takes the option `--max-nodes N` to limit the positions searched on each deal, and the option
+///    `--verdicts FILE` to keep the deals that were solved in FILE and not search them again, see `verdicts`.
+///    The option `--order NAME` searches the moves in another order, `foundations` (the default), `reveals` or
+///    `unordered`, see `solitaire::solver::MoveOrder`, and the flag `--compare-orders` solves each deal in every order
+///    and tells how many positions each took. The option `--algorithm pn` searches with proof-number search instead of
+///    IDA* (`ida`), see `solitaire::solver::Algorithm`
+///  - `solitaire race [AI] [AI]` races two AIs, `greedy` and `phased` by default, on the deals of the batch, see `race`
+///  - `solitaire variant [NAME] [N_DEALS]` plays the first deals of a variant that is not played on the Klondike tableau,
+///    `golf`, `tripeaks`, `canfield`, `clock` or `accordion`, or of each of them with `all` (the default).
+///    `solitaire variant DEAL...` plays the deals with codes such as `golf.1:golf:42`
+///
+/// An AI is one of `simple`, `greedy`, `phased`, `linear`, `mcts`, `ismcts` and `expectimax`, or a profile in the config file.
+/// A DEAL is either a seed or a deal code like `softie:42` or `klondike.1:softie:42`, see `rules::DealCode`.
+/// All commands take the option `--rules NAME` to select a rule preset, the option `--config FILE` to read another
+/// config file than `solitaire.toml`, and the flag `--auto-reveal` to reveal cards in the depots without an explicit action.
+/// With the flag `--auto-finish`, the cards are played to the foundations as soon as that wins the game, so that neither
+/// the AIs nor you have to make the obvious moves at the end.
+/// With the flag `--end-when-stuck`, a game is lost as soon as nothing is left to do that makes progress, so that the
+/// AIs need not find out for themselves, see `engine::GameEngine::is_stuck`.
+/// With the flag `--timed`, points are taken off as the clock runs and a win earns a bonus for speed, and the batch
+/// reports the time-adjusted scores, see `engine::GameEngine::timed_score`.
+/// With the option `--records FILE`, the batch, `watch`, `cast` and `play` keep the best game on each deal in FILE,
+/// and write the moves of each best game to a `best-*.txt` file next to it. `best` reads the same file.
+/// The batch of games takes the option `--trace FILE` to write the decisions of the AIs as JSON Lines,
+/// the option `--deals FILE` to play the deals in a file with one seed or deal code per line, instead of the first ten seeds,
+/// the option `--max-nodes N` to limit the memory of each AI (an AI that reaches the limit gives up),
+/// and the option `--ai AI,AI,...` to play with other AIs than `simple`, `greedy`, `phased` and `linear`.
+/// With the option `--openings DIR`, the greedy AI breaks ties in the opening by how the games in the replay files in DIR
+/// went, see `openings`.
+/// Ctrl-C stops the batch, and the games completed so far are summarized (with the `interrupt` feature, on by default).
+/// A game where the AI is stuck in a loop is aborted as a loss. With the option `--livelock-dir DIR`, its moves are
+/// written to a `livelock-*.txt` file in DIR.
+/// A game where the AI suggests an illegal action is also counted as a loss.
+/// With the flag `--porcelain`, the batch prints nothing but a single line of JSON with the summary of each AI.
+/// With the option `--format csv`, `--format json` or `--format jsonl`, the batch prints nothing but one record per game
+/// in that format, see `export`.
+/// With the option `--stream ADDR`, each finished game is sent as a line of JSON to `HOST:PORT` or `unix:PATH`.
+/// With the option `--serve ADDR`, the batch serves a dashboard of the games on e.g. `127.0.0.1:8080`, where the game in progress
+/// can be watched live, move by move and as a board (with the `server` feature).
+/// With the option `--sqlite FILE`, the games and their moves are added to an SQLite database (with the `sqlite` feature).
+/// With the option `--replays DIR`, the deal and the moves of every game are written to a replay file in DIR, named
+/// after the AI profile and the deal, which `solitaire replay` plays again.
+/// With the flag `--agreement`, the solver looks at the games on every deal it finds a way to win, and the summary of each
+/// AI tells how many of its moves kept the game winnable, see `solitaire::solver::Solver::agreement`. This is slow, and the
+/// option `--verdicts FILE` keeps the deals that were solved in FILE, as for `solve`.
+/// With the flag `--verify-observer`, a view that is updated move by move like the ones of the AIs is compared to
+/// a fresh view of the game after each move, and both are printed with the move when they differ.
+///
+/// Exit codes:
+///  - 0: all games were played
+///  - 1: an error, e.g. in the arguments or when reading or writing a file
+///  - 2: an AI suggested an illegal action in some game
+///  - 130: the batch was interrupted with Ctrl-C
+fn main() -> Result<std::process::ExitCode, std::io::Error> {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let preset = take_option(&mut args, "--rules")?.unwrap_or(rules::RuleSet::PRESETS[0].0.to_string());
+    rules::RuleSet::preset(&preset).map_err(|e| invalid_input(e.to_string()))?;
+    let options = engine::DealOptions {
+        auto_reveal: take_flag(&mut args, "--auto-reveal"),
+        auto_finish: take_flag(&mut args, "--auto-finish"),
+        end_when_stuck: take_flag(&mut args, "--end-when-stuck"),
+        timed: take_flag(&mut args, "--timed"),
+        assistance: engine::Assistance::None,
+    };
+    let config = match take_option(&mut args, "--config")? {
+        Some(path) => read_config(&path)?,
+        None if std::path::Path::new(config::DEFAULT_PATH).exists() => read_config(config::DEFAULT_PATH)?,
+        None => config::Config::default(),
+    };
+    let profiles = take_option(&mut args, "--ai")?
+        .unwrap_or(ai::profile::DEFAULT.join(","))
+        .split(',')
+        .map(|name| config.ai_profile(name).map_err(|e| invalid_input(e.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let profiles = match take_option(&mut args, "--openings")? {
+        Some(dir) => {
+            let prior = read_openings(&[dir])?.prior();
+            profiles.into_iter().map(|profile| profile.with_opening_prior(prior.clone())).collect()
+        }
+        None => profiles,
+    };
+    let records_path = take_option(&mut args, "--records")?;
+    let batch = BatchOptions {
+        trace_path: take_option(&mut args, "--trace")?,
+        porcelain: take_flag(&mut args, "--porcelain"),
+        format: take_option(&mut args, "--format")?
+            .map(|s| s.parse::<export::Format>().map_err(invalid_input))
+            .transpose()?,
+        sqlite_path: take_option(&mut args, "--sqlite")?,
+        stream_addr: take_option(&mut args, "--stream")?,
+        serve_addr: take_option(&mut args, "--serve")?,
+        verify_observer: take_flag(&mut args, "--verify-observer"),
+        agreement: take_flag(&mut args, "--agreement"),
+        verdicts_path: take_option(&mut args, "--verdicts")?,
+        replay_dir: take_option(&mut args, "--replays")?,
+        livelock_dir: take_option(&mut args, "--livelock-dir")?,
+        node_limit: take_option(&mut args, "--max-nodes")?
+            .map(|s| s.parse::<usize>().map_err(|_| invalid_input(format!("Not a number: {s}"))))
+            .transpose()?,
+    };
+    let deals = match take_option(&mut args, "--deals")? {
+        Some(path) => rules::DealCode::parse_list(&std::fs::read_to_string(&path)?, &preset)
+            .map_err(|e| invalid_input(format!("{path}: {e}")))?,
+        None => (0..10).map(|seed| rules::DealCode::new(&preset, seed).expect("Checked above")).collect(),
+    };
+    let command = match args.first().map(String::as_str) {
+        Some("watch") => watch(&args[1..], &preset, &options, &config, records_path.as_deref()),
+        Some("cast") => cast(&args[1..], &preset, &options, &config, records_path.as_deref()),
+        Some("encode") => encode(&args[1..], &preset, &options, &config),
+        Some("features") => features(&args[1..], &preset, &options, &config),
+        Some("tune") => tune(&args[1..], &preset, &options),
+        Some("best") => best(&args[1..], &preset, records_path.as_deref()),
+        Some("variant") => variant(&args[1..]),
+        Some("play") => play(&args[1..], &preset, &options, &config, records_path.as_deref(), &batch),
+        Some("replay") => replay(&args[1..], &config),
+        Some("report") => report(&args[1..], batch.node_limit),
+        Some("openings") => read_openings(&args[1..]).map(|openings| print!("{}", openings.to_text())),
+        Some("bench") => bench(&args[1..]),
+        Some("solve") => solve(&args[1..], &preset, &options, batch.node_limit, batch.verdicts_path.as_deref()),
+        Some("race") => race(&args[1..], &deals, &options, &config, batch.node_limit),
+        _ => return play_many(&preset, &deals, &profiles, &options, &batch, records_path.as_deref()),
+    };
+    command.map(|()| std::process::ExitCode::SUCCESS)
+}
+
+/// The exit code when an AI suggested an illegal action, see `main`
+const EXIT_AI_ERRORS: u8 = 2;
+/// The exit code when a batch was interrupted, see `main`
+const EXIT_INTERRUPTED: u8 = 130;
+
+/// Report a game in a livelock, and write its deal and moves to a file in `dir`, if given, so that it can be inspected
+/// and replayed
+fn dump_livelock(
+    dir: Option<&str>,
+    label: &str,
+    profile: &str,
+    gs: &engine::GameEngine,
+    code: &rules::DealCode,
+    actions: &[core::Action],
+    rng_checkpoints: &[u64],
+) -> Result<(), std::io::Error> {
+    let Some(dir) = dir else {
+        eprintln!("Livelock of {label} on {code}");
+        return Ok(());
+    };
+    let path = std::path::Path::new(dir).join(format!("livelock-{profile}-{}.txt", code.to_string().replace(':', "-")));
+    let path = path.to_string_lossy();
+    let comment = format!("{label} was stuck in a loop after {} moves", actions.len());
+    write_moves(&path, &comment, gs, code, actions, Some((profile, rng_checkpoints)))?;
+    eprintln!("Livelock of {label} on {code}, wrote the moves to {path}");
+    Ok(())
+}
+
+/// Write the moves of a game to a replay file, see `replay`
+///
+/// A game played by an AI comes with the name of its profile and the checkpoints of its random number generator.
+fn write_moves(
+    path: &str,
+    comment: &str,
+    gs: &engine::GameEngine,
+    code: &rules::DealCode,
+    actions: &[core::Action],
+    ai: Option<(&str, &[u64])>,
+) -> Result<(), std::io::Error> {
+    let replay = replay::Replay {
+        comment: comment.to_string(),
+        code: code.clone(),
+        options: gs.options().clone(),
+        actions: actions.to_vec(),
+        ai: ai.map(|(profile, _)| profile.to_string()),
+        rng_checkpoints: ai.map(|(_, checkpoints)| checkpoints.to_vec()).unwrap_or_default(),
+    };
+    std::fs::write(path, replay.to_text())
+}
+
+/// Read the personal bests, or start from scratch if there is no database yet
+fn read_records(path: &str) -> Result<records::Records, std::io::Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => records::Records::parse(&text).map_err(|e| invalid_input(format!("{path}: {e}"))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(records::Records::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Offer a finished game to the personal bests in the database, if one was given with `--records`
+fn keep_record(
+    records_path: Option<&str>,
+    player: &str,
+    code: &rules::DealCode,
+    gs: &engine::GameEngine,
+    actions: &[core::Action],
+    ai: Option<(&str, &[u64])>,
+) -> Result<(), std::io::Error> {
+    let Some(records_path) = records_path else {
+        return Ok(());
+    };
+    let mut records = read_records(records_path)?;
+    if offer_record(&mut records, records_path, player, code, gs, actions, ai)? {
+        println!("New best on {code}");
+    }
+    std::fs::write(records_path, records.to_text())
+}
+
+/// Offer a finished game to the personal bests. The moves of a new best are written next to the database.
+/// Returns true if it was a new best.
+fn offer_record(
+    records: &mut records::Records,
+    records_path: &str,
+    player: &str,
+    code: &rules::DealCode,
+    gs: &engine::GameEngine,
+    actions: &[core::Action],
+    ai: Option<(&str, &[u64])>,
+) -> Result<bool, std::io::Error> {
+    let file_name = format!("best-{}.txt", code.to_string().replace(':', "-"));
+    let replay = std::path::Path::new(records_path).with_file_name(file_name).to_string_lossy().to_string();
+    let record = records::Record {
+        score: gs.score(),
+        moves: actions.len(),
+        won: gs.is_won(),
+        player: player.to_string(),
+        replay: replay.clone(),
+    };
+    let is_best = records.offer(&code.to_string(), record);
+    if is_best {
+        write_moves(&replay, &format!("{player} scored {} in {} moves", gs.score(), actions.len()), gs, code, actions, ai)?;
+    }
+    Ok(is_best)
+}
+
+/// Read the verdicts of the solver, or start from scratch if there is no database yet
+fn read_verdicts(path: &str) -> Result<verdicts::Verdicts, std::io::Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => verdicts::Verdicts::parse(&text).map_err(|e| invalid_input(format!("{path}: {e}"))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(verdicts::Verdicts::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// What the solver finds out about a deal, looked up in the verdicts first, and kept there, see `verdicts`
+fn solve_deal(
+    solver: &solver::Solver,
+    code: &rules::DealCode,
+    gs: &engine::GameEngine,
+    verdicts: Option<&mut verdicts::Verdicts>,
+) -> solver::Solution {
+    // The assistance of the player doesn't change the moves of the game
+    let plain = engine::DealOptions { assistance: gs.options().assistance, ..engine::DealOptions::default() };
+    let Some(verdicts) = verdicts.filter(|_| *gs.options() == plain) else {
+        return solver.solve(gs);
+    };
+    let code = code.to_string();
+    if let Some(solution) = verdicts.get(&code) {
+        return solution.clone();
+    }
+    let solution = solver.solve(gs);
+    verdicts.insert(&code, &solution);
+    solution
+}
+
+fn read_config(path: &str) -> Result<config::Config, std::io::Error> {
+    config::Config::parse(&std::fs::read_to_string(path)?).map_err(|e| invalid_input(format!("{path}: {e}")))
+}
+
+fn invalid_input(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, msg)
+}
+
+/// The items written one after another, with a separator between them
+fn join<T: std::fmt::Display>(items: impl IntoIterator<Item = T>, separator: &str) -> String {
+    items.into_iter().map(|item| item.to_string()).collect::<Vec<_>>().join(separator)
+}
+
+/// Remove `name VALUE` from the arguments, and return the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, std::io::Error> {
+    match args.iter().position(|a| a == name) {
+        Some(i) if i + 1 < args.len() => {
+            let value = args.remove(i + 1);
+            args.remove(i);
+            Ok(Some(value))
+        }
+        Some(_) => Err(invalid_input(format!("Missing value for {name}"))),
+        None => Ok(None),
+    }
+}
+
+/// Remove the flag `name` from the arguments, and return whether it was there
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let n_args = args.len();
+    args.retain(|a| a != name);
+    args.len() != n_args
+}
+
+/// Deal the game with the given deal code
+fn deal(code: &rules::DealCode, options: &engine::DealOptions) -> Result<engine::GameEngine, std::io::Error> {
+    let rules = code.rules().map_err(|e| invalid_input(format!("Cannot deal {code}: {e}")))?;
+    engine::GameEngine::builder()
+        .seed(code.seed)
+        .rules(rules)
+        .options(options.clone())
+        .build()
+        .map_err(|e| invalid_input(format!("Cannot deal {code}: {e}")))
+}
+
+/// Parse the optional `[AI] [DEAL]` arguments for commands playing a single game
+fn parse_ai_and_deal(
+    args: &[String],
+    preset: &str,
+    config: &config::Config,
+) -> Result<(ai::profile::AiProfile, rules::DealCode), std::io::Error> {
+    let ai_name = args.first().map(String::as_str).unwrap_or("greedy");
+    let profile = config.ai_profile(ai_name).map_err(|e| invalid_input(e.to_string()))?;
+    let code = match args.get(1) {
+        Some(s) => rules::DealCode::parse_with_preset(s, preset),
+        None => rules::DealCode::new(preset, 0),
+    }
+    .map_err(|e| invalid_input(e.to_string()))?;
+    Ok((profile, code))
+}
+
+/// Play one game and write it as an asciinema cast
+fn cast(
+    args: &[String],
+    preset: &str,
+    options: &engine::DealOptions,
+    config: &config::Config,
+    records_path: Option<&str>,
+) -> Result<(), std::io::Error> {
+    let (profile, code) = parse_ai_and_deal(args, preset, config)?;
+    let path = args.get(2).map(String::as_str).unwrap_or("game.cast");
+
+    let mut gs = deal(&code, options)?;
+    gs.start_clock();
+    let mut ai = profile.make(gs.observe(), gs.rules());
+    let mut recording = cast::Cast::new(format!("{} playing deal {code}", ai.name()), 0.5);
+    let mut last_time = std::time::Duration::ZERO;
+    let mut actions = vec![];
+    let mut rng_checkpoints = vec![];
+    recording.push_frame(&gs.to_string());
+    engine::show_open_cards(&gs, ai.as_mut());
+    while gs.is_running() {
+        rng_checkpoints.extend(ai.rng_checkpoint());
+        let action = ai.make_move();
+        let res = gs.act(&action)
+            .unwrap_or_else(|_| panic!("The AI suggested {action} an illegal move!"));
+        ai.update(action.clone(), res);
+        engine::show_open_cards(&gs, ai.as_mut());
+        let time = gs.duration().expect("The clock was started");
+        recording.push_frame_after((time - last_time).as_secs_f64(), &format!("{action}\n{gs}Score {}", gs.score()));
+        last_time = time;
+        actions.push(action);
+    }
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    recording.write_to(&mut file)?;
+    println!("Wrote {path}");
+    keep_record(records_path, &profile.label(ai.name()), &code, &gs, &actions, Some((&profile.name, &rng_checkpoints)))
+}
+
+/// Play one game and print the encodings of all positions as hex strings, one position per line
+fn encode(
+    args: &[String],
+    preset: &str,
+    options: &engine::DealOptions,
+    config: &config::Config,
+) -> Result<(), std::io::Error> {
+    let (profile, code) = parse_ai_and_deal(args, preset, config)?;
+    let hex = |bytes: Vec<u8>| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+    let mut gs = deal(&code, options)?;
+    let mut ai = profile.make(gs.observe(), gs.rules());
+    engine::show_open_cards(&gs, ai.as_mut());
+    while gs.is_running() {
+        let view = gs.observe();
+        println!("{} {}", hex(view.encode()), hex(view.canonical_encoding()));
+        let action = ai.make_move();
+        let res = gs.act(&action)
+            .unwrap_or_else(|_| panic!("The AI suggested {action} an illegal move!"));
+        ai.update(action, res);
+        engine::show_open_cards(&gs, ai.as_mut());
+    }
+    Ok(())
+}
+
+/// Play one game and print the features of all positions as CSV, one position per line
+fn features(
+    args: &[String],
+    preset: &str,
+    options: &engine::DealOptions,
+    config: &config::Config,
+) -> Result<(), std::io::Error> {
+    let mut args = args.to_vec();
+    let temperature = take_option(&mut args, "--temperature")?
+        .map(|s| s.parse::<f64>().map_err(|_| invalid_input(format!("Not a number: {s}"))))
+        .transpose()?;
+    let (profile, code) = parse_ai_and_deal(&args, preset, config)?;
+
+    let mut gs = deal(&code, options)?;
+    let mut ai = profile.make(gs.observe(), gs.rules());
+    if let Some(temperature) = temperature {
+        ai = Box::new(ai::StochasticAi::new(ai, temperature).with_seed(code.seed));
+    }
+    println!("# feature set version {}", ai::features::VERSION);
+    println!("{}", ai::features::NAMES.join(","));
+    engine::show_open_cards(&gs, ai.as_mut());
+    while gs.is_running() {
+        println!("{}", join(ai::features::extract(&gs.observe()), ","));
+        let action = ai.make_move();
+        let res = gs.act(&action)
+            .unwrap_or_else(|_| panic!("The AI suggested {action} an illegal move!"));
+        ai.update(action, res);
+        engine::show_open_cards(&gs, ai.as_mut());
+    }
+    Ok(())
+}
+
+/// Tune the weights of the evaluation function on the first deals of the rule preset, and print them
+fn tune(args: &[String], preset: &str, options: &engine::DealOptions) -> Result<(), std::io::Error> {
+    let number = |i: usize, default: usize| match args.get(i) {
+        Some(s) => s.parse().map_err(|_| invalid_input(format!("Not a number: {s}"))),
+        None => Ok(default),
+    };
+    let n_deals = number(2, 20)?;
+    let config = tune::TuneConfig {
+        generations: number(0, 5)?,
+        population: number(1, 16)?,
+        elite_fraction: 0.25,
+        initial_std: 2.0,
+        seeds: (0..n_deals as u64).collect(),
+        rules: rules::RuleSet::preset(preset).expect("Checked in main"),
+        options: options.clone(),
+    };
+    deal(&rules::DealCode::new(preset, 0).expect("Checked in main"), options)?;
+
+    let initial = ai::eval::default_weights();
+    println!("Initial: {:?}", tune::fitness(&initial, &config));
+    let mut rng: rand::rngs::StdRng = rand::SeedableRng::seed_from_u64(0);
+    let weights = tune::cross_entropy(&initial, &config, &mut rng, |generation, fitness, _| {
+        println!("Generation {generation}: {fitness:?}");
+    });
+    println!("# feature set version {}", ai::features::VERSION);
+    for (name, weight) in ai::features::NAMES.iter().zip(weights) {
+        println!("{name} {weight}");
+    }
+    Ok(())
+}
+
+/// Play one game and print the board after each move, together with an estimate of the win probability
+fn watch(
+    args: &[String],
+    preset: &str,
+    options: &engine::DealOptions,
+    config: &config::Config,
+    records_path: Option<&str>,
+) -> Result<(), std::io::Error> {
+    let (profile, code) = parse_ai_and_deal(args, preset, config)?;
+    let n_rollouts = 20;
+
+    let mut gs = deal(&code, options)?;
+    let mut ai = profile.make(gs.observe(), gs.rules());
+    let mut actions = vec![];
+    let mut rng_checkpoints = vec![];
+    let mut n_actions_taken = 0;
+    println!("{gs}");
+    engine::show_open_cards(&gs, ai.as_mut());
+    while gs.is_running() {
+        rng_checkpoints.extend(ai.rng_checkpoint());
+        let action = ai.make_move();
+        let points = gs.rules().scoring.policy().points(&action);
+        let res = gs.act(&action)
+            .unwrap_or_else(|_| panic!("The AI suggested {action} an illegal move!"));
+        ai.update(action.clone(), res);
+        engine::show_open_cards(&gs, ai.as_mut());
+        actions.push(action.clone());
+        n_actions_taken += 1;
+        let p_win = if gs.is_running() {
+            analysis::win_probability(&gs.observe(), gs.rules(), n_rollouts, n_actions_taken)
+        } else {
+            gs.is_won() as u8 as f64
+        };
+        println!("Move {n_actions_taken}: {action} ({points:+} points)");
+        println!("{gs}");
+        println!("Score {}. Win probability {:.0}%. {:?}", gs.score(), 100.0 * p_win, gs.phase());
+        if let Some(deadlock) = analysis::find_deadlock(&gs).filter(|_| gs.is_running()) {
+            println!("The game can't be won: {deadlock}");
+        } else if gs.is_running() {
+            println!("At least {} moves to go", analysis::min_moves_to_win(&gs));
+            if let Some(plan) = analysis::plan_empty_column(&gs, 3).filter(|plan| !plan.is_empty()) {
+                println!("A column can be emptied in {} moves: {}", plan.len(), join(&plan, ", "));
+            }
+        }
+        println!();
+    }
+    println!("{} {} with score {}", ai.name(), if gs.is_won() { "won" } else { "lost" }, gs.score());
+    keep_record(records_path, &profile.label(ai.name()), &code, &gs, &actions, Some((&profile.name, &rng_checkpoints)))
+}
+
+/// Play one game from the keyboard, or from commands on the standard input, and keep it if it is a new best
+fn play(
+    args: &[String],
+    preset: &str,
+    options: &engine::DealOptions,
+    config: &config::Config,
+    records_path: Option<&str>,
+    batch: &BatchOptions,
+) -> Result<(), std::io::Error> {
+    let mut args = args.to_vec();
+    let lines = take_flag(&mut args, "--lines");
+    let assistance = match take_option(&mut args, "--assist")? {
+        Some(s) => s.parse::<engine::Assistance>().map_err(invalid_input)?,
+        None => engine::Assistance::None,
+    };
+    if lines && assistance != engine::Assistance::None {
+        return Err(invalid_input("--assist needs the terminal, not --lines".to_string()));
+    }
+    let options = &engine::DealOptions { assistance, ..options.clone() };
+    let report_path = take_option(&mut args, "--report")?;
+    let settings = report_settings(&mut args, batch.node_limit)?;
+    let mut advisor = take_option(&mut args, "--advisor")?
+        .map(|name| config.ai_profile(&name).map(advisor::Advisor::new).map_err(|e| invalid_input(e.to_string())))
+        .transpose()?;
+    let code = match args.first() {
+        Some(s) => rules::DealCode::parse_with_preset(s, preset),
+        None => rules::DealCode::new(preset, 0),
+    }
+    .map_err(|e| invalid_input(e.to_string()))?;
+    let mut gs = deal(&code, options)?;
+    let start = gs.clone();
+    let actions = if lines {
+        console::play(&mut gs, std::io::stdin().lock(), std::io::stdout().lock(), advisor.as_mut())?
+    } else {
+        #[cfg(feature = "tui")]
+        {
+            let app = tui::play(gs, advisor.take())?;
+            gs = app.game().clone();
+            if let Some(advisor) = app.advisor() {
+                println!("{}", advisor.summary());
+            }
+            app.actions().to_vec()
+        }
+        #[cfg(not(feature = "tui"))]
+        return Err(invalid_input("play needs the tui feature, build with --features tui, or use --lines".to_string()));
+    };
+    let result = match gs.outcome() {
+        Some(core::Outcome::Won { .. }) => "won",
+        Some(core::Outcome::Lost { reason: core::LossReason::Quit }) => "gave up",
+        Some(core::Outcome::Lost { reason: core::LossReason::Stuck }) => "got stuck",
+        None => "stopped",
+    };
+    println!("You {result} with score {}", gs.score());
+    let mut verdicts = batch.verdicts_path.as_deref().map(read_verdicts).transpose()?;
+    let solver = solver::Solver::new().with_node_limit(settings.node_limit);
+    match solve_deal(&solver, &code, &start, verdicts.as_mut()) {
+        solver::Solution::Won(line) if gs.is_won() => {
+            println!("You took {} moves, par is {}", actions.len(), line.len())
+        }
+        solver::Solution::Won(line) => println!("Par is {} moves", line.len()),
+        solver::Solution::Lost => println!("The deal can't be won"),
+        solver::Solution::Unknown => {}
+    }
+    if let (Some(verdicts), Some(path)) = (verdicts, batch.verdicts_path.as_deref()) {
+        std::fs::write(path, verdicts.to_text())?;
+    }
+    let player = match assistance {
+        engine::Assistance::None => "Human".to_string(),
+        assistance => format!("Human ({} assistance)", assistance.name()),
+    };
+    keep_record(records_path, &player, &code, &gs, &actions, None)?;
+    if let Some(path) = report_path {
+        let report = report::analyze(&start, &actions, &settings).map_err(|e| invalid_input(e.to_string()))?;
+        let title = format!("Your game on {code}");
+        let text = if path.ends_with(".html") { report.to_html(&title) } else { report.to_markdown(&title) };
+        std::fs::write(&path, text)?;
+        println!("Wrote a report on the game to {path}");
+    }
+    Ok(())
+}
+
+/// The settings of a report, with the options `--rollouts N` and `--max-solves N`
+fn report_settings(args: &mut Vec<String>, node_limit: Option<usize>) -> Result<report::Settings, std::io::Error> {
+    let defaults = report::Settings::default();
+    Ok(report::Settings {
+        n_rollouts: match take_option(args, "--rollouts")? {
+            Some(s) => s.parse().map_err(|_| invalid_input(format!("Not a number: {s}")))?,
+            None => defaults.n_rollouts,
+        },
+        node_limit: node_limit.unwrap_or(defaults.node_limit),
+        max_solves: match take_option(args, "--max-solves")? {
+            Some(s) => s.parse().map_err(|_| invalid_input(format!("Not a number: {s}")))?,
+            None => defaults.max_solves,
+        },
+    })
+}
+
+/// Print a report on the game in a replay file, see `report`
+fn report(args: &[String], node_limit: Option<usize>) -> Result<(), std::io::Error> {
+    let mut args = args.to_vec();
+    let html = take_flag(&mut args, "--html");
+    let settings = report_settings(&mut args, node_limit)?;
+    let path = args.first().ok_or_else(|| invalid_input("Expected the path of a replay file".to_string()))?;
+    let replay = replay::Replay::parse(&std::fs::read_to_string(path)?)
+        .map_err(|e| invalid_input(format!("{path}: {e}")))?;
+    let gs = deal(&replay.code, &replay.options)?;
+    let report = report::analyze(&gs, &replay.actions, &settings).map_err(|e| invalid_input(format!("{path}: {e}")))?;
+    let title = match replay.comment.as_str() {
+        "" => replay.code.to_string(),
+        comment => format!("{}: {comment}", replay.code),
+    };
+    print!("{}", if html { report.to_html(&title) } else { report.to_markdown(&title) });
+    Ok(())
+}
+
+/// Play the games in the replay files in some directories again, and gather their openings, see `openings`
+fn read_openings(dirs: &[String]) -> Result<openings::Openings, std::io::Error> {
+    if dirs.is_empty() {
+        return Err(invalid_input("Expected a directory of replay files".to_string()));
+    }
+    let mut openings = openings::Openings::default();
+    for dir in dirs {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "txt") {
+                continue;
+            }
+            let name = path.display();
+            let replay = replay::Replay::parse(&std::fs::read_to_string(&path)?)
+                .map_err(|e| invalid_input(format!("{name}: {e}")))?;
+            let gs = deal(&replay.code, &replay.options)?;
+            openings.add_game(&gs, &replay.actions).map_err(|e| invalid_input(format!("{name}: {e}")))?;
+        }
+    }
+    Ok(openings)
+}
+
+/// Show the personal bests on some deals, or on all deals in the database
+fn best(args: &[String], preset: &str, records_path: Option<&str>) -> Result<(), std::io::Error> {
+    let records_path =
+        records_path.ok_or_else(|| invalid_input("best needs the database of personal bests, see --records".to_string()))?;
+    let records = read_records(records_path)?;
+    let codes = args
+        .iter()
+        .map(|s| rules::DealCode::parse_with_preset(s, preset).map(|c| c.to_string()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid_input(e.to_string()))?;
+    let codes = if codes.is_empty() { records.iter().map(|(code, _)| code.clone()).collect() } else { codes };
+    for code in codes {
+        match records.get(&code) {
+            Some(r) => println!(
+                "{code}: {} with score {} in {} moves by {}, replay in {}",
+                if r.won { "won" } else { "lost" },
+                r.score,
+                r.moves,
+                r.player,
+                r.replay
+            ),
+            None => println!("{code}: not played yet"),
+        }
+    }
+    Ok(())
+}
+
+/// Play the first deals of one or all variants that are not played on the Klondike tableau, or the deals with the
+/// given codes, and summarize the games
+fn variant(args: &[String]) -> Result<(), std::io::Error> {
+    let not_here = |variant: rules::Variant| {
+        let names = variants::variants().map(|v| v.name()).collect::<Vec<_>>();
+        invalid_input(format!("{} is played with solitaire play, expected one of {}", variant.name(), names.join(", ")))
+    };
+    let mut games = vec![];
+    if args.first().is_some_and(|arg| arg.contains(':')) {
+        for arg in args {
+            let code = arg.parse::<rules::DealCode>().map_err(|e| invalid_input(e.to_string()))?;
+            games.push((code.variant, code.seed..code.seed + 1));
+        }
+    } else {
+        let n_deals = match args.get(1) {
+            Some(s) => s.parse().map_err(|_| invalid_input(format!("Not a number: {s}")))?,
+            None => 100,
+        };
+        match args.first().map_or("all", String::as_str) {
+            "all" => games.extend(variants::variants().map(|v| (v, 0..n_deals))),
+            name => {
+                let variant = rules::Variant::parse_versioned(name).map_err(|e| invalid_input(e.to_string()))?;
+                games.push((variant, 0..n_deals));
+            }
+        }
+    }
+    for (variant, seeds) in games {
+        let summary = variants::play(variant, seeds).ok_or_else(|| not_here(variant))?;
+        println!(
+            "{}: win rate {:.1}%, avg score {:.1}, avg moves {:.1} ({} games)",
+            summary.name,
+            100.0 * summary.win_rate(),
+            summary.avg_score(),
+            summary.avg_moves(),
+            summary.games
+        );
+    }
+    Ok(())
+}
+
+/// Play the moves of a replay file again, and show the board at the end. Replays of older versions are upgraded.
+///
+/// With the flag `--check-ai`, the AI is made again from the profile in the replay, and it has to choose each move
+/// again, from the same state of its random number generator. The first move where it doesn't is an error.
+fn replay(args: &[String], config: &config::Config) -> Result<(), std::io::Error> {
+    let mut args = args.to_vec();
+    let check_ai = take_flag(&mut args, "--check-ai");
+    let path = args.first().ok_or_else(|| invalid_input("Expected the path of a replay file".to_string()))?;
+    let text = std::fs::read_to_string(path)?;
+    let replay = replay::Replay::parse(&text).map_err(|e| invalid_input(format!("{path}: {e}")))?;
+    let version = replay::version(&text).expect("Checked by parse");
+    if version < replay::VERSION {
+        println!("Upgraded {path} from version {version} to {}", replay::VERSION);
+    }
+    println!("{}", replay.comment);
+    let mut gs = deal(&replay.code, &replay.options)?;
+    let mut ai = match (check_ai, &replay.ai) {
+        (false, _) => None,
+        (true, Some(name)) => {
+            let profile = config.ai_profile(name).map_err(|e| invalid_input(format!("{path}: {e}")))?;
+            Some(profile.make(gs.observe(), gs.rules()))
+        }
+        (true, None) => return Err(invalid_input(format!("{path}: The replay doesn't say which AI played"))),
+    };
+    if let Some(ai) = ai.as_mut() {
+        engine::show_open_cards(&gs, ai.as_mut());
+    }
+    for (i, action) in replay.actions.iter().enumerate() {
+        if let Some(ai) = ai.as_mut() {
+            let (expected, actual) = (replay.rng_checkpoints.get(i).copied(), ai.rng_checkpoint());
+            if actual != expected {
+                let hex = |c: Option<u64>| c.map_or("none".to_string(), |c| format!("{c:016x}"));
+                let (actual, expected) = (hex(actual), hex(expected));
+                return Err(invalid_input(format!("{path}: move {} is drawn with rng {actual} instead of {expected}", i + 1)));
+            }
+            let chosen = ai.make_move();
+            if chosen != *action {
+                return Err(invalid_input(format!("{path}: move {} is {chosen} instead of {action}", i + 1)));
+            }
+        }
+        let res = gs.act(action)
+            .map_err(|e| invalid_input(format!("{path}: move {} {action} is illegal on {}: {e}", i + 1, replay.code)))?;
+        if let Some(ai) = ai.as_mut() {
+            ai.update(action.clone(), res);
+            engine::show_open_cards(&gs, ai.as_mut());
+        }
+    }
+    println!("{gs}");
+    let result = match gs.outcome() {
+        Some(core::Outcome::Won { .. }) => "Won".to_string(),
+        Some(core::Outcome::Lost { reason }) => format!("Lost ({reason:?})"),
+        None => "Running".to_string(),
+    };
+    println!("{result} with score {} after {} moves", gs.score(), replay.actions.len());
+    if let Some(ai) = ai {
+        let rng = if replay.rng_checkpoints.is_empty() { "" } else { " with the same random numbers" };
+        println!("{} chose the same moves{rng}", ai.name());
+    }
+    Ok(())
+}
+
+/// The name of the benchmark workload. A change of the workload gets a new name, so that results are only compared
+/// when they come from the same workload.
+const BENCH_SUITE: &str = "bench-v1";
+/// The workload: the first deals of the classic rules, played by each AI with limited memory
+const BENCH_PRESET: &str = "klondike-classic";
+const BENCH_DEALS: u64 = 1000;
+const BENCH_AIS: [&str; 2] = ["simple", "greedy"];
+const BENCH_NODE_LIMIT: usize = 5000;
+/// A game that is not over after this many moves is stopped, as a safety net
+const BENCH_MAX_ACTIONS: usize = 100_000;
+
+/// Run the standard benchmark, and print the throughput and the win rate of each AI
+///
+/// The workload is fixed, see `BENCH_SUITE`, and the options of the other commands don't change it, so that results
+/// from different machines and versions can be compared. Only the number of deals can be lowered for a quick check,
+/// and such a run is not labeled as the suite.
+fn bench(args: &[String]) -> Result<(), std::io::Error> {
+    let n_deals = match args.first() {
+        Some(s) => s.parse().map_err(|_| invalid_input(format!("Not a number: {s}")))?,
+        None => BENCH_DEALS,
+    };
+    let suite = if n_deals == BENCH_DEALS {
+        format!("Benchmark {BENCH_SUITE}")
+    } else {
+        format!("Partial benchmark, not comparable to {BENCH_SUITE}")
+    };
+    let build = if cfg!(debug_assertions) { "debug" } else { "release" };
+    println!("{suite}: {n_deals} deals of {BENCH_PRESET}, at most {BENCH_NODE_LIMIT} nodes per AI");
+    println!("solitaire {} ({build} build)", env!("CARGO_PKG_VERSION"));
+    println!("{:<10} {:>6} {:>9} {:>10} {:>10} {:>9} {:>10}", "AI", "games", "win rate", "avg score", "avg moves", "games/s", "moves/s");
+    let t_begin = std::time::Instant::now();
+    for name in BENCH_AIS {
+        let profile = ai::profile::AiProfile::builtin(name).expect("The benchmark AIs are built in");
+        let mut summary = None;
+        let t_ai = std::time::Instant::now();
+        for seed in 0..n_deals {
+            let code = rules::DealCode::new(BENCH_PRESET, seed).expect("The benchmark preset exists");
+            let mut gs = deal(&code, &engine::DealOptions::default())?;
+            let mut ai = profile.make(gs.observe(), gs.rules());
+            ai.set_node_limit(BENCH_NODE_LIMIT);
+            let n_actions = engine::run_game(&mut gs, ai.as_mut(), BENCH_MAX_ACTIONS);
+            let end = stats::GameEnd::from(gs.outcome());
+            summary.get_or_insert_with(|| stats::Summary::new(ai.name())).add_game(gs.score(), end, n_actions);
+        }
+        let seconds = t_ai.elapsed().as_secs_f64();
+        let summary = summary.unwrap_or_else(|| stats::Summary::new(name));
+        println!(
+            "{:<10} {:>6} {:>8.1}% {:>10.1} {:>10.1} {:>9.1} {:>10.0}",
+            summary.name,
+            summary.games,
+            100.0 * summary.win_rate(),
+            summary.avg_score(),
+            summary.avg_moves(),
+            summary.games as f64 / seconds,
+            summary.total_moves as f64 / seconds,
+        );
+    }
+    println!("Total time {:.1} s", t_begin.elapsed().as_secs_f64());
+    Ok(())
+}
+
+/// Solve the deals, or the first ten, and print whether each can be won
+fn solve(
+    args: &[String],
+    preset: &str,
+    options: &engine::DealOptions,
+    node_limit: Option<usize>,
+    verdicts_path: Option<&str>,
+) -> Result<(), std::io::Error> {
+    use solver::{Algorithm, MoveOrder, Solution, Solver, DEFAULT_NODE_LIMIT};
+    let mut args = args.to_vec();
+    let algorithm =
+        take_option(&mut args, "--algorithm")?.map(|s| s.parse::<Algorithm>().map_err(invalid_input)).transpose()?;
+    let order = take_option(&mut args, "--order")?.map(|s| s.parse::<MoveOrder>().map_err(invalid_input)).transpose()?;
+    let compare_orders = take_flag(&mut args, "--compare-orders");
+    let mut verdicts = verdicts_path.map(read_verdicts).transpose()?;
+    let codes = args
+        .iter()
+        .map(|s| rules::DealCode::parse_with_preset(s, preset))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid_input(e.to_string()))?;
+    let codes = if codes.is_empty() {
+        (0..10).map(|seed| rules::DealCode::new(preset, seed).expect("Checked in main")).collect()
+    } else {
+        codes
+    };
+    let solver = Solver::new()
+        .with_node_limit(node_limit.unwrap_or(DEFAULT_NODE_LIMIT))
+        .with_algorithm(algorithm.unwrap_or_default());
+    if compare_orders {
+        return compare_orders_on(&solver, &codes, options);
+    }
+    let solver = solver.with_order(order.unwrap_or_default());
+    for code in codes {
+        let gs = deal(&code, options)?;
+        match solve_deal(&solver, &code, &gs, verdicts.as_mut()) {
+            Solution::Won(line) => println!("{code}: can be won in {} moves: {}", line.len(), join(&line, ", ")),
+            Solution::Lost => println!("{code}: can't be won"),
+            Solution::Unknown => println!("{code}: unknown, the search gave up"),
+        }
+    }
+    if let (Some(verdicts), Some(path)) = (verdicts, verdicts_path) {
+        std::fs::write(path, verdicts.to_text())?;
+    }
+    Ok(())
+}
+
+/// Solve each deal in every order of the moves, and print how many positions each took, see `solver::MoveOrder`
+fn compare_orders_on(
+    solver: &solver::Solver,
+    codes: &[rules::DealCode],
+    options: &engine::DealOptions,
+) -> Result<(), std::io::Error> {
+    use solver::{MoveOrder, Solution};
+    // The proofs and the positions they took, for each order
+    let mut totals = [(0, 0); MoveOrder::ALL.len()];
+    for code in codes {
+        let gs = deal(code, options)?;
+        let mut results = vec![];
+        for (order, total) in MoveOrder::ALL.into_iter().zip(totals.iter_mut()) {
+            let (solution, effort) = solver.clone().with_order(order).solve_with_effort(&gs);
+            let verdict = match solution {
+                Solution::Won(_) => "won",
+                Solution::Lost => "lost",
+                Solution::Unknown => "gave up",
+            };
+            if solution != Solution::Unknown {
+                *total = (total.0 + 1, total.1 + effort.n_nodes);
+            }
+            results.push(format!("{} {verdict} after {} positions", order.name(), effort.n_nodes));
+        }
+        println!("{code}: {}", results.join(", "));
+    }
+    println!("{:<12} {:>6} {:>12} {:>12}", "Order", "Proofs", "Positions", "Per proof");
+    for (order, (n_proofs, n_nodes)) in MoveOrder::ALL.into_iter().zip(totals) {
+        let per_proof = if n_proofs == 0 { 0.0 } else { n_nodes as f64 / n_proofs as f64 };
+        println!("{:<12} {n_proofs:>6} {n_nodes:>12} {per_proof:>12.0}", order.name());
+    }
+    Ok(())
+}
+
+/// Race two AIs on each deal, and summarize the match
+fn race(
+    args: &[String],
+    deals: &[rules::DealCode],
+    options: &engine::DealOptions,
+    config: &config::Config,
+    node_limit: Option<usize>,
+) -> Result<(), std::io::Error> {
+    let names = [args.first().map_or("greedy", String::as_str), args.get(1).map_or("phased", String::as_str)];
+    let [first, second] = names.map(|name| config.ai_profile(name).map_err(|e| invalid_input(e.to_string())));
+    let profiles = [first?, second?];
+    let labels = match names {
+        [a, b] if a == b => [format!("{a} 1"), format!("{b} 2")],
+        [a, b] => [a.to_string(), b.to_string()],
+    };
+    let mut stats = race::Match::default();
+    for code in deals {
+        let gs = deal(code, options)?;
+        let ais = profiles.each_ref().map(|profile| {
+            let mut ai = profile.make(gs.observe(), gs.rules());
+            if let Some(limit) = node_limit {
+                ai.set_node_limit(limit);
+            }
+            ai
+        });
+        let result = race::race(&gs, ais);
+        stats.add(&result);
+        let outcome = match result.winner {
+            Some(i) if result.won[i] => format!("{} won first", labels[i]),
+            Some(i) => format!("{} scored more", labels[i]),
+            None => "a draw".to_string(),
+        };
+        let [(s1, m1), (s2, m2)] = [0, 1].map(|i| (result.scores[i], result.moves[i]));
+        println!("{code}: {outcome}, with scores {s1} and {s2} after {m1} and {m2} moves");
+    }
+    println!("{} against {} in {} races", labels[0], labels[1], stats.races);
+    for (i, label) in labels.iter().enumerate() {
+        println!(
+            "  {label}: {} races won, {} deals won, mean score {:.1}",
+            stats.wins[i],
+            stats.deals_won[i],
+            stats.mean_score(i)
+        );
+    }
+    println!("  {} draws", stats.draws);
+    Ok(())
+}
+
+/// Options that only apply to a batch of games
+struct BatchOptions {
+    /// Write the decisions of the AIs to this file
+    trace_path: Option<String>,
+    /// Limit the memory of each AI
+    node_limit: Option<usize>,
+    /// Print a JSON summary instead of the report
+    porcelain: bool,
+    /// Print the games in this format instead of the report
+    format: Option<export::Format>,
+    /// Add the games to this SQLite database
+    sqlite_path: Option<String>,
+    /// Send the finished games to this address
+    stream_addr: Option<String>,
+    /// Serve a dashboard on this address
+    serve_addr: Option<String>,
+    /// Check the updates of the views of the AIs against the game after each move
+    verify_observer: bool,
+    /// Measure how many moves of the AIs agree with the solver
+    agreement: bool,
+    /// Look up and keep the verdicts of the solver in this file
+    verdicts_path: Option<String>,
+    /// Write a replay of every game to this directory
+    replay_dir: Option<String>,
+    /// Write the moves of the games in a livelock to this directory
+    livelock_dir: Option<String>,
+}
+
+/// Play a batch of games with all AIs and report statistics
+fn play_many(
+    preset: &str,
+    deals: &[rules::DealCode],
+    profiles: &[ai::profile::AiProfile],
+    options: &engine::DealOptions,
+    batch: &BatchOptions,
+    records_path: Option<&str>,
+) -> Result<std::process::ExitCode, std::io::Error> {
+    use std::io::Write;
+    let BatchOptions {
+        ref trace_path, node_limit, porcelain, format, ref sqlite_path, ref stream_addr, ref serve_addr, verify_observer,
+        agreement, ref verdicts_path, ref replay_dir, ref livelock_dir,
+    } = *batch;
+    if porcelain && format.is_some() {
+        return Err(invalid_input("--porcelain and --format both print to the standard output, use one of them".to_string()));
+    }
+    let mut stream = stream_addr.as_deref().map(stream::Stream::connect).transpose()?;
+    let mut records = records_path.map(read_records).transpose()?;
+    let mut verdicts = verdicts_path.as_deref().filter(|_| agreement).map(read_verdicts).transpose()?;
+    #[cfg(feature = "sqlite")]
+    let mut export = match sqlite_path {
+        Some(path) => Some(sqlite::Export::open(path, preset, profiles).map_err(std::io::Error::other)?),
+        None => None,
+    };
+    #[cfg(not(feature = "sqlite"))]
+    if sqlite_path.is_some() {
+        return Err(invalid_input("--sqlite needs the sqlite feature, build with --features sqlite".to_string()));
+    }
+    #[cfg(feature = "server")]
+    let position = engine::SharedGame::default();
+    #[cfg(feature = "server")]
+    let dashboard = match serve_addr {
+        Some(addr) => {
+            let dashboard = std::sync::Arc::new(std::sync::Mutex::new(server::Dashboard::default()));
+            server::serve(addr, dashboard.clone(), position.clone())?;
+            eprintln!("Serving the dashboard on http://{addr}/");
+            Some(dashboard)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "server"))]
+    if serve_addr.is_some() {
+        return Err(invalid_input("--serve needs the server feature, build with --features server".to_string()));
+    }
+    // The positions of a game are searched with the same effort as in a report
+    let solver = agreement.then(|| solver::Solver::new().with_node_limit(report::Settings::default().node_limit));
     let mut game_statistics = Vec::new();
+    // The human readable report is left out when the output is for scripts, and so are the statistics only it shows
+    let detailed = !(porcelain || format.is_some());
+    let mut out: Box<dyn Write> = if !detailed {
+        Box::new(std::io::sink())
+    } else {
+        Box::new(std::io::stdout())
+    };
+    let mut game_export = format.map(|format| export::Export::new(std::io::stdout().lock(), format)).transpose()?;
+    let mut trace = match trace_path {
+        Some(path) => Some(trace::Trace::new(std::io::BufWriter::new(std::fs::File::create(path)?))),
+        None => None,
+    };
+    for dir in [replay_dir, livelock_dir].into_iter().flatten() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    writeln!(out, "Rules: {preset}")?;
+    let rule_set = rules::RuleSet::preset(preset).expect("Checked in main");
+    for (rule, setting) in rules::describe(&rule_set) {
+        writeln!(out, "  {rule}: {setting}")?;
+    }
+    writeln!(out, "Points:")?;
+    for (kind, points) in rule_set.scoring.policy().table() {
+        writeln!(out, "  {kind}: {points:+}")?;
+    }
+    for profile in profiles.iter().filter(|p| p.name != p.base) {
+        writeln!(out, "AI profile {}: based on {}", profile.name, profile.base)?;
+        for (key, value) in profile.settings.iter() {
+            writeln!(out, "  {key} = {value}")?;
+        }
+        if let Some(rollout) = profile.rollout {
+            writeln!(out, "  rollout = {rollout}")?;
+        }
+    }
+
+    // On Ctrl-C, the game in progress is dropped and the completed games are summarized
+    let interrupted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    #[cfg(feature = "interrupt")]
+    {
+        let flag = interrupted.clone();
+        ctrlc::set_handler(move || flag.store(true, std::sync::atomic::Ordering::Relaxed))
+            .map_err(std::io::Error::other)?;
+    }
+    let is_interrupted = || interrupted.load(std::sync::atomic::Ordering::Relaxed);
 
-    for k in 0..n_games_to_play {
-        let make_greedy: fn(ai::SolitaireObserver) -> Box<dyn ai::Ai> = |obs| Box::from(ai::GreedyAi::new(obs)); 
-        let make_simple: fn(ai::SolitaireObserver) -> Box<dyn ai::Ai> = |obs| Box::from(ai::SimpleAi::new(obs));
-        let ai_makers  = [make_simple, make_greedy];
-        for make_ai in ai_makers {
-            let mut gs = engine::GameEngine::deal(k);
+    'deals: for code in deals {
+        for profile in profiles {
+            let mut gs = deal(code, options)?;
             let t_begin = std::time::Instant::now();
-            let mut ai: Box<dyn ai::Ai> = make_ai(gs.observe());
+            gs.start_clock();
+            let mut ai: Box<dyn ai::Ai> = profile.make(gs.observe(), gs.rules());
+            let label = profile.label(ai.name());
+            if let Some(limit) = node_limit {
+                ai.set_node_limit(limit);
+            }
+            let mut peak_nodes = 0;
             let mut n_actions_taken = 0;
+            let mut n_productive = 0;
+            let mut branching = std::collections::BTreeMap::<core::Phase, stats::BranchingFactor>::new();
+            let mut watchdog = watchdog::Watchdog::default();
+            let mut actions = vec![];
+            let mut rng_checkpoints = vec![];
+            let mut livelock = false;
+            let mut illegal = false;
+            let mut view = verify_observer.then(|| gs.observe());
+            #[cfg(feature = "server")]
+            if let Some(dashboard) = dashboard.as_ref() {
+                dashboard.lock().expect("The server does not panic while holding the lock").start(&label, &code.to_string());
+                position.publish(&gs);
+            }
+            engine::show_open_cards(&gs, ai.as_mut());
             while gs.is_running() {
+                if is_interrupted() {
+                    break 'deals;
+                }
+                if detailed {
+                    branching.entry(gs.phase()).or_default().add(gs.n_legal_actions(), gs.n_pruned_actions());
+                }
+                rng_checkpoints.extend(ai.rng_checkpoint());
                 let action = ai.make_move();
-                let res = gs.act(&action)
-                    .unwrap_or_else(|_| panic!("The AI suggested {:?} an illegal move!", action));
+                if detailed {
+                    n_productive += engine::is_productive(&action, &gs) as usize;
+                }
+                if let Some(trace) = trace.as_mut() {
+                    trace.record(&label, &code.to_string(), n_actions_taken, &ai.candidates(), &ai.action_probabilities(), &action)?;
+                }
+                let res = match gs.act(&action) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        eprintln!("{label} suggested the illegal action {action} on {code}: {e}");
+                        illegal = true;
+                        break;
+                    }
+                };
+                #[cfg(feature = "server")]
+                if let Some(dashboard) = dashboard.as_ref() {
+                    dashboard.lock().expect("The server does not panic while holding the lock").add_move(&action);
+                    position.publish(&gs);
+                }
+                actions.push(action.clone());
+                if let Some(view) = view.as_mut() {
+                    view.update(action.clone(), res);
+                    let observed = gs.observe();
+                    if *view != observed {
+                        eprintln!("The updated view differs from the game after move {} {action} of {label} on {code}", n_actions_taken + 1);
+                        eprintln!("  updated:  {view:?}");
+                        eprintln!("  observed: {observed:?}");
+                        *view = observed;
+                    }
+                }
                 ai.update(action, res);
+                engine::show_open_cards(&gs, ai.as_mut());
                 n_actions_taken += 1;
+                peak_nodes = peak_nodes.max(ai.n_nodes());
+                if watchdog.check(&gs.layout()) {
+                    livelock = true;
+                    dump_livelock(livelock_dir.as_deref(), &label, &profile.name, &gs, code, &actions, &rng_checkpoints)?;
+                    break;
+                }
             }
             let t_end = std::time::Instant::now();
-            let stats = (
-                ai.name(),
-                k,
-                gs.score(),
-                gs.is_won(),
-                n_actions_taken,
-                t_end - t_begin,
-            );
-            game_statistics.push(stats);
-            println!("{:?}", stats);
-        }
-    }
-    game_statistics
-        .iter()
-        .sorted()
-        .group_by(|x| x.0)
-        .into_iter()
-        .for_each(|(key, group)| {
-            let group = group.collect::<Vec<_>>();
-            let wins = group.iter().fold(0u8, |acc, tup| acc + tup.3 as u8);
-            let score = group.iter().fold(0, |acc, tup| acc + tup.2);
-            println!("{key}: {wins} wins. Total score {score}");
-        });
-    Ok(())
+            let end = match (stats::GameEnd::from(gs.outcome()), livelock, illegal) {
+                (stats::GameEnd::Won, _, _) => stats::GameEnd::Won,
+                (_, true, _) => stats::GameEnd::Livelock,
+                (_, _, true) => stats::GameEnd::IllegalAction,
+                (end, false, false) => end,
+            };
+            let record = stats::GameRecord {
+                ai: label,
+                deal: code.to_string(),
+                score: gs.score(),
+                end,
+                moves: n_actions_taken,
+                seconds: (t_end - t_begin).as_secs_f64(),
+                peak_nodes,
+                wasted_moves: gs.n_wasted_moves(),
+            };
+            writeln!(out, "{record}")?;
+            if let Some(stream) = stream.as_mut() {
+                stream.send(&record)?;
+            }
+            if let Some(game_export) = game_export.as_mut() {
+                game_export.add(&record)?;
+            }
+            #[cfg(feature = "server")]
+            if let Some(dashboard) = dashboard.as_ref() {
+                dashboard.lock().expect("The server does not panic while holding the lock").add(&record, &actions);
+            }
+            #[cfg(feature = "sqlite")]
+            if let Some(export) = export.as_mut() {
+                export.add_game(&record, &actions).map_err(std::io::Error::other)?;
+            }
+            if let Some(dir) = replay_dir {
+                let path = std::path::Path::new(dir).join(format!("{}-{}.txt", profile.name, code.to_string().replace(':', "-")));
+                let comment = format!("{} scored {} in {} moves ({:?})", record.ai, record.score, record.moves, record.end);
+                write_moves(&path.to_string_lossy(), &comment, &gs, code, &actions, Some((&profile.name, &rng_checkpoints)))?;
+            }
+            if let (Some(records), Some(path)) = (records.as_mut(), records_path) {
+                if offer_record(records, path, &record.ai, code, &gs, &actions, Some((&profile.name, &rng_checkpoints)))? {
+                    writeln!(out, "New best on {code}")?;
+                }
+            }
+            game_statistics.push(stats::GameStats {
+                record,
+                branching,
+                productive_moves: n_productive,
+                action_times: gs.action_times().collect(),
+                timed_score: gs.timed_score(),
+                agreement: match solver.as_ref() {
+                    Some(solver) => {
+                        let start = deal(code, options)?;
+                        match solve_deal(solver, code, &start, verdicts.as_mut()) {
+                            solver::Solution::Won(line) => Some(solver.agreement(&start, &line, &actions)),
+                            _ => None,
+                        }
+                    }
+                    None => None,
+                },
+            });
+        }
+    }
+    let exit_code = if is_interrupted() {
+        writeln!(out, "Interrupted after {} games", game_statistics.len())?;
+        EXIT_INTERRUPTED
+    } else if game_statistics.iter().any(|game| game.record.end == stats::GameEnd::IllegalAction) {
+        EXIT_AI_ERRORS
+    } else {
+        0
+    };
+    if let Some(trace) = trace.as_mut() {
+        trace.flush()?;
+    }
+    if let Some(game_export) = game_export {
+        game_export.finish()?;
+    }
+    if let (Some(records), Some(path)) = (records, records_path) {
+        std::fs::write(path, records.to_text())?;
+    }
+    if let (Some(verdicts), Some(path)) = (verdicts, verdicts_path) {
+        std::fs::write(path, verdicts.to_text())?;
+    }
+    let mut summaries = std::collections::BTreeMap::new();
+    for game in game_statistics.iter() {
+        let ai = &game.record.ai;
+        summaries.entry(ai.clone()).or_insert_with(|| stats::Summary::new(ai)).add_stats(game);
+    }
+    let summaries = summaries.into_values().collect::<Vec<_>>();
+    for summary in summaries.iter() {
+        writeln!(out, "{summary}")?;
+    }
+    writeln!(out, "Pareto front (win rate, score, moves):")?;
+    for summary in stats::pareto_front(&summaries) {
+        writeln!(out, "  {}", summary.name)?;
+    }
+    if porcelain {
+        let ais = join(summaries.iter().map(stats::Summary::to_json), ", ");
+        println!(
+            "{{\"exit_code\": {exit_code}, \"games\": {}, \"interrupted\": {}, \"ais\": [{ais}]}}",
+            game_statistics.len(),
+            is_interrupted()
+        );
+    }
+    Ok(std::process::ExitCode::from(exit_code))
 }
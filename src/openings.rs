@@ -0,0 +1,138 @@
+//! Opening statistics: how games went, by how they began
+//!
+//! The games in replay files, see `--replays`, are played again and grouped in two ways: by the depot where the first
+//! card was revealed, and by the kind of the first action. Each group is summarized with its win rate and average
+//! score, see `stats::Summary`. Over many deals, this tells which openings tend to pay off, though a good AI may
+//! choose an opening because the deal is easy, not the other way around.
+//!
+//! The win rates by the first depot make an `ai::OpeningPrior` for the greedy AI, in thousandths so that the AI compares
+//! integers. They are smoothed by counting one won and one lost game more in each depot, so that a depot with few games
+//! is not trusted too much.
+//!
+//! `solitaire openings DIR...` prints the statistics, and `--openings DIR` lets the AIs of a batch use the prior.
+
+use crate::ai::OpeningPrior;
+use crate::core::{Action, Addr, MoveError};
+use crate::engine::GameEngine;
+use crate::stats::{GameEnd, Summary};
+use std::collections::BTreeMap;
+
+/// The outcomes of games by their openings
+#[derive(Debug, Clone, Default)]
+pub struct Openings {
+    /// By the depot where the first card was revealed, or `None` if no card was
+    pub first_reveal: BTreeMap<Option<usize>, Summary>,
+    /// By the kind of the first action, see `kind`
+    pub first_action: BTreeMap<&'static str, Summary>,
+}
+
+/// The kind of an action, to group openings by
+fn kind(action: &Action) -> &'static str {
+    match action {
+        Action::Take => "take",
+        Action::Turnover => "turnover",
+        Action::Reveal(_) => "reveal",
+        Action::Move(Addr::Waste, _, _) => "move from the waste",
+        Action::Move(_, to, _) if to.is_foundation() => "move to a foundation",
+        Action::Move(..) => "move between depots",
+        Action::MoveFoundationPile(..) => "move a foundation pile",
+        Action::Quit => "quit",
+    }
+}
+
+/// The name of the depot where the first card was revealed
+fn depot_name(depot: Option<usize>) -> String {
+    match depot {
+        Some(i) => Addr::DEPOTS[i].to_string(),
+        None => "none".to_string(),
+    }
+}
+
+impl Openings {
+    /// Play a game again from the start, and add it to the statistics
+    pub fn add_game(&mut self, start: &GameEngine, actions: &[Action]) -> Result<(), MoveError> {
+        let mut gs = start.clone();
+        let mut first_reveal = None;
+        for action in actions {
+            let turned = gs.act(action)?;
+            // Taking a card turns it too, but it reveals nothing in the depots
+            let from = match action {
+                Action::Reveal(addr) | Action::Move(addr, _, _) => addr.depot_id(),
+                _ => None,
+            };
+            if first_reveal.is_none() && !turned.is_empty() {
+                first_reveal = from.map(|depot| depot.index());
+            }
+        }
+        let end = GameEnd::from(gs.outcome());
+        let summaries = [
+            self.first_reveal
+                .entry(first_reveal)
+                .or_insert_with(|| Summary::new(&depot_name(first_reveal))),
+            self.first_action
+                .entry(actions.first().map_or("none", kind))
+                .or_insert_with_key(|name| Summary::new(name)),
+        ];
+        for summary in summaries {
+            summary.add_game(gs.score(), end, actions.len());
+        }
+        Ok(())
+    }
+
+    /// The smoothed win rate of the games by the depot where they revealed the first card, in thousandths
+    pub fn prior(&self) -> OpeningPrior {
+        OpeningPrior(std::array::from_fn(|i| {
+            let (wins, games) = self
+                .first_reveal
+                .get(&Some(i))
+                .map_or((0, 0), |s| (s.wins, s.games));
+            (1000 * (wins + 1) / (games + 2)) as u32
+        }))
+    }
+
+    /// The statistics as text, one line per group
+    pub fn to_text(&self) -> String {
+        let line = |s: &Summary| {
+            format!(
+                "  {}: win rate {:.1}%, avg score {:.1} ({} games)\n",
+                s.name,
+                100.0 * s.win_rate(),
+                s.avg_score(),
+                s.games
+            )
+        };
+        let mut text = "By the depot of the first revealed card:\n".to_string();
+        text.extend(self.first_reveal.values().map(line));
+        text.push_str("By the first action:\n");
+        text.extend(self.first_action.values().map(line));
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_games_by_opening() {
+        let gs = GameEngine::deal(2);
+        let mut openings = Openings::default();
+        openings.add_game(&gs, &[Action::Quit]).unwrap();
+        let reveal = [
+            Action::Take,
+            Action::Move(Addr::Depot4, Addr::Depot3, 1),
+            Action::Reveal(Addr::Depot4),
+        ];
+        openings.add_game(&gs, &reveal).unwrap();
+        assert_eq!(openings.first_action["quit"].games, 1);
+        assert_eq!(openings.first_action["take"].games, 1);
+        assert_eq!(openings.first_reveal[&None].games, 1);
+        assert_eq!(openings.first_reveal[&Some(3)].games, 1);
+        assert_eq!(openings.prior().0[3], 333);
+        assert_eq!(openings.prior().0[0], 500);
+        assert!(openings.to_text().contains("  D4: win rate 0.0%"));
+        assert!(openings
+            .add_game(&gs, &[Action::Move(Addr::Depot1, Addr::Depot2, 1)])
+            .is_err());
+    }
+}
@@ -0,0 +1,41 @@
+//! The types needed for typical use, to import with `use solitaire::prelude::*`
+//!
+//! Deal a game with `GameEngine::deal`, create an AI from `GameEngine::observe`, and play it out with `run_game`.
+//!
+
+pub use crate::ai::{Ai, SolitaireObserver};
+pub use crate::core::{Action, Addr, CardView, Outcome, Suit, Turned, Value};
+pub use crate::engine::{run_game, GameEngine};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tiny AI, written as a downstream user would with only the prelude in scope
+    struct TakeAndQuit(SolitaireObserver);
+
+    impl Ai for TakeAndQuit {
+        fn make_move(&mut self) -> Action {
+            match self.0.card_at(&Addr::Waste, 1) {
+                Some(CardView::FaceUp(_, value)) if value == Value::KING => Action::Quit,
+                _ if self.0.talon_size > 0 => Action::Take,
+                _ => Action::Quit,
+            }
+        }
+        fn name(&self) -> &'static str {
+            "TakeAndQuit"
+        }
+        fn update(&mut self, action: Action, res: Turned) {
+            self.0.update(action, res)
+        }
+    }
+
+    #[test]
+    fn play_with_the_prelude() {
+        let mut gs = GameEngine::deal(1);
+        let mut ai = TakeAndQuit(gs.observe());
+        let n_actions = run_game(&mut gs, &mut ai, 100);
+        assert!(n_actions <= 25);
+        assert!(!gs.is_running());
+    }
+}
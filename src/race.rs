@@ -0,0 +1,146 @@
+//! Races: two AIs play the same deal at the same time, each on a board of its own
+//!
+//! The racers take turns, one action each per round. The first to win the deal wins the race, and if both win in the
+//! same round, it is a draw. If neither wins, the race is decided by the higher score once both games are over, and
+//! equal scores are a draw. A racer whose game is over waits for the other one to finish. A racer that is stuck in
+//! a loop, see `watchdog`, or that suggests an illegal action is out of the race with the score it has.
+//!
+//! `solitaire race` runs a match of races on many deals and summarizes it, see `Match`.
+
+use crate::ai::Ai;
+use crate::engine::{self, GameEngine};
+use crate::watchdog::Watchdog;
+
+/// How a race went
+#[derive(Debug, Clone, PartialEq)]
+pub struct Race {
+    /// The racer that won, 0 or 1, or `None` for a draw
+    pub winner: Option<usize>,
+    pub scores: [i32; 2],
+    pub moves: [usize; 2],
+    pub won: [bool; 2],
+}
+
+/// Race two AIs, each on its own copy of the game
+pub fn race(gs: &GameEngine, mut ais: [Box<dyn Ai>; 2]) -> Race {
+    let mut games = [gs.clone(), gs.clone()];
+    let mut watchdogs = [Watchdog::default(), Watchdog::default()];
+    let mut moves = [0; 2];
+    let mut is_out = [false; 2];
+    for (gs, ai) in games.iter().zip(ais.iter_mut()) {
+        engine::show_open_cards(gs, ai.as_mut());
+    }
+    while games
+        .iter()
+        .zip(is_out)
+        .any(|(gs, out)| gs.is_running() && !out)
+    {
+        for i in 0..2 {
+            let (gs, ai) = (&mut games[i], &mut ais[i]);
+            if !gs.is_running() || is_out[i] {
+                continue;
+            }
+            let action = ai.make_move();
+            match gs.act(&action) {
+                Ok(res) => {
+                    ai.update(action, res);
+                    engine::show_open_cards(gs, ai.as_mut());
+                    moves[i] += 1;
+                    is_out[i] = watchdogs[i].check(&gs.observe());
+                }
+                Err(_) => is_out[i] = true,
+            }
+        }
+        if games.iter().any(GameEngine::is_won) {
+            break;
+        }
+    }
+    let won = games.each_ref().map(GameEngine::is_won);
+    let scores = games.each_ref().map(GameEngine::score);
+    let winner = match won {
+        [true, false] => Some(0),
+        [false, true] => Some(1),
+        [true, true] => None,
+        [false, false] if scores[0] == scores[1] => None,
+        [false, false] => Some((scores[1] > scores[0]) as usize),
+    };
+    Race {
+        winner,
+        scores,
+        moves,
+        won,
+    }
+}
+
+/// The statistics of a match of races between the same two racers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Match {
+    pub races: usize,
+    /// The races each racer won
+    pub wins: [usize; 2],
+    pub draws: usize,
+    /// The deals each racer won, whether it won the race or not
+    pub deals_won: [usize; 2],
+    pub total_scores: [i64; 2],
+}
+
+impl Match {
+    pub fn add(&mut self, race: &Race) {
+        self.races += 1;
+        match race.winner {
+            Some(i) => self.wins[i] += 1,
+            None => self.draws += 1,
+        }
+        for i in 0..2 {
+            self.deals_won[i] += race.won[i] as usize;
+            self.total_scores[i] += race.scores[i] as i64;
+        }
+    }
+
+    pub fn mean_score(&self, racer: usize) -> f64 {
+        self.total_scores[racer] as f64 / self.races.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::GreedyAi;
+    use crate::core::{Action, Turned};
+
+    /// A racer that gives up at once
+    struct Quitter;
+
+    impl Ai for Quitter {
+        fn make_move(&mut self) -> Action {
+            Action::Quit
+        }
+        fn name(&self) -> &'static str {
+            "Quitter"
+        }
+        fn update(&mut self, _action: Action, _res: Turned) {}
+    }
+
+    fn greedy(gs: &GameEngine) -> Box<dyn Ai> {
+        Box::new(GreedyAi::new(gs.observe(), gs.rules().scoring.policy()))
+    }
+
+    #[test]
+    fn races_are_decided() {
+        let gs = GameEngine::deal(0);
+        let mut stats = Match::default();
+        let quit = race(&gs, [Box::new(Quitter), greedy(&gs)]);
+        assert_eq!(quit.winner, Some(1));
+        assert_eq!(quit.moves[0], 1);
+        assert!(quit.won[1]);
+        stats.add(&quit);
+
+        // The same AI on the same deal makes the same moves, and it's a draw
+        let mirror = race(&gs, [greedy(&gs), greedy(&gs)]);
+        assert_eq!(mirror.winner, None);
+        assert_eq!(mirror.moves[0], mirror.moves[1]);
+        stats.add(&mirror);
+        assert_eq!(stats.wins, [0, 1]);
+        assert_eq!(stats.draws, 1);
+    }
+}
@@ -0,0 +1,141 @@
+//! Personal bests per deal
+//!
+//! With the option `--records FILE`, every game that is played updates a small database with the best result on its
+//! deal, so that runs in different sessions can be compared. The file has one line per deal, with tab separated
+//! fields: the deal code, the score, the number of moves, `won` or `lost`, the player and the file with the moves of
+//! the best game.
+//!
+//! A won game is better than a lost one. Between two wins or two losses, the higher score is better,
+//! and for the same score the shorter game is better.
+
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Errors in the database file
+#[derive(Error, Debug, PartialEq)]
+pub enum RecordsError {
+    #[error("Line {0}: {1}")]
+    Syntax(usize, String),
+}
+
+/// The best result on a deal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub score: i32,
+    pub moves: usize,
+    pub won: bool,
+    /// The AI (or person) that played the game
+    pub player: String,
+    /// The file with the moves of the game
+    pub replay: String,
+}
+
+impl Record {
+    /// Is this a better result than `other`?
+    pub fn beats(&self, other: &Record) -> bool {
+        (self.won, self.score, std::cmp::Reverse(self.moves))
+            > (other.won, other.score, std::cmp::Reverse(other.moves))
+    }
+}
+
+/// The best results, by deal code
+#[derive(Debug, Default, PartialEq)]
+pub struct Records {
+    best: BTreeMap<String, Record>,
+}
+
+impl Records {
+    pub fn parse(text: &str) -> Result<Records, RecordsError> {
+        let mut records = Records::default();
+        for (i, line) in text.lines().enumerate() {
+            let syntax = |msg: &str| RecordsError::Syntax(i + 1, msg.to_string());
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [code, score, moves, won, player, replay] = fields[..] else {
+                return Err(syntax("Expected 6 tab separated fields"));
+            };
+            let record = Record {
+                score: score
+                    .parse()
+                    .map_err(|_| syntax("The score should be an integer"))?,
+                moves: moves
+                    .parse()
+                    .map_err(|_| syntax("The moves should be a number"))?,
+                won: match won {
+                    "won" => true,
+                    "lost" => false,
+                    _ => return Err(syntax("Expected won or lost")),
+                },
+                player: player.to_string(),
+                replay: replay.to_string(),
+            };
+            records.best.insert(code.to_string(), record);
+        }
+        Ok(records)
+    }
+
+    /// The database in the file format
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# deal\tscore\tmoves\tresult\tplayer\treplay\n");
+        for (code, r) in self.best.iter() {
+            let won = if r.won { "won" } else { "lost" };
+            text += &format!(
+                "{code}\t{}\t{}\t{won}\t{}\t{}\n",
+                r.score, r.moves, r.player, r.replay
+            );
+        }
+        text
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Record> {
+        self.best.get(code)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Record)> {
+        self.best.iter()
+    }
+
+    /// Keep the record if it is the best on its deal. Returns true if it was kept.
+    pub fn offer(&mut self, code: &str, record: Record) -> bool {
+        match self.best.get(code) {
+            Some(best) if !record.beats(best) => false,
+            _ => {
+                self.best.insert(code.to_string(), record);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(score: i32, moves: usize, won: bool) -> Record {
+        Record {
+            score,
+            moves,
+            won,
+            player: "GreedyAi (greedy-fast)".to_string(),
+            replay: "best-klondike-0.txt".to_string(),
+        }
+    }
+
+    #[test]
+    fn keeps_the_best_and_round_trips() {
+        let mut records = Records::default();
+        assert!(records.offer("klondike:0", record(100, 200, false)));
+        assert!(!records.offer("klondike:0", record(90, 100, false)));
+        assert!(records.offer("klondike:0", record(100, 150, false)));
+        assert!(records.offer("klondike:0", record(50, 300, true)));
+        assert!(records.offer("klondike:1", record(10, 300, false)));
+        assert_eq!(records.get("klondike:0"), Some(&record(50, 300, true)));
+        assert_eq!(Records::parse(&records.to_text()), Ok(records));
+        assert_eq!(
+            Records::parse("klondike:0\t1\t2\tdrawn\tme\tx.txt"),
+            Err(RecordsError::Syntax(1, "Expected won or lost".to_string()))
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Replay files: the deal and the moves of a game, so that it can be inspected and played again
+//!
+//! A replay is a text file. It starts with a header line with the version of the format, followed by `key: value`
+//! lines with the deal code and the options of the deal, and then one action per line. Lines starting with `#` are
+//! comments.
+//!
+//! ```text
+//! solitaire-replay 8
+//! # MctsAi scored 230 in 156 moves
+//! deal: klondike.1:klondike-classic:11
+//! auto-reveal: false
+//! auto-finish: false
+//! end-when-stuck: false
+//! timed: false
+//! assistance: none
+//! ai: mcts
+//! rng: 7c1f5a0e93d2b684
+//! D3->F1
+//! rng: 0b9e44d1c2a7f365
+//! R D3
+//! ```
+//!
+//! The actions are written in the move notation of `Action`.
+//!
+//! A game played by an AI names its profile, and if the AI draws random numbers, an `rng` line before each action
+//! tells the state of its random number generator when it chose the action, see `Ai::rng_checkpoint`. With them,
+//! `solitaire replay --check-ai` can make the AI play the game again, and point out the first move where it draws
+//! other random numbers or chooses another action.
+//!
+//! Replays are kept in archives of experiments, so files of older versions are still read. They are upgraded to the
+//! current version one version at a time, by the migrations in `MIGRATIONS`, before they are parsed. A change of the
+//! format bumps `VERSION` and adds a migration from the previous version.
+//!
+//! Versions:
+//!  - 1: no header. A comment, the deal code and the actions. The options of the deal were not written down,
+//!    and are taken to be the defaults.
+//!  - 2: the header, and the options of the deal.
+//!  - 3: the profile of the AI that played, and the checkpoints of its random number generator.
+//!  - 4: the actions in the move notation, instead of as written by `Debug`, e.g. `D3->F1` for
+//!    `Move(Depot3, Foundation1, 1)`.
+//!  - 5: the option `auto-finish`. The moves it makes are not written, as the engine makes them again.
+//!  - 6: the option `end-when-stuck`.
+//!  - 7: the option `timed`.
+//!  - 8: the assistance the player had, see `Assistance`.
+
+use crate::core::{parse_action, Action};
+use crate::engine::{Assistance, DealOptions};
+use crate::rules::{DealCode, RulesError};
+use thiserror::Error;
+
+/// The version of the format that is written
+pub const VERSION: u32 = 8;
+
+/// The start of the header line, which is followed by the version
+const HEADER: &str = "solitaire-replay";
+
+/// An upgrade of a replay to the next version
+type Migration = fn(&str) -> String;
+
+/// The upgrades, by version. The first one upgrades version 1 to version 2.
+const MIGRATIONS: [Migration; VERSION as usize - 1] = [
+    v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8,
+];
+
+/// Errors in a replay file
+#[derive(Error, Debug, PartialEq)]
+pub enum ReplayError {
+    #[error("Replay version {0} is newer than this program, which reads up to version {VERSION}")]
+    NewerVersion(u32),
+    #[error("Line {0}: {1}")]
+    Syntax(usize, String),
+    #[error(transparent)]
+    Deal(#[from] RulesError),
+}
+
+/// The deal and the moves of a game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// A line about the game, e.g. who played it and how it went
+    pub comment: String,
+    pub code: DealCode,
+    pub options: DealOptions,
+    pub actions: Vec<Action>,
+    /// The profile of the AI that played the game, if an AI did
+    pub ai: Option<String>,
+    /// The state of the random number generator of the AI before each action, or none if it has none
+    pub rng_checkpoints: Vec<u64>,
+}
+
+impl Replay {
+    /// Parse a replay of any version up to the current one
+    pub fn parse(text: &str) -> Result<Replay, ReplayError> {
+        Replay::parse_current(&upgrade(text)?)
+    }
+
+    fn parse_current(text: &str) -> Result<Replay, ReplayError> {
+        let mut comment = String::new();
+        let mut code = None;
+        let mut options = DealOptions::default();
+        let mut actions = vec![];
+        let mut ai = None;
+        let mut rng_checkpoints = vec![];
+        // The header is checked by `upgrade`
+        for (i, line) in text.lines().enumerate().skip(1) {
+            let syntax = |msg: &str| ReplayError::Syntax(i + 1, msg.to_string());
+            if let Some(text) = line.strip_prefix('#') {
+                if comment.is_empty() {
+                    comment = text.trim().to_string();
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.split_once(": ") {
+                Some(("deal", value)) => code = Some(value.parse::<DealCode>()?),
+                Some(("auto-reveal", value)) => {
+                    options.auto_reveal = value
+                        .parse()
+                        .map_err(|_| syntax("Expected true or false"))?
+                }
+                Some(("auto-finish", value)) => {
+                    options.auto_finish = value
+                        .parse()
+                        .map_err(|_| syntax("Expected true or false"))?
+                }
+                Some(("end-when-stuck", value)) => {
+                    options.end_when_stuck = value
+                        .parse()
+                        .map_err(|_| syntax("Expected true or false"))?
+                }
+                Some(("timed", value)) => {
+                    options.timed = value
+                        .parse()
+                        .map_err(|_| syntax("Expected true or false"))?
+                }
+                Some(("assistance", value)) => {
+                    options.assistance = value.parse::<Assistance>().map_err(|e| syntax(&e))?
+                }
+                Some(("ai", value)) => ai = Some(value.to_string()),
+                Some(("rng", value)) => rng_checkpoints.push(
+                    u64::from_str_radix(value, 16)
+                        .map_err(|_| syntax("Expected a hexadecimal number"))?,
+                ),
+                Some((key, _)) => return Err(syntax(&format!("Unknown key {key}"))),
+                None => actions.push(line.parse().map_err(|_| syntax("Expected an action"))?),
+            }
+        }
+        if !rng_checkpoints.is_empty() && rng_checkpoints.len() != actions.len() {
+            return Err(ReplayError::Syntax(
+                text.lines().count(),
+                "Expected an rng checkpoint before each action".to_string(),
+            ));
+        }
+        Ok(Replay {
+            comment,
+            code: code.ok_or_else(|| ReplayError::Syntax(1, "The deal is missing".to_string()))?,
+            options,
+            actions,
+            ai,
+            rng_checkpoints,
+        })
+    }
+
+    /// The replay in the current version of the format
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{HEADER} {VERSION}\n");
+        if !self.comment.is_empty() {
+            text += &format!("# {}\n", self.comment);
+        }
+        text += &format!("deal: {}\n", self.code);
+        text += &format!("auto-reveal: {}\n", self.options.auto_reveal);
+        text += &format!("auto-finish: {}\n", self.options.auto_finish);
+        text += &format!("end-when-stuck: {}\n", self.options.end_when_stuck);
+        text += &format!("timed: {}\n", self.options.timed);
+        text += &format!("assistance: {}\n", self.options.assistance.name());
+        if let Some(ai) = &self.ai {
+            text += &format!("ai: {ai}\n");
+        }
+        for (i, action) in self.actions.iter().enumerate() {
+            if let Some(checkpoint) = self.rng_checkpoints.get(i) {
+                text += &format!("rng: {checkpoint:016x}\n");
+            }
+            text += &format!("{action}\n");
+        }
+        text
+    }
+}
+
+/// The version of a replay. Files without a header are version 1.
+pub fn version(text: &str) -> Result<u32, ReplayError> {
+    let first_line = text.lines().next().unwrap_or_default();
+    match first_line.strip_prefix(HEADER) {
+        Some(version) => version
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&v| v >= 2)
+            .ok_or_else(|| {
+                ReplayError::Syntax(1, "Expected a version after the header".to_string())
+            }),
+        None => Ok(1),
+    }
+}
+
+/// Upgrade a replay of any version to the current version
+pub fn upgrade(text: &str) -> Result<String, ReplayError> {
+    let version = version(text)?;
+    if version > VERSION {
+        return Err(ReplayError::NewerVersion(version));
+    }
+    Ok(MIGRATIONS[version as usize - 1..]
+        .iter()
+        .fold(text.to_string(), |text, migrate| migrate(&text)))
+}
+
+/// Add the header, name the deal code, and write down the options of the deal, which are the defaults
+fn v1_to_v2(text: &str) -> String {
+    let mut upgraded = format!("{HEADER} 2\n");
+    let mut has_deal = false;
+    for line in text.lines() {
+        if line.starts_with('#') || line.trim().is_empty() || has_deal {
+            upgraded += line;
+        } else {
+            upgraded += &format!("deal: {line}\nauto-reveal: false");
+            has_deal = true;
+        }
+        upgraded.push('\n');
+    }
+    upgraded
+}
+
+/// Bump the version. The AI and its checkpoints are new, and older replays have neither.
+fn v2_to_v3(text: &str) -> String {
+    let (_, rest) = text.split_once('\n').unwrap_or((text, ""));
+    format!("{HEADER} 3\n{rest}")
+}
+
+/// Write the actions in the move notation. Lines that are not actions are kept as they are.
+fn v3_to_v4(text: &str) -> String {
+    let mut upgraded = format!("{HEADER} 4\n");
+    for line in text.lines().skip(1) {
+        match parse_action(line) {
+            Some(action) if !line.starts_with('#') => upgraded += &action.to_string(),
+            _ => upgraded += line,
+        }
+        upgraded.push('\n');
+    }
+    upgraded
+}
+
+/// Write down the option to finish automatically, which older versions didn't have
+fn v4_to_v5(text: &str) -> String {
+    let mut upgraded = format!("{HEADER} 5\n");
+    for line in text.lines().skip(1) {
+        upgraded += line;
+        if line.starts_with("auto-reveal: ") {
+            upgraded += "\nauto-finish: false";
+        }
+        upgraded.push('\n');
+    }
+    upgraded
+}
+
+/// Write down the option to end stuck games, which older versions didn't have
+fn v5_to_v6(text: &str) -> String {
+    let mut upgraded = format!("{HEADER} 6\n");
+    for line in text.lines().skip(1) {
+        upgraded += line;
+        if line.starts_with("auto-finish: ") {
+            upgraded += "\nend-when-stuck: false";
+        }
+        upgraded.push('\n');
+    }
+    upgraded
+}
+
+/// Write down the option to time the game, which older versions didn't have
+fn v6_to_v7(text: &str) -> String {
+    let mut upgraded = format!("{HEADER} 7\n");
+    for line in text.lines().skip(1) {
+        upgraded += line;
+        if line.starts_with("end-when-stuck: ") {
+            upgraded += "\ntimed: false";
+        }
+        upgraded.push('\n');
+    }
+    upgraded
+}
+
+/// Write down the assistance of the player, which older versions didn't have
+fn v7_to_v8(text: &str) -> String {
+    let mut upgraded = format!("{HEADER} 8\n");
+    for line in text.lines().skip(1) {
+        upgraded += line;
+        if line.starts_with("timed: ") {
+            upgraded += "\nassistance: none";
+        }
+        upgraded.push('\n');
+    }
+    upgraded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Addr;
+    use crate::engine::GameEngine;
+
+    /// A game written by the first version, before replays had a header
+    const V1: &str = include_str!("../fixtures/replay-v1.txt");
+
+    #[test]
+    fn reads_version_1() {
+        assert_eq!(version(V1), Ok(1));
+        let replay = Replay::parse(V1).unwrap();
+        assert_eq!(replay.comment, "GreedyAi scored 230 in 156 moves");
+        assert_eq!(
+            replay.code,
+            "klondike.1:klondike-classic:11".parse().unwrap()
+        );
+        assert_eq!(replay.options, DealOptions::default());
+        assert_eq!(replay.actions.len(), 156);
+        assert_eq!(
+            replay.actions[0],
+            Action::Move(Addr::Depot3, Addr::Foundation1, 1)
+        );
+
+        // The moves are still legal on the deal, and end the same way
+        let mut gs = GameEngine::builder()
+            .seed(replay.code.seed)
+            .rules(replay.code.rules().unwrap())
+            .options(replay.options.clone())
+            .build()
+            .unwrap();
+        for action in &replay.actions {
+            gs.act(action).unwrap();
+        }
+        assert_eq!(gs.score(), 230);
+        assert!(!gs.is_running());
+
+        let text = replay.to_text();
+        assert_eq!(version(&text), Ok(VERSION));
+        assert_eq!(upgrade(V1), Ok(text.clone()));
+        assert_eq!(Replay::parse(&text), Ok(replay));
+    }
+
+    /// The AI and its checkpoints are written down and read back
+    #[test]
+    fn ai_checkpoints() {
+        let replay = Replay {
+            comment: "MctsAi lost".to_string(),
+            code: "klondike.1:klondike-classic:3".parse().unwrap(),
+            options: DealOptions::default(),
+            actions: vec![Action::Take, Action::Quit],
+            ai: Some("mcts".to_string()),
+            rng_checkpoints: vec![0x7c1f5a0e93d2b684, 12],
+        };
+        let text = replay.to_text();
+        assert!(text.ends_with("ai: mcts\nrng: 7c1f5a0e93d2b684\nT\nrng: 000000000000000c\nQ\n"));
+        assert_eq!(Replay::parse(&text), Ok(replay.clone()));
+        // Version 3 wrote the actions as by `Debug`
+        let v3 = text
+            .replace("replay 8", "replay 3")
+            .replace("auto-finish: false\n", "")
+            .replace("end-when-stuck: false\n", "")
+            .replace("timed: false\n", "")
+            .replace("assistance: none\n", "")
+            .replace("\nT\n", "\nTake\n")
+            .replace("\nQ\n", "\nQuit\n");
+        assert_eq!(upgrade(&v3), Ok(text.clone()));
+        assert_eq!(Replay::parse(&v3), Ok(replay));
+        let missing = text.replace("rng: 000000000000000c\n", "");
+        assert_eq!(
+            Replay::parse(&missing),
+            Err(ReplayError::Syntax(
+                12,
+                "Expected an rng checkpoint before each action".to_string()
+            ))
+        );
+    }
+
+    /// A timed game is still timed when it is read back, and older replays were not timed
+    #[test]
+    fn timed_games() {
+        let replay = Replay {
+            comment: String::new(),
+            code: "klondike.1:klondike-classic:3".parse().unwrap(),
+            options: DealOptions {
+                timed: true,
+                ..DealOptions::default()
+            },
+            actions: vec![Action::Take],
+            ai: None,
+            rng_checkpoints: vec![],
+        };
+        let text = replay.to_text();
+        assert!(text.contains("\ntimed: true\n"));
+        assert_eq!(Replay::parse(&text), Ok(replay.clone()));
+        let v6 = text
+            .replace("replay 8", "replay 6")
+            .replace("timed: true\n", "")
+            .replace("assistance: none\n", "");
+        let upgraded = Replay::parse(&v6).unwrap();
+        assert_eq!(upgraded.options, DealOptions::default());
+        assert_eq!(
+            upgraded.to_text(),
+            text.replace("timed: true", "timed: false")
+        );
+    }
+
+    /// The assistance of the player is read back, and older replays had none
+    #[test]
+    fn assistance() {
+        let replay = Replay {
+            comment: String::new(),
+            code: "klondike.1:klondike-classic:3".parse().unwrap(),
+            options: DealOptions {
+                assistance: Assistance::SolverMoves,
+                ..DealOptions::default()
+            },
+            actions: vec![Action::Take],
+            ai: None,
+            rng_checkpoints: vec![],
+        };
+        let text = replay.to_text();
+        assert!(text.contains("\nassistance: solver\n"));
+        assert_eq!(Replay::parse(&text), Ok(replay.clone()));
+        let v7 = text
+            .replace("replay 8", "replay 7")
+            .replace("assistance: solver\n", "");
+        assert_eq!(Replay::parse(&v7).unwrap().options, DealOptions::default());
+        assert_eq!(
+            Replay::parse(&text.replace("solver", "lots")),
+            Err(ReplayError::Syntax(
+                7,
+                "Unknown assistance lots, use none, playable, solver or no-losing".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert_eq!(
+            Replay::parse("solitaire-replay 99\ndeal: 1\n"),
+            Err(ReplayError::NewerVersion(99))
+        );
+        assert_eq!(
+            Replay::parse("solitaire-replay 8\ndeal: 1\ntimed: true\nFly(Waste)\n"),
+            Err(ReplayError::Syntax(4, "Expected an action".to_string()))
+        );
+    }
+}
@@ -0,0 +1,477 @@
+//! Post-game reports: how a game was won or lost, as Markdown or HTML
+//!
+//! A report looks back at a game, played by you or by an AI, and answers three questions
+//!  - where were the turning points? These are the actions after which the estimated chance to win dropped the most,
+//!    see `analysis::win_probability`. The estimate only uses what the player could see at the time.
+//!  - which foundation moves were missed? A card could go to its foundation without being missed in the depots, see
+//!    `analysis::is_safe_to_foundation`, and the player did something else. A move that is passed up for several
+//!    actions in a row is reported once.
+//!  - when was the game lost? After the game all cards are known, so the solver looks at the whole deal, see
+//!    `solver`. The positions are proven lost from the end of the game backwards, until the solver finds a
+//!    way to win or gives up. In the latter case the game may have been lost earlier than reported.
+//!
+//! `solitaire report FILE` prints the report on a replay file, and `solitaire play --report FILE` writes the one on
+//! your game.
+
+use crate::analysis::{is_safe_to_foundation, win_probability};
+use crate::core::{Action, Addr, CardView, MoveError, Outcome};
+use crate::engine::GameEngine;
+use crate::solver::{Solution, Solver};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use thiserror::Error;
+
+/// The number of turning points in a report
+const N_TURNING_POINTS: usize = 3;
+
+/// The seed of the rollouts. All positions use the same one, so that their estimates are comparable.
+const ROLLOUT_SEED: u64 = 0;
+
+/// How much effort goes into a report
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The rollouts per position that estimate the chance to win
+    pub n_rollouts: usize,
+    /// The positions the solver visits at most, for each position of the game
+    pub node_limit: usize,
+    /// The positions of the game the solver looks at at most, going back from the end, which bounds the work on a
+    /// long game to `max_solves * node_limit`
+    pub max_solves: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            n_rollouts: 20,
+            node_limit: 100_000,
+            max_solves: 50,
+        }
+    }
+}
+
+/// An action after which the chance to win dropped
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurningPoint {
+    /// The number of the action, from 1
+    pub move_number: usize,
+    pub action: Action,
+    /// The estimated chance to win before and after the action
+    pub before: f64,
+    pub after: f64,
+}
+
+/// A safe move to a foundation that the player didn't make
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissedMove {
+    pub move_number: usize,
+    pub played: Action,
+    pub missed: Action,
+}
+
+/// When the game was lost, as far as the solver can tell
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Won,
+    /// The deal could not be won from the start
+    Unwinnable,
+    /// The game could not be won after this action. If `proven`, it could be won before it, else the solver gave up
+    /// on the position before it, or didn't look at it, see `Settings::max_solves`.
+    LostWith {
+        move_number: usize,
+        action: Action,
+        proven: bool,
+    },
+    /// The game could still be won when it ended, in this many moves
+    Winnable(usize),
+    /// The solver gave up on the position at the end of the game
+    Unknown,
+}
+
+/// An action of the game is not legal
+#[derive(Error, Debug)]
+#[error("Move {move_number} {action} is illegal: {source}")]
+pub struct IllegalMove {
+    move_number: usize,
+    action: Action,
+    source: MoveError,
+}
+
+/// The findings on a game
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub outcome: Option<Outcome>,
+    pub score: i32,
+    pub n_moves: usize,
+    pub n_rollouts: usize,
+    pub turning_points: Vec<TurningPoint>,
+    pub missed_moves: Vec<MissedMove>,
+    pub verdict: Verdict,
+}
+
+/// Analyze a game that started in `start` and was played with `actions`
+pub fn analyze(
+    start: &GameEngine,
+    actions: &[Action],
+    settings: &Settings,
+) -> Result<Report, IllegalMove> {
+    let mut gs = start.clone();
+    let mut probabilities = HashMap::new();
+    let mut probability = |gs: &GameEngine| {
+        let view = gs.observe();
+        *probabilities.entry(view.clone()).or_insert_with(|| {
+            win_probability(&view, gs.rules(), settings.n_rollouts, ROLLOUT_SEED)
+        })
+    };
+    let mut drops = vec![];
+    let mut missed_moves = vec![];
+    let mut passed_up = vec![];
+    for (i, action) in actions.iter().enumerate() {
+        let before = probability(&gs);
+        let missed = safe_foundation_moves(&gs);
+        if !is_to_foundation(action) {
+            missed_moves.extend(missed.iter().filter(|m| !passed_up.contains(*m)).map(|m| {
+                MissedMove {
+                    move_number: i + 1,
+                    played: action.clone(),
+                    missed: m.clone(),
+                }
+            }));
+        }
+        passed_up = missed;
+        gs.act(action).map_err(|source| IllegalMove {
+            move_number: i + 1,
+            action: action.clone(),
+            source,
+        })?;
+        if *action != Action::Quit {
+            drops.push(TurningPoint {
+                move_number: i + 1,
+                action: action.clone(),
+                before,
+                after: probability(&gs),
+            });
+        }
+    }
+    drops.retain(|t| t.after < t.before);
+    drops.sort_by(|a, b| (b.before - b.after).total_cmp(&(a.before - a.after)));
+    drops.truncate(N_TURNING_POINTS);
+    let (outcome, score) = (gs.outcome(), gs.score());
+    let verdict = judge(gs, actions, settings);
+    Ok(Report {
+        outcome,
+        score,
+        n_moves: actions.len(),
+        n_rollouts: settings.n_rollouts,
+        turning_points: drops,
+        missed_moves,
+        verdict,
+    })
+}
+
+fn is_to_foundation(action: &Action) -> bool {
+    match action {
+        Action::Move(_, to, _) | Action::MoveFoundationPile(_, to) => to.is_foundation(),
+        _ => false,
+    }
+}
+
+/// The legal moves of a single card to a foundation that can't hurt, one for each card
+fn safe_foundation_moves(gs: &GameEngine) -> Vec<Action> {
+    let view = gs.observe();
+    let is_safe = |from: &Addr| match view.card_at(from, 1) {
+        Some(CardView::FaceUp(suit, value)) => is_safe_to_foundation(&view, (suit, value)),
+        _ => false,
+    };
+    Addr::DEPOTS_AND_WASTE
+        .into_iter()
+        .filter(is_safe)
+        .filter_map(|from| {
+            Addr::FOUNDATIONS
+                .map(|to| Action::Move(from, to, 1))
+                .into_iter()
+                .find(|action| gs.is_legal(action))
+        })
+        .collect()
+}
+
+/// Find the first position the solver proves lost, going back from the end of the game
+fn judge(mut gs: GameEngine, actions: &[Action], settings: &Settings) -> Verdict {
+    if gs.is_won() {
+        return Verdict::Won;
+    }
+    let mut n = actions.len();
+    if actions.last() == Some(&Action::Quit) {
+        gs.undo();
+        n -= 1;
+    }
+    let solver = Solver::new().with_node_limit(settings.node_limit);
+    let mut solutions = HashMap::new();
+    let mut lost_from = None;
+    for _ in 0..settings.max_solves.max(1) {
+        let mut hasher = DefaultHasher::new();
+        gs.hash(&mut hasher);
+        let solution = solutions
+            .entry(hasher.finish())
+            .or_insert_with(|| solver.solve(&gs));
+        match (solution, lost_from) {
+            (Solution::Lost, _) => lost_from = Some(n),
+            (Solution::Won(line), None) => return Verdict::Winnable(line.len()),
+            (Solution::Unknown, None) => return Verdict::Unknown,
+            (solution, Some(n)) => {
+                return Verdict::LostWith {
+                    move_number: n,
+                    action: actions[n - 1].clone(),
+                    proven: matches!(solution, Solution::Won(_)),
+                }
+            }
+        }
+        if n == 0 {
+            return Verdict::Unwinnable;
+        }
+        gs.undo();
+        n -= 1;
+    }
+    let n = lost_from.expect("Only a lost position is looked back from");
+    Verdict::LostWith {
+        move_number: n,
+        action: actions[n - 1].clone(),
+        proven: false,
+    }
+}
+
+/// A part of a report: a heading, a paragraph, and a table if there is anything to list
+struct Section {
+    title: &'static str,
+    text: String,
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl Report {
+    fn summary(&self) -> String {
+        match self.outcome {
+            Some(Outcome::Won { .. }) => {
+                format!("Won with score {} in {} moves.", self.score, self.n_moves)
+            }
+            Some(Outcome::Lost { reason }) => format!(
+                "Lost ({reason:?}) with score {} after {} moves.",
+                self.score, self.n_moves
+            ),
+            None => format!(
+                "Stopped with score {} after {} moves.",
+                self.score, self.n_moves
+            ),
+        }
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let percent = |p: f64| format!("{:.0}%", 100.0 * p);
+        let turning_points = Section {
+            title: "Turning points",
+            text: format!(
+                "The actions after which the estimated chance to win dropped the most, from {} rollouts per position.",
+                self.n_rollouts
+            ),
+            header: &["Move", "Action", "Before", "After"],
+            rows: self
+                .turning_points
+                .iter()
+                .map(|t| {
+                    vec![
+                        t.move_number.to_string(),
+                        t.action.to_string(),
+                        percent(t.before),
+                        percent(t.after),
+                    ]
+                })
+                .collect(),
+        };
+        let missed_moves = Section {
+            title: "Missed foundation moves",
+            text: format!(
+                "Cards that could safely go to their foundations while something else was played: {}.",
+                self.missed_moves.len()
+            ),
+            header: &["Move", "Played", "Missed"],
+            rows: self
+                .missed_moves
+                .iter()
+                .map(|m| {
+                    vec![
+                        m.move_number.to_string(),
+                        m.played.to_string(),
+                        m.missed.to_string(),
+                    ]
+                })
+                .collect(),
+        };
+        let verdict = match &self.verdict {
+            Verdict::Won => "The game was won.".to_string(),
+            Verdict::Unwinnable => "The deal could not be won from the start.".to_string(),
+            Verdict::LostWith { move_number, action, proven: true } => format!(
+                "Move {move_number}, {action}, lost the game: the deal could be won before it, and not after it."
+            ),
+            Verdict::LostWith { move_number, action, proven: false } => format!(
+                "The game was lost with move {move_number}, {action}, at the latest. \
+                 The solver gave up on the position before it, or didn't get to it."
+            ),
+            Verdict::Winnable(n) => format!("The game could still be won in {n} more moves when it ended."),
+            Verdict::Unknown => {
+                "The solver could not tell whether the game could still be won when it ended.".to_string()
+            }
+        };
+        let verdict = Section {
+            title: "Verdict",
+            text: verdict,
+            header: &[],
+            rows: vec![],
+        };
+        vec![turning_points, missed_moves, verdict]
+    }
+
+    /// The report as Markdown, under a title such as the deal and who played it
+    pub fn to_markdown(&self, title: &str) -> String {
+        let mut text = format!("# {title}\n\n{}\n", self.summary());
+        for section in self.sections() {
+            text += &format!("\n## {}\n\n{}\n", section.title, section.text);
+            if section.rows.is_empty() {
+                continue;
+            }
+            text += &format!("\n| {} |\n", section.header.join(" | "));
+            text += &format!("|{}\n", " --- |".repeat(section.header.len()));
+            for row in &section.rows {
+                text += &format!("| {} |\n", row.join(" | "));
+            }
+        }
+        text
+    }
+
+    /// The report as a web page of its own
+    pub fn to_html(&self, title: &str) -> String {
+        let title = escape_html(title);
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n\
+             <h1>{title}</h1>\n<p>{}</p>\n",
+            self.summary()
+        );
+        for section in self.sections() {
+            html += &format!(
+                "<h2>{}</h2>\n<p>{}</p>\n",
+                section.title,
+                escape_html(&section.text)
+            );
+            if section.rows.is_empty() {
+                continue;
+            }
+            let cells = |tag: &str, row: &[String]| {
+                row.iter()
+                    .map(|cell| format!("<{tag}>{}</{tag}>", escape_html(cell)))
+                    .collect::<String>()
+            };
+            let header = section
+                .header
+                .iter()
+                .map(|h| h.to_string())
+                .collect::<Vec<_>>();
+            html += &format!("<table>\n<tr>{}</tr>\n", cells("th", &header));
+            for row in &section.rows {
+                html += &format!("<tr>{}</tr>\n", cells("td", row));
+            }
+            html += "</table>\n";
+        }
+        html + "</body>\n</html>\n"
+    }
+}
+
+/// Escape the characters that have a meaning in HTML, such as the `>` of the move notation
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn reports_on_games() {
+        // A winning line from the solver, without rollouts to keep it quick
+        let start = GameEngine::deal(7);
+        let Solution::Won(line) = Solver::new().solve(&start) else {
+            panic!("The deal can be won");
+        };
+        let quick = Settings {
+            n_rollouts: 0,
+            ..Settings::default()
+        };
+        let report = analyze(&start, &line, &quick).unwrap();
+        assert_eq!(report.verdict, Verdict::Won);
+        assert!(report.turning_points.is_empty());
+        let markdown = report.to_markdown("Deal 7");
+        assert!(
+            markdown.starts_with("# Deal 7\n\nWon with score"),
+            "{markdown}"
+        );
+        assert!(report
+            .to_html("Deal 7")
+            .contains("<h2>Verdict</h2>\n<p>The game was won.</p>"));
+
+        // Giving up at once misses the ace in depot 6
+        let start = GameEngine::deal(2);
+        let settings = Settings {
+            n_rollouts: 4,
+            node_limit: 1000,
+            max_solves: 10,
+        };
+        let quit = analyze(&start, &[Action::Take, Action::Quit], &settings).unwrap();
+        assert_eq!(quit.missed_moves.len(), 1, "{quit:?}");
+        assert!(quit.to_markdown("Quit").contains("| 1 | T | D6->F1 |"));
+        assert!(quit.turning_points.len() <= 1);
+        assert!(analyze(&start, &[Action::Turnover], &settings).is_err());
+    }
+
+    #[test]
+    fn reports_on_draw_three() {
+        // The talon doesn't come back to where it started once a card was played from the waste
+        let start = GameEngine::builder()
+            .seed(0)
+            .rules(RuleSet::KLONDIKE_DRAW3)
+            .build()
+            .unwrap();
+        let settings = Settings {
+            n_rollouts: 0,
+            ..Settings::default()
+        };
+        let actions = [Action::Take, Action::Take, Action::Quit];
+        let report = analyze(&start, &actions, &settings).unwrap();
+        assert!(matches!(report.verdict, Verdict::Winnable(_)), "{report:?}");
+    }
+
+    #[test]
+    fn bounds_the_solves() {
+        // Deal 10 can't be won, so every position back to the start is proven lost, unless the solver stops looking
+        let start = GameEngine::deal(10);
+        let actions = [Action::Take, Action::Take, Action::Take, Action::Quit];
+        let settings = Settings {
+            n_rollouts: 0,
+            node_limit: 1000,
+            max_solves: 2,
+        };
+        let report = analyze(&start, &actions, &settings).unwrap();
+        let expected = Verdict::LostWith {
+            move_number: 2,
+            action: Action::Take,
+            proven: false,
+        };
+        assert_eq!(report.verdict, expected);
+        let settings = Settings {
+            max_solves: 4,
+            ..settings
+        };
+        let report = analyze(&start, &actions, &settings).unwrap();
+        assert_eq!(report.verdict, Verdict::Unwinnable);
+    }
+}
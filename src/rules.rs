@@ -0,0 +1,701 @@
+//! The rules of the game, described as data
+//!
+//! There are many variations of Klondike, and a few variants that are played on the same tableau.
+//! To never be ambiguous about what rules some results were obtained with, the rule set is spelled out here and printed at the start of every run.
+//! The common variations are available as named presets, and a deal code names the variant, the preset and the seed of a deal.
+use crate::core::{Action, Suit, Value, MAX_TURNED};
+use thiserror::Error;
+
+/// Which cards may be placed on an empty depot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EmptyColumnRule {
+    /// Only a king (possibly with a sequence on top) may fill an empty depot
+    KingsOnly,
+    /// Any face up card or sequence may fill an empty depot
+    AnyCard,
+}
+
+/// Which cards may be placed on a card in a depot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BuildRule {
+    /// A card of the other color, one lower
+    AlternateColors,
+    /// A card of the same suit, one lower
+    SameSuit,
+}
+
+impl BuildRule {
+    /// May `card` be placed on `onto` in a depot?
+    pub fn fits(&self, card: (Suit, Value), onto: (Suit, Value)) -> bool {
+        card.1.numeric_value() + 1 == onto.1.numeric_value()
+            && match self {
+                BuildRule::AlternateColors => card.0.color() != onto.0.color(),
+                BuildRule::SameSuit => card.0 == onto.0,
+            }
+    }
+}
+
+/// How points are awarded during a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Scoring {
+    /// The rules at <https://australiancardgames.com.au/solitaire/>
+    Standard,
+    /// Pay 52 points for the deal, and win 5 points for every card on the foundations
+    Vegas,
+}
+
+/// The points awarded for each kind of action, as data
+///
+/// Moves between depots, taking from the talon and quitting are worth nothing in all policies.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScoringPolicy {
+    /// The score at the deal
+    pub initial: i32,
+    pub waste_to_foundation: i32,
+    pub waste_to_depot: i32,
+    pub depot_to_foundation: i32,
+    pub foundation_to_depot: i32,
+    pub reveal: i32,
+    pub turnover: i32,
+    /// The score never goes below zero
+    pub floor_at_zero: bool,
+    /// The points for every ten seconds of a timed game, see `DealOptions::timed`
+    pub time_penalty: i32,
+    /// The bonus for winning a timed game is this divided by the seconds it took, if it took at least thirty
+    pub time_bonus: i32,
+}
+
+impl ScoringPolicy {
+    pub const STANDARD: ScoringPolicy = ScoringPolicy {
+        initial: 0,
+        waste_to_foundation: 10,
+        waste_to_depot: 5,
+        depot_to_foundation: 10,
+        foundation_to_depot: -15,
+        reveal: 5,
+        turnover: -100,
+        floor_at_zero: true,
+        time_penalty: -2,
+        time_bonus: 700_000,
+    };
+
+    pub const VEGAS: ScoringPolicy = ScoringPolicy {
+        initial: -52,
+        waste_to_foundation: 5,
+        waste_to_depot: 0,
+        depot_to_foundation: 5,
+        foundation_to_depot: -5,
+        reveal: 0,
+        turnover: 0,
+        floor_at_zero: false,
+        time_penalty: 0,
+        time_bonus: 0,
+    };
+
+    /// The points for an action, assuming it is legal
+    pub fn points(&self, action: &Action) -> i32 {
+        match action {
+            Action::Move(from, to, _) => {
+                if from.is_waste() && to.is_foundation() {
+                    self.waste_to_foundation
+                } else if from.is_waste() && to.is_depot() {
+                    self.waste_to_depot
+                } else if from.is_depot() && to.is_foundation() {
+                    self.depot_to_foundation
+                } else if from.is_foundation() && to.is_depot() {
+                    self.foundation_to_depot
+                } else {
+                    0
+                }
+            }
+            Action::Reveal(_) => self.reveal,
+            Action::Turnover => self.turnover,
+            Action::Take | Action::Quit | Action::MoveFoundationPile(..) => 0,
+        }
+    }
+
+    /// The points for the time a timed game has taken, with the bonus if it was won
+    pub fn time_points(&self, seconds: u64, won: bool) -> i32 {
+        let penalty = self.time_penalty * (seconds / 10) as i32;
+        let bonus = if won && seconds >= 30 {
+            self.time_bonus / seconds as i32
+        } else {
+            0
+        };
+        penalty + bonus
+    }
+
+    /// The score after an action with the given points
+    pub fn add(&self, score: i32, points: i32) -> i32 {
+        if self.floor_at_zero {
+            (score + points).max(0)
+        } else {
+            score + points
+        }
+    }
+
+    /// The points of each kind of action, for display
+    pub fn table(&self) -> Vec<(&'static str, i32)> {
+        vec![
+            ("waste to foundation", self.waste_to_foundation),
+            ("waste to depot", self.waste_to_depot),
+            ("depot to foundation", self.depot_to_foundation),
+            ("foundation to depot", self.foundation_to_depot),
+            ("reveal", self.reveal),
+            ("turnover", self.turnover),
+        ]
+    }
+}
+
+impl Scoring {
+    pub fn policy(&self) -> ScoringPolicy {
+        match self {
+            Scoring::Standard => ScoringPolicy::STANDARD,
+            Scoring::Vegas => ScoringPolicy::VEGAS,
+        }
+    }
+}
+
+/// A complete rule set for a game of Klondike, or of a variant played on the same tableau
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuleSet {
+    /// The variant decides how the cards are dealt
+    pub variant: Variant,
+    /// How many cards are turned from the talon to the waste on each take, at most `core::MAX_TURNED`
+    pub draw: u8,
+    /// How many passes through the talon are allowed. `None` means no limit
+    pub passes: Option<u32>,
+    pub empty_column: EmptyColumnRule,
+    pub building: BuildRule,
+    /// May cards be moved back from the foundations to the depots?
+    pub foundation_retreat: bool,
+    /// May a whole foundation pile be moved to an empty foundation? A house rule of some apps.
+    pub foundation_pile_moves: bool,
+    pub scoring: Scoring,
+    /// Are the face down cards known to the player? (Thoughtful solitaire)
+    pub open_information: bool,
+}
+
+impl RuleSet {
+    /// The most common rules, and the default ones
+    pub const KLONDIKE_CLASSIC: RuleSet = RuleSet {
+        variant: Variant::Klondike,
+        draw: 1,
+        passes: None,
+        empty_column: EmptyColumnRule::KingsOnly,
+        building: BuildRule::AlternateColors,
+        foundation_retreat: true,
+        foundation_pile_moves: false,
+        scoring: Scoring::Standard,
+        open_information: false,
+    };
+
+    /// Casino rules. Draw three, at most three passes, and you pay for the deal.
+    pub const VEGAS_DRAW3: RuleSet = RuleSet {
+        draw: 3,
+        passes: Some(3),
+        foundation_retreat: false,
+        scoring: Scoring::Vegas,
+        ..RuleSet::KLONDIKE_CLASSIC
+    };
+
+    /// Casino rules with one card at a time, and a single pass through the talon
+    pub const VEGAS_DRAW1: RuleSet = RuleSet {
+        draw: 1,
+        passes: Some(1),
+        ..RuleSet::VEGAS_DRAW3
+    };
+
+    /// Classic rules, but three cards are turned from the talon at a time
+    pub const KLONDIKE_DRAW3: RuleSet = RuleSet {
+        draw: 3,
+        ..RuleSet::KLONDIKE_CLASSIC
+    };
+
+    /// Classic rules, but all cards are known to the player
+    pub const THOUGHTFUL: RuleSet = RuleSet {
+        open_information: true,
+        ..RuleSet::KLONDIKE_CLASSIC
+    };
+
+    /// Lenient rules, where any card can fill an empty depot
+    pub const SOFTIE: RuleSet = RuleSet {
+        empty_column: EmptyColumnRule::AnyCard,
+        ..RuleSet::KLONDIKE_CLASSIC
+    };
+
+    /// Classic rules with the house rule of some apps, where a foundation pile can be moved to an empty foundation
+    pub const CASUAL: RuleSet = RuleSet {
+        foundation_pile_moves: true,
+        ..RuleSet::KLONDIKE_CLASSIC
+    };
+
+    /// Yukon. All cards are dealt to the depots, and any face up group of cards can be moved
+    pub const YUKON: RuleSet = RuleSet {
+        variant: Variant::Yukon,
+        ..RuleSet::KLONDIKE_CLASSIC
+    };
+
+    /// Russian solitaire. Yukon, but building down in suit
+    pub const RUSSIAN: RuleSet = RuleSet {
+        variant: Variant::Russian,
+        building: BuildRule::SameSuit,
+        ..RuleSet::KLONDIKE_CLASSIC
+    };
+
+    /// The named rule sets
+    pub const PRESETS: [(&'static str, RuleSet); 9] = [
+        ("klondike-classic", RuleSet::KLONDIKE_CLASSIC),
+        ("klondike-draw3", RuleSet::KLONDIKE_DRAW3),
+        ("vegas-draw3", RuleSet::VEGAS_DRAW3),
+        ("vegas-draw1", RuleSet::VEGAS_DRAW1),
+        ("thoughtful", RuleSet::THOUGHTFUL),
+        ("softie", RuleSet::SOFTIE),
+        ("casual", RuleSet::CASUAL),
+        ("yukon", RuleSet::YUKON),
+        ("russian", RuleSet::RUSSIAN),
+    ];
+
+    /// Look up a named rule set
+    pub fn preset(name: &str) -> Result<RuleSet, RulesError> {
+        RuleSet::PRESETS
+            .iter()
+            .find(|(preset_name, _)| *preset_name == name)
+            .map(|(_, rules)| rules.clone())
+            .ok_or_else(|| RulesError::UnknownPreset(name.to_string()))
+    }
+
+    /// How many times the waste may be turned over into the talon, one less than the passes. `None` means no limit
+    pub fn redeals(&self) -> Option<u32> {
+        self.passes.map(|n| n.saturating_sub(1))
+    }
+
+    /// Check that the rules make sense together
+    pub fn validate(&self) -> Result<(), RulesError> {
+        if self.draw == 0 {
+            return Err(RulesError::Invalid("drawing no cards"));
+        }
+        if self.draw as usize > MAX_TURNED {
+            return Err(RulesError::Invalid("drawing more than three cards"));
+        }
+        if self.passes == Some(0) {
+            return Err(RulesError::Invalid("no passes through the talon"));
+        }
+        if self.scoring == Scoring::Vegas && self.passes.is_none() {
+            // With unlimited passes every card can be played eventually, and the bet is meaningless
+            return Err(RulesError::Incompatible(
+                "Vegas scoring",
+                "unlimited passes",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::KLONDIKE_CLASSIC
+    }
+}
+
+/// Errors with selecting rules
+#[derive(Error, Debug, PartialEq)]
+pub enum RulesError {
+    #[error("Unknown rule preset {0}")]
+    UnknownPreset(String),
+    #[error("Invalid deal code {0}")]
+    InvalidDealCode(String),
+    #[error("Unknown variant {0}")]
+    UnknownVariant(String),
+    /// The variant has an engine of its own, see `Variant::is_tableau`
+    #[error("{0} is not played on the Klondike tableau")]
+    NotOnTableau(&'static str),
+    /// The deal code was made with other options than the current ones, and would deal another game
+    #[error("Deal codes for {0} are at options version {1}, not {2}")]
+    UnsupportedVersion(&'static str, u32, u32),
+    /// A single rule has a value that makes no sense
+    #[error("Invalid rules: {0}")]
+    Invalid(&'static str),
+    #[error("{0} cannot be combined with {1}")]
+    Incompatible(&'static str, &'static str),
+}
+
+/// A family of solitaire games. The rule presets belong to a variant.
+///
+/// The variants from Golf on are not played on the Klondike tableau. They have engines of their own, and a single
+/// preset named after the variant, see `solitaire variant`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    /// Seven depots with one to seven cards, and the rest in the talon
+    Klondike,
+    /// Seven depots with one to eleven cards, five of them face up in all depots but the first. No talon.
+    Yukon,
+    /// Dealt like Yukon
+    Russian,
+    Golf,
+    TriPeaks,
+    Canfield,
+    Clock,
+    Accordion,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 8] = [
+        Variant::Klondike,
+        Variant::Yukon,
+        Variant::Russian,
+        Variant::Golf,
+        Variant::TriPeaks,
+        Variant::Canfield,
+        Variant::Clock,
+        Variant::Accordion,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Klondike => "klondike",
+            Variant::Yukon => "yukon",
+            Variant::Russian => "russian",
+            Variant::Golf => "golf",
+            Variant::TriPeaks => "tripeaks",
+            Variant::Canfield => "canfield",
+            Variant::Clock => "clock",
+            Variant::Accordion => "accordion",
+        }
+    }
+
+    /// Is the variant played on the Klondike tableau, by `GameEngine` and with the rule presets?
+    pub fn is_tableau(&self) -> bool {
+        matches!(self, Variant::Klondike | Variant::Yukon | Variant::Russian)
+    }
+
+    /// The version of the presets and the dealing of the variant.
+    /// It is bumped when an old deal code would deal another game, so that old codes are rejected.
+    pub fn options_version(&self) -> u32 {
+        match self {
+            Variant::Klondike | Variant::Yukon | Variant::Russian => 1,
+            Variant::Golf
+            | Variant::TriPeaks
+            | Variant::Canfield
+            | Variant::Clock
+            | Variant::Accordion => 1,
+        }
+    }
+
+    /// Parse `NAME.VERSION`, or just `NAME` for the current version
+    pub fn parse_versioned(s: &str) -> Result<Variant, RulesError> {
+        let (name, version) = s.split_once('.').unwrap_or((s, ""));
+        let variant = Variant::ALL
+            .into_iter()
+            .find(|v| v.name() == name)
+            .ok_or_else(|| RulesError::UnknownVariant(name.to_string()))?;
+        if !version.is_empty() {
+            let version = version
+                .parse()
+                .map_err(|_| RulesError::InvalidDealCode(s.to_string()))?;
+            if version != variant.options_version() {
+                return Err(RulesError::UnsupportedVersion(
+                    variant.name(),
+                    variant.options_version(),
+                    version,
+                ));
+            }
+        }
+        Ok(variant)
+    }
+}
+
+/// A deal code identifies a deal, including the variant and the rules it is played by
+///
+/// It is written as `VARIANT.VERSION:PRESET:SEED`, e.g. `klondike.1:vegas-draw3:42`, where the version is the
+/// options version of the variant. Shorter codes are at the current version: `PRESET:SEED`, and a bare seed means
+/// the default rules. A variant that is not played on the Klondike tableau has one preset, its name, as in
+/// `golf.1:golf:42`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DealCode {
+    pub variant: Variant,
+    pub preset: &'static str,
+    pub seed: u64,
+}
+
+impl DealCode {
+    pub fn new(preset: &str, seed: u64) -> Result<Self, RulesError> {
+        if let Some(variant) = Variant::ALL
+            .into_iter()
+            .find(|v| !v.is_tableau() && v.name() == preset)
+        {
+            return Ok(DealCode {
+                variant,
+                preset: variant.name(),
+                seed,
+            });
+        }
+        let (preset, rules) = RuleSet::PRESETS
+            .iter()
+            .find(|(name, _)| *name == preset)
+            .ok_or_else(|| RulesError::UnknownPreset(preset.to_string()))?;
+        Ok(DealCode {
+            variant: rules.variant,
+            preset,
+            seed,
+        })
+    }
+
+    /// The rules of the deal, if it is played on the Klondike tableau
+    pub fn rules(&self) -> Result<RuleSet, RulesError> {
+        if !self.variant.is_tableau() {
+            return Err(RulesError::NotOnTableau(self.variant.name()));
+        }
+        Ok(RuleSet::preset(self.preset).expect("Deal codes only hold known presets"))
+    }
+
+    /// Parse a deal code, where a bare seed means the given preset instead of the default one
+    pub fn parse_with_preset(s: &str, preset: &str) -> Result<Self, RulesError> {
+        let code = s.trim();
+        if code.contains(':') {
+            code.parse()
+        } else {
+            let seed = code
+                .parse()
+                .map_err(|_| RulesError::InvalidDealCode(s.to_string()))?;
+            DealCode::new(preset, seed)
+        }
+    }
+
+    /// Parse a list of deal codes, one per line. Empty lines and lines starting with `#` are skipped.
+    pub fn parse_list(text: &str, preset: &str) -> Result<Vec<Self>, RulesError> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| DealCode::parse_with_preset(line, preset))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for DealCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}.{}:{}:{}",
+            self.variant.name(),
+            self.variant.options_version(),
+            self.preset,
+            self.seed
+        )
+    }
+}
+
+impl std::str::FromStr for DealCode {
+    type Err = RulesError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (variant, preset, seed) = match s.split(':').collect::<Vec<_>>()[..] {
+            [seed] => (None, RuleSet::PRESETS[0].0, seed),
+            [preset, seed] => (None, preset, seed),
+            [variant, preset, seed] => (Some(Variant::parse_versioned(variant)?), preset, seed),
+            _ => return Err(RulesError::InvalidDealCode(s.to_string())),
+        };
+        let seed = seed
+            .parse()
+            .map_err(|_| RulesError::InvalidDealCode(s.to_string()))?;
+        let code = DealCode::new(preset, seed)?;
+        if variant.is_some_and(|v| v != code.variant) {
+            return Err(RulesError::InvalidDealCode(s.to_string()));
+        }
+        Ok(code)
+    }
+}
+
+/// Describe a rule set as a list of (rule, setting) pairs
+pub fn describe(rules: &RuleSet) -> Vec<(&'static str, String)> {
+    vec![
+        ("variant", rules.variant.name().to_string()),
+        ("draw", rules.draw.to_string()),
+        (
+            "passes",
+            match rules.passes {
+                Some(n) => n.to_string(),
+                None => "unlimited".to_string(),
+            },
+        ),
+        (
+            "empty column",
+            match rules.empty_column {
+                EmptyColumnRule::KingsOnly => "kings only",
+                EmptyColumnRule::AnyCard => "any card",
+            }
+            .to_string(),
+        ),
+        (
+            "building",
+            match rules.building {
+                BuildRule::AlternateColors => "alternate colors",
+                BuildRule::SameSuit => "same suit",
+            }
+            .to_string(),
+        ),
+        (
+            "foundation retreat",
+            if rules.foundation_retreat {
+                "allowed"
+            } else {
+                "forbidden"
+            }
+            .to_string(),
+        ),
+        (
+            "foundation pile moves",
+            if rules.foundation_pile_moves {
+                "allowed"
+            } else {
+                "forbidden"
+            }
+            .to_string(),
+        ),
+        (
+            "scoring",
+            match rules.scoring {
+                Scoring::Standard => "standard",
+                Scoring::Vegas => "vegas",
+            }
+            .to_string(),
+        ),
+        (
+            "information",
+            if rules.open_information {
+                "open"
+            } else {
+                "hidden"
+            }
+            .to_string(),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_default_rules() {
+        let description = describe(&RuleSet::default());
+        assert_eq!(description[0], ("variant", "klondike".to_string()));
+        assert_eq!(description[1], ("draw", "1".to_string()));
+        assert_eq!(description[2], ("passes", "unlimited".to_string()));
+        assert_eq!(description.len(), 9);
+    }
+
+    #[test]
+    fn scoring_policy_points() {
+        use crate::core::Addr;
+        let to_foundation = Action::Move(Addr::Waste, Addr::Foundation1, 1);
+        let policy = Scoring::Standard.policy();
+        assert_eq!(policy.points(&to_foundation), 10);
+        assert_eq!(
+            policy.points(&Action::Move(Addr::Depot1, Addr::Depot2, 3)),
+            0
+        );
+        assert_eq!(policy.add(50, policy.points(&Action::Turnover)), 0);
+        let vegas = Scoring::Vegas.policy();
+        assert_eq!(vegas.add(vegas.initial, vegas.points(&to_foundation)), -47);
+        assert_eq!(policy.table().len(), 6);
+        assert_eq!(policy.time_points(95, false), -18);
+        assert_eq!(policy.time_points(100, true), -20 + 7000);
+        assert_eq!(policy.time_points(29, true), -4);
+        assert_eq!(vegas.time_points(100, true), 0);
+    }
+
+    #[test]
+    fn validate_rules() {
+        for (_, rules) in RuleSet::PRESETS {
+            assert_eq!(rules.validate(), Ok(()));
+        }
+        let no_draw = RuleSet {
+            draw: 0,
+            ..RuleSet::default()
+        };
+        assert!(matches!(no_draw.validate(), Err(RulesError::Invalid(_))));
+        let draw_four = RuleSet {
+            draw: 4,
+            ..RuleSet::default()
+        };
+        assert_eq!(
+            draw_four.validate(),
+            Err(RulesError::Invalid("drawing more than three cards"))
+        );
+        let unlimited_vegas = RuleSet {
+            scoring: Scoring::Vegas,
+            ..RuleSet::default()
+        };
+        assert_eq!(
+            unlimited_vegas.validate(),
+            Err(RulesError::Incompatible(
+                "Vegas scoring",
+                "unlimited passes"
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_deal_codes() {
+        let code: DealCode = "vegas-draw3:42".parse().unwrap();
+        assert_eq!(code.rules(), Ok(RuleSet::VEGAS_DRAW3));
+        assert_eq!(code.seed, 42);
+        assert_eq!(code.to_string(), "klondike.1:vegas-draw3:42");
+        assert_eq!(code.to_string().parse(), Ok(code.clone()));
+        assert_eq!("klondike:vegas-draw3:42".parse(), Ok(code));
+        assert_eq!(
+            "spider.1:vegas-draw3:42".parse::<DealCode>(),
+            Err(RulesError::UnknownVariant("spider".to_string()))
+        );
+        assert_eq!(
+            "russian.1:russian:3"
+                .parse::<DealCode>()
+                .and_then(|c| c.rules()),
+            Ok(RuleSet::RUSSIAN)
+        );
+        let golf: DealCode = "golf.1:golf:42".parse().unwrap();
+        assert_eq!((golf.variant, golf.seed), (Variant::Golf, 42));
+        assert_eq!(golf.to_string(), "golf.1:golf:42");
+        assert_eq!("golf:42".parse(), Ok(golf.clone()));
+        assert_eq!(golf.rules(), Err(RulesError::NotOnTableau("golf")));
+        assert_eq!(
+            "golf.1:softie:42".parse::<DealCode>(),
+            Err(RulesError::InvalidDealCode("golf.1:softie:42".to_string()))
+        );
+        assert_eq!(
+            "tripeaks.2:tripeaks:1".parse::<DealCode>(),
+            Err(RulesError::UnsupportedVersion("tripeaks", 1, 2))
+        );
+        assert_eq!(
+            "yukon.1:vegas-draw3:42".parse::<DealCode>(),
+            Err(RulesError::InvalidDealCode(
+                "yukon.1:vegas-draw3:42".to_string()
+            ))
+        );
+        assert_eq!(
+            "klondike.0:vegas-draw3:42".parse::<DealCode>(),
+            Err(RulesError::UnsupportedVersion("klondike", 1, 0))
+        );
+        assert_eq!("7".parse(), DealCode::new("klondike-classic", 7));
+        assert_eq!(
+            "freecell:7".parse::<DealCode>(),
+            Err(RulesError::UnknownPreset("freecell".to_string()))
+        );
+        assert_eq!(
+            "softie:x".parse::<DealCode>(),
+            Err(RulesError::InvalidDealCode("softie:x".to_string()))
+        );
+        assert_eq!(
+            DealCode::parse_list("# curated\n3\n\nthoughtful:4\n", "softie"),
+            Ok(vec![
+                DealCode::new("softie", 3).unwrap(),
+                DealCode::new("thoughtful", 4).unwrap()
+            ])
+        );
+    }
+}
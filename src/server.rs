@@ -0,0 +1,391 @@
+//! A web dashboard for a running batch
+//!
+//! Only built with the `server` feature. With `--serve ADDR`, the batch serves a small web page on `ADDR`
+//! (e.g. `127.0.0.1:8080`) that shows the summary of each AI, a histogram of the scores and the most recent games.
+//! The page polls these JSON endpoints, which can also be used directly:
+//!
+//!  - `/summary.json`: the number of games, and the summary and the score histogram (buckets of 100 points) of each AI
+//!  - `/recent.json`: the most recent games, newest first, with an id
+//!  - `/replay/ID`: the moves of a recent game as text, one action per line
+//!  - `/live`: the game in progress as Server-Sent Events, one JSON object per event, with the actions in move notation
+//!  - `/position`: the position of the game in progress as text
+//!
+//! The server is a single thread that answers one request at a time, which is plenty for a dashboard.
+//! Spectators of `/live` get a thread each, but there are at most `MAX_SPECTATORS` of them, and others are told to
+//! come back later. The events are sent at most `EVENTS_PER_SECOND`, so that a person can follow the game. When the
+//! batch has moved on to the next game in the meantime, the spectator skips to it.
+//!
+//! The position is not part of the dashboard, but a `SharedGame` of its own, so that showing it neither waits for the
+//! lock of the dashboard nor copies the game while the batch waits.
+
+use crate::core::Action;
+use crate::engine::SharedGame;
+use crate::json::json_string;
+use crate::stats::{GameRecord, Summary};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The number of recent games that are kept for replay
+const RECENT_GAMES: usize = 20;
+
+/// The width of the buckets in the score histograms
+const BUCKET: i32 = 100;
+
+/// The number of spectators of the live game at the same time
+const MAX_SPECTATORS: usize = 4;
+
+/// The rate at which the events of the live game are sent to each spectator
+const EVENTS_PER_SECOND: u64 = 20;
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Solitaire batch</title>
+<style>body { font-family: sans-serif; } td, th { padding: 0 1em; text-align: right; } .bar { background: #48c; height: 1em; display: inline-block; }</style>
+</head>
+<body>
+<h1>Solitaire batch</h1>
+<p id="games"></p>
+<table id="summary"></table>
+<div id="histograms"></div>
+<h2>Live game</h2>
+<pre id="position"></pre>
+<pre id="live"></pre>
+<h2>Recent games</h2>
+<ul id="recent"></ul>
+<script>
+let live = [];
+new EventSource("/live").onmessage = e => {
+    const event = JSON.parse(e.data);
+    live = event.ai ? [] : live.slice(-19);
+    live.push(event.ai ? `${event.ai} on ${event.deal}` : event.action ? `${event.move}: ${event.action}` : event.result);
+    document.getElementById("live").textContent = live.join("\n");
+};
+async function refresh() {
+    const summary = await (await fetch("/summary.json")).json();
+    document.getElementById("games").textContent = summary.games + " games played";
+    let rows = "<tr><th>AI</th><th>games</th><th>win rate</th><th>avg score</th><th>avg moves</th></tr>";
+    let histograms = "";
+    for (const ai of summary.ais) {
+        const s = ai.summary;
+        rows += `<tr><td>${s.name}</td><td>${s.games}</td><td>${(100 * s.win_rate).toFixed(1)}%</td><td>${s.avg_score.toFixed(1)}</td><td>${s.avg_moves.toFixed(1)}</td></tr>`;
+        const max = Math.max(...ai.histogram.map(b => b[1]));
+        histograms += `<h2>Scores of ${s.name}</h2>`;
+        for (const [bucket, count] of ai.histogram) {
+            histograms += `<div>${bucket} <span class="bar" style="width: ${20 * count / max}em"></span> ${count}</div>`;
+        }
+    }
+    document.getElementById("summary").innerHTML = rows;
+    document.getElementById("histograms").innerHTML = histograms;
+    const position = await fetch("/position");
+    document.getElementById("position").textContent = position.ok ? await position.text() : "";
+    const recent = await (await fetch("/recent.json")).json();
+    document.getElementById("recent").innerHTML = recent
+        .map(g => `<li><a href="/replay/${g.id}">${g.game.ai} on ${g.game.deal}</a>: ${g.game.result}, score ${g.game.score}</li>`)
+        .join("");
+}
+refresh();
+setInterval(refresh, 2000);
+</script>
+</body>
+</html>
+"#;
+
+/// The games played so far, as shown on the dashboard
+#[derive(Default)]
+pub struct Dashboard {
+    n_games: usize,
+    summaries: BTreeMap<String, Summary>,
+    histograms: BTreeMap<String, BTreeMap<i32, usize>>,
+    /// The id, the record and the moves of the most recent games, newest last
+    recent: VecDeque<(usize, GameRecord, Vec<Action>)>,
+    /// The id and the events of the game in progress, or of the last game
+    live: (usize, Vec<String>),
+}
+
+impl Dashboard {
+    /// Start a new live game
+    pub fn start(&mut self, ai: &str, deal: &str) {
+        let start = format!(
+            "{{\"ai\": {}, \"deal\": {}}}",
+            json_string(ai),
+            json_string(deal)
+        );
+        self.live = (self.n_games, vec![start]);
+    }
+
+    /// Add a move to the live game
+    pub fn add_move(&mut self, action: &Action) {
+        let n = self.live.1.len() - 1;
+        self.live.1.push(format!(
+            "{{\"move\": {n}, \"action\": {}}}",
+            json_string(&action.to_string())
+        ));
+    }
+
+    /// The events of the live game from event `n` on, with the id of the game
+    fn live_events(&self, n: usize) -> (usize, &[String]) {
+        (self.live.0, self.live.1.get(n..).unwrap_or_default())
+    }
+
+    /// Add a finished game, which ends the live game
+    pub fn add(&mut self, record: &GameRecord, actions: &[Action]) {
+        self.live.1.push(format!(
+            "{{\"result\": \"{:?}, score {}\"}}",
+            record.end, record.score
+        ));
+        self.summaries
+            .entry(record.ai.clone())
+            .or_insert_with(|| Summary::new(&record.ai))
+            .add_game(record.score, record.end, record.moves);
+        *self
+            .histograms
+            .entry(record.ai.clone())
+            .or_default()
+            .entry(record.score.div_euclid(BUCKET) * BUCKET)
+            .or_default() += 1;
+        if self.recent.len() == RECENT_GAMES {
+            self.recent.pop_front();
+        }
+        self.recent
+            .push_back((self.n_games, record.clone(), actions.to_vec()));
+        self.n_games += 1;
+    }
+
+    /// The status, the content type and the body of the response to a GET request
+    fn respond(&self, path: &str) -> (&'static str, &'static str, String) {
+        match path {
+            "/" => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+            "/summary.json" => {
+                let ais = self
+                    .summaries
+                    .iter()
+                    .map(|(ai, summary)| {
+                        let histogram = self.histograms[ai]
+                            .iter()
+                            .map(|(bucket, count)| format!("[{bucket}, {count}]"))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!(
+                            "{{\"summary\": {}, \"histogram\": [{histogram}]}}",
+                            summary.to_json()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let body = format!("{{\"games\": {}, \"ais\": [{ais}]}}", self.n_games);
+                ("200 OK", "application/json", body)
+            }
+            "/recent.json" => {
+                let games = self
+                    .recent
+                    .iter()
+                    .rev()
+                    .map(|(id, record, _)| {
+                        format!("{{\"id\": {id}, \"game\": {}}}", record.to_json())
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                ("200 OK", "application/json", format!("[{games}]"))
+            }
+            _ => {
+                let game = path
+                    .strip_prefix("/replay/")
+                    .and_then(|id| id.parse::<usize>().ok())
+                    .and_then(|id| self.recent.iter().find(|(i, _, _)| *i == id));
+                match game {
+                    Some((_, record, actions)) => {
+                        let mut body = format!("# {} on {}\n", record.ai, record.deal);
+                        for action in actions {
+                            body += &format!("{action}\n");
+                        }
+                        ("200 OK", "text/plain; charset=utf-8", body)
+                    }
+                    None => (
+                        "404 Not Found",
+                        "application/json",
+                        format!("{{\"error\": {}}}", json_string("Not found")),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// The response to a GET request of the position of the live game
+fn respond_position(position: &SharedGame) -> (&'static str, &'static str, String) {
+    match position.load() {
+        Some(snapshot) => {
+            let body = format!(
+                "{}Score {}, {} actions",
+                snapshot.game,
+                snapshot.game.score(),
+                snapshot.n_actions
+            );
+            ("200 OK", "text/plain; charset=utf-8", body)
+        }
+        None => (
+            "404 Not Found",
+            "application/json",
+            format!("{{\"error\": {}}}", json_string("No game has started")),
+        ),
+    }
+}
+
+/// Serve the dashboard, and the position of the live game, on `addr` in a background thread
+pub fn serve(
+    addr: &str,
+    dashboard: Arc<Mutex<Dashboard>>,
+    position: SharedGame,
+) -> Result<(), std::io::Error> {
+    let listener = TcpListener::bind(addr)?;
+    let spectators = Arc::new(AtomicUsize::new(0));
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A client that goes away is not our problem
+            let _ = answer(stream, &dashboard, &position, &spectators);
+        }
+    });
+    Ok(())
+}
+
+/// Send the events of the live game to a spectator, until the spectator goes away
+fn spectate(
+    mut stream: std::net::TcpStream,
+    dashboard: &Mutex<Dashboard>,
+) -> Result<(), std::io::Error> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    let (mut game, mut n) = (usize::MAX, 0);
+    loop {
+        let event = {
+            let dashboard = dashboard
+                .lock()
+                .expect("The batch does not panic while holding the lock");
+            let (live_game, _) = dashboard.live_events(0);
+            if live_game != game {
+                (game, n) = (live_game, 0);
+            }
+            dashboard.live_events(n).1.first().cloned()
+        };
+        match event {
+            Some(event) => {
+                write!(stream, "data: {event}\n\n")?;
+                stream.flush()?;
+                n += 1;
+            }
+            // Keep the connection alive, and notice when the spectator is gone
+            None => write!(stream, ": waiting\n\n")?,
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1000 / EVENTS_PER_SECOND));
+    }
+}
+
+/// Answer a single HTTP request
+fn answer(
+    mut stream: std::net::TcpStream,
+    dashboard: &Arc<Mutex<Dashboard>>,
+    position: &SharedGame,
+    spectators: &Arc<AtomicUsize>,
+) -> Result<(), std::io::Error> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let (status, content_type, body) = match request_line.split_whitespace().collect::<Vec<_>>()[..]
+    {
+        ["GET", "/live", _] if spectators.fetch_add(1, Ordering::SeqCst) < MAX_SPECTATORS => {
+            let (dashboard, spectators) = (dashboard.clone(), spectators.clone());
+            std::thread::spawn(move || {
+                let _ = spectate(stream, &dashboard);
+                spectators.fetch_sub(1, Ordering::SeqCst);
+            });
+            return Ok(());
+        }
+        ["GET", "/live", _] => {
+            spectators.fetch_sub(1, Ordering::SeqCst);
+            write!(
+                stream,
+                "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 10\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )?;
+            return stream.flush();
+        }
+        ["GET", "/position", _] => respond_position(position),
+        ["GET", path, _] => dashboard
+            .lock()
+            .expect("The batch does not panic while holding the lock")
+            .respond(path),
+        _ => ("405 Method Not Allowed", "text/plain", String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameEnd;
+
+    #[test]
+    fn summary_and_replays() {
+        let mut dashboard = Dashboard::default();
+        for (score, end) in [
+            (650, GameEnd::Won),
+            (-20, GameEnd::Lost),
+            (30, GameEnd::Lost),
+        ] {
+            dashboard.start("GreedyAi", "klondike-classic:0");
+            dashboard.add_move(&Action::Take);
+            let record = GameRecord {
+                ai: "GreedyAi".to_string(),
+                deal: "klondike-classic:0".to_string(),
+                score,
+                end,
+                moves: 1,
+                seconds: 0.5,
+                peak_nodes: 1,
+                wasted_moves: 0,
+            };
+            dashboard.add(&record, &[Action::Take]);
+        }
+        let (status, _, body) = dashboard.respond("/summary.json");
+        assert_eq!(status, "200 OK");
+        assert!(body.starts_with("{\"games\": 3, \"ais\": [{\"summary\": {\"name\": \"GreedyAi\", \"games\": 3, \"wins\": 1"));
+        assert!(body.ends_with("\"histogram\": [[-100, 1], [0, 1], [600, 1]]}]}"));
+        assert_eq!(
+            dashboard.respond("/replay/1").2,
+            "# GreedyAi on klondike-classic:0\nT\n"
+        );
+        assert_eq!(dashboard.respond("/replay/3").0, "404 Not Found");
+        dashboard.start("GreedyAi", "klondike-classic:1");
+        dashboard.add_move(&Action::Quit);
+        let (game, events) = dashboard.live_events(1);
+        assert_eq!(game, 3);
+        assert_eq!(events, ["{\"move\": 0, \"action\": \"Q\"}"]);
+    }
+
+    #[test]
+    fn position_of_the_live_game() {
+        let position = SharedGame::default();
+        assert_eq!(respond_position(&position).0, "404 Not Found");
+        let mut gs = crate::engine::GameEngine::deal(0);
+        gs.act(&Action::Take).unwrap();
+        position.publish(&gs);
+        let (status, _, body) = respond_position(&position);
+        assert_eq!(status, "200 OK");
+        assert!(body.starts_with("Talon size 23\n"));
+        assert!(body.ends_with("Score 0, 1 actions"));
+    }
+}
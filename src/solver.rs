@@ -0,0 +1,809 @@
+//! A solver for games where all cards are known
+//!
+//! The solver looks at the full game, face down cards and talon included, and searches for a way to win it with
+//! iterative deepening A* (IDA*). Each iteration is a depth first search that cuts off every line of play where the
+//! number of actions taken plus a lower bound on the number of actions left exceeds a limit, and the next iteration
+//! raises the limit to the smallest value that was cut off. A search where nothing was cut off proves that the deal
+//! can't be won.
+//!
+//! The search is pruned in four ways
+//!  - a position that was already reached in the iteration with as few actions is not searched again, which also
+//!    breaks cycles such as moving a card back and forth. Positions are told apart by a 64 bit hash of where the
+//!    cards lie, see `GameEngine::layout`.
+//!  - revealing a card and moving an ace from a depot to a foundation are never wrong, so when one of them is legal,
+//!    it is the only action that is searched
+//!  - a position where `analysis::find_deadlock` finds a stuck card is lost
+//!  - of the empty depots, only the first is tried, as they are all the same
+//!
+//! Taking from the talon and turning over the waste only matter for the card that turns up on the waste, so they are
+//! searched together with a move of that card.
+//!
+//! How fast a winning line is found depends mostly on the order in which the moves are searched, see `MoveOrder`.
+//! `Solver::solve_with_effort` tells how many positions a search took, to compare the orders on a set of deals.
+//!
+//! Instead of IDA*, the solver can use proof-number search, see `Algorithm::ProofNumber`. It keeps the tree of the
+//! positions it has seen, and always expands the position that is closest to a win by the lower bound, which is
+//! often much faster on deals with a narrow winning line. It searches the same moves, and leaves out the positions
+//! that are already in the tree by the same hash.
+//!
+//! A shortest line is slow to find for most deals, so by default the lower bound is weighted, see
+//! `Solver::with_weight`. The solver is the backbone of winnability studies: how many deals can be won at all, and
+//! how far the AIs fall short.
+
+use crate::ai::SolitaireObserver;
+use crate::analysis::{is_hopeless, min_moves_to_win};
+use crate::core::{Action, Addr, CardView, Suit, Value};
+use crate::engine::GameEngine;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// The default number of positions the solver visits before it gives up
+pub const DEFAULT_NODE_LIMIT: usize = 1_000_000;
+
+/// The default weight of the lower bound, see `Solver::with_weight`
+pub const DEFAULT_WEIGHT: usize = 2;
+
+/// What the solver found out about a game
+#[derive(Debug, Clone, PartialEq)]
+pub enum Solution {
+    /// The game can be won with these actions
+    Won(Vec<Action>),
+    /// The game can't be won
+    Lost,
+    /// The solver gave up after visiting its limit of positions
+    Unknown,
+}
+
+/// The order in which the moves from a position are searched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MoveOrder {
+    /// Moves to the foundations first
+    #[default]
+    Foundations,
+    /// Moves that uncover a face down card first, and then moves to the foundations
+    Reveals,
+    /// As `GameEngine::legal_actions` lists them, with the turns of the talon last
+    Unordered,
+}
+
+impl MoveOrder {
+    pub const ALL: [MoveOrder; 3] = [
+        MoveOrder::Foundations,
+        MoveOrder::Reveals,
+        MoveOrder::Unordered,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveOrder::Foundations => "foundations",
+            MoveOrder::Reveals => "reveals",
+            MoveOrder::Unordered => "unordered",
+        }
+    }
+}
+
+impl std::str::FromStr for MoveOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MoveOrder::ALL
+            .into_iter()
+            .find(|order| order.name() == s)
+            .ok_or_else(|| format!("Unknown move order {s}, use foundations, reveals or unordered"))
+    }
+}
+
+/// How the solver searches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Iterative deepening A*, a depth first search that finds a short line
+    #[default]
+    Ida,
+    /// Proof-number search, a best first search for any winning line
+    ProofNumber,
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ida" => Ok(Algorithm::Ida),
+            "pn" => Ok(Algorithm::ProofNumber),
+            _ => Err(format!("Unknown algorithm {s}, use ida or pn")),
+        }
+    }
+}
+
+/// How much work a search took
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Effort {
+    /// The positions visited, in all iterations
+    pub n_nodes: usize,
+    /// The iterations of deepening, none for proof-number search
+    pub n_iterations: usize,
+}
+
+/// How often a player's moves kept a won game winnable, see `Solver::agreement`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Agreement {
+    /// The moves after which the game could still be won
+    pub agreed: usize,
+    /// The moves from a position that could be won
+    pub judged: usize,
+}
+
+/// An IDA* solver for games where all cards are known
+#[derive(Debug, Clone)]
+pub struct Solver {
+    node_limit: usize,
+    weight: usize,
+    order: MoveOrder,
+    algorithm: Algorithm,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Solver {
+            node_limit: DEFAULT_NODE_LIMIT,
+            weight: DEFAULT_WEIGHT,
+            order: MoveOrder::default(),
+            algorithm: Algorithm::default(),
+        }
+    }
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Solver::default()
+    }
+
+    /// Give up after visiting this many positions
+    pub fn with_node_limit(mut self, node_limit: usize) -> Self {
+        self.node_limit = node_limit;
+        self
+    }
+
+    /// Multiply the lower bound on the actions left by `weight`
+    ///
+    /// With weight 1 the winning line is a shortest one, but that takes long to find for most deals. Larger weights
+    /// find longer lines in fewer positions. The weight doesn't change which games are proven lost.
+    pub fn with_weight(mut self, weight: usize) -> Self {
+        self.weight = weight.max(1);
+        self
+    }
+
+    /// Search the moves from each position in this order
+    pub fn with_order(mut self, order: MoveOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Search with this algorithm. The weight only applies to IDA*.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Find a winning line of play from the position, or prove that there is none
+    pub fn solve(&self, gs: &GameEngine) -> Solution {
+        self.solve_with_effort(gs).0
+    }
+
+    /// Solve the position, and tell how much work that took
+    pub fn solve_with_effort(&self, gs: &GameEngine) -> (Solution, Effort) {
+        let mut effort = Effort::default();
+        if gs.is_won() {
+            return (Solution::Won(vec![]), effort);
+        }
+        if !gs.is_running() || is_hopeless(gs) {
+            return (Solution::Lost, effort);
+        }
+        let mut search = Search {
+            gs: gs.clone(),
+            line: vec![],
+            depths: HashMap::new(),
+            n_nodes: 0,
+            node_limit: self.node_limit,
+            weight: self.weight,
+            order: self.order,
+        };
+        let probe = match self.algorithm {
+            Algorithm::Ida => {
+                let mut limit = self.weight * lower_bound(gs, &gs.observe());
+                loop {
+                    search.depths.clear();
+                    effort.n_iterations += 1;
+                    match search.probe(0, limit) {
+                        Probe::Beyond(next) if next != usize::MAX => limit = next,
+                        probe => break probe,
+                    }
+                }
+            }
+            Algorithm::ProofNumber => search.prove(),
+        };
+        let solution = match probe {
+            Probe::Found => Solution::Won(search.line),
+            Probe::Beyond(_) => Solution::Lost,
+            Probe::OutOfNodes => Solution::Unknown,
+        };
+        effort.n_nodes = search.n_nodes.min(self.node_limit);
+        (solution, effort)
+    }
+
+    /// The legal actions after which the solver finds a way to win. Quitting is never one of them.
+    pub fn winning_actions(&self, gs: &GameEngine) -> Vec<Action> {
+        gs.legal_actions()
+            .into_iter()
+            .filter(|action| {
+                let mut next = gs.clone();
+                next.act(action).is_ok() && matches!(self.solve(&next), Solution::Won(_))
+            })
+            .collect()
+    }
+
+    /// How many of the actions, played from `start`, lie on some winning line
+    ///
+    /// `line` is a winning line from `start`, e.g. from `solve`. An action agrees with the solver if the game can still
+    /// be won after it. The first action that doesn't loses the game, so nothing after it is judged, and neither is
+    /// anything after a position the solver gives up on. An action that is the next one on the last winning line found
+    /// needs no new search.
+    pub fn agreement(&self, start: &GameEngine, line: &[Action], actions: &[Action]) -> Agreement {
+        let mut line = line.iter().rev().cloned().collect::<Vec<_>>();
+        let mut gs = start.clone();
+        let mut agreement = Agreement::default();
+        for action in actions {
+            if gs.act(action).is_err() {
+                break;
+            }
+            if line.last() == Some(action) {
+                line.pop();
+            } else {
+                match self.solve(&gs) {
+                    Solution::Won(next) => line = next.into_iter().rev().collect(),
+                    Solution::Lost => {
+                        agreement.judged += 1;
+                        break;
+                    }
+                    Solution::Unknown => break,
+                }
+            }
+            agreement.agreed += 1;
+            agreement.judged += 1;
+        }
+        agreement
+    }
+}
+
+/// A lower bound on the number of actions left to win
+///
+/// Every card that is not on a foundation has to be moved there, every face down card has to be revealed unless
+/// that is automatic, and the talon has to be taken. No action does more than one of these, and neither do the actions
+/// that get past the cards counted by `n_blocked_piles`.
+fn lower_bound(gs: &GameEngine, view: &SolitaireObserver) -> usize {
+    let n_face_down = if gs.options().auto_reveal {
+        0
+    } else {
+        view.depots.iter().map(|d| d.n_face_down).sum()
+    };
+    min_moves_to_win(gs)
+        + n_face_down
+        + view.talon_size.div_ceil(gs.rules().draw as usize)
+        + n_blocked_piles(gs)
+}
+
+/// The number of moves it takes at least to get the cards out of the way that lie above a lower card of their own suit
+///
+/// Such a card has to be moved off its pile before it can go to a foundation, or the waste turned over to get past
+/// it. One move may take several cards off a depot, and one turnover gets past all of the waste, so each pile counts
+/// once.
+fn n_blocked_piles(gs: &GameEngine) -> usize {
+    let mut n = 0;
+    let mut pile = (Addr::Talon, [u8::MAX; 4], false);
+    for (addr, _, card) in gs.cards() {
+        if !(addr == Addr::Waste || addr.is_depot()) {
+            continue;
+        }
+        if pile.0 != addr {
+            pile = (addr, [u8::MAX; 4], false);
+        }
+        let (suit, value) = card.clone().into();
+        let lowest = &mut pile.1[suit as usize];
+        if value.numeric_value() > *lowest && !pile.2 {
+            n += 1;
+            pile.2 = true;
+        }
+        *lowest = (*lowest).min(value.numeric_value());
+    }
+    n
+}
+
+/// The key of a position in the tables of the searches: a hash of where the cards lie, see `GameEngine::layout`
+fn key(gs: &GameEngine) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    gs.layout().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A position in the tree of a proof-number search
+struct Node {
+    /// The actions from the position before
+    actions: Vec<Action>,
+    children: Vec<usize>,
+    /// How far the position is from a proof that it can be won, or that it can't, `usize::MAX` if it can't be proven.
+    /// A position that was not expanded yet starts from the lower bound on the actions left, and 1.
+    proof: usize,
+    disproof: usize,
+    expanded: bool,
+}
+
+/// The outcome of searching below a position
+enum Probe {
+    /// A winning line, which is in `Search::line`
+    Found,
+    /// No winning line within the limit. The smallest limit that would search further, or `usize::MAX` if none would.
+    Beyond(usize),
+    OutOfNodes,
+}
+
+/// The state of an IDA* search
+struct Search {
+    gs: GameEngine,
+    /// The actions from the start to `gs`
+    line: Vec<Action>,
+    /// The fewest actions each position was reached with in this iteration
+    depths: HashMap<u64, usize>,
+    n_nodes: usize,
+    node_limit: usize,
+    weight: usize,
+    order: MoveOrder,
+}
+
+impl Search {
+    /// Search for a winning line from `gs`, which was reached with `depth` actions, of at most `limit` actions in all
+    fn probe(&mut self, depth: usize, limit: usize) -> Probe {
+        if self.gs.is_won() {
+            return Probe::Found;
+        }
+        let view = self.gs.observe();
+        let bound = depth + self.weight * lower_bound(&self.gs, &view);
+        if bound > limit {
+            return Probe::Beyond(bound);
+        }
+        match self.depths.entry(key(&self.gs)) {
+            Entry::Occupied(e) if *e.get() <= depth => return Probe::Beyond(usize::MAX),
+            Entry::Occupied(mut e) => {
+                e.insert(depth);
+            }
+            Entry::Vacant(e) => {
+                e.insert(depth);
+            }
+        }
+        self.n_nodes += 1;
+        if self.n_nodes > self.node_limit {
+            return Probe::OutOfNodes;
+        }
+        let mut next = usize::MAX;
+        for actions in self.moves(&view) {
+            for action in &actions {
+                self.gs.act(action).expect("The action is legal");
+            }
+            let to_foundation =
+                matches!(actions.last(), Some(Action::Move(_, to, _)) if to.is_foundation());
+            let n = actions.len();
+            self.line.extend(actions);
+            // A card can only get stuck when the card it could go on goes to a foundation
+            let probe = if to_foundation && is_hopeless(&self.gs) {
+                Probe::Beyond(usize::MAX)
+            } else {
+                self.probe(depth + n, limit)
+            };
+            match probe {
+                Probe::Found => return Probe::Found,
+                Probe::Beyond(bound) => next = next.min(bound),
+                Probe::OutOfNodes => return Probe::OutOfNodes,
+            }
+            self.line.truncate(self.line.len() - n);
+            for _ in 0..n {
+                self.gs.undo();
+            }
+        }
+        Probe::Beyond(next)
+    }
+
+    /// Search for a winning line from `gs` with proof-number search
+    ///
+    /// The tree grows by the position with the smallest proof number, found by going down to the child with the
+    /// smallest proof number from the start. A position can be won if any child can, and can't be won if no child
+    /// can, so its proof number is the smallest of its children and its disproof number their sum.
+    fn prove(&mut self) -> Probe {
+        let mut tree = vec![Node {
+            actions: vec![],
+            children: vec![],
+            proof: 1,
+            disproof: 1,
+            expanded: false,
+        }];
+        let mut seen = HashSet::from([key(&self.gs)]);
+        while tree[0].proof != 0 && tree[0].disproof != 0 {
+            let mut path = vec![0];
+            while tree[path[path.len() - 1]].expanded {
+                let children = &tree[path[path.len() - 1]].children;
+                let child = *children
+                    .iter()
+                    .min_by_key(|&&child| tree[child].proof)
+                    .expect("A position without children is proven lost");
+                for action in &tree[child].actions {
+                    self.gs.act(action).expect("The action is legal");
+                }
+                path.push(child);
+            }
+            self.expand(&mut tree, &mut seen, path[path.len() - 1]);
+            for node in path.into_iter().rev() {
+                let children = &tree[node].children;
+                let proof = children
+                    .iter()
+                    .map(|&child| tree[child].proof)
+                    .min()
+                    .unwrap_or(usize::MAX);
+                let disproof = children
+                    .iter()
+                    .map(|&child| tree[child].disproof)
+                    .fold(0, usize::saturating_add);
+                (tree[node].proof, tree[node].disproof) = (proof, disproof);
+                for _ in 0..tree[node].actions.len() {
+                    self.gs.undo();
+                }
+            }
+            if self.n_nodes > self.node_limit {
+                return Probe::OutOfNodes;
+            }
+        }
+        if tree[0].disproof == 0 {
+            return Probe::Beyond(usize::MAX);
+        }
+        let mut node = 0;
+        while let Some(&child) = tree[node]
+            .children
+            .iter()
+            .find(|&&child| tree[child].proof == 0)
+        {
+            self.line.extend(tree[child].actions.iter().cloned());
+            node = child;
+        }
+        Probe::Found
+    }
+
+    /// Add the children of `node`, the position in `gs`, to the tree, except for the ones that are already in it
+    fn expand(&mut self, tree: &mut Vec<Node>, seen: &mut HashSet<u64>, node: usize) {
+        tree[node].expanded = true;
+        for actions in self.moves(&self.gs.observe()) {
+            for action in &actions {
+                self.gs.act(action).expect("The action is legal");
+            }
+            if seen.insert(key(&self.gs)) {
+                let to_foundation =
+                    matches!(actions.last(), Some(Action::Move(_, to, _)) if to.is_foundation());
+                let (proof, disproof) = if self.gs.is_won() {
+                    (0, usize::MAX)
+                } else if !self.gs.is_running() || (to_foundation && is_hopeless(&self.gs)) {
+                    (usize::MAX, 0)
+                } else {
+                    (lower_bound(&self.gs, &self.gs.observe()).max(1), 1)
+                };
+                self.n_nodes += 1;
+                let child = tree.len();
+                tree[node].children.push(child);
+                tree.push(Node {
+                    actions: actions.clone(),
+                    children: vec![],
+                    proof,
+                    disproof,
+                    expanded: false,
+                });
+            }
+            for _ in &actions {
+                self.gs.undo();
+            }
+        }
+    }
+
+    /// The moves to search from `gs`, each a few actions taken together, in the order of the search
+    ///
+    /// A safe action is the only move if there is one, and the turns of the talon come with a move of the card that
+    /// turns up.
+    fn moves(&mut self, view: &SolitaireObserver) -> Vec<Vec<Action>> {
+        let mut actions = self.gs.legal_actions();
+        if let Some(safe) = actions.iter().find(|a| self.is_safe(a)) {
+            return vec![vec![safe.clone()]];
+        }
+        let first_empty = Addr::DEPOTS
+            .into_iter()
+            .zip(&view.depots)
+            .find(|(_, depot)| depot.is_empty())
+            .map(|(addr, _)| addr);
+        let is_tried = |action: &Action| match action {
+            Action::Take | Action::Turnover => false,
+            Action::Move(_, to, _) if view.card_at(to, 1).is_none() && to.is_depot() => {
+                Some(*to) == first_empty
+            }
+            _ => true,
+        };
+        actions.retain(is_tried);
+        let mut moves = actions.into_iter().map(|a| vec![a]).collect::<Vec<_>>();
+        let mut turns = vec![];
+        // With more than one card at a time, the talon may not come back to where it started, but to some other state
+        // that was already seen
+        let mut seen = HashSet::from([(self.gs.talon_len(), self.gs.waste().len())]);
+        loop {
+            let turn = if self.gs.talon_len() != 0 {
+                Action::Take
+            } else if self.gs.is_legal(&Action::Turnover) {
+                Action::Turnover
+            } else {
+                break;
+            };
+            self.gs.act(&turn).expect("The action is legal");
+            turns.push(turn);
+            if !seen.insert((self.gs.talon_len(), self.gs.waste().len())) {
+                break;
+            }
+            for to in Addr::FOUNDATIONS.into_iter().chain(Addr::DEPOTS) {
+                let action = Action::Move(Addr::Waste, to, 1);
+                if self.gs.is_legal(&action) && is_tried(&action) {
+                    moves.push(turns.iter().cloned().chain([action]).collect());
+                }
+            }
+        }
+        for _ in &turns {
+            self.gs.undo();
+        }
+        let to_foundation =
+            |m: &[Action]| matches!(m.last(), Some(Action::Move(_, to, _)) if to.is_foundation());
+        let reveals = |m: &[Action]| match m.last() {
+            Some(Action::Move(from, _, n)) => view.card_at(from, n + 1) == Some(CardView::FaceDown),
+            _ => false,
+        };
+        match self.order {
+            MoveOrder::Foundations => moves.sort_by_key(|m| !to_foundation(m)),
+            MoveOrder::Reveals => moves.sort_by_key(|m| (!reveals(m), !to_foundation(m))),
+            MoveOrder::Unordered => {}
+        }
+        moves
+    }
+
+    /// Revealing a card and moving an ace from a depot to a foundation never hurt
+    fn is_safe(&self, action: &Action) -> bool {
+        match action {
+            Action::Reveal(_) => true,
+            Action::Move(from, to, 1) if from.is_depot() && to.is_foundation() => {
+                let top = self.gs.cards().filter(|(addr, _, _)| addr == from).last();
+                top.is_some_and(|(_, _, card)| <(Suit, Value)>::from(card.clone()).1 == Value::ACE)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn solves_deals() {
+        let gs = GameEngine::deal(7);
+        let Solution::Won(line) = Solver::new().solve(&gs) else {
+            panic!("The deal can be won");
+        };
+        let mut won = gs.clone();
+        for action in &line {
+            won.act(action).unwrap();
+        }
+        assert!(won.is_won());
+        assert!(line.len() >= lower_bound(&gs, &gs.observe()));
+        assert_eq!(
+            Solver::new().with_node_limit(10).solve(&gs),
+            Solution::Unknown
+        );
+    }
+
+    #[test]
+    fn proves_lost_deals() {
+        // The seven of clubs in depot 7 is stuck, see `analysis::find_deadlock`
+        assert_eq!(
+            Solver::new()
+                .with_node_limit(1)
+                .solve(&GameEngine::deal(10)),
+            Solution::Lost
+        );
+        let mut gs = GameEngine::deal(0);
+        gs.act(&Action::Quit).unwrap();
+        assert_eq!(Solver::new().solve(&gs), Solution::Lost);
+    }
+
+    #[test]
+    fn never_takes_a_move_back() {
+        // Both deals were once won with a card that went to a foundation and straight back, which changed the score
+        for seed in [3, 7] {
+            let gs = GameEngine::deal(seed);
+            let Solution::Won(line) = Solver::new().solve(&gs) else {
+                panic!("The deal can be won");
+            };
+            for pair in line.windows(2) {
+                if let [Action::Move(from, to, n), Action::Move(back_from, back_to, back_n)] = pair
+                {
+                    assert!(
+                        (from, to, n) != (back_to, back_from, back_n),
+                        "{pair:?} in {line:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bound_is_below_known_lines() {
+        // Only the spades are left, in the waste, in the order that draws them up from the ace three at a time
+        let spades = [3, 2, 1, 6, 5, 4, 9, 8, 7, 12, 11, 10, 13]
+            .map(|v| (Suit::Spades, Value::try_from(v).unwrap()));
+        let mut open = GameEngine::deal(0).observe_open();
+        open.view.talon_size = 0;
+        open.view.waste = spades.to_vec();
+        open.view.foundation_tops = [Suit::Hearts, Suit::Diamonds, Suit::Clubs]
+            .map(|suit| Some((suit, Value::KING)))
+            .into_iter()
+            .chain([None])
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        open.view.depots = Default::default();
+        open.talon = vec![];
+        open.face_down = Default::default();
+        let gs = GameEngine::from_open(&open, &RuleSet::KLONDIKE_DRAW3);
+        let play = Action::Move(Addr::Waste, Addr::Foundation4, 1);
+        let mut line = vec![Action::Turnover];
+        for _ in 0..4 {
+            line.extend([Action::Take, play.clone(), play.clone(), play.clone()]);
+        }
+        line.extend([Action::Take, play]);
+        let mut won = gs.clone();
+        for action in &line {
+            won.act(action).unwrap();
+        }
+        assert!(won.is_won());
+        // Counting each card above the ace would make 23
+        assert_eq!(lower_bound(&gs, &gs.observe()), 14);
+        assert_eq!(Solver::new().with_weight(1).solve(&gs), Solution::Won(line));
+    }
+
+    #[test]
+    fn solves_draw_three() {
+        // The talon doesn't come back to where it started once a card was played from the waste
+        let gs = GameEngine::builder()
+            .seed(0)
+            .rules(RuleSet::KLONDIKE_DRAW3)
+            .build()
+            .unwrap();
+        let Solution::Won(line) = Solver::new().solve(&gs) else {
+            panic!("The deal can be won");
+        };
+        let mut won = gs.clone();
+        for action in &line {
+            won.act(action).unwrap();
+        }
+        assert!(won.is_won());
+    }
+
+    #[test]
+    fn any_order_solves() {
+        // Deals that each order solves in a few thousand positions
+        for (seed, order) in [
+            (3, MoveOrder::Foundations),
+            (3, MoveOrder::Reveals),
+            (1, MoveOrder::Unordered),
+        ] {
+            let gs = GameEngine::deal(seed);
+            let (solution, effort) = Solver::new().with_order(order).solve_with_effort(&gs);
+            let Solution::Won(line) = solution else {
+                panic!("The deal can be won with {order:?}");
+            };
+            let mut won = gs.clone();
+            for action in &line {
+                won.act(action).unwrap();
+            }
+            assert!(won.is_won());
+            assert!(effort.n_nodes > 0 && effort.n_iterations > 0, "{effort:?}");
+            assert_eq!(order.name().parse(), Ok(order));
+        }
+        let (_, effort) = Solver::new()
+            .with_node_limit(10)
+            .solve_with_effort(&GameEngine::deal(3));
+        assert_eq!(effort.n_nodes, 10);
+    }
+
+    #[test]
+    fn proof_number_search() {
+        let solver = Solver::new().with_algorithm(Algorithm::ProofNumber);
+        let gs = GameEngine::deal(3);
+        let Solution::Won(line) = solver.solve(&gs) else {
+            panic!("The deal can be won");
+        };
+        let mut won = gs.clone();
+        for action in &line {
+            won.act(action).unwrap();
+        }
+        assert!(won.is_won());
+        assert_eq!(solver.solve(&GameEngine::deal(10)), Solution::Lost);
+        assert_eq!(
+            solver
+                .clone()
+                .with_node_limit(10)
+                .solve(&GameEngine::deal(3)),
+            Solution::Unknown
+        );
+        assert_eq!("pn".parse(), Ok(Algorithm::ProofNumber));
+    }
+
+    #[test]
+    fn agrees_with_winning_lines() {
+        let gs = GameEngine::deal(7);
+        let Solution::Won(line) = Solver::new().solve(&gs) else {
+            panic!("The deal can be won");
+        };
+        let all = Agreement {
+            agreed: line.len(),
+            judged: line.len(),
+        };
+        assert_eq!(Solver::new().agreement(&gs, &line, &line), all);
+        let mut given_up = line[..3].to_vec();
+        given_up.extend([Action::Quit, Action::Take]);
+        let agreement = Agreement {
+            agreed: 3,
+            judged: 4,
+        };
+        assert_eq!(Solver::new().agreement(&gs, &line, &given_up), agreement);
+    }
+
+    #[test]
+    fn winning_actions_start_winning_lines() {
+        let gs = GameEngine::deal(7);
+        let Solution::Won(line) = Solver::new().solve(&gs) else {
+            panic!("The deal can be won");
+        };
+        assert!(Solver::new().winning_actions(&gs).contains(&line[0]));
+        assert!(Solver::new()
+            .winning_actions(&GameEngine::deal(10))
+            .is_empty());
+    }
+
+    #[test]
+    #[ignore = "solves hundreds of deals, which takes about half an hour with --release"]
+    fn most_deals_can_be_won() {
+        // 81.96% of the deals of Klondike with draw three and no limit on the passes can be won, see Blake and Gent,
+        // "The Winnability of Klondike Solitaire and Many Other Patience Games" (2019). The deals the solver gives up on
+        // may go either way, so the won deals and the won or unknown ones bound the fraction. A bound that is more
+        // than three standard errors off is a bug in the rules or in the solver.
+        let n_deals = 200;
+        let (mut n_won, mut n_unknown) = (0, 0);
+        for seed in 0..n_deals {
+            let gs = GameEngine::builder()
+                .seed(seed)
+                .rules(RuleSet::KLONDIKE_DRAW3)
+                .build()
+                .unwrap();
+            match Solver::new().solve(&gs) {
+                Solution::Won(_) => n_won += 1,
+                Solution::Lost => {}
+                Solution::Unknown => n_unknown += 1,
+            }
+        }
+        let (p, n) = (0.8196, n_deals as f64);
+        let margin = 3.0 * (p * (1.0 - p) / n).sqrt();
+        assert!(
+            n_won as f64 / n <= p + margin && (n_won + n_unknown) as f64 / n >= p - margin,
+            "{n_won} won and {n_unknown} unknown of {n_deals}"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Export of a batch of games to an SQLite database
+//!
+//! Only built with the `sqlite` feature. Each batch adds a row to `manifests`, and each game adds a row to `games` and
+//! one row per move to `moves`, so that many batches can be collected in one file and queried with SQL.
+//!
+//! ```sql
+//! CREATE TABLE manifests (
+//!     id INTEGER PRIMARY KEY,
+//!     started INTEGER NOT NULL,     -- seconds since the Unix epoch
+//!     version TEXT NOT NULL,        -- the version of the solitaire crate
+//!     rules TEXT NOT NULL,          -- the rule preset
+//!     ais TEXT NOT NULL,            -- the AI profiles, comma separated
+//!     profiles TEXT NOT NULL,       -- the settings of the AI profiles, as sections of the configuration file
+//!     rule_details TEXT NOT NULL    -- each rule of the preset, one `rule: setting` per line
+//! );
+//! CREATE TABLE games (
+//!     id INTEGER PRIMARY KEY,
+//!     manifest INTEGER NOT NULL REFERENCES manifests(id),
+//!     ai TEXT NOT NULL,
+//!     deal TEXT NOT NULL,           -- the deal code, e.g. klondike.1:klondike-classic:42
+//!     score INTEGER NOT NULL,
+//!     result TEXT NOT NULL,         -- Won, Lost, Livelock or IllegalAction
+//!     moves INTEGER NOT NULL,
+//!     seconds REAL NOT NULL,
+//!     peak_nodes INTEGER NOT NULL
+//! );
+//! CREATE TABLE moves (
+//!     game INTEGER NOT NULL REFERENCES games(id),
+//!     n INTEGER NOT NULL,           -- the move number, from 0
+//!     action TEXT NOT NULL,         -- in move notation, e.g. W->D3
+//!     PRIMARY KEY (game, n)
+//! );
+//! ```
+
+use crate::ai::profile::AiProfile;
+use crate::core::Action;
+use crate::rules::{self, RuleSet};
+use crate::stats::GameRecord;
+use rusqlite::{params, Connection};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS manifests (
+    id INTEGER PRIMARY KEY,
+    started INTEGER NOT NULL,
+    version TEXT NOT NULL,
+    rules TEXT NOT NULL,
+    ais TEXT NOT NULL,
+    profiles TEXT NOT NULL DEFAULT '',
+    rule_details TEXT NOT NULL DEFAULT ''
+);
+CREATE TABLE IF NOT EXISTS games (
+    id INTEGER PRIMARY KEY,
+    manifest INTEGER NOT NULL REFERENCES manifests(id),
+    ai TEXT NOT NULL,
+    deal TEXT NOT NULL,
+    score INTEGER NOT NULL,
+    result TEXT NOT NULL,
+    moves INTEGER NOT NULL,
+    seconds REAL NOT NULL,
+    peak_nodes INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS moves (
+    game INTEGER NOT NULL REFERENCES games(id),
+    n INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    PRIMARY KEY (game, n)
+);
+";
+
+/// The section of the configuration file that defines a profile, which `config::Config::parse` reads back as the same profile
+fn config_section(profile: &AiProfile) -> String {
+    let mut text = format!("[ai.{}]\nbase = \"{}\"\n", profile.name, profile.base);
+    if let Some(rollout) = profile.rollout {
+        text += &format!("rollout = \"{rollout}\"\n");
+    }
+    for (key, value) in &profile.settings {
+        text += &format!("{key} = {value}\n");
+    }
+    text
+}
+
+/// An open database, with the manifest of the current batch
+pub struct Export {
+    conn: Connection,
+    manifest: i64,
+}
+
+impl Export {
+    /// Open or create the database at `path`, and add the manifest of a new batch
+    pub fn open(path: &str, preset: &str, profiles: &[AiProfile]) -> rusqlite::Result<Export> {
+        Export::new(Connection::open(path)?, preset, profiles)
+    }
+
+    fn new(conn: Connection, preset: &str, profiles: &[AiProfile]) -> rusqlite::Result<Export> {
+        conn.execute_batch(SCHEMA)?;
+        // Databases from before the settings were recorded lack their columns
+        for column in ["profiles", "rule_details"] {
+            let has_column = conn
+                .prepare("SELECT 1 FROM pragma_table_info('manifests') WHERE name = ?1")?
+                .exists([column])?;
+            if !has_column {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE manifests ADD COLUMN {column} TEXT NOT NULL DEFAULT ''"
+                ))?;
+            }
+        }
+        let started = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let ais = profiles
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let sections = profiles
+            .iter()
+            .map(config_section)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let rule_details = match RuleSet::preset(preset) {
+            Ok(rule_set) => rules::describe(&rule_set)
+                .into_iter()
+                .map(|(rule, setting)| format!("{rule}: {setting}\n"))
+                .collect(),
+            Err(_) => String::new(),
+        };
+        conn.execute(
+            "INSERT INTO manifests (started, version, rules, ais, profiles, rule_details)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                started,
+                env!("CARGO_PKG_VERSION"),
+                preset,
+                ais,
+                sections,
+                rule_details
+            ],
+        )?;
+        let manifest = conn.last_insert_rowid();
+        Ok(Export { conn, manifest })
+    }
+
+    /// Add a game and its moves
+    pub fn add_game(&mut self, game: &GameRecord, actions: &[Action]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO games (manifest, ai, deal, score, result, moves, seconds, peak_nodes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                self.manifest,
+                game.ai,
+                game.deal,
+                game.score,
+                format!("{:?}", game.end),
+                game.moves as i64,
+                game.seconds,
+                game.peak_nodes as i64
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        {
+            let mut insert =
+                tx.prepare("INSERT INTO moves (game, n, action) VALUES (?1, ?2, ?3)")?;
+            for (n, action) in actions.iter().enumerate() {
+                insert.execute(params![id, n as i64, action.to_string()])?;
+            }
+        }
+        tx.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::core::Addr;
+    use crate::stats::GameEnd;
+
+    #[test]
+    fn writes_games_and_moves() {
+        let profiles = [AiProfile::new(
+            "greedy-slow",
+            "greedy",
+            vec![("turnover_penalty".to_string(), -20)],
+        )
+        .unwrap()];
+        let mut export = Export::new(
+            Connection::open_in_memory().unwrap(),
+            "klondike-classic",
+            &profiles,
+        )
+        .unwrap();
+        let (ais, sections, rule_details): (String, String, String) = export
+            .conn
+            .query_row(
+                "SELECT ais, profiles, rule_details FROM manifests",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(ais, "greedy-slow");
+        assert_eq!(Config::parse(&sections).unwrap().ai_profiles, profiles);
+        let mcts = AiProfile::builtin("mcts")
+            .unwrap()
+            .with_rollout("greedy")
+            .unwrap();
+        assert_eq!(
+            Config::parse(&config_section(&mcts)).unwrap().ai_profiles,
+            [mcts]
+        );
+        assert!(
+            rule_details.starts_with("variant: klondike\ndraw: 1\n"),
+            "{rule_details}"
+        );
+        let actions = [Action::Take, Action::Move(Addr::Waste, Addr::Depot3, 1)];
+        let game = GameRecord {
+            ai: "GreedyAi".to_string(),
+            deal: "klondike-classic:0".to_string(),
+            score: 5,
+            end: GameEnd::Lost,
+            moves: actions.len(),
+            seconds: 0.5,
+            peak_nodes: 10,
+            wasted_moves: 0,
+        };
+        export.add_game(&game, &actions).unwrap();
+        export.add_game(&game, &actions).unwrap();
+        let (games, result): (i64, String) = export
+            .conn
+            .query_row(
+                "SELECT COUNT(*), MAX(result) FROM games WHERE manifest = ?1",
+                [export.manifest],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((games, result.as_str()), (2, "Lost"));
+        let action: String = export
+            .conn
+            .query_row(
+                "SELECT action FROM moves WHERE game = 2 AND n = 1",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(action, "W->D3");
+    }
+
+    #[test]
+    fn adds_the_settings_to_old_databases() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE manifests (id INTEGER PRIMARY KEY, started INTEGER NOT NULL, version TEXT NOT NULL, \
+             rules TEXT NOT NULL, ais TEXT NOT NULL);
+             INSERT INTO manifests (started, version, rules, ais) VALUES (0, '0.1.0', 'softie', 'simple');",
+        )
+        .unwrap();
+        let export = Export::new(conn, "softie", &[AiProfile::builtin("simple").unwrap()]).unwrap();
+        let n: i64 = export
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM manifests WHERE profiles = ''",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!((export.manifest, n), (2, 1));
+    }
+}
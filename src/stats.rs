@@ -0,0 +1,477 @@
+//! Statistics over many played games
+//!
+//! Each AI is summarized over several objectives (win rate, average score and average number of moves).
+//! Instead of collapsing these into one number, we report them side by side and extract the Pareto front,
+//! i.e. the AIs that no other AI beats on all objectives at once.
+//!
+//! We also record the branching factor (the number of legal actions) of the states visited in each phase of the game,
+//! before and after pruning the moves between depots that change nothing, and how long the moves took when the games
+//! were timed, see `GameEngine::start_clock`. Timed games, see `DealOptions::timed`, also have a time-adjusted score.
+//!
+//! On deals the solver finds a way to win, the optimal-move agreement is how many moves of an AI kept the game
+//! winnable, see `Solver::agreement`.
+
+use crate::core::{Outcome, Phase};
+use crate::json::json_string;
+use crate::solver::Agreement;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Statistics on the number of legal actions in the states visited
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BranchingFactor {
+    pub n_states: usize,
+    pub total: usize,
+    pub max: usize,
+    /// The total number of legal actions that remain after pruning, see `SolitaireObserver::is_pruned`
+    pub total_unpruned: usize,
+}
+
+impl BranchingFactor {
+    /// Record a state with `n_actions` legal actions, of which `n_pruned` are pruned
+    pub fn add(&mut self, n_actions: usize, n_pruned: usize) {
+        self.n_states += 1;
+        self.total += n_actions;
+        self.total_unpruned += n_actions - n_pruned;
+        self.max = self.max.max(n_actions);
+    }
+
+    pub fn merge(&mut self, other: &BranchingFactor) {
+        self.n_states += other.n_states;
+        self.total += other.total;
+        self.total_unpruned += other.total_unpruned;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.n_states == 0 {
+            0.0
+        } else {
+            self.total as f64 / self.n_states as f64
+        }
+    }
+
+    /// The mean number of legal actions that remain after pruning
+    pub fn mean_unpruned(&self) -> f64 {
+        if self.n_states == 0 {
+            0.0
+        } else {
+            self.total_unpruned as f64 / self.n_states as f64
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEnd {
+    Won,
+    /// The AI quit, or could not finish the game
+    Lost,
+    /// The game was aborted as a loss, since the AI was stuck in a loop. See `watchdog`
+    Livelock,
+    /// The game was aborted as a loss, since the AI suggested an illegal action
+    IllegalAction,
+}
+
+impl From<Option<Outcome>> for GameEnd {
+    /// The end of a game as the engine saw it. A game that is still running when the AI stops is lost.
+    fn from(outcome: Option<Outcome>) -> Self {
+        match outcome {
+            Some(Outcome::Won { .. }) => GameEnd::Won,
+            Some(Outcome::Lost { .. }) | None => GameEnd::Lost,
+        }
+    }
+}
+
+/// The result of a single game
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub ai: String,
+    pub deal: String,
+    pub score: i32,
+    pub end: GameEnd,
+    pub moves: usize,
+    pub seconds: f64,
+    pub peak_nodes: usize,
+    /// Moves that a later move took back, see `GameEngine::n_wasted_moves`
+    pub wasted_moves: usize,
+}
+
+impl GameRecord {
+    /// The record as a JSON object, for scripts
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"ai\": {}, \"deal\": {}, \"score\": {}, \"won\": {}, \"result\": \"{:?}\", \"moves\": {}, \"seconds\": {:.3}, \"peak_nodes\": {}, \"wasted_moves\": {}}}",
+            json_string(&self.ai),
+            json_string(&self.deal),
+            self.score,
+            self.end == GameEnd::Won,
+            self.end,
+            self.moves,
+            self.seconds,
+            self.peak_nodes,
+            self.wasted_moves
+        )
+    }
+}
+
+impl std::fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{} on {}: {:?} with score {} in {} moves, {:.3} s, peak nodes {}",
+            self.ai, self.deal, self.end, self.score, self.moves, self.seconds, self.peak_nodes
+        )
+    }
+}
+
+/// A game of a batch, with what the summary needs to know of it besides the record
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameStats {
+    pub record: GameRecord,
+    pub branching: BTreeMap<Phase, BranchingFactor>,
+    /// Moves that were productive, see `engine::is_productive`
+    pub productive_moves: usize,
+    /// See `GameEngine::action_times`
+    pub action_times: Vec<Option<Duration>>,
+    /// See `GameEngine::timed_score`
+    pub timed_score: Option<i32>,
+    /// See `Solver::agreement`
+    pub agreement: Option<Agreement>,
+}
+
+/// Summary of all games played by a single AI
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub name: String,
+    pub games: usize,
+    pub wins: usize,
+    pub total_score: i32,
+    pub total_moves: usize,
+    /// Moves that were productive, see `engine::is_productive`
+    pub productive_moves: usize,
+    /// Moves that a later move took back, see `GameEngine::n_wasted_moves`
+    pub wasted_moves: usize,
+    /// The largest number of nodes an AI kept in memory in any game
+    pub peak_nodes: usize,
+    /// Games that were aborted as a loss in a livelock
+    pub livelocks: usize,
+    /// Games that were aborted as a loss after an illegal action
+    pub errors: usize,
+    pub branching: BTreeMap<Phase, BranchingFactor>,
+    /// The moves that were timed, and the seconds they took in all and at most
+    pub timed_moves: usize,
+    pub move_seconds: f64,
+    pub slowest_move: f64,
+    /// The games that were timed, and their time-adjusted scores in all, see `GameEngine::timed_score`
+    pub timed_games: usize,
+    pub total_timed_score: i32,
+    /// The moves on won deals that kept the game winnable, of all moves from winnable positions, see
+    /// `Solver::agreement`
+    pub agreement: Agreement,
+}
+
+impl Summary {
+    pub fn new(name: &str) -> Self {
+        Summary {
+            name: name.to_string(),
+            games: 0,
+            wins: 0,
+            total_score: 0,
+            total_moves: 0,
+            productive_moves: 0,
+            wasted_moves: 0,
+            peak_nodes: 0,
+            livelocks: 0,
+            errors: 0,
+            branching: BTreeMap::new(),
+            timed_moves: 0,
+            move_seconds: 0.0,
+            slowest_move: 0.0,
+            timed_games: 0,
+            total_timed_score: 0,
+            agreement: Agreement::default(),
+        }
+    }
+
+    /// Add the result of one game to the summary
+    pub fn add_game(&mut self, score: i32, end: GameEnd, moves: usize) {
+        self.games += 1;
+        self.wins += (end == GameEnd::Won) as usize;
+        self.livelocks += (end == GameEnd::Livelock) as usize;
+        self.errors += (end == GameEnd::IllegalAction) as usize;
+        self.total_score += score;
+        self.total_moves += moves;
+    }
+
+    /// Add all there is to know of one game of a batch to the summary
+    pub fn add_stats(&mut self, game: &GameStats) {
+        let record = &game.record;
+        self.add_game(record.score, record.end, record.moves);
+        self.add_branching(&game.branching);
+        self.add_productive(game.productive_moves);
+        self.add_wasted(record.wasted_moves);
+        self.add_peak_nodes(record.peak_nodes);
+        self.add_action_times(&game.action_times);
+        self.add_timed_score(game.timed_score);
+        if let Some(agreement) = &game.agreement {
+            self.add_agreement(agreement);
+        }
+    }
+
+    /// Add the number of productive moves of one game to the summary
+    pub fn add_productive(&mut self, productive_moves: usize) {
+        self.productive_moves += productive_moves;
+    }
+
+    /// Add the number of wasted moves of one game to the summary
+    pub fn add_wasted(&mut self, wasted_moves: usize) {
+        self.wasted_moves += wasted_moves;
+    }
+
+    /// Add the peak memory use of one game to the summary
+    pub fn add_peak_nodes(&mut self, peak_nodes: usize) {
+        self.peak_nodes = self.peak_nodes.max(peak_nodes);
+    }
+
+    /// Add the branching factors of one game to the summary
+    pub fn add_branching(&mut self, branching: &BTreeMap<Phase, BranchingFactor>) {
+        for (phase, b) in branching {
+            self.branching.entry(*phase).or_default().merge(b);
+        }
+    }
+
+    /// Add the times of the actions of one game, see `GameEngine::action_times`, to the summary
+    ///
+    /// A move took the time since the action before it, or since the clock was started.
+    pub fn add_action_times(&mut self, times: &[Option<Duration>]) {
+        let mut last = Duration::ZERO;
+        for time in times.iter().flatten() {
+            let seconds = time.saturating_sub(last).as_secs_f64();
+            self.timed_moves += 1;
+            self.move_seconds += seconds;
+            self.slowest_move = self.slowest_move.max(seconds);
+            last = *time;
+        }
+    }
+
+    /// Add the time-adjusted score of one game to the summary, if it was timed
+    pub fn add_timed_score(&mut self, timed_score: Option<i32>) {
+        if let Some(score) = timed_score {
+            self.timed_games += 1;
+            self.total_timed_score += score;
+        }
+    }
+
+    /// Add how many moves of one game agreed with the solver to the summary
+    pub fn add_agreement(&mut self, agreement: &Agreement) {
+        self.agreement.agreed += agreement.agreed;
+        self.agreement.judged += agreement.judged;
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        self.per_game(self.wins as f64)
+    }
+
+    pub fn avg_score(&self) -> f64 {
+        self.per_game(self.total_score as f64)
+    }
+
+    pub fn avg_moves(&self) -> f64 {
+        self.per_game(self.total_moves as f64)
+    }
+
+    /// The summary as a JSON object, for scripts
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"name\": {}, \"games\": {}, \"wins\": {}, \"win_rate\": {:.4}, \"avg_score\": {:.2}, \"avg_moves\": {:.2}, \"livelocks\": {}, \"errors\": {}}}",
+            json_string(&self.name),
+            self.games,
+            self.wins,
+            self.win_rate(),
+            self.avg_score(),
+            self.avg_moves(),
+            self.livelocks,
+            self.errors
+        )
+    }
+
+    /// The fraction of all moves that were productive
+    pub fn productive_ratio(&self) -> f64 {
+        if self.total_moves == 0 {
+            0.0
+        } else {
+            self.productive_moves as f64 / self.total_moves as f64
+        }
+    }
+
+    /// The fraction of all moves that were wasted
+    pub fn wasted_ratio(&self) -> f64 {
+        if self.total_moves == 0 {
+            0.0
+        } else {
+            self.wasted_moves as f64 / self.total_moves as f64
+        }
+    }
+
+    fn per_game(&self, total: f64) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            total / self.games as f64
+        }
+    }
+
+    /// The objectives, oriented so that larger is always better
+    fn objectives(&self) -> [f64; 3] {
+        [self.win_rate(), self.avg_score(), -self.avg_moves()]
+    }
+
+    /// Is this summary at least as good as `other` on every objective, and strictly better on some?
+    pub fn dominates(&self, other: &Summary) -> bool {
+        let (a, b) = (self.objectives(), other.objectives());
+        a.iter().zip(b.iter()).all(|(x, y)| x >= y) && a.iter().zip(b.iter()).any(|(x, y)| x > y)
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}: win rate {:.1}%, avg score {:.1}, avg moves {:.1}, productive moves {:.1}%, wasted moves {:.1}%, peak nodes {} ({} games)",
+            self.name,
+            100.0 * self.win_rate(),
+            self.avg_score(),
+            self.avg_moves(),
+            100.0 * self.productive_ratio(),
+            100.0 * self.wasted_ratio(),
+            self.peak_nodes,
+            self.games
+        )?;
+        if self.livelocks > 0 {
+            write!(f, "\n  Livelocks: {}", self.livelocks)?;
+        }
+        if self.errors > 0 {
+            write!(f, "\n  Illegal actions: {}", self.errors)?;
+        }
+        if self.timed_moves > 0 {
+            write!(
+                f,
+                "\n  Move time: mean {:.3} ms, slowest {:.3} ms",
+                1000.0 * self.move_seconds / self.timed_moves as f64,
+                1000.0 * self.slowest_move
+            )?;
+        }
+        if self.timed_games > 0 {
+            write!(
+                f,
+                "\n  Time-adjusted score: mean {:.1} ({} timed games)",
+                self.total_timed_score as f64 / self.timed_games as f64,
+                self.timed_games
+            )?;
+        }
+        if self.agreement.judged > 0 {
+            write!(
+                f,
+                "\n  Optimal-move agreement: {:.1}% ({} moves on solved deals)",
+                100.0 * self.agreement.agreed as f64 / self.agreement.judged as f64,
+                self.agreement.judged
+            )?;
+        }
+        for (phase, b) in self.branching.iter() {
+            write!(
+                f,
+                "\n  {phase:?} branching factor: mean {:.1} ({:.1} after pruning), max {}",
+                b.mean(),
+                b.mean_unpruned(),
+                b.max
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The summaries that are not dominated by any other summary
+pub fn pareto_front(summaries: &[Summary]) -> Vec<&Summary> {
+    summaries
+        .iter()
+        .filter(|s| !summaries.iter().any(|other| other.dominates(s)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(name: &'static str, games: &[(i32, bool, usize)]) -> Summary {
+        let mut s = Summary::new(name);
+        for &(score, won, moves) in games {
+            let end = if won { GameEnd::Won } else { GameEnd::Lost };
+            s.add_game(score, end, moves);
+        }
+        s
+    }
+
+    #[test]
+    fn pareto_front_keeps_tradeoffs() {
+        let winner = summary("winner", &[(100, true, 300), (50, false, 200)]);
+        let scorer = summary("scorer", &[(400, false, 300), (400, false, 300)]);
+        let dominated = summary("dominated", &[(50, false, 300), (50, false, 300)]);
+        let summaries = vec![winner.clone(), scorer.clone(), dominated];
+        let front = pareto_front(&summaries);
+        assert_eq!(front, vec![&winner, &scorer]);
+        assert_eq!(
+            winner.to_json(),
+            "{\"name\": \"winner\", \"games\": 2, \"wins\": 1, \"win_rate\": 0.5000, \"avg_score\": 75.00, \"avg_moves\": 250.00, \"livelocks\": 0, \"errors\": 0}"
+        );
+    }
+
+    #[test]
+    fn move_times() {
+        let mut s = Summary::new("timed");
+        let ms = |ms| Some(Duration::from_millis(ms));
+        s.add_action_times(&[None, ms(2), ms(3), ms(9)]);
+        assert_eq!((s.timed_moves, s.slowest_move), (3, 0.006));
+        assert!(s
+            .to_string()
+            .ends_with("Move time: mean 3.000 ms, slowest 6.000 ms"));
+
+        s.add_timed_score(None);
+        assert!(!s.to_string().contains("Time-adjusted"));
+        s.add_timed_score(Some(-4));
+        s.add_timed_score(Some(7));
+        assert!(s
+            .to_string()
+            .ends_with("Time-adjusted score: mean 1.5 (2 timed games)"));
+
+        s.add_agreement(&Agreement {
+            agreed: 3,
+            judged: 4,
+        });
+        assert!(s
+            .to_string()
+            .ends_with("Optimal-move agreement: 75.0% (4 moves on solved deals)"));
+    }
+
+    #[test]
+    fn game_record_as_json() {
+        let record = GameRecord {
+            ai: "GreedyAi".to_string(),
+            deal: "klondike-classic:0".to_string(),
+            score: 600,
+            end: GameEnd::Won,
+            moves: 278,
+            seconds: 0.0114,
+            peak_nodes: 278,
+            wasted_moves: 4,
+        };
+        assert_eq!(
+            record.to_json(),
+            "{\"ai\": \"GreedyAi\", \"deal\": \"klondike-classic:0\", \"score\": 600, \"won\": true, \"result\": \"Won\", \"moves\": 278, \"seconds\": 0.011, \"peak_nodes\": 278, \"wasted_moves\": 4}"
+        );
+        assert_eq!(
+            record.to_string(),
+            "GreedyAi on klondike-classic:0: Won with score 600 in 278 moves, 0.011 s, peak nodes 278"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Streaming of game results while a batch is running
+//!
+//! With `--stream ADDR`, the batch connects to a listener and sends one JSON object per line for each finished game
+//! (see `GameRecord::to_json`), so that a dashboard can follow the batch without polling files.
+//! The address is `HOST:PORT` for TCP, or `unix:PATH` for a Unix socket. Try it with `nc -l 7878`.
+
+use crate::stats::GameRecord;
+use std::io::Write;
+
+/// A connection that game records are sent to
+pub struct Stream {
+    out: Box<dyn Write>,
+}
+
+impl Stream {
+    /// Connect to `HOST:PORT`, or to the Unix socket `unix:PATH`
+    pub fn connect(addr: &str) -> Result<Self, std::io::Error> {
+        let out: Box<dyn Write> = match addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => Box::new(std::os::unix::net::UnixStream::connect(path)?),
+            #[cfg(not(unix))]
+            Some(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "Unix sockets need a Unix",
+                ))
+            }
+            None => Box::new(std::net::TcpStream::connect(addr)?),
+        };
+        Ok(Stream { out })
+    }
+
+    /// Send a record right away
+    pub fn send(&mut self, record: &GameRecord) -> Result<(), std::io::Error> {
+        writeln!(self.out, "{}", record.to_json())?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameEnd;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn sends_one_line_per_game() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut stream = Stream::connect(&listener.local_addr().unwrap().to_string()).unwrap();
+        let record = GameRecord {
+            ai: "SimpleAi".to_string(),
+            deal: "klondike-classic:1".to_string(),
+            score: -20,
+            end: GameEnd::Lost,
+            moves: 50,
+            seconds: 0.25,
+            peak_nodes: 10,
+            wasted_moves: 0,
+        };
+        stream.send(&record).unwrap();
+        stream.send(&record).unwrap();
+        let (conn, _) = listener.accept().unwrap();
+        let lines = BufReader::new(conn)
+            .lines()
+            .take(2)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(lines, vec![record.to_json(), record.to_json()]);
+    }
+}
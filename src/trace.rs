@@ -0,0 +1,105 @@
+//! Traces of the decisions of an AI
+//!
+//! A trace is a JSON Lines file with one object per move: the game, the move number, the candidate actions the AI
+//! considered with their scores, best first, and the action it chose. This is meant for offline analysis of many games.
+//! The actions are in move notation, e.g. `W->D1`, as in replay files.
+//!
+//! For AIs that have `Ai::action_probabilities`, each candidate also has the probability of playing it, and the line has
+//! the entropy of the choice in bits, e.g. as targets for imitation learning or to compare how decided the AIs play.
+
+use crate::ai::entropy;
+use crate::core::Action;
+use crate::json::json_string;
+use std::io::Write;
+
+/// Writes the decisions of AIs to a JSON Lines file
+pub struct Trace<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Trace<W> {
+    pub fn new(out: W) -> Self {
+        Trace { out }
+    }
+
+    /// Write one line for a decision
+    pub fn record(
+        &mut self,
+        ai: &str,
+        deal: &str,
+        move_number: usize,
+        candidates: &[(i64, Action)],
+        probabilities: &[(f64, Action)],
+        chosen: &Action,
+    ) -> Result<(), std::io::Error> {
+        let candidates = candidates
+            .iter()
+            .map(|(score, action)| {
+                let probability = probabilities
+                    .iter()
+                    .find(|(_, a)| a == action)
+                    .map_or(String::new(), |(p, _)| format!(", \"probability\": {p}"));
+                format!(
+                    "{{\"action\": {}, \"score\": {score}{probability}}}",
+                    json_string(&action.to_string())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let entropy = if probabilities.is_empty() {
+            String::new()
+        } else {
+            format!(", \"entropy\": {}", entropy(probabilities))
+        };
+        writeln!(
+            self.out,
+            "{{\"ai\": {}, \"deal\": {}, \"move\": {move_number}, \"candidates\": [{candidates}]{entropy}, \"chosen\": {}}}",
+            json_string(ai),
+            json_string(deal),
+            json_string(&chosen.to_string())
+        )
+    }
+
+    /// Write out everything recorded so far
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Addr;
+
+    #[test]
+    fn writes_one_line_per_decision() {
+        let mut trace = Trace::new(vec![]);
+        let chosen = Action::Move(Addr::Waste, Addr::Depot1, 1);
+        trace
+            .record(
+                "GreedyAi",
+                "softie:3",
+                7,
+                &[(5, chosen.clone()), (0, Action::Take)],
+                &[],
+                &chosen,
+            )
+            .unwrap();
+        trace
+            .record(
+                "GreedyAi",
+                "softie:3",
+                8,
+                &[(0, Action::Take), (0, Action::Quit)],
+                &[(0.5, Action::Take), (0.5, Action::Quit)],
+                &Action::Take,
+            )
+            .unwrap();
+        let out = String::from_utf8(trace.out).unwrap();
+        assert_eq!(
+            out,
+            "{\"ai\": \"GreedyAi\", \"deal\": \"softie:3\", \"move\": 7, \"candidates\": [{\"action\": \"W->D1\", \"score\": 5}, {\"action\": \"T\", \"score\": 0}], \"chosen\": \"W->D1\"}\n\
+             {\"ai\": \"GreedyAi\", \"deal\": \"softie:3\", \"move\": 8, \"candidates\": [{\"action\": \"T\", \"score\": 0, \"probability\": 0.5}, {\"action\": \"Q\", \"score\": 0, \"probability\": 0.5}], \"entropy\": 1, \"chosen\": \"T\"}\n"
+        );
+    }
+}
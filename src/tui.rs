@@ -0,0 +1,532 @@
+//! Play a game yourself in the terminal
+//!
+//! `solitaire play [DEAL]` shows the talon, the waste, the foundations and the depots, as a player sees them, and
+//! turns the keys into actions for the engine:
+//!
+//!  - space takes from the talon, or turns over the waste when the talon is empty
+//!  - `w` or `1` to `7` picks the waste or a depot to move from, and then `1` to `7` or `f` picks the depot or the
+//!    foundation to move to. The most cards that can go there are moved.
+//!  - `1` to `7` on a depot with a face down card on top reveals it
+//!  - `u` takes back the last action, Esc forgets the picked pile, and `q` gives up the game
+//!  - `a` plays all cards to the foundations once the talon and the waste are empty and all cards are face up
+//!  - `h` asks the advisor for a hint
+//!  - `p` lets the AI of the advisor play on for you, one action at a time, until any key is pressed
+//!  - `s` lists the moves after which the solver still finds a way to win, with the assistance to do so
+//!
+//! With an advisor, see `advisor`, the message line tells what it would have played instead.
+//!
+//! The assistance of the game, see `Assistance`, marks the waste and the depots with a card that can be played with a
+//! `+`, lets `s` ask the solver, or refuses the moves after which the solver proves that the game can't be won. The
+//! solver knows the face down cards, and gives up on positions that take too long, see `ASSIST_NODE_LIMIT`.
+
+use crate::advisor::Advisor;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use solitaire::core::{Action, Addr, CardView, Color, Suit, Value};
+use solitaire::engine::{Assistance, Autoplay, GameEngine, Snapshot, Stop};
+use solitaire::solver::{Solution, Solver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+
+/// A game played from the keyboard
+pub struct App {
+    gs: GameEngine,
+    /// The actions taken so far, without the ones taken back
+    actions: Vec<Action>,
+    /// The pile to move from, once it is picked
+    selected: Option<Addr>,
+    message: String,
+    advisor: Option<Advisor>,
+    /// The position to show while the advisor plays on, see `play_on`
+    playing: Option<Snapshot>,
+}
+
+impl App {
+    pub fn new(gs: GameEngine) -> Self {
+        App {
+            gs,
+            actions: vec![],
+            selected: None,
+            message: String::new(),
+            advisor: None,
+            playing: None,
+        }
+    }
+
+    pub fn with_advisor(mut self, advisor: Option<Advisor>) -> Self {
+        self.advisor = advisor;
+        self
+    }
+
+    pub fn advisor(&self) -> Option<&Advisor> {
+        self.advisor.as_ref()
+    }
+
+    pub fn game(&self) -> &GameEngine {
+        &self.gs
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    fn assistance(&self) -> Assistance {
+        self.gs.options().assistance
+    }
+
+    /// Handle a key press. Returns false once the game is over.
+    pub fn on_key(&mut self, key: KeyCode) -> bool {
+        self.message.clear();
+        let action = match key {
+            KeyCode::Char(' ') if self.gs.is_legal(&Action::Take) => Some(Action::Take),
+            KeyCode::Char(' ') => Some(Action::Turnover),
+            KeyCode::Char('q') => Some(Action::Quit),
+            KeyCode::Char('u') => {
+                self.selected = None;
+                match self.gs.undo() {
+                    Some(action) => {
+                        self.actions.pop();
+                        self.message = format!("Took back {action}");
+                    }
+                    None => self.message = "Nothing to take back".to_string(),
+                }
+                None
+            }
+            KeyCode::Esc => {
+                self.selected = None;
+                None
+            }
+            KeyCode::Char('a') => {
+                self.selected = None;
+                let finish = self.gs.auto_finish();
+                if finish.is_empty() {
+                    self.message = "The cards can't all go to the foundations yet".to_string();
+                }
+                self.actions.extend(finish);
+                None
+            }
+            KeyCode::Char('h') => {
+                self.message = match &self.advisor {
+                    Some(advisor) => {
+                        format!("{} would play {}.", advisor.name(), advisor.hint(&self.gs))
+                    }
+                    None => "Hints need an advisor, see --advisor".to_string(),
+                };
+                None
+            }
+            KeyCode::Char('s') => {
+                self.message = if self.assistance() >= Assistance::SolverMoves {
+                    match Solver::new()
+                        .with_node_limit(ASSIST_NODE_LIMIT)
+                        .winning_actions(&self.gs)[..]
+                    {
+                        [] => "The solver finds no way to win from here".to_string(),
+                        ref actions => {
+                            format!("The solver wins after {}", crate::join(actions, ", "))
+                        }
+                    }
+                } else {
+                    "Solver moves need the assistance, see --assist".to_string()
+                };
+                None
+            }
+            KeyCode::Char('w') if self.selected.is_none() => {
+                self.selected = Some(Addr::Waste);
+                None
+            }
+            KeyCode::Char('f') => match self.selected.take() {
+                Some(from) => {
+                    let to = Addr::FOUNDATIONS
+                        .into_iter()
+                        .find(|to| self.gs.is_legal(&Action::Move(from, *to, 1)));
+                    Some(Action::Move(from, to.unwrap_or(Addr::Foundation1), 1))
+                }
+                None => {
+                    self.message = "Pick a pile to move from first".to_string();
+                    None
+                }
+            },
+            KeyCode::Char(c @ '1'..='7') => {
+                let depot = Addr::DEPOTS[c as usize - '1' as usize];
+                match self.selected.take() {
+                    Some(from) => Some(self.move_to_depot(from, depot)),
+                    None if self.gs.is_legal(&Action::Reveal(depot)) => Some(Action::Reveal(depot)),
+                    None => {
+                        self.selected = Some(depot);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        if let Some(action) = action.filter(|action| !self.refuses(action)) {
+            if let Some(advisor) = self.advisor.as_mut().filter(|_| self.gs.is_legal(&action)) {
+                if let Some(advice) = advisor.review(&self.gs, &action) {
+                    self.message = format!(
+                        "{} prefers {} ({:+}).",
+                        advisor.name(),
+                        advice.preferred,
+                        advice.gain
+                    );
+                }
+            }
+            match self.gs.act(&action) {
+                Ok(_) => self.actions.push(action),
+                Err(e) => self.message = format!("{action} is not allowed: {e}"),
+            }
+        }
+        self.gs.is_running()
+    }
+
+    /// Let the AI of the advisor play on, and show each position with `show` until it returns false or the game ends
+    ///
+    /// The AI plays a copy of the game on a thread of its own, so that `show` sees a key press while the AI thinks. The
+    /// game goes on from the last position shown: an action the AI is still thinking about when it is stopped is dropped.
+    ///
+    /// The actions the AI takes are the player's, and can be taken back like any other.
+    pub fn play_on(
+        &mut self,
+        mut show: impl FnMut(&App) -> Result<bool, std::io::Error>,
+    ) -> Result<(), std::io::Error> {
+        self.selected = None;
+        let Some(advisor) = self.advisor.clone() else {
+            self.message = "Playing on needs an advisor, see --advisor".to_string();
+            return Ok(());
+        };
+        let name = advisor.name().to_string();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, snapshots) = mpsc::sync_channel(0);
+        let player = {
+            let mut gs = self.gs.clone();
+            let cancel = Arc::clone(&cancel);
+            std::thread::spawn(move || {
+                let mut ai = advisor.make_ai(&gs);
+                let mut autoplay = Autoplay::new(&mut gs, ai.as_mut()).cancel_on(&cancel);
+                for snapshot in autoplay.by_ref() {
+                    if sender.send(snapshot).is_err() {
+                        break;
+                    }
+                }
+                autoplay.stop().cloned()
+            })
+        };
+        self.message = format!("{name} plays, press any key to take over.");
+        let mut n_actions = 0;
+        let mut result = Ok(());
+        loop {
+            match snapshots.recv_timeout(AUTOPLAY_DELAY) {
+                Ok(snapshot) => {
+                    // The snapshot has no history, so the action is taken again here to be taken back later
+                    if let Some(action) = &snapshot.last_action {
+                        self.gs
+                            .act(action)
+                            .expect("The AI took the action in the same game");
+                        self.actions.push(action.clone());
+                    }
+                    n_actions += 1;
+                    self.playing = Some(snapshot);
+                }
+                // The AI is still thinking, and the player may take over meanwhile
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            match show(self) {
+                Ok(true) => {}
+                stopped => {
+                    result = stopped.map(|_| ());
+                    cancel.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+        // A player that took over doesn't wait for the AI to finish thinking
+        let stop = if cancel.load(Ordering::Relaxed) {
+            Some(Stop::Cancelled)
+        } else {
+            player.join().ok().flatten()
+        };
+        self.message = match stop {
+            Some(Stop::IllegalAction(action)) => {
+                format!("{name} suggested {action}, which is not allowed.")
+            }
+            _ => format!("{name} played {n_actions} actions."),
+        };
+        self.playing = None;
+        result
+    }
+
+    /// Should the action be refused, as the solver proves that the game can't be won after it? Tells why if so.
+    fn refuses(&mut self, action: &Action) -> bool {
+        if self.assistance() < Assistance::NoLosingMoves || *action == Action::Quit {
+            return false;
+        }
+        let mut next = self.gs.clone();
+        if next.act(action).is_err() {
+            return false;
+        }
+        let lost = Solver::new()
+            .with_node_limit(ASSIST_NODE_LIMIT)
+            .solve(&next)
+            == Solution::Lost;
+        if lost {
+            self.message = format!("{action} would lose the game");
+        }
+        lost
+    }
+
+    /// Can a card be played from the pile?
+    fn is_playable(&self, from: Addr) -> bool {
+        self.gs.legal_actions().iter().any(|action| match action {
+            Action::Move(addr, _, _) => *addr == from,
+            Action::Reveal(addr) => *addr == from,
+            _ => false,
+        })
+    }
+
+    /// The move of the most cards from a pile to a depot that is legal, or of one card if none is
+    fn move_to_depot(&self, from: Addr, to: Addr) -> Action {
+        let n_takeable = self.gs.observe().n_takeable_cards(&from);
+        (1..=n_takeable)
+            .rev()
+            .map(|n| Action::Move(from, to, n))
+            .find(|action| self.gs.is_legal(action))
+            .unwrap_or(Action::Move(from, to, 1))
+    }
+}
+
+/// A card as three characters, in the color of its suit
+fn card_span(card: Option<CardView>) -> Span<'static> {
+    match card {
+        Some(CardView::FaceUp(suit, value)) => suit_span(suit, value),
+        Some(CardView::FaceDown) => Span::raw("▨▨▨"),
+        None => Span::raw("   "),
+    }
+}
+
+fn suit_span(suit: Suit, value: Value) -> Span<'static> {
+    let span = Span::raw(format!("{suit}{value}"));
+    match suit.color() {
+        Color::Red => span.red(),
+        Color::Black => span,
+    }
+}
+
+/// Draw the game as the player sees it
+pub fn render(frame: &mut ratatui::Frame, app: &App) {
+    let gs = app
+        .playing
+        .as_ref()
+        .map_or(&app.gs, |snapshot| &snapshot.game);
+    let view = gs.observe();
+    let marker = |addr: Addr| {
+        if app.selected == Some(addr) {
+            "*"
+        } else if app.assistance() >= Assistance::PlayableCards
+            && app.playing.is_none()
+            && app.is_playable(addr)
+        {
+            "+"
+        } else {
+            " "
+        }
+    };
+    let mut top = vec![Span::raw(format!(
+        "Talon {:2} {}Waste ",
+        view.talon_size,
+        marker(Addr::Waste)
+    ))];
+    for &(suit, value) in view.waste.iter().rev().take(3).rev() {
+        top.extend([suit_span(suit, value), Span::raw(" ")]);
+    }
+    top.push(Span::raw(" Foundations "));
+    for tops in view.foundation_tops {
+        match tops {
+            Some((suit, value)) => top.push(suit_span(suit, value)),
+            None => top.push(Span::raw(" □ ")),
+        }
+        top.push(Span::raw(" "));
+    }
+    let mut lines = vec![Line::from(top), Line::default()];
+    lines.push(Line::from(
+        Addr::DEPOTS
+            .iter()
+            .enumerate()
+            .map(|(i, addr)| Span::raw(format!("{}{}   ", marker(*addr), i + 1)))
+            .collect::<Vec<_>>(),
+    ));
+    let height = view.depots.iter().map(|d| d.len()).max().unwrap_or(0);
+    for row in 0..height {
+        let mut spans = vec![];
+        for depot in view.depots.iter() {
+            let card = (row < depot.len())
+                .then(|| depot.card(depot.len() - row))
+                .flatten();
+            spans.extend([Span::raw(" "), card_span(card), Span::raw(" ")]);
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::default());
+    let picked = match app.selected {
+        Some(addr) => format!("Moving from {addr:?}. "),
+        None => String::new(),
+    };
+    lines.push(Line::from(format!(
+        "Score {}. {picked}{}",
+        gs.score(),
+        app.message
+    )));
+    let help =
+        "space take, w/1-7 pick, 1-7/f move, u undo, a finish, h hint, p play on, s solver, Esc cancel, q give up";
+    lines.push(Line::from(help).style(Style::default().dim()));
+    let block = Block::bordered().title(" Solitaire ");
+    frame.render_widget(Paragraph::new(lines).block(block), frame.area());
+}
+
+/// The positions the solver searches for each move it judges, so that the assistance answers in about a second
+const ASSIST_NODE_LIMIT: usize = 20_000;
+
+/// The time each position is shown while the advisor plays on
+const AUTOPLAY_DELAY: std::time::Duration = std::time::Duration::from_millis(150);
+
+/// Play a game until it is won or given up, and return it with the actions taken
+pub fn play(gs: GameEngine, advisor: Option<Advisor>) -> Result<App, std::io::Error> {
+    let mut terminal = ratatui::try_init()?;
+    let mut app = App::new(gs).with_advisor(advisor);
+    let result = (|| loop {
+        terminal.draw(|frame| render(frame, &app))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Char('p') {
+                app.play_on(|app| {
+                    terminal.draw(|frame| render(frame, app))?;
+                    // Any key takes over, and is not taken as a command
+                    let pressed = event::poll(AUTOPLAY_DELAY)?;
+                    if pressed {
+                        event::read()?;
+                    }
+                    Ok(!pressed)
+                })?;
+                if !app.gs.is_running() {
+                    return Ok(());
+                }
+            } else if !app.on_key(key.code) {
+                return Ok(());
+            }
+        }
+    })();
+    ratatui::restore();
+    result.map(|()| app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_become_actions() {
+        let mut app = App::new(GameEngine::deal(0));
+        assert!(app.on_key(KeyCode::Char(' ')));
+        assert_eq!(app.game().talon_len(), 23);
+        // The waste card is picked, and a move that is not legal leaves the game as it was
+        app.on_key(KeyCode::Char('w'));
+        let before = app.game().clone();
+        let illegal = Addr::DEPOTS
+            .iter()
+            .position(|to| !app.game().is_legal(&Action::Move(Addr::Waste, *to, 1)))
+            .unwrap();
+        app.on_key(KeyCode::Char((b'1' + illegal as u8) as char));
+        assert_eq!(app.game(), &before);
+        assert!(app.message.contains("not allowed"));
+        assert_eq!(app.actions(), [Action::Take]);
+
+        app.on_key(KeyCode::Char('u'));
+        assert_eq!(app.game().talon_len(), 24);
+        assert!(app.actions().is_empty());
+        assert!(!app.on_key(KeyCode::Char('q')));
+
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 16)).unwrap();
+        let buffer = terminal
+            .draw(|frame| render(frame, &app))
+            .unwrap()
+            .buffer
+            .clone();
+        let text = buffer
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+        assert!(text.contains("Talon 24"));
+    }
+
+    #[test]
+    fn assistance() {
+        let mut app = App::new(GameEngine::deal(10));
+        app.on_key(KeyCode::Char('s'));
+        assert!(app.message.contains("need the assistance"));
+        let gs = GameEngine::builder()
+            .seed(10)
+            .assistance(Assistance::NoLosingMoves)
+            .build()
+            .unwrap();
+        let mut app = App::new(gs);
+        app.on_key(KeyCode::Char('s'));
+        assert_eq!(app.message, "The solver finds no way to win from here");
+        // The deal can't be won, so every move is refused, but the game can still be given up
+        assert!(app.on_key(KeyCode::Char(' ')));
+        assert_eq!(app.message, "T would lose the game");
+        assert!(app.actions().is_empty());
+
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 16)).unwrap();
+        let buffer = terminal
+            .draw(|frame| render(frame, &app))
+            .unwrap()
+            .buffer
+            .clone();
+        let text = buffer
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+        let n_playable = Addr::DEPOTS
+            .iter()
+            .filter(|addr| app.is_playable(**addr))
+            .count();
+        assert!(n_playable > 0);
+        assert_eq!(text.matches('+').count(), n_playable);
+        assert!(!app.on_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn advisor_plays_on() {
+        let mut app = App::new(GameEngine::deal(0));
+        app.play_on(|_| Ok(true)).unwrap();
+        assert!(app.message.contains("needs an advisor"));
+        let advisor = Advisor::new(solitaire::ai::profile::AiProfile::builtin("greedy").unwrap());
+        let mut app = app.with_advisor(Some(advisor));
+        // The AI may take longer than the delay for a move, and then the same snapshot, or none yet, is shown again
+        let mut shown: Vec<Snapshot> = vec![];
+        app.play_on(|app| {
+            if let Some(snapshot) = &app.playing {
+                if shown.last().map(|s| s.n_actions) != Some(snapshot.n_actions) {
+                    shown.push(snapshot.clone());
+                }
+            }
+            Ok(shown.len() < 3)
+        })
+        .unwrap();
+        assert_eq!(app.actions().len(), 3);
+        assert_eq!(shown.last().map(|s| &s.game), Some(app.game()));
+        assert_eq!(app.message, "greedy played 3 actions.");
+        assert!(app.playing.is_none());
+        // The actions of the advisor can be taken back
+        app.on_key(KeyCode::Char('u'));
+        assert_eq!(app.actions().len(), 2);
+    }
+}
@@ -0,0 +1,148 @@
+//! Tuning of the weights of the evaluation function
+//!
+//! Uses the cross-entropy method: each generation, a population of weight vectors is drawn from a normal distribution,
+//! every candidate plays the same suite of deals with a `LinearAi`, and the distribution is refitted to the best candidates.
+
+use crate::ai::eval::Weights;
+use crate::ai::features::N_FEATURES;
+use crate::ai::LinearAi;
+use crate::engine::{run_game, DealOptions, GameEngine};
+use crate::rules::RuleSet;
+use rand::prelude::*;
+
+/// A game that has not finished after this many actions is counted as a loss
+const MAX_ACTIONS: usize = 2000;
+
+/// Noise added to the standard deviations each generation, so that the search does not stop too early
+const EXTRA_STD: f64 = 0.25;
+
+/// Settings for the tuner
+#[derive(Debug, Clone)]
+pub struct TuneConfig {
+    pub generations: usize,
+    pub population: usize,
+    /// The fraction of each generation used to refit the distribution
+    pub elite_fraction: f64,
+    /// The standard deviation of the weights in the first generation
+    pub initial_std: f64,
+    /// The seeds of the deals that every candidate plays
+    pub seeds: Vec<u64>,
+    pub rules: RuleSet,
+    pub options: DealOptions,
+}
+
+/// How well a weight vector plays on the suite. Compared by wins first, and cards on the foundations second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Fitness {
+    pub wins: usize,
+    pub foundation_cards: usize,
+}
+
+/// Play a game with a `LinearAi`, and return whether it was won and the number of cards on the foundations
+fn play(weights: &Weights, seed: u64, rules: &RuleSet, options: &DealOptions) -> (bool, usize) {
+    let mut gs = GameEngine::builder()
+        .seed(seed)
+        .rules(rules.clone())
+        .options(options.clone())
+        .build()
+        .expect("Rules checked by the caller");
+    let mut ai = LinearAi::with_weights(gs.observe(), *weights);
+    run_game(&mut gs, &mut ai, MAX_ACTIONS);
+    let foundation_cards = gs
+        .observe()
+        .foundation_tops
+        .iter()
+        .map(|t| t.map_or(0, |(_, v)| v.numeric_value() as usize))
+        .sum();
+    (gs.is_won(), foundation_cards)
+}
+
+/// Evaluate a weight vector on all deals of the suite
+pub fn fitness(weights: &Weights, config: &TuneConfig) -> Fitness {
+    let mut f = Fitness::default();
+    for &seed in &config.seeds {
+        let (won, foundation_cards) = play(weights, seed, &config.rules, &config.options);
+        f.wins += won as usize;
+        f.foundation_cards += foundation_cards;
+    }
+    f
+}
+
+/// A sample from the standard normal distribution, by the Box-Muller transform
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Tune the weights with the cross-entropy method, starting from `initial`
+///
+/// After each generation, `report` is called with the generation number, the best fitness and the best weights so far.
+pub fn cross_entropy(
+    initial: &Weights,
+    config: &TuneConfig,
+    rng: &mut impl Rng,
+    mut report: impl FnMut(usize, Fitness, &Weights),
+) -> Weights {
+    let n_elite = ((config.population as f64 * config.elite_fraction).round() as usize)
+        .clamp(1, config.population.max(1));
+    let mut mean = initial.map(|w| w as f64);
+    let mut std = [config.initial_std; N_FEATURES];
+    let mut best = (fitness(initial, config), *initial);
+
+    for generation in 0..config.generations {
+        let mut candidates = (0..config.population)
+            .map(|_| {
+                let weights: Weights = std::array::from_fn(|i| {
+                    (mean[i] + std[i] * standard_normal(rng)).round() as i32
+                });
+                (fitness(&weights, config), weights)
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.0));
+        if let Some(top) = candidates.first() {
+            if top.0 > best.0 {
+                best = *top;
+            }
+        }
+
+        let elite = &candidates[..n_elite.min(candidates.len())];
+        if !elite.is_empty() {
+            for i in 0..N_FEATURES {
+                let n = elite.len() as f64;
+                let m = elite.iter().map(|(_, w)| w[i] as f64).sum::<f64>() / n;
+                let var = elite
+                    .iter()
+                    .map(|(_, w)| (w[i] as f64 - m).powi(2))
+                    .sum::<f64>()
+                    / n;
+                mean[i] = m;
+                std[i] = var.sqrt() + EXTRA_STD;
+            }
+        }
+        report(generation, best.0, &best.1);
+    }
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_worse_than_initial() {
+        let config = TuneConfig {
+            generations: 1,
+            population: 2,
+            elite_fraction: 0.5,
+            initial_std: 1.0,
+            seeds: vec![0, 1],
+            rules: RuleSet::default(),
+            options: DealOptions::default(),
+        };
+        let initial = crate::ai::eval::default_weights();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let tuned = cross_entropy(&initial, &config, &mut rng, |_, _, _| {});
+        assert!(fitness(&tuned, &config) >= fitness(&initial, &config));
+    }
+}
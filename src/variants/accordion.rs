@@ -0,0 +1,151 @@
+//! Accordion
+//!
+//! All cards are dealt face up in a row of 52 piles. A pile can be moved onto the pile right before it, or onto the
+//! pile three places before it, if their top cards have the same suit or the same rank. The game is won when all
+//! cards are gathered in a single pile.
+
+use super::{Solitaire, VariantAi};
+use crate::core::{MoveError, Suit, Value};
+use crate::engine::shuffled_deck;
+
+/// An action in Accordion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Move the pile with this index onto the pile right before it
+    Left(usize),
+    /// Move the pile with this index onto the pile three places before it
+    ThreeLeft(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accordion {
+    /// The piles from left to right. Only the top cards matter, but the piles are kept to count the cards.
+    piles: Vec<Vec<(Suit, Value)>>,
+}
+
+/// All cards are face up, but only the top card of each pile matters
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccordionObserver {
+    pub tops: Vec<(Suit, Value)>,
+}
+
+impl AccordionObserver {
+    /// The pile moved and the pile it is moved onto
+    fn piles(action: Action) -> Option<(usize, usize)> {
+        match action {
+            Action::Left(i) => Some((i, i.checked_sub(1)?)),
+            Action::ThreeLeft(i) => Some((i, i.checked_sub(3)?)),
+        }
+    }
+
+    pub fn is_legal(&self, action: Action) -> bool {
+        let Some((from, to)) = AccordionObserver::piles(action) else {
+            return false;
+        };
+        match (self.tops.get(from), self.tops.get(to)) {
+            (Some(a), Some(b)) => a.0 == b.0 || a.1 == b.1,
+            _ => false,
+        }
+    }
+
+    pub fn legal_actions(&self) -> Vec<Action> {
+        (0..self.tops.len())
+            .flat_map(|i| [Action::Left(i), Action::ThreeLeft(i)])
+            .filter(|a| self.is_legal(*a))
+            .collect()
+    }
+
+    /// The view after a legal action
+    fn after(&self, action: Action) -> AccordionObserver {
+        let (from, to) = AccordionObserver::piles(action).expect("The action is legal");
+        let mut tops = self.tops.clone();
+        tops[to] = tops.remove(from);
+        AccordionObserver { tops }
+    }
+}
+
+/// Make the move that leaves the most moves open
+pub struct AccordionAi;
+
+impl VariantAi<Accordion> for AccordionAi {
+    fn make_move(&mut self, view: &AccordionObserver) -> Action {
+        view.legal_actions()
+            .into_iter()
+            .max_by_key(|a| view.after(*a).legal_actions().len())
+            .expect("The game is running, so there is a legal action")
+    }
+}
+
+impl Solitaire for Accordion {
+    type Action = Action;
+    type Observer = AccordionObserver;
+
+    fn deal(seed: u64) -> Self {
+        Accordion {
+            piles: shuffled_deck(seed)
+                .into_iter()
+                .map(|card| vec![card.into()])
+                .collect(),
+        }
+    }
+
+    fn observe(&self) -> AccordionObserver {
+        AccordionObserver {
+            tops: self
+                .piles
+                .iter()
+                .map(|p| *p.last().expect("Piles are never empty"))
+                .collect(),
+        }
+    }
+
+    fn act(&mut self, action: Action) -> Result<(), MoveError> {
+        if !self.observe().is_legal(action) {
+            return Err(MoveError::WithDescription(
+                format!("{action:?} is not legal").into(),
+            ));
+        }
+        let (from, to) = AccordionObserver::piles(action).expect("Checked to be legal");
+        let pile = self.piles.remove(from);
+        self.piles[to].extend(pile);
+        Ok(())
+    }
+
+    fn is_won(&self) -> bool {
+        self.piles.len() == 1
+    }
+
+    fn is_running(&self) -> bool {
+        !self.observe().legal_actions().is_empty()
+    }
+
+    /// The number of piles that have been gathered
+    fn score(&self) -> i32 {
+        52 - self.piles.len() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_left_and_three_left() {
+        let card = |suit, value| vec![(suit, Value::try_from(value).unwrap())];
+        let mut gs = Accordion {
+            piles: vec![
+                card(Suit::Hearts, 2),
+                card(Suit::Clubs, 5),
+                card(Suit::Spades, 9),
+                card(Suit::Clubs, 2),
+            ],
+        };
+        assert_eq!(gs.observe().legal_actions(), vec![Action::ThreeLeft(3)]);
+        gs.act(Action::ThreeLeft(3)).unwrap();
+        assert_eq!(gs.observe().tops[0], (Suit::Clubs, Value::TWO));
+        gs.act(Action::Left(1)).unwrap();
+        assert_eq!(gs.score(), 50);
+        assert!(!gs.is_running());
+        assert!(gs.act(Action::Left(0)).is_err());
+    }
+}
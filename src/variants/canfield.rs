@@ -0,0 +1,312 @@
+//! Canfield
+//!
+//! 13 cards are dealt to the reserve, with only the top card face up. The next card goes to the first foundation,
+//! and its rank is the base of all four foundations. Four tableau piles get one card each, and the other 34 cards
+//! are the stock.
+//!
+//!  - The foundations are built up in suit from the base, wrapping from king to ace
+//!  - The tableau piles are built down in alternating colors, wrapping from ace to king.
+//!    Only whole piles are moved, and only onto another pile.
+//!  - An empty tableau pile is filled from the reserve right away. When the reserve is empty, the top card of the
+//!    waste may be moved to it.
+//!  - The stock is turned onto the waste three cards at a time. When the stock is empty, the waste is turned over
+//!    to be the stock again, as often as the player likes.
+//!
+//! The reserve is a pile that Klondike does not have, so Canfield addresses its piles with its own `Pile`.
+
+use super::{Solitaire, VariantAi};
+use crate::core::{MoveError, Suit, Value};
+use crate::engine::shuffled_deck;
+
+/// The number of cards dealt to the reserve
+const RESERVE_SIZE: usize = 13;
+
+/// The number of cards turned from the stock at a time
+const DRAW: usize = 3;
+
+/// A pile in Canfield
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pile {
+    Reserve,
+    Waste,
+    Foundation(usize),
+    Tableau(usize),
+}
+
+/// An action in Canfield
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Move the top card of the reserve, the waste or a tableau pile to a foundation,
+    /// or the top card of the reserve or the waste, or a whole tableau pile, to a tableau pile
+    Move(Pile, Pile),
+    /// Turn three cards from the stock onto the waste, or turn the waste over when the stock is empty
+    Draw,
+    /// Give up the game
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canfield {
+    reserve: Vec<(Suit, Value)>,
+    stock: Vec<(Suit, Value)>,
+    waste: Vec<(Suit, Value)>,
+    /// The rank that each foundation starts with
+    base: Value,
+    foundations: [Vec<(Suit, Value)>; 4],
+    tableau: [Vec<(Suit, Value)>; 4],
+    quit: bool,
+}
+
+/// What a player sees: the top cards of the reserve and the waste, and all cards in the foundations and the tableau
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanfieldObserver {
+    pub reserve_top: Option<(Suit, Value)>,
+    pub reserve_size: usize,
+    pub stock_size: usize,
+    pub waste_top: Option<(Suit, Value)>,
+    pub waste_size: usize,
+    pub base: Value,
+    pub foundation_tops: [Option<(Suit, Value)>; 4],
+    pub tableau: [Vec<(Suit, Value)>; 4],
+}
+
+/// The next higher value, where the king is followed by the ace
+fn next_up(value: Value) -> Value {
+    Value::try_from(value.numeric_value() % 13 + 1).expect("Known to be in range")
+}
+
+impl CanfieldObserver {
+    /// Does the card go on the foundation?
+    pub fn fits_foundation(&self, card: (Suit, Value), foundation: usize) -> bool {
+        match self.foundation_tops[foundation] {
+            None => card.1 == self.base,
+            Some((suit, value)) => card.0 == suit && card.1 == next_up(value),
+        }
+    }
+
+    /// Does the card go on the tableau pile? Cards only go on an empty pile when the reserve is empty.
+    pub fn fits_tableau(&self, card: (Suit, Value), pile: usize) -> bool {
+        match self.tableau[pile].last() {
+            None => self.reserve_size == 0,
+            Some(&(suit, value)) => card.0.color() != suit.color() && next_up(card.1) == value,
+        }
+    }
+
+    /// Is the action legal?
+    pub fn is_legal(&self, action: Action) -> bool {
+        let (from, to) = match action {
+            Action::Draw => return self.stock_size + self.waste_size > 0,
+            Action::Quit => return true,
+            Action::Move(from, to) => (from, to),
+        };
+        let card = match (from, to) {
+            _ if from == to || !from.exists() || !to.exists() => None,
+            (Pile::Reserve, _) => self.reserve_top,
+            (Pile::Waste, _) => self.waste_top,
+            // Whole piles are moved onto another pile, but only the top card goes to a foundation
+            (Pile::Tableau(i), Pile::Tableau(_)) => self.tableau[i].first().copied(),
+            (Pile::Tableau(i), _) => self.tableau[i].last().copied(),
+            (Pile::Foundation(_), _) => None,
+        };
+        match (card, to) {
+            (Some(card), Pile::Foundation(f)) => self.fits_foundation(card, f),
+            (Some(_), Pile::Tableau(t))
+                if matches!(from, Pile::Tableau(_)) && self.tableau[t].is_empty() =>
+            {
+                false
+            }
+            (Some(card), Pile::Tableau(t)) => self.fits_tableau(card, t),
+            _ => false,
+        }
+    }
+}
+
+impl Pile {
+    /// Is there such a pile?
+    fn exists(&self) -> bool {
+        match self {
+            Pile::Foundation(i) | Pile::Tableau(i) => *i < 4,
+            Pile::Reserve | Pile::Waste => true,
+        }
+    }
+}
+
+impl Canfield {
+    fn pile(&mut self, pile: Pile) -> &mut Vec<(Suit, Value)> {
+        match pile {
+            Pile::Reserve => &mut self.reserve,
+            Pile::Waste => &mut self.waste,
+            Pile::Foundation(i) => &mut self.foundations[i],
+            Pile::Tableau(i) => &mut self.tableau[i],
+        }
+    }
+}
+
+impl Solitaire for Canfield {
+    type Action = Action;
+    type Observer = CanfieldObserver;
+
+    fn deal(seed: u64) -> Self {
+        let mut deck = shuffled_deck(seed).into_iter().map(<(Suit, Value)>::from);
+        let reserve = deck.by_ref().take(RESERVE_SIZE).collect();
+        let base = deck.next().expect("The deck has 52 cards");
+        let tableau = std::array::from_fn(|_| deck.by_ref().take(1).collect());
+        Canfield {
+            reserve,
+            base: base.1,
+            foundations: [vec![base], vec![], vec![], vec![]],
+            tableau,
+            stock: deck.collect(),
+            waste: vec![],
+            quit: false,
+        }
+    }
+
+    fn observe(&self) -> CanfieldObserver {
+        CanfieldObserver {
+            reserve_top: self.reserve.last().copied(),
+            reserve_size: self.reserve.len(),
+            stock_size: self.stock.len(),
+            waste_top: self.waste.last().copied(),
+            waste_size: self.waste.len(),
+            base: self.base,
+            foundation_tops: std::array::from_fn(|i| self.foundations[i].last().copied()),
+            tableau: self.tableau.clone(),
+        }
+    }
+
+    fn act(&mut self, action: Action) -> Result<(), MoveError> {
+        if !self.observe().is_legal(action) {
+            return Err(MoveError::WithDescription(
+                format!("{action:?} is not legal").into(),
+            ));
+        }
+        match action {
+            Action::Draw if self.stock.is_empty() => {
+                self.stock = self.waste.drain(..).rev().collect();
+            }
+            Action::Draw => {
+                let n = self.stock.len().min(DRAW);
+                let turned = self.stock.split_off(self.stock.len() - n);
+                self.waste.extend(turned.into_iter().rev());
+            }
+            Action::Quit => self.quit = true,
+            Action::Move(Pile::Tableau(i), Pile::Tableau(j)) => {
+                let pile = std::mem::take(&mut self.tableau[i]);
+                self.tableau[j].extend(pile);
+            }
+            Action::Move(from, to) => {
+                let card = self.pile(from).pop().expect("Checked to be legal");
+                self.pile(to).push(card);
+            }
+        }
+        for pile in self.tableau.iter_mut().filter(|p| p.is_empty()) {
+            pile.extend(self.reserve.pop());
+        }
+        Ok(())
+    }
+
+    fn is_won(&self) -> bool {
+        self.foundations.iter().all(|f| f.len() == 13)
+    }
+
+    fn is_running(&self) -> bool {
+        !self.is_won() && !self.quit
+    }
+
+    /// The number of cards on the foundations
+    fn score(&self) -> i32 {
+        self.foundations.iter().map(Vec::len).sum::<usize>() as i32
+    }
+}
+
+/// Play to the foundations first, then from the reserve, then build the tableau, and draw otherwise.
+/// Gives up after turning through the whole stock without a move.
+#[derive(Debug, Default)]
+pub struct CanfieldAi {
+    idle_draws: usize,
+}
+
+impl VariantAi<Canfield> for CanfieldAi {
+    fn make_move(&mut self, view: &CanfieldObserver) -> Action {
+        let tableau = (0..4).map(Pile::Tableau);
+        let sources = [Pile::Reserve, Pile::Waste]
+            .into_iter()
+            .chain(tableau.clone());
+        let to_foundations =
+            sources.flat_map(|from| (0..4).map(move |f| Action::Move(from, Pile::Foundation(f))));
+        let to_tableau = [Pile::Reserve]
+            .into_iter()
+            .chain(tableau.clone())
+            .chain([Pile::Waste])
+            .flat_map(|from| tableau.clone().map(move |to| Action::Move(from, to)));
+        if let Some(action) = to_foundations.chain(to_tableau).find(|a| view.is_legal(*a)) {
+            self.idle_draws = 0;
+            return action;
+        }
+        self.idle_draws += 1;
+        if self.idle_draws > (view.stock_size + view.waste_size) / DRAW + 2
+            || !view.is_legal(Action::Draw)
+        {
+            self.idle_draws = 0;
+            return Action::Quit;
+        }
+        Action::Draw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deal_and_moves() {
+        let gs = Canfield::deal(0);
+        let view = gs.observe();
+        assert_eq!(
+            (view.reserve_size, view.stock_size, view.waste_size),
+            (13, 34, 0)
+        );
+        assert_eq!(view.foundation_tops[0].map(|c| c.1), Some(view.base));
+
+        let card = |suit, value| (suit, Value::try_from(value).unwrap());
+        let mut gs = Canfield {
+            reserve: vec![card(Suit::Clubs, 9)],
+            stock: vec![],
+            waste: vec![card(Suit::Diamonds, 5), card(Suit::Hearts, 13)],
+            base: Value::QUEEN,
+            foundations: [
+                vec![card(Suit::Spades, 12), card(Suit::Spades, 13)],
+                vec![],
+                vec![],
+                vec![],
+            ],
+            tableau: [
+                vec![card(Suit::Clubs, 1)],
+                vec![card(Suit::Diamonds, 2)],
+                vec![card(Suit::Clubs, 7)],
+                vec![card(Suit::Spades, 1)],
+            ],
+            quit: false,
+        };
+        // The king goes on an ace of the other color
+        gs.act(Action::Move(Pile::Waste, Pile::Tableau(0))).unwrap();
+        // Only whole piles move, and the emptied pile is filled from the reserve
+        gs.act(Action::Move(Pile::Tableau(0), Pile::Tableau(1)))
+            .unwrap();
+        assert_eq!(gs.tableau[0], vec![card(Suit::Clubs, 9)]);
+        assert_eq!(gs.tableau[1].len(), 3);
+        // The ace goes on the king of its suit on the foundation, and leaves an empty pile
+        gs.act(Action::Move(Pile::Tableau(3), Pile::Foundation(0)))
+            .unwrap();
+        assert!(gs
+            .act(Action::Move(Pile::Tableau(2), Pile::Tableau(3)))
+            .is_err());
+        // With the reserve empty, an empty pile takes the top card of the waste
+        gs.act(Action::Move(Pile::Waste, Pile::Tableau(3))).unwrap();
+        assert!(!gs.observe().is_legal(Action::Draw));
+        assert!(!gs
+            .observe()
+            .is_legal(Action::Move(Pile::Tableau(4), Pile::Foundation(1))));
+    }
+}
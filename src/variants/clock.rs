@@ -0,0 +1,110 @@
+//! Clock
+//!
+//! The cards are dealt face down into 13 piles of four, one for each rank, like the hours on a clock with the kings
+//! in the middle. The top card of the kings is turned and put face up under the pile of its rank, then the top card
+//! of that pile is turned, and so on. The game is lost when the fourth king is turned before all other cards.
+//!
+//! There are no choices to make, so there is a single action. It is the smallest game that the runner can play.
+
+use super::{Solitaire, VariantAi};
+use crate::core::{MoveError, Suit, Value};
+use crate::engine::shuffled_deck;
+
+/// The pile of the kings, in the middle of the clock
+const KINGS: usize = 12;
+
+/// The only action in Clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Turn the top face down card of the current pile, and put it under the pile of its rank
+    Turn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    /// The face down cards of each pile, by rank from the aces to the kings
+    face_down: [Vec<(Suit, Value)>; 13],
+    /// The face up cards of each pile
+    face_up: [Vec<(Suit, Value)>; 13],
+    /// The pile to turn a card from
+    current: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockObserver {
+    pub n_face_down: [usize; 13],
+    pub face_up: [Vec<(Suit, Value)>; 13],
+    pub current: usize,
+}
+
+/// Turn the cards until the game is over
+pub struct ClockAi;
+
+impl VariantAi<Clock> for ClockAi {
+    fn make_move(&mut self, _view: &ClockObserver) -> Action {
+        Action::Turn
+    }
+}
+
+impl Solitaire for Clock {
+    type Action = Action;
+    type Observer = ClockObserver;
+
+    fn deal(seed: u64) -> Self {
+        let mut deck = shuffled_deck(seed).into_iter().map(<(Suit, Value)>::from);
+        Clock {
+            face_down: std::array::from_fn(|_| deck.by_ref().take(4).collect()),
+            face_up: Default::default(),
+            current: KINGS,
+        }
+    }
+
+    fn observe(&self) -> ClockObserver {
+        ClockObserver {
+            n_face_down: std::array::from_fn(|i| self.face_down[i].len()),
+            face_up: self.face_up.clone(),
+            current: self.current,
+        }
+    }
+
+    fn act(&mut self, action: Action) -> Result<(), MoveError> {
+        match action {
+            Action::Turn => {
+                let card = self.face_down[self.current]
+                    .pop()
+                    .ok_or(MoveError::NoCardToMove)?;
+                self.current = card.1.numeric_value() as usize - 1;
+                self.face_up[self.current].push(card);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_won(&self) -> bool {
+        self.face_down.iter().all(Vec::is_empty)
+    }
+
+    fn is_running(&self) -> bool {
+        !self.face_down[self.current].is_empty()
+    }
+
+    /// The number of cards turned face up
+    fn score(&self) -> i32 {
+        self.face_up.iter().map(Vec::len).sum::<usize>() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::run_game;
+
+    #[test]
+    fn ends_with_the_fourth_king() {
+        let mut gs = Clock::deal(0);
+        let n_actions = run_game(&mut gs, &mut ClockAi, 100);
+        assert_eq!(n_actions as i32, gs.score());
+        assert_eq!(gs.face_up[KINGS].len(), 4);
+        assert!(gs.act(Action::Turn).is_err());
+    }
+}
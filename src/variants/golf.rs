@@ -0,0 +1,140 @@
+//! Golf
+//!
+//! Seven columns of five cards are dealt face up, one card starts the waste and the other 16 cards are the stock.
+//! The top card of a column can be played to the waste if it is one higher or one lower than the waste card,
+//! regardless of suit. Aces and kings do not go on each other. The game is won when all columns are cleared.
+
+use super::{is_neighbour, Action, Solitaire, WasteView};
+use crate::core::{MoveError, Suit, Value};
+use crate::engine::shuffled_deck;
+
+/// The number of cards dealt to each column
+const COLUMN_SIZE: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Golf {
+    columns: [Vec<(Suit, Value)>; 7],
+    stock: Vec<(Suit, Value)>,
+    waste: Vec<(Suit, Value)>,
+}
+
+/// All cards but the stock are face up, so the player sees the whole game except the order of the stock
+#[derive(Debug, Clone, PartialEq)]
+pub struct GolfObserver {
+    pub waste_top: (Suit, Value),
+    pub stock_size: usize,
+    pub columns: [Vec<(Suit, Value)>; 7],
+}
+
+impl Solitaire for Golf {
+    type Action = Action;
+    type Observer = GolfObserver;
+
+    fn deal(seed: u64) -> Self {
+        let mut deck = shuffled_deck(seed).into_iter().map(<(Suit, Value)>::from);
+        let columns = std::array::from_fn(|_| deck.by_ref().take(COLUMN_SIZE).collect());
+        let waste = deck.by_ref().take(1).collect();
+        Golf {
+            columns,
+            waste,
+            stock: deck.collect(),
+        }
+    }
+
+    fn observe(&self) -> GolfObserver {
+        GolfObserver {
+            waste_top: *self.waste.last().expect("The waste is never empty"),
+            stock_size: self.stock.len(),
+            columns: self.columns.clone(),
+        }
+    }
+
+    fn act(&mut self, action: Action) -> Result<(), MoveError> {
+        match action {
+            Action::Play(i) => {
+                let card = *self
+                    .columns
+                    .get(i)
+                    .and_then(|c| c.last())
+                    .ok_or(MoveError::NoCardToMove)?;
+                if !is_neighbour(card.1, self.observe().waste_top.1, false) {
+                    return Err(MoveError::WithDescription(
+                        format!("{}{} does not go on the waste", card.0, card.1).into(),
+                    ));
+                }
+                self.columns[i].pop();
+                self.waste.push(card);
+            }
+            Action::Draw => self
+                .waste
+                .push(self.stock.pop().ok_or(MoveError::NoCardToMove)?),
+        }
+        Ok(())
+    }
+
+    fn is_won(&self) -> bool {
+        self.columns.iter().all(Vec::is_empty)
+    }
+
+    fn is_running(&self) -> bool {
+        let view = self.observe();
+        !self.is_won()
+            && (view.stock_size > 0
+                || view
+                    .free_cards()
+                    .iter()
+                    .any(|(_, v)| is_neighbour(*v, view.waste_top(), false)))
+    }
+
+    /// The number of cards cleared from the columns
+    fn score(&self) -> i32 {
+        (7 * COLUMN_SIZE - self.columns.iter().map(Vec::len).sum::<usize>()) as i32
+    }
+}
+
+impl WasteView for GolfObserver {
+    fn waste_top(&self) -> Value {
+        self.waste_top.1
+    }
+
+    fn free_cards(&self) -> Vec<(usize, Value)> {
+        self.columns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| c.last().map(|card| (i, card.1)))
+            .collect()
+    }
+
+    fn wraps(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deal_and_play() {
+        let mut gs = Golf::deal(0);
+        let view = gs.observe();
+        assert!(view.columns.iter().all(|c| c.len() == COLUMN_SIZE));
+        assert_eq!(view.stock_size, 16);
+        let tops = view.free_cards();
+        let playable = tops
+            .iter()
+            .find(|(_, v)| is_neighbour(*v, view.waste_top(), false));
+        let blocked = tops
+            .iter()
+            .find(|(_, v)| !is_neighbour(*v, view.waste_top(), false));
+        if let Some((i, _)) = blocked {
+            assert!(gs.act(Action::Play(*i)).is_err());
+        }
+        match playable {
+            Some((i, _)) => gs.act(Action::Play(*i)).unwrap(),
+            None => gs.act(Action::Draw).unwrap(),
+        }
+        assert_eq!(gs.waste.len(), 2);
+        assert_eq!(gs.score(), playable.is_some() as i32);
+    }
+}
@@ -0,0 +1,181 @@
+//! Solitaire games that are not played on the Klondike tableau
+//!
+//! Each variant has its own engine, actions and observer, and implements `Solitaire` so that it can be played out
+//! by the generic runner `run_game` and summarized like the Klondike batch. An AI for a variant implements
+//! `VariantAi`, and is handed the observer of the game before each move.
+//!
+//! Golf and TriPeaks are both built on a single waste pile, where a card goes on a card one higher or one lower
+//! regardless of suit. They share their actions and `ChainAi`. Canfield has a reserve pile, and its own piles and actions.
+//! Clock and Accordion are tiny games, with a single action and with actions that take piles out of a row.
+
+pub mod accordion;
+pub mod canfield;
+pub mod clock;
+pub mod golf;
+pub mod tripeaks;
+
+use crate::core::{MoveError, Value};
+use crate::rules::Variant;
+use crate::stats::{GameEnd, Summary};
+use accordion::{Accordion, AccordionAi};
+use canfield::{Canfield, CanfieldAi};
+use clock::{Clock, ClockAi};
+use golf::Golf;
+use tripeaks::TriPeaks;
+
+/// A game that has not ended after this many actions is counted as a loss
+const MAX_ACTIONS: usize = 5000;
+
+/// A game of solitaire
+pub trait Solitaire {
+    type Action: Copy + std::fmt::Debug;
+    type Observer;
+
+    /// Deal a new game
+    fn deal(seed: u64) -> Self;
+    /// What a player can see of the game
+    fn observe(&self) -> Self::Observer;
+    fn act(&mut self, action: Self::Action) -> Result<(), MoveError>;
+    fn is_won(&self) -> bool;
+    /// Is there any legal action left?
+    fn is_running(&self) -> bool;
+    /// The score so far. Higher is better.
+    fn score(&self) -> i32;
+}
+
+/// An AI for a game of solitaire
+pub trait VariantAi<G: Solitaire> {
+    fn make_move(&mut self, view: &G::Observer) -> G::Action;
+}
+
+/// Play a game until it is over or `max_actions` actions have been taken. Returns the number of actions taken.
+pub fn run_game<G: Solitaire>(gs: &mut G, ai: &mut dyn VariantAi<G>, max_actions: usize) -> usize {
+    let mut n_actions_taken = 0;
+    while gs.is_running() && n_actions_taken < max_actions {
+        let action = ai.make_move(&gs.observe());
+        gs.act(action)
+            .unwrap_or_else(|_| panic!("The AI suggested {:?} an illegal move!", action));
+        n_actions_taken += 1;
+    }
+    n_actions_taken
+}
+
+/// Play the deals with the given seeds, and summarize the games
+pub fn play_deals<G: Solitaire>(
+    ai: &mut dyn VariantAi<G>,
+    label: &str,
+    seeds: impl Iterator<Item = u64>,
+) -> Summary {
+    let mut summary = Summary::new(label);
+    for seed in seeds {
+        let mut gs = G::deal(seed);
+        let moves = run_game(&mut gs, ai, MAX_ACTIONS);
+        let end = if gs.is_won() {
+            GameEnd::Won
+        } else {
+            GameEnd::Lost
+        };
+        summary.add_game(gs.score(), end, moves);
+    }
+    summary
+}
+
+/// The variants that are played here, which are the ones not played on the Klondike tableau
+pub fn variants() -> impl Iterator<Item = Variant> {
+    Variant::ALL.into_iter().filter(|v| !v.is_tableau())
+}
+
+/// Play the deals with the given seeds of a variant with its AI, or `None` if the variant is played on the Klondike
+/// tableau
+pub fn play(variant: Variant, seeds: impl Iterator<Item = u64>) -> Option<Summary> {
+    let summary = match variant {
+        Variant::Golf => play_deals::<Golf>(&mut ChainAi, "ChainAi on golf", seeds),
+        Variant::TriPeaks => play_deals::<TriPeaks>(&mut ChainAi, "ChainAi on tripeaks", seeds),
+        Variant::Canfield => {
+            play_deals::<Canfield>(&mut CanfieldAi::default(), "CanfieldAi on canfield", seeds)
+        }
+        Variant::Clock => play_deals::<Clock>(&mut ClockAi, "ClockAi on clock", seeds),
+        Variant::Accordion => {
+            play_deals::<Accordion>(&mut AccordionAi, "AccordionAi on accordion", seeds)
+        }
+        Variant::Klondike | Variant::Yukon | Variant::Russian => return None,
+    };
+    Some(summary)
+}
+
+/// An action in Golf or TriPeaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Play the tableau card with this index to the waste
+    Play(usize),
+    /// Turn the next card of the stock onto the waste
+    Draw,
+}
+
+/// Does a card go on the waste card? With `wrap`, aces and kings go on each other
+fn is_neighbour(card: Value, waste: Value, wrap: bool) -> bool {
+    let (a, b) = (card.numeric_value(), waste.numeric_value());
+    a.abs_diff(b) == 1 || (wrap && a.abs_diff(b) == 12)
+}
+
+/// What `ChainAi` needs to know about a game that is built on a single waste pile
+pub trait WasteView {
+    fn waste_top(&self) -> Value;
+    /// The tableau cards that are free to be played, by their index
+    fn free_cards(&self) -> Vec<(usize, Value)>;
+    /// Do aces and kings go on each other?
+    fn wraps(&self) -> bool;
+}
+
+/// Play the free card that starts the longest chain of free cards, and draw when no card goes on the waste
+pub struct ChainAi;
+
+impl ChainAi {
+    /// The length of the longest chain of the free cards that can be played onto `waste`, one after the other
+    fn chain_length(waste: Value, free: &[(usize, Value)], wrap: bool) -> usize {
+        (0..free.len())
+            .filter(|&i| is_neighbour(free[i].1, waste, wrap))
+            .map(|i| {
+                let mut rest = free.to_vec();
+                let (_, card) = rest.swap_remove(i);
+                1 + ChainAi::chain_length(card, &rest, wrap)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl<G> VariantAi<G> for ChainAi
+where
+    G: Solitaire<Action = Action>,
+    G::Observer: WasteView,
+{
+    fn make_move(&mut self, view: &G::Observer) -> Action {
+        let free = view.free_cards();
+        (0..free.len())
+            .filter(|&i| is_neighbour(free[i].1, view.waste_top(), view.wraps()))
+            .max_by_key(|&i| {
+                let mut rest = free.clone();
+                let (_, card) = rest.swap_remove(i);
+                ChainAi::chain_length(card, &rest, view.wraps())
+            })
+            .map_or(Action::Draw, |i| Action::Play(free[i].0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The AIs only make legal moves, and play every game to the end
+    #[test]
+    fn ais_play_out_all_variants() {
+        for variant in variants() {
+            let summary = play(variant, 0..50).unwrap();
+            assert_eq!(summary.games, 50);
+            assert!(summary.avg_score() > 5.0, "{variant:?}");
+        }
+        assert!(play(Variant::TriPeaks, 0..50).unwrap().wins > 0);
+        assert!(play(Variant::Klondike, 0..1).is_none());
+    }
+}
@@ -0,0 +1,176 @@
+//! TriPeaks
+//!
+//! 28 cards are dealt in three overlapping peaks of four rows, one card starts the waste and the other 23 cards
+//! are the stock. The tableau is numbered row by row from the tops of the peaks:
+//!
+//! ```text
+//!          0                 1                 2
+//!      3       4         5       6         7       8
+//!    9   10   11      12   13   14      15   16   17
+//!  18  19  20  21  22  23  24  25  26  27
+//! ```
+//!
+//! Only the bottom row is dealt face up, and a card is turned face up when both cards covering it are played.
+//! A face up card can be played to the waste if it is one higher or one lower than the waste card, regardless of suit.
+//! Aces and kings go on each other. The game is won when all three peaks are cleared.
+
+use super::{is_neighbour, Action, Solitaire, WasteView};
+use crate::core::{CardView, MoveError, Suit, Value};
+use crate::engine::shuffled_deck;
+
+/// The number of cards in the tableau
+const N_TABLEAU: usize = 28;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriPeaks {
+    /// The cards in the tableau, `None` when played
+    tableau: [Option<(Suit, Value)>; N_TABLEAU],
+    stock: Vec<(Suit, Value)>,
+    waste: Vec<(Suit, Value)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriPeaksObserver {
+    pub waste_top: (Suit, Value),
+    pub stock_size: usize,
+    /// The cards in the tableau, `None` when played
+    pub tableau: [Option<CardView>; N_TABLEAU],
+}
+
+/// The two cards that cover a card in the tableau, or none for the bottom row
+fn covering(i: usize) -> Option<[usize; 2]> {
+    let first = match i {
+        0..=2 => 3 + 2 * i,
+        3..=8 => 9 + 3 * ((i - 3) / 2) + (i - 3) % 2,
+        9..=17 => 18 + (i - 9),
+        _ => return None,
+    };
+    Some([first, first + 1])
+}
+
+impl TriPeaks {
+    fn is_face_up(&self, i: usize) -> bool {
+        covering(i).is_none_or(|c| c.iter().all(|&j| self.tableau[j].is_none()))
+    }
+}
+
+impl Solitaire for TriPeaks {
+    type Action = Action;
+    type Observer = TriPeaksObserver;
+
+    fn deal(seed: u64) -> Self {
+        let mut deck = shuffled_deck(seed).into_iter().map(<(Suit, Value)>::from);
+        let tableau = std::array::from_fn(|_| deck.next());
+        let waste = deck.by_ref().take(1).collect();
+        TriPeaks {
+            tableau,
+            waste,
+            stock: deck.collect(),
+        }
+    }
+
+    fn observe(&self) -> TriPeaksObserver {
+        TriPeaksObserver {
+            waste_top: *self.waste.last().expect("The waste is never empty"),
+            stock_size: self.stock.len(),
+            tableau: std::array::from_fn(|i| {
+                self.tableau[i].map(|card| {
+                    if self.is_face_up(i) {
+                        card.into()
+                    } else {
+                        CardView::FaceDown
+                    }
+                })
+            }),
+        }
+    }
+
+    fn act(&mut self, action: Action) -> Result<(), MoveError> {
+        match action {
+            Action::Play(i) => {
+                let card = match self.tableau.get(i) {
+                    Some(Some(card)) if self.is_face_up(i) => *card,
+                    _ => return Err(MoveError::NoCardToMove),
+                };
+                if !is_neighbour(card.1, self.observe().waste_top.1, true) {
+                    return Err(MoveError::WithDescription(
+                        format!("{}{} does not go on the waste", card.0, card.1).into(),
+                    ));
+                }
+                self.tableau[i] = None;
+                self.waste.push(card);
+            }
+            Action::Draw => self
+                .waste
+                .push(self.stock.pop().ok_or(MoveError::NoCardToMove)?),
+        }
+        Ok(())
+    }
+
+    fn is_won(&self) -> bool {
+        self.tableau.iter().all(Option::is_none)
+    }
+
+    fn is_running(&self) -> bool {
+        let view = self.observe();
+        !self.is_won()
+            && (view.stock_size > 0
+                || view
+                    .free_cards()
+                    .iter()
+                    .any(|(_, v)| is_neighbour(*v, view.waste_top(), true)))
+    }
+
+    /// The number of cards cleared from the tableau
+    fn score(&self) -> i32 {
+        self.tableau.iter().filter(|c| c.is_none()).count() as i32
+    }
+}
+
+impl WasteView for TriPeaksObserver {
+    fn waste_top(&self) -> Value {
+        self.waste_top.1
+    }
+
+    fn free_cards(&self) -> Vec<(usize, Value)> {
+        self.tableau
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match c {
+                Some(CardView::FaceUp(_, v)) => Some((i, *v)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn wraps(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cards_are_revealed_when_uncovered() {
+        assert_eq!(covering(1), Some([5, 6]));
+        assert_eq!(covering(6), Some([13, 14]));
+        assert_eq!(covering(17), Some([26, 27]));
+        assert_eq!(covering(18), None);
+
+        let mut gs = TriPeaks::deal(0);
+        assert_eq!(gs.observe().free_cards().len(), 10);
+        assert_eq!(gs.observe().tableau[9], Some(CardView::FaceDown));
+        assert!(gs.act(Action::Play(9)).is_err());
+        // Play the two cards over card 9 onto a waste card that fits each of them
+        for i in [18, 19] {
+            let card = gs.tableau[i].unwrap();
+            let below = Value::try_from(card.1.numeric_value() % 13 + 1).unwrap();
+            gs.waste.push((Suit::Hearts, below));
+            gs.act(Action::Play(i)).unwrap();
+        }
+        assert_eq!(gs.observe().tableau[9], gs.tableau[9].map(CardView::from));
+        assert_eq!(gs.score(), 2);
+    }
+}
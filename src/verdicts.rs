@@ -0,0 +1,128 @@
+//! Verdicts of the solver per deal
+//!
+//! Solving a deal can take long, and the same deals are solved again and again. With the option `--verdicts FILE`,
+//! `solve`, the batch with `--agreement` and the par of `play` look up the deals in a small database first, and add
+//! what the solver found out about the others. The file has one line per deal, with tab separated fields: the deal code, `won` or
+//! `lost`, the number of moves of the winning line, and its moves in move notation, separated by commas.
+//!
+//! Only proofs are kept, so a deal the solver gave up on is searched again, e.g. with a larger limit. The moves of a
+//! winning line depend on the options of the deal, e.g. revealing cards automatically saves actions, so only deals
+//! without options are kept.
+
+use solitaire::core::Action;
+use solitaire::solver::Solution;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Errors in the database file
+#[derive(Error, Debug, PartialEq)]
+pub enum VerdictsError {
+    #[error("Line {0}: {1}")]
+    Syntax(usize, String),
+}
+
+/// The proven verdicts, by deal code
+#[derive(Debug, Default, PartialEq)]
+pub struct Verdicts {
+    solutions: BTreeMap<String, Solution>,
+}
+
+impl Verdicts {
+    pub fn parse(text: &str) -> Result<Verdicts, VerdictsError> {
+        let mut verdicts = Verdicts::default();
+        for (i, line) in text.lines().enumerate() {
+            let syntax = |msg: &str| VerdictsError::Syntax(i + 1, msg.to_string());
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [code, verdict, moves, line] = fields[..] else {
+                return Err(syntax("Expected 4 tab separated fields"));
+            };
+            let moves: usize = moves
+                .parse()
+                .map_err(|_| syntax("The moves should be a number"))?;
+            let line = line
+                .split(", ")
+                .filter(|s| !s.is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<Action>, _>>()
+                .map_err(|e| syntax(&e.to_string()))?;
+            if line.len() != moves {
+                return Err(syntax("The number of moves doesn't match the line"));
+            }
+            let solution = match verdict {
+                "won" => Solution::Won(line),
+                "lost" if line.is_empty() => Solution::Lost,
+                "lost" => return Err(syntax("A lost deal has no line")),
+                _ => return Err(syntax("Expected won or lost")),
+            };
+            verdicts.solutions.insert(code.to_string(), solution);
+        }
+        Ok(verdicts)
+    }
+
+    /// The database in the file format
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# deal\tverdict\tmoves\tline\n");
+        for (code, solution) in self.solutions.iter() {
+            let (verdict, line) = match solution {
+                Solution::Won(line) => ("won", &line[..]),
+                _ => ("lost", &[][..]),
+            };
+            let moves = line
+                .iter()
+                .map(Action::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            text += &format!("{code}\t{verdict}\t{}\t{moves}\n", line.len());
+        }
+        text
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Solution> {
+        self.solutions.get(code)
+    }
+
+    /// Keep what the solver found out about a deal, if it is a proof. Returns true if it was kept.
+    pub fn insert(&mut self, code: &str, solution: &Solution) -> bool {
+        if *solution == Solution::Unknown {
+            return false;
+        }
+        self.solutions.insert(code.to_string(), solution.clone());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solitaire::core::Addr;
+
+    #[test]
+    fn keeps_proofs_and_round_trips() {
+        let mut verdicts = Verdicts::default();
+        let line = vec![
+            Action::Take,
+            Action::Move(Addr::Depot1, Addr::Depot2, 3),
+            Action::Reveal(Addr::Depot1),
+        ];
+        assert!(verdicts.insert("klondike:0", &Solution::Won(line.clone())));
+        assert!(verdicts.insert("klondike:10", &Solution::Lost));
+        assert!(!verdicts.insert("klondike:2", &Solution::Unknown));
+        assert_eq!(verdicts.get("klondike:0"), Some(&Solution::Won(line)));
+        assert_eq!(verdicts.get("klondike:2"), None);
+        assert_eq!(
+            verdicts.to_text(),
+            "# deal\tverdict\tmoves\tline\nklondike:0\twon\t3\tT, D1->D2 x3, R D1\nklondike:10\tlost\t0\t\n"
+        );
+        assert_eq!(Verdicts::parse(&verdicts.to_text()), Ok(verdicts));
+        assert_eq!(
+            Verdicts::parse("klondike:0\twon\t2\tT"),
+            Err(VerdictsError::Syntax(
+                1,
+                "The number of moves doesn't match the line".to_string()
+            ))
+        );
+    }
+}
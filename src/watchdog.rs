@@ -0,0 +1,76 @@
+//! Detection of AIs that are stuck in a loop
+//!
+//! The AIs remember the states and actions they have tried, but they can still move back and forth between a handful
+//! of states for thousands of moves, e.g. a king between two empty depots. The watchdog keeps the hashes of the
+//! states in a rolling window of the last moves. When a single state shows up too often in the window, no progress is
+//! being made and the game should be aborted as a loss.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Watches the states of a game for a livelock
+pub struct Watchdog {
+    window: VecDeque<u64>,
+    counts: HashMap<u64, usize>,
+    window_size: usize,
+    max_repeats: usize,
+}
+
+impl Default for Watchdog {
+    /// A state may be visited 20 times in the last 1000 moves
+    fn default() -> Self {
+        Watchdog::new(1000, 20)
+    }
+}
+
+impl Watchdog {
+    /// A watchdog that allows a state to be visited `max_repeats` times in the last `window_size` moves
+    pub fn new(window_size: usize, max_repeats: usize) -> Self {
+        Watchdog {
+            window: VecDeque::with_capacity(window_size),
+            counts: HashMap::new(),
+            window_size,
+            max_repeats,
+        }
+    }
+
+    /// Record the state after a move. Returns true if the game is in a livelock
+    pub fn check(&mut self, state: &impl Hash) -> bool {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if self.window.len() == self.window_size {
+            let old = self.window.pop_front().expect("The window is not empty");
+            if let Some(count) = self.counts.get_mut(&old) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(&old);
+                }
+            }
+        }
+        self.window.push_back(hash);
+        let count = self.counts.entry(hash).or_default();
+        *count += 1;
+        *count > self.max_repeats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_ping_pong() {
+        let mut watchdog = Watchdog::new(10, 3);
+        // Steady progress is never a livelock
+        assert!((0..100).all(|state| !watchdog.check(&state)));
+        // Repeats that are spread out fall out of the window
+        assert!((0..100).all(|state| !watchdog.check(&(state % 4))));
+        let mut watchdog = Watchdog::new(10, 3);
+        let ping_pong = [1, 2, 1, 2, 1, 2, 1];
+        let livelocks = ping_pong.map(|state| watchdog.check(&state));
+        assert_eq!(livelocks, [false, false, false, false, false, false, true]);
+    }
+}